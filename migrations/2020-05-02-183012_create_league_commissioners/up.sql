@@ -0,0 +1,8 @@
+CREATE TABLE league_commissioners (
+  id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE
+);
+
+INSERT INTO league_commissioners (id, user_id, league_id) VALUES
+  ('00000000-0000-0000-0000-000000000000', '00000000-0000-0000-0000-000000000000', '00000000-0000-0000-0000-000000000000');
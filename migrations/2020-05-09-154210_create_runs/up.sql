@@ -0,0 +1,17 @@
+CREATE TABLE runs (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  distance DOUBLE PRECISION NOT NULL,
+  duration INT NOT NULL,
+  started_at TIMESTAMP NOT NULL,
+  elevation_gain DOUBLE PRECISION,
+  heart_rate INT,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX runs_user_id_started_at_idx ON runs (user_id, started_at);
+
+INSERT INTO runs (id, user_id, distance, duration, started_at, elevation_gain, heart_rate) VALUES
+  ('00000000-0000-0000-0000-000000000000', '11111111-1111-1111-1111-111111111111', 3.1, 1680, NOW() - INTERVAL '1 day', 120.0, 152),
+  ('11111111-1111-1111-1111-111111111111', '22222222-2222-2222-2222-222222222222', 6.2, 3300, NOW() - INTERVAL '2 days', NULL, NULL);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS player_scores;
+DROP TABLE IF EXISTS team_scores;
@@ -0,0 +1,23 @@
+CREATE TABLE team_scores (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE,
+  team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  round INT NOT NULL,
+  miles DOUBLE PRECISION NOT NULL,
+  points DOUBLE PRECISION NOT NULL,
+  UNIQUE (team_id, round)
+);
+
+CREATE TABLE player_scores (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE,
+  team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  round INT NOT NULL,
+  miles DOUBLE PRECISION NOT NULL,
+  points DOUBLE PRECISION NOT NULL,
+  UNIQUE (team_id, user_id, round)
+);
+
+CREATE INDEX team_scores_league_id_round_idx ON team_scores (league_id, round);
+CREATE INDEX player_scores_league_id_round_idx ON player_scores (league_id, round);
@@ -0,0 +1,8 @@
+ALTER TABLE league_rulesets
+  DROP COLUMN pace_bonus_target,
+  DROP COLUMN pace_bonus_per_mile,
+  DROP COLUMN points_per_100_feet,
+  DROP COLUMN min_run_distance,
+  DROP COLUMN daily_mileage_cap,
+  DROP COLUMN long_run_distance,
+  DROP COLUMN long_run_multiplier;
@@ -0,0 +1,8 @@
+ALTER TABLE league_rulesets
+  ADD COLUMN pace_bonus_target INT,
+  ADD COLUMN pace_bonus_per_mile INT NOT NULL DEFAULT 0,
+  ADD COLUMN points_per_100_feet INT NOT NULL DEFAULT 0,
+  ADD COLUMN min_run_distance DOUBLE PRECISION NOT NULL DEFAULT 0,
+  ADD COLUMN daily_mileage_cap DOUBLE PRECISION,
+  ADD COLUMN long_run_distance DOUBLE PRECISION,
+  ADD COLUMN long_run_multiplier DOUBLE PRECISION NOT NULL DEFAULT 1;
@@ -0,0 +1,11 @@
+CREATE TABLE matchups (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE,
+  round INT NOT NULL,
+  home_team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  -- NULL when the home team has a bye
+  away_team_id UUID REFERENCES teams (id) ON DELETE CASCADE,
+  UNIQUE (league_id, round, home_team_id)
+);
+
+CREATE INDEX matchups_league_id_round_idx ON matchups (league_id, round);
@@ -0,0 +1,3 @@
+ALTER TABLE matchups
+  DROP COLUMN home_points,
+  DROP COLUMN away_points;
@@ -0,0 +1,4 @@
+-- Points scored by each side once the round is closed, NULL until then
+ALTER TABLE matchups
+  ADD COLUMN home_points DOUBLE PRECISION,
+  ADD COLUMN away_points DOUBLE PRECISION;
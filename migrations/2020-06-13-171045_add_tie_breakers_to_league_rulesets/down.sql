@@ -0,0 +1 @@
+ALTER TABLE league_rulesets DROP COLUMN tie_breakers;
@@ -0,0 +1,3 @@
+-- Applied in order to rank teams with the same win percentage
+ALTER TABLE league_rulesets
+  ADD COLUMN tie_breakers TEXT[] NOT NULL DEFAULT '{head_to_head,total_points,total_miles}';
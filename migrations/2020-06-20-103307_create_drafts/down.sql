@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS draft_queues;
+DROP TABLE IF EXISTS draft_picks;
+DROP TABLE IF EXISTS draft_slots;
+DROP TABLE IF EXISTS drafts;
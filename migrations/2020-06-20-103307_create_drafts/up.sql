@@ -0,0 +1,44 @@
+CREATE TABLE drafts (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  league_id UUID NOT NULL UNIQUE REFERENCES leagues (id) ON DELETE CASCADE,
+  -- snake or linear
+  mode VARCHAR(20) NOT NULL,
+  rounds INT NOT NULL,
+  pick_seconds INT NOT NULL,
+  -- scheduled, in_progress or complete
+  status VARCHAR(20) NOT NULL DEFAULT 'scheduled',
+  current_pick INT NOT NULL DEFAULT 0,
+  pick_started_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE draft_slots (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  draft_id UUID NOT NULL REFERENCES drafts (id) ON DELETE CASCADE,
+  team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  position INT NOT NULL,
+  UNIQUE (draft_id, position),
+  UNIQUE (draft_id, team_id)
+);
+
+CREATE TABLE draft_picks (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  draft_id UUID NOT NULL REFERENCES drafts (id) ON DELETE CASCADE,
+  pick_number INT NOT NULL,
+  team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  -- NULL when the pick expired with nobody left in the team's queue
+  user_id UUID REFERENCES users (id) ON DELETE CASCADE,
+  auto_picked BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  UNIQUE (draft_id, pick_number)
+);
+
+CREATE TABLE draft_queues (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  draft_id UUID NOT NULL REFERENCES drafts (id) ON DELETE CASCADE,
+  team_id UUID NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  rank INT NOT NULL,
+  UNIQUE (draft_id, team_id, user_id)
+);
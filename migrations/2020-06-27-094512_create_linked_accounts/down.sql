@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS runs_provider_activity_idx;
+ALTER TABLE runs
+  DROP COLUMN IF EXISTS provider,
+  DROP COLUMN IF EXISTS provider_activity_id;
+DROP TABLE IF EXISTS linked_accounts;
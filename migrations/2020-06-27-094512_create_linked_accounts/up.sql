@@ -0,0 +1,25 @@
+CREATE TABLE linked_accounts (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  -- name of the activity provider, e.g. strava
+  provider VARCHAR(50) NOT NULL,
+  provider_user_id VARCHAR(100) NOT NULL,
+  access_token TEXT NOT NULL,
+  refresh_token TEXT NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  -- start of the newest activity synced, the next sync asks for later ones
+  sync_cursor TIMESTAMP,
+  synced_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  UNIQUE (user_id, provider),
+  UNIQUE (provider, provider_user_id)
+);
+
+-- Runs synced from a provider remember the activity they came from, so
+-- syncing it again updates the run instead of adding another one
+ALTER TABLE runs
+  ADD COLUMN provider VARCHAR(50),
+  ADD COLUMN provider_activity_id VARCHAR(100);
+
+CREATE UNIQUE INDEX runs_provider_activity_idx ON runs (provider, provider_activity_id);
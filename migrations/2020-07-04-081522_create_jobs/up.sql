@@ -0,0 +1,35 @@
+CREATE TABLE jobs (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  -- close_round, round_reminder or expire_picks
+  kind VARCHAR(50) NOT NULL,
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE,
+  -- the round the job is about, or the pick for expire_picks
+  round INT NOT NULL,
+  run_at TIMESTAMP NOT NULL,
+  -- pending, running, done or failed
+  status VARCHAR(20) NOT NULL DEFAULT 'pending',
+  attempts INT NOT NULL DEFAULT 0,
+  -- a running job whose lease ran out is claimed again
+  locked_until TIMESTAMP,
+  last_error TEXT,
+  finished_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  UNIQUE (kind, league_id, round)
+);
+
+CREATE INDEX jobs_status_run_at_idx ON jobs (status, run_at);
+
+CREATE TABLE notifications (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  league_id UUID NOT NULL REFERENCES leagues (id) ON DELETE CASCADE,
+  -- round_reminder
+  kind VARCHAR(50) NOT NULL,
+  round INT NOT NULL,
+  message TEXT NOT NULL,
+  -- NULL until it's delivered
+  sent_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  UNIQUE (user_id, league_id, kind, round)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE leagues
+  DROP COLUMN timezone,
+  DROP COLUMN round_days;
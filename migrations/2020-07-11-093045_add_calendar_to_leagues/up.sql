@@ -0,0 +1,4 @@
+ALTER TABLE leagues
+  -- IANA name, the league's rounds start at midnight there
+  ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+  ADD COLUMN round_days INT NOT NULL DEFAULT 7;
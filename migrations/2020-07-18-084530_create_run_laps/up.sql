@@ -0,0 +1,11 @@
+-- Laps of runs imported from a file, in the order they were run
+CREATE TABLE run_laps (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  run_id UUID NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+  -- numbered from 1
+  lap_number INT NOT NULL,
+  started_at TIMESTAMP NOT NULL,
+  distance DOUBLE PRECISION NOT NULL,
+  duration INT NOT NULL,
+  UNIQUE (run_id, lap_number)
+);
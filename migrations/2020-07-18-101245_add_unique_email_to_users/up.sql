@@ -0,0 +1,2 @@
+-- One account per email, ignoring case
+CREATE UNIQUE INDEX users_email_key ON users (lower(email));
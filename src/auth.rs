@@ -1,7 +1,16 @@
 use crate::config::CONFIG;
-use argon2rs::argon2i_simple;
-// use chrono::{Duration, Utc};
-// use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use crate::errors::ApiError;
+use crate::models::users::AuthUser;
+use actix_identity::{CookieIdentityPolicy, IdentityService};
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use argon2rs::{
+    argon2i_simple, defaults,
+    verifier::{constant_eq, Encoded},
+};
+use chrono::{Duration, Utc};
+use futures::future::{err, ok, Ready};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
 use uuid::Uuid;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -11,78 +20,260 @@ pub struct PrivateClaim {
     exp: i64,
 }
 
-// impl PrivateClaim {
-//     pub fn new(user_id: Uuid, email: String) -> Self {
-//         Self {
-//             user_id,
-//             email,
-//             exp: (Utc::now() + Duration::hours(CONFIG.jwt_expiration)).timestamp(),
-//         }
-//     }
-// }
-
-// /// Create a json web token (JWT)
-// pub fn create_jwt(private_claim: PrivateClaim) -> Result<String, ApiError> {
-//     let encoding_key = EncodingKey::from_secret(&CONFIG.jwt_key.as_ref());
-//     encode(
-//         &Header::default(),
-//         &private_claim,
-//         &encoding_key,
-//     )
-//     .map_err(|e| ApiError::CannotEncodeJwtToken(e.to_string()))
-// }
-
-// /// Decode a json web token (JWT)
-// pub fn decode_jwt(token: &str) -> Result<PrivateClaim, ApiError> {
-//     let decoding_key = DecodingKey::from_secret(&CONFIG.jwt_key.as_ref());
-//     decode::<PrivateClaim>(token, &decoding_key, &Validation::default())
-//         .map(|data| data.claims)
-//         .map_err(|e| ApiError::CannotDecodeJwtToken(e.to_string()))
-// }
+impl PrivateClaim {
+    pub fn new(user_id: Uuid, email: String) -> Self {
+        Self {
+            user_id,
+            email,
+            exp: (Utc::now() + Duration::hours(CONFIG.jwt_expiration)).timestamp(),
+        }
+    }
+}
+
+/// Create a json web token (JWT)
+pub fn create_jwt(private_claim: PrivateClaim) -> Result<String, ApiError> {
+    let encoding_key = EncodingKey::from_secret(CONFIG.jwt_key.as_ref());
+    encode(&Header::default(), &private_claim, &encoding_key)
+        .map_err(|e| ApiError::CannotEncodeJwtToken(e.to_string()))
+}
+
+/// Decode a json web token (JWT)
+pub fn decode_jwt(token: &str) -> Result<PrivateClaim, ApiError> {
+    let decoding_key = DecodingKey::from_secret(CONFIG.jwt_key.as_ref());
+    decode::<PrivateClaim>(token, &decoding_key, &Validation::default())
+        .map(|data| data.claims)
+        .map_err(|e| ApiError::CannotDecodeJwtToken(e.to_string()))
+}
+
+/// What a calendar feed link shows: a league, or a team of it
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CalendarClaim {
+    pub league_id: Uuid,
+    pub team_id: Option<Uuid>,
+}
+
+/// Sign a calendar feed link's claim into a token for its URL
+///
+/// Calendar apps subscribe to a URL and can't log in, so the token is all
+/// they send. It doesn't expire, to keep them subscribed.
+pub fn create_calendar_token(claim: &CalendarClaim) -> Result<String, ApiError> {
+    let encoding_key = EncodingKey::from_secret(CONFIG.jwt_key.as_ref());
+    encode(&Header::default(), claim, &encoding_key)
+        .map_err(|e| ApiError::CannotEncodeJwtToken(e.to_string()))
+}
+
+/// Decode the token of a calendar feed link
+pub fn decode_calendar_token(token: &str) -> Result<CalendarClaim, ApiError> {
+    let decoding_key = DecodingKey::from_secret(CONFIG.jwt_key.as_ref());
+    let validation = Validation {
+        validate_exp: false,
+        ..Validation::default()
+    };
+    decode::<CalendarClaim>(token, &decoding_key, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| ApiError::CannotDecodeJwtToken(e.to_string()))
+}
+
+/// Length of the random salt generated for each password
+const SALT_LENGTH: usize = 16;
 
 /// Encrypt a password
 ///
-/// Uses the argon2i algorithm.
-/// auth_salt is environment-configured.
+/// Uses the argon2i algorithm with a random per-password salt.
+/// The result is a PHC-style string carrying the algorithm, its parameters,
+/// the salt and the hash, e.g. `$argon2i$m=4096,t=3,p=1$<salt>$<hash>`.
 pub fn hash(password: &str) -> String {
-    argon2i_simple(&password, &CONFIG.auth_salt)
+    let salt: [u8; SALT_LENGTH] = rand::thread_rng().gen();
+    let encoded = Encoded::default2i(password.as_bytes(), &salt, &[], &[]);
+    String::from_utf8(encoded.to_u8()).expect("Encoded hashes are always valid UTF-8")
+}
+
+/// Verify a password against a stored hash
+///
+/// Accepts PHC-style hashes created by `hash` as well as legacy hex hashes
+/// created with the environment-configured auth_salt.
+pub fn verify(password: &str, hashed: &str) -> bool {
+    if is_legacy_hash(hashed) {
+        return constant_eq(legacy_hash(password).as_bytes(), hashed.as_bytes());
+    }
+
+    match Encoded::from_u8(hashed.as_bytes()) {
+        Ok(encoded) => encoded.verify(password.as_bytes()),
+        Err(_) => false,
+    }
+}
+
+/// Determine if a stored hash should be replaced by a fresh `hash`
+///
+/// True for legacy hashes and hashes made with outdated parameters.
+pub fn needs_rehash(hashed: &str) -> bool {
+    if is_legacy_hash(hashed) {
+        return true;
+    }
+
+    let current_params = format!(
+        "$argon2i$m={},t={},p={}$",
+        defaults::KIB,
+        defaults::PASSES,
+        defaults::LANES
+    );
+    !hashed.starts_with(&current_params)
+}
+
+/// Hashes created before per-user salts were hex strings without a `$` prefix
+fn is_legacy_hash(hashed: &str) -> bool {
+    !hashed.starts_with('$')
+}
+
+/// Encrypt a password the way it was done before per-user salts
+///
+/// Only used to verify existing hashes so they can be upgraded at login.
+pub fn legacy_hash(password: &str) -> String {
+    argon2i_simple(password, &CONFIG.auth_salt)
         .iter()
         .map(|b| format!("{:02x}", b))
         .collect()
 }
 
+/// Gets the identity service for injection into an Actix app
+///
+/// The identity cookie holds the JWT issued at login so that browser clients
+/// don't need to manage a bearer token themselves.
+pub fn get_identity_service() -> IdentityService<CookieIdentityPolicy> {
+    IdentityService::new(
+        CookieIdentityPolicy::new(CONFIG.session_key.as_ref())
+            .name(&CONFIG.session_name)
+            .path("/")
+            .max_age(CONFIG.session_timeout * 60)
+            .secure(CONFIG.session_secure),
+    )
+}
+
+/// Extract the AuthUser the auth middleware stored on the request
+impl FromRequest for AuthUser {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        match req.extensions().get::<AuthUser>() {
+            Some(auth_user) => ok(auth_user.clone()),
+            None => err(ApiError::Unauthorized("Unauthorized".into())),
+        }
+    }
+}
+
+impl From<PrivateClaim> for AuthUser {
+    fn from(private_claim: PrivateClaim) -> Self {
+        AuthUser {
+            id: private_claim.user_id,
+            email: private_claim.email,
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    // static EMAIL: &str = "test@test.com";
+    use actix_web::test;
+    static EMAIL: &str = "test@test.com";
+
+    #[test]
+    fn it_decodes_a_calendar_token() {
+        let claim = CalendarClaim {
+            league_id: Uuid::new_v4(),
+            team_id: None,
+        };
+        let token = create_calendar_token(&claim).unwrap();
+        assert_eq!(decode_calendar_token(&token).unwrap(), claim);
+    }
+
+    #[test]
+    fn it_doesnt_take_a_login_token_for_a_calendar_token() {
+        let private_claim = PrivateClaim::new(Uuid::new_v4(), EMAIL.into());
+        let token = create_jwt(private_claim).unwrap();
+        assert!(decode_calendar_token(&token).is_err());
+    }
 
     #[test]
     fn it_hashes_a_password() {
         let password = "password";
         let hashed = hash(password);
         assert_ne!(password, hashed);
+        assert!(hashed.starts_with("$argon2i$"));
     }
 
     #[test]
-    fn it_matches_2_hashed_passwords() {
+    fn it_salts_each_hash() {
         let password = "password";
         let hashed = hash(password);
         let hashed_again = hash(password);
-        assert_eq!(hashed, hashed_again);
-    }
-
-    // #[test]
-    // fn it_creates_a_jwt() {
-    //     let private_claim = PrivateClaim::new(Uuid::new_v4(), EMAIL.into());
-    //     let jwt = create_jwt(private_claim);
-    //     assert!(jwt.is_ok());
-    // }
-
-    // #[test]
-    // fn it_decodes_a_jwt() {
-    //     let private_claim = PrivateClaim::new(Uuid::new_v4(), EMAIL.into());
-    //     let jwt = create_jwt(private_claim.clone()).unwrap();
-    //     let decoded = decode_jwt(&jwt).unwrap();
-    //     assert_eq!(private_claim, decoded);
-    // }
+        assert_ne!(hashed, hashed_again);
+    }
+
+    #[test]
+    fn it_verifies_a_password() {
+        let hashed = hash("password");
+        assert!(verify("password", &hashed));
+        assert!(!verify("not-the-password", &hashed));
+    }
+
+    #[test]
+    fn it_verifies_a_legacy_password() {
+        let hashed = legacy_hash("password");
+        assert!(verify("password", &hashed));
+        assert!(!verify("not-the-password", &hashed));
+    }
+
+    #[test]
+    fn it_doesnt_verify_a_malformed_hash() {
+        assert!(!verify("password", "$argon2i$nonsense"));
+    }
+
+    #[test]
+    fn it_needs_a_rehash() {
+        assert!(needs_rehash(&legacy_hash("password")));
+        assert!(needs_rehash("$argon2i$m=8,t=1,p=1$c2FsdHNhbHQ$aGFzaA"));
+        assert!(!needs_rehash(&hash("password")));
+    }
+
+    #[test]
+    fn it_creates_a_jwt() {
+        let private_claim = PrivateClaim::new(Uuid::new_v4(), EMAIL.into());
+        let jwt = create_jwt(private_claim);
+        assert!(jwt.is_ok());
+    }
+
+    #[test]
+    fn it_decodes_a_jwt() {
+        let private_claim = PrivateClaim::new(Uuid::new_v4(), EMAIL.into());
+        let jwt = create_jwt(private_claim.clone()).unwrap();
+        let decoded = decode_jwt(&jwt).unwrap();
+        assert_eq!(private_claim, decoded);
+    }
+
+    #[test]
+    fn it_doesnt_decode_an_invalid_jwt() {
+        let decoded = decode_jwt("not.a.jwt");
+        assert!(decoded.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn it_extracts_an_auth_user() {
+        let user_id = Uuid::new_v4();
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(AuthUser {
+            id: user_id,
+            email: EMAIL.into(),
+        });
+        let auth_user = AuthUser::extract(&req).await.unwrap();
+        assert_eq!(auth_user.id, user_id);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_extract_a_missing_auth_user() {
+        let req = test::TestRequest::default().to_http_request();
+        let auth_user = AuthUser::extract(&req).await;
+        assert!(auth_user.is_err());
+    }
 }
@@ -0,0 +1,336 @@
+//! Response cache for read-heavy endpoints
+//!
+//! Caching is opt-in: responses are only cached in Redis when REDIS_URL is
+//! set, otherwise every read goes to the database. Cached responses live for
+//! CACHE_TTL seconds (60 by default) and are grouped by what they depend on,
+//! so a manager that changes a league can drop every response about it at
+//! once.
+//!
+//! Redis is called synchronously, like the database, so the cache has to be
+//! used from inside of `web::block`.
+
+use crate::config::{Config, CONFIG};
+use crate::errors::ApiError;
+use actix::Addr;
+use actix_redis::{Command, RedisActor};
+use actix_web::web::ServiceConfig;
+use futures::executor::block_on;
+use redis_async::resp::RespValue;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub const DEFAULT_TTL: u64 = 60;
+
+/// Group of every cached list of leagues
+pub const LEAGUES: &str = "leagues";
+
+/// Group of everything cached about a single league
+pub fn league_group(league_id: Uuid) -> String {
+    format!("league:{}", league_id)
+}
+
+/// Key of the set of keys cached in a group, kept apart from the keys of
+/// responses so a response can be cached under its group's name
+fn group_key(group: &str) -> String {
+    format!("group:{}", group)
+}
+
+#[derive(Clone)]
+pub struct Cache {
+    backend: CacheBackend,
+    ttl: Duration,
+}
+
+#[derive(Clone)]
+pub enum CacheBackend {
+    Redis(Addr<RedisActor>),
+    /// In-process store, for tests
+    Memory(Arc<Mutex<MemoryStore>>),
+    Disabled,
+}
+
+/// Entries and groups share their keys, like in Redis, so a key can't be
+/// both a response and a group
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: HashMap<String, (String, Instant)>,
+    groups: HashMap<String, HashSet<String>>,
+}
+
+impl Cache {
+    /// Cache in Redis when REDIS_URL is set, don't cache otherwise
+    pub fn from_config(config: &Config) -> Self {
+        let ttl = config.cache_ttl.unwrap_or(DEFAULT_TTL);
+        match &config.redis_url {
+            Some(redis_url) if !redis_url.is_empty() && ttl > 0 => {
+                Cache::new(CacheBackend::Redis(start_redis(redis_url)), ttl)
+            }
+            _ => Cache::disabled(),
+        }
+    }
+
+    pub fn new(backend: CacheBackend, ttl: u64) -> Self {
+        Cache {
+            backend,
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn memory(ttl: u64) -> Self {
+        Cache::new(CacheBackend::Memory(Arc::default()), ttl)
+    }
+
+    pub fn disabled() -> Self {
+        Cache::new(CacheBackend::Disabled, 0)
+    }
+
+    /// Get a cached response, or load it and cache it under key
+    ///
+    /// The response is dropped whenever one of its groups is invalidated.
+    /// Errors are never cached and a broken cache falls back to loading.
+    pub fn cached<T, F>(&self, key: &str, groups: &[&str], load: F) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, ApiError>,
+    {
+        if let CacheBackend::Disabled = self.backend {
+            return load();
+        }
+
+        match self.get(key) {
+            Ok(Some(value)) => {
+                if let Ok(response) = serde_json::from_str(&value) {
+                    return Ok(response);
+                }
+            }
+            Ok(None) => {}
+            Err(error) => log::warn!("Reading {} from the cache failed: {:?}", key, error),
+        }
+
+        let response = load()?;
+        let stored = serde_json::to_string(&response)
+            .map_err(|error| ApiError::CacheError(error.to_string()))
+            .and_then(|value| self.set(key, &value, groups));
+        if let Err(error) = stored {
+            log::warn!("Caching {} failed: {:?}", key, error);
+        }
+        Ok(response)
+    }
+
+    /// Drop every response cached in one of the groups
+    ///
+    /// Call after changes are committed. Failures are only logged: the write
+    /// already happened and stale responses expire with their TTL.
+    pub fn forget(&self, groups: &[&str]) {
+        if let Err(error) = self.invalidate(groups) {
+            log::error!("Invalidating {:?} in the cache failed: {:?}", groups, error);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, ApiError> {
+        match &self.backend {
+            CacheBackend::Redis(redis) => match send(redis, vec!["GET", key])? {
+                RespValue::BulkString(bytes) => Ok(String::from_utf8(bytes).ok()),
+                _ => Ok(None),
+            },
+            CacheBackend::Memory(store) => {
+                let store = lock_store(store)?;
+                Ok(store
+                    .entries
+                    .get(key)
+                    .filter(|(_, expires_at)| *expires_at > Instant::now())
+                    .map(|(value, _)| value.to_string()))
+            }
+            CacheBackend::Disabled => Ok(None),
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &str, groups: &[&str]) -> Result<(), ApiError> {
+        match &self.backend {
+            CacheBackend::Redis(redis) => {
+                let ttl = self.ttl.as_secs().to_string();
+                send(redis, vec!["SET", key, value, "EX", &ttl])?;
+                for &group in groups {
+                    let group = group_key(group);
+                    send(redis, vec!["SADD", &group, key])?;
+                    send(redis, vec!["EXPIRE", &group, &ttl])?;
+                }
+                Ok(())
+            }
+            CacheBackend::Memory(store) => {
+                let mut store = lock_store(store)?;
+                if store.groups.contains_key(key) {
+                    return Err(wrong_type(key));
+                }
+                let expires_at = Instant::now() + self.ttl;
+                store.entries.insert(key.into(), (value.into(), expires_at));
+                for &group in groups {
+                    let group = group_key(group);
+                    if store.entries.contains_key(&group) {
+                        return Err(wrong_type(&group));
+                    }
+                    store.groups.entry(group).or_default().insert(key.into());
+                }
+                Ok(())
+            }
+            CacheBackend::Disabled => Ok(()),
+        }
+    }
+
+    pub fn invalidate(&self, groups: &[&str]) -> Result<(), ApiError> {
+        match &self.backend {
+            CacheBackend::Redis(redis) => {
+                for &group in groups {
+                    let group = group_key(group);
+                    let mut keys = match send(redis, vec!["SMEMBERS", &group])? {
+                        RespValue::Array(members) => members
+                            .into_iter()
+                            .filter_map(|member| match member {
+                                RespValue::BulkString(bytes) => String::from_utf8(bytes).ok(),
+                                _ => None,
+                            })
+                            .collect(),
+                        _ => vec![],
+                    };
+                    keys.push(group);
+                    let mut command = vec!["DEL"];
+                    command.extend(keys.iter().map(String::as_str));
+                    send(redis, command)?;
+                }
+                Ok(())
+            }
+            CacheBackend::Memory(store) => {
+                let mut store = lock_store(store)?;
+                for &group in groups {
+                    for key in store.groups.remove(&group_key(group)).unwrap_or_default() {
+                        store.entries.remove(&key);
+                    }
+                }
+                Ok(())
+            }
+            CacheBackend::Disabled => Ok(()),
+        }
+    }
+}
+
+/// Add the cache to the app's data, see Cache::from_config
+pub fn add_cache(cfg: &mut ServiceConfig) {
+    cfg.data(Cache::from_config(&CONFIG));
+}
+
+/// Start an actor talking to the Redis at REDIS_URL
+pub fn start_redis(redis_url: &str) -> Addr<RedisActor> {
+    RedisActor::start(redis_url.trim_start_matches("redis://").to_string())
+}
+
+/// Send a command to Redis and wait for its reply
+pub fn send(redis: &Addr<RedisActor>, command: Vec<&str>) -> Result<RespValue, ApiError> {
+    let name = command[0].to_string();
+    let command = RespValue::Array(
+        command
+            .into_iter()
+            .map(|part| RespValue::BulkString(part.as_bytes().to_vec()))
+            .collect(),
+    );
+    let error = |message: String| ApiError::CacheError(format!("Redis {}: {}", name, message));
+    match block_on(redis.send(Command(command))) {
+        Ok(Ok(RespValue::Error(message))) => Err(error(message)),
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(redis_error)) => Err(error(redis_error.to_string())),
+        Err(mailbox_error) => Err(error(mailbox_error.to_string())),
+    }
+}
+
+/// The error Redis gives for a key holding another type of value
+fn wrong_type(key: &str) -> ApiError {
+    ApiError::CacheError(format!("WRONGTYPE {} holds another kind of value", key))
+}
+
+fn lock_store(
+    store: &Mutex<MemoryStore>,
+) -> Result<std::sync::MutexGuard<'_, MemoryStore>, ApiError> {
+    store
+        .lock()
+        .map_err(|_| ApiError::CacheError("The in-process cache is poisoned".into()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use actix_web::web::Data;
+    use std::cell::Cell;
+
+    /// An in-process cache wrapped in Actix Application Data
+    pub fn get_data_cache() -> Data<Cache> {
+        Data::new(Cache::memory(DEFAULT_TTL))
+    }
+
+    fn load(calls: &Cell<i32>) -> Result<String, ApiError> {
+        calls.set(calls.get() + 1);
+        Ok(format!("load {}", calls.get()))
+    }
+
+    #[test]
+    fn it_caches_until_invalidated() {
+        let cache = Cache::memory(DEFAULT_TTL);
+        let calls = Cell::new(0);
+        let group = league_group(Uuid::nil());
+        let groups = [group.as_str()];
+
+        let first = cache.cached("key", &groups, || load(&calls)).unwrap();
+        let second = cache.cached("key", &groups, || load(&calls)).unwrap();
+        assert_eq!(first, "load 1");
+        assert_eq!(second, "load 1");
+
+        cache.forget(&groups);
+        let third = cache.cached("key", &groups, || load(&calls)).unwrap();
+        assert_eq!(third, "load 2");
+    }
+
+    #[test]
+    fn it_caches_a_response_under_its_groups_name() {
+        let cache = Cache::memory(DEFAULT_TTL);
+        let calls = Cell::new(0);
+        let group = league_group(Uuid::nil());
+        let groups = [group.as_str()];
+
+        cache.cached(&group, &groups, || load(&calls)).unwrap();
+        assert!(cache.get(&group).unwrap().is_some());
+        cache.forget(&groups);
+        assert_eq!(cache.get(&group).unwrap(), None);
+        let reloaded = cache.cached(&group, &groups, || load(&calls)).unwrap();
+        assert_eq!(reloaded, "load 2");
+    }
+
+    #[test]
+    fn it_expires_entries() {
+        let cache = Cache::memory(0);
+        let calls = Cell::new(0);
+        cache.cached("key", &[], || load(&calls)).unwrap();
+        cache.cached("key", &[], || load(&calls)).unwrap();
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn it_doesnt_cache_errors() {
+        let cache = Cache::memory(DEFAULT_TTL);
+        let response: Result<String, ApiError> =
+            cache.cached("key", &[], || Err(ApiError::NotFound("gone".into())));
+        assert!(response.is_err());
+        assert_eq!(cache.get("key").unwrap(), None);
+    }
+
+    #[test]
+    fn it_falls_back_to_loading_without_redis() {
+        let mut config = CONFIG.clone();
+        config.redis_url = None;
+        let cache = Cache::from_config(&config);
+        let calls = Cell::new(0);
+        cache.cached("key", &[], || load(&calls)).unwrap();
+        cache.cached("key", &[], || load(&calls)).unwrap();
+        assert_eq!(calls.get(), 2);
+    }
+}
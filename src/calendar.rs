@@ -0,0 +1,165 @@
+//! When a league's rounds start and end.
+//!
+//! A league starts at midnight of its start date in its own timezone and its
+//! rounds last round_days days each, midnight to midnight local time. Runs
+//! are stored in UTC, so round boundaries are turned into UTC instants here:
+//! a round spanning a daylight saving change is an hour shorter or longer.
+
+use crate::models::leagues::League;
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+pub const WEEKLY: &str = "weekly";
+pub const BIWEEKLY: &str = "biweekly";
+pub const CUSTOM: &str = "custom";
+
+/// Every round length
+pub const ROUND_LENGTHS: [&str; 3] = [WEEKLY, BIWEEKLY, CUSTOM];
+
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// Days in a round unless a league says otherwise
+pub const DEFAULT_ROUND_DAYS: i32 = 7;
+
+/// Days in a round of a round_length, custom rounds last round_days
+pub fn round_days(round_length: &str, round_days: Option<i32>) -> Option<i32> {
+    match round_length {
+        WEEKLY => Some(DEFAULT_ROUND_DAYS),
+        BIWEEKLY => Some(14),
+        CUSTOM => round_days,
+        _ => None,
+    }
+}
+
+/// Find a timezone by its IANA name, e.g. America/New_York
+pub fn parse_timezone(timezone: &str) -> Option<Tz> {
+    timezone.parse().ok()
+}
+
+/// The rounds of a league, start being midnight local time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calendar {
+    pub start: NaiveDateTime,
+    pub timezone: Tz,
+    pub round_days: i32,
+}
+
+impl Calendar {
+    /// A league's calendar, leagues with an unknown timezone use UTC
+    pub fn of(league: &League) -> Self {
+        Calendar {
+            start: league.start,
+            timezone: parse_timezone(&league.timezone).unwrap_or(Tz::UTC),
+            round_days: league.round_days,
+        }
+    }
+
+    /// Get the `[start, end)` window of a round, in UTC
+    ///
+    /// Rounds are numbered from 1, the first round begins when the league
+    /// starts.
+    pub fn round_window(&self, round: i32) -> (NaiveDateTime, NaiveDateTime) {
+        (self.round_start(round), self.round_start(round + 1))
+    }
+
+    /// Noon of a local date, in UTC
+    ///
+    /// Runs only known by their date start then, far enough from midnight
+    /// that no offset or daylight saving change moves them to another day.
+    pub fn noon(&self, date: NaiveDate) -> NaiveDateTime {
+        to_utc(self.timezone, date.and_hms(12, 0, 0))
+    }
+
+    /// A UTC instant in the league's timezone
+    pub fn local(&self, instant: NaiveDateTime) -> DateTime<Tz> {
+        self.timezone.from_utc_datetime(&instant)
+    }
+
+    fn round_start(&self, round: i32) -> NaiveDateTime {
+        let local = self.start + Duration::days(i64::from(self.round_days * (round - 1)));
+        to_utc(self.timezone, local)
+    }
+}
+
+/// The UTC instant of a local time, the first one when clocks go back
+fn to_utc(timezone: Tz, local: NaiveDateTime) -> NaiveDateTime {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(instant) | LocalResult::Ambiguous(instant, _) => instant.naive_utc(),
+        // Skipped by clocks going forward, the day starts once they did
+        LocalResult::None => to_utc(timezone, local + Duration::hours(1)),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn calendar(timezone: &str, round_days: i32) -> Calendar {
+        Calendar {
+            start: NaiveDate::from_ymd(2020, 3, 2).and_hms(0, 0, 0),
+            timezone: parse_timezone(timezone).unwrap(),
+            round_days,
+        }
+    }
+
+    fn utc(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2020, 3, day).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    fn it_gets_round_windows() {
+        let (start, end) = calendar(DEFAULT_TIMEZONE, 7).round_window(1);
+        assert_eq!(start, utc(2, 0));
+        assert_eq!(end, utc(9, 0));
+        let (start, _) = calendar(DEFAULT_TIMEZONE, 7).round_window(3);
+        assert_eq!(start, utc(16, 0));
+    }
+
+    #[test]
+    fn it_starts_rounds_at_local_midnight() {
+        let (start, end) = calendar("Asia/Tokyo", 14).round_window(1);
+        assert_eq!(start, utc(1, 15));
+        assert_eq!(end, utc(15, 15));
+    }
+
+    #[test]
+    fn it_follows_daylight_saving_changes() {
+        // Clocks went forward in New York on March 8th 2020
+        let (start, end) = calendar("America/New_York", 7).round_window(1);
+        assert_eq!(start, utc(2, 5));
+        assert_eq!(end, utc(9, 4));
+        assert_eq!(end - start, Duration::hours(7 * 24 - 1));
+    }
+
+    #[test]
+    fn it_starts_a_day_skipped_at_midnight_once_clocks_go_forward() {
+        // Clocks went from midnight to 1am in Santiago on September 6th 2020
+        let calendar = Calendar {
+            start: NaiveDate::from_ymd(2020, 9, 6).and_hms(0, 0, 0),
+            ..calendar("America/Santiago", 1)
+        };
+        let (start, _) = calendar.round_window(1);
+        assert_eq!(start, NaiveDate::from_ymd(2020, 9, 6).and_hms(4, 0, 0));
+    }
+
+    #[test]
+    fn it_starts_runs_of_a_date_at_local_noon() {
+        let noon = calendar("America/Los_Angeles", 7).noon(NaiveDate::from_ymd(2020, 3, 2));
+        assert_eq!(noon, utc(2, 20));
+    }
+
+    #[test]
+    fn it_gets_days_of_round_lengths() {
+        assert_eq!(round_days(WEEKLY, None), Some(7));
+        assert_eq!(round_days(BIWEEKLY, Some(3)), Some(14));
+        assert_eq!(round_days(CUSTOM, Some(3)), Some(3));
+        assert_eq!(round_days(CUSTOM, None), None);
+        assert_eq!(round_days("monthly", None), None);
+    }
+
+    #[test]
+    fn it_parses_timezones() {
+        assert_eq!(parse_timezone("Europe/Paris"), Some(Tz::Europe__Paris));
+        assert_eq!(parse_timezone("Mars/Olympus_Mons"), None);
+    }
+}
@@ -9,19 +9,34 @@
 //! multiple processing.
 
 use crate::database::DatabaseConnection;
+use crate::sessions::AuthMode;
 use dotenv::dotenv;
 use serde::Deserialize;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Config {
+    pub auth_mode: Option<AuthMode>,
     pub auth_salt: String,
+    pub cache_ttl: Option<u64>,
     pub database: DatabaseConnection,
     pub database_url: String,
     pub conn_per_pool: u32,
+    pub jwt_expiration: i64,
+    pub jwt_key: String,
+    pub redis_url: Option<String>,
     pub rust_backtrace: u8,
     pub rust_log: String,
+    pub scheduler_interval: Option<u64>,
     pub server: String,
     pub server_workers: usize,
+    pub session_key: String,
+    pub session_name: String,
+    pub session_secure: bool,
+    pub session_timeout: i64,
+    pub strava_client_id: Option<String>,
+    pub strava_client_secret: Option<String>,
+    pub strava_url: Option<String>,
+    pub sync_interval: Option<u64>,
 }
 
 // Throw the Config struct into a CONFIG lazy_static to avoid multiple processing
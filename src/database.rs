@@ -1,5 +1,6 @@
 //! Database-related functions
 use crate::config::{Config, CONFIG};
+use crate::errors::ApiError;
 use actix_web::web;
 use diesel::{
     pg::PgConnection,
@@ -52,3 +53,73 @@ pub fn add_pool(cfg: &mut web::ServiceConfig) {
         InferPool::Postgres(postgres_pool) => cfg.data(postgres_pool),
     };
 }
+
+/// Run a multi-step write inside of one database transaction
+///
+/// Managers should write through the connection handed to `operation`: when
+/// it returns an error every statement is rolled back, so no half-written rows
+/// are left behind.
+pub fn transaction<T, F>(pool: &PoolType, operation: F) -> Result<T, ApiError>
+where
+    F: FnOnce(&PgConnection) -> Result<T, ApiError>,
+{
+    let conn = pool.get()?;
+    conn.transaction(|| operation(&conn))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::calendar::{DEFAULT_ROUND_DAYS, DEFAULT_TIMEZONE};
+    use crate::models::leagues::League;
+    use crate::schema::leagues;
+    use crate::tests::helpers::tests::get_pool;
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    fn league_exists(league_id: Uuid) -> bool {
+        let conn = get_pool().get().unwrap();
+        let count: i64 = leagues::table
+            .filter(leagues::id.eq(league_id))
+            .count()
+            .get_result(&conn)
+            .unwrap();
+        count > 0
+    }
+
+    fn insert_league(conn: &PgConnection) -> Result<Uuid, ApiError> {
+        let league = League {
+            id: Uuid::new_v4(),
+            name: "Transaction League".into(),
+            start: Utc::now().naive_utc(),
+            rounds: 4,
+            current_round: 0,
+            timezone: DEFAULT_TIMEZONE.into(),
+            round_days: DEFAULT_ROUND_DAYS,
+        };
+        diesel::insert_into(leagues::table)
+            .values(&league)
+            .execute(conn)?;
+        Ok(league.id)
+    }
+
+    #[test]
+    fn it_commits_a_transaction() {
+        let league_id = transaction(&get_pool(), insert_league).unwrap();
+        assert!(league_exists(league_id));
+    }
+
+    #[test]
+    fn it_rolls_back_a_failed_transaction() {
+        let mut inserted = None;
+        let response: Result<(), ApiError> = transaction(&get_pool(), |conn| {
+            inserted = Some(insert_league(conn)?);
+            Err(ApiError::InternalServerError(
+                "Failed halfway through".into(),
+            ))
+        });
+        assert!(response.is_err());
+        assert!(!league_exists(inserted.unwrap()));
+    }
+}
@@ -0,0 +1,87 @@
+//! Draft order and auto-pick rules.
+//!
+//! A draft goes through the teams in their draft order once per round. In a
+//! linear draft every round uses the same order, in a snake draft every other
+//! round is reversed so the team picking last in one round picks first in the
+//! next.
+
+use chrono::{Duration, NaiveDateTime};
+use uuid::Uuid;
+
+pub const SNAKE: &str = "snake";
+pub const LINEAR: &str = "linear";
+
+/// Every draft mode
+pub const MODES: [&str; 2] = [SNAKE, LINEAR];
+
+pub const SCHEDULED: &str = "scheduled";
+pub const IN_PROGRESS: &str = "in_progress";
+pub const COMPLETE: &str = "complete";
+
+/// Get the team making every pick of a draft, in order
+pub fn pick_order(team_order: &[Uuid], rounds: i32, mode: &str) -> Vec<Uuid> {
+    (0..rounds.max(0))
+        .flat_map(|round| {
+            let mut round_order = team_order.to_vec();
+            if mode == SNAKE && round % 2 == 1 {
+                round_order.reverse();
+            }
+            round_order
+        })
+        .collect()
+}
+
+/// When the current pick runs out of time
+pub fn pick_deadline(pick_started_at: NaiveDateTime, pick_seconds: i32) -> NaiveDateTime {
+    pick_started_at + Duration::seconds(pick_seconds.into())
+}
+
+/// Pick the highest ranked user of a queue that hasn't been taken yet
+pub fn auto_pick(queue: &[Uuid], taken: &[Uuid]) -> Option<Uuid> {
+    queue
+        .iter()
+        .find(|user_id| !taken.contains(user_id))
+        .copied()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn teams() -> Vec<Uuid> {
+        (0..3).map(|_| Uuid::new_v4()).collect()
+    }
+
+    #[test]
+    fn it_orders_a_linear_draft() {
+        let teams = teams();
+        let order = pick_order(&teams, 2, LINEAR);
+        assert_eq!(order, [teams.clone(), teams].concat());
+    }
+
+    #[test]
+    fn it_orders_a_snake_draft() {
+        let teams = teams();
+        let order = pick_order(&teams, 3, SNAKE);
+        let reversed: Vec<Uuid> = teams.iter().rev().copied().collect();
+        assert_eq!(order, [teams.clone(), reversed, teams].concat());
+    }
+
+    #[test]
+    fn it_gets_pick_deadlines() {
+        let started = NaiveDate::from_ymd(2020, 6, 20).and_hms(12, 0, 0);
+        assert_eq!(
+            pick_deadline(started, 90),
+            NaiveDate::from_ymd(2020, 6, 20).and_hms(12, 1, 30)
+        );
+    }
+
+    #[test]
+    fn it_auto_picks_the_best_available_user() {
+        let queue: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        assert_eq!(auto_pick(&queue, &[queue[0]]), Some(queue[1]));
+        assert_eq!(auto_pick(&queue, &queue), None);
+        assert_eq!(auto_pick(&[], &[]), None);
+    }
+}
@@ -1,3 +1,21 @@
+//! API errors and the JSON they're sent back as.
+//!
+//! Every error response has the same shape:
+//!
+//! ```json
+//! {
+//!   "code": "validation_error",
+//!   "status": 422,
+//!   "message": "name must be between 3 and 100 characters",
+//!   "errors": ["name must be between 3 and 100 characters"],
+//!   "fields": {"name": ["name must be between 3 and 100 characters"]},
+//!   "request_id": "5b2f0e9c-..."
+//! }
+//! ```
+//!
+//! `code` is stable and safe to match on, `fields` is only there for
+//! validation errors and `request_id` matches the `X-Request-Id` header.
+
 use actix_web::{
     error::{BlockingError, ResponseError},
     http::StatusCode,
@@ -9,8 +27,12 @@ use diesel::{
     r2d2::PoolError,
     result::{DatabaseErrorKind, Error as DBError},
 };
+use std::collections::BTreeMap;
 use uuid::parser::ParseError as UuidParseError;
 
+/// Error messages keyed by the path of the field they're about
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
 #[derive(Debug, Display, PartialEq)]
 #[allow(dead_code)]
 pub enum ApiError {
@@ -19,70 +41,163 @@ pub enum ApiError {
     CacheError(String),
     CannotDecodeJwtToken(String),
     CannotEncodeJwtToken(String),
+    Conflict(String),
+    Forbidden(String),
     InternalServerError(String),
+    MethodNotAllowed(String),
     NotFound(String),
     ParseError(String),
+    PayloadTooLarge(String),
     PoolError(String),
+    ProviderError(String),
+    UnsupportedMediaType(String),
     #[display(fmt = "")]
-    ValidationError(Vec<String>),
+    ValidationError(FieldErrors),
     Unauthorized(String),
 }
 
-/// User-friendly error messages
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ErrorResponse {
-    errors: Vec<String>,
-}
+impl ApiError {
+    /// A validation error about a single field
+    pub fn invalid_field(field: &str, message: &str) -> ApiError {
+        let mut errors = FieldErrors::new();
+        errors.insert(field.into(), vec![message.into()]);
+        ApiError::ValidationError(errors)
+    }
 
-/// Automatically convert ApiErrors to external Response Errors
-impl ResponseError for ApiError {
-    fn error_response(&self) -> HttpResponse {
+    /// Machine-readable code of the error
+    pub fn code(&self) -> &'static str {
         match self {
-            ApiError::BadRequest(error) => {
-                HttpResponse::BadRequest().json::<ErrorResponse>(error.into())
-            }
-            ApiError::NotFound(message) => {
-                HttpResponse::NotFound().json::<ErrorResponse>(message.into())
-            }
-            ApiError::ValidationError(errors) => {
-                HttpResponse::UnprocessableEntity().json::<ErrorResponse>(errors.to_vec().into())
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::BlockingError(_) => "service_unavailable",
+            ApiError::CacheError(_) => "cache_unavailable",
+            ApiError::CannotDecodeJwtToken(_) => "invalid_token",
+            ApiError::CannotEncodeJwtToken(_) => "internal_error",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::InternalServerError(_) => "internal_error",
+            ApiError::MethodNotAllowed(_) => "method_not_allowed",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::ParseError(_) => "parse_error",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::PoolError(_) => "database_unavailable",
+            ApiError::ProviderError(_) => "provider_error",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::ValidationError(_) => "validation_error",
+            ApiError::Unauthorized(_) => "unauthorized",
+        }
+    }
+
+    /// Send the error as JSON, tagged with the id of the request that caused it
+    pub fn to_response(&self, request_id: Option<String>) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorResponse::new(self, request_id))
+    }
+
+    /// What users get to see, internal details are left out
+    fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Conflict(message)
+            | ApiError::Forbidden(message)
+            | ApiError::MethodNotAllowed(message)
+            | ApiError::NotFound(message)
+            | ApiError::ParseError(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::ProviderError(message)
+            | ApiError::UnsupportedMediaType(message)
+            | ApiError::Unauthorized(message) => message.to_string(),
+            ApiError::CannotDecodeJwtToken(_) => "Invalid authentication token".into(),
+            ApiError::ValidationError(errors) => errors
+                .values()
+                .flatten()
+                .next()
+                .map_or_else(|| "Invalid request".into(), |message| message.to_string()),
+            ApiError::BlockingError(_) | ApiError::CacheError(_) | ApiError::PoolError(_) => {
+                "The service is unavailable, try again later".into()
             }
-            ApiError::Unauthorized(error) => {
-                HttpResponse::Unauthorized().json::<ErrorResponse>(error.into())
+            ApiError::CannotEncodeJwtToken(_) | ApiError::InternalServerError(_) => {
+                "Something went wrong".into()
             }
-            _ => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
         }
     }
 }
 
-/// Utility to make transforming a string reference into an ErrorResponse
-impl From<&String> for ErrorResponse {
-    fn from(error: &String) -> Self {
+/// User-friendly error messages
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub status: u16,
+    pub message: String,
+    pub errors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<FieldErrors>,
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    pub fn new(error: &ApiError, request_id: Option<String>) -> Self {
+        let message = error.message();
+        let (errors, fields) = match error {
+            ApiError::ValidationError(fields) => (
+                fields.values().flatten().cloned().collect(),
+                Some(fields.clone()),
+            ),
+            _ => (vec![message.clone()], None),
+        };
         ErrorResponse {
-            errors: vec![error.into()],
+            code: error.code().into(),
+            status: error.status_code().as_u16(),
+            message,
+            errors,
+            fields,
+            request_id,
         }
     }
 }
 
-/// Utility to make transforming a vector of strings into an ErrorResponse
-impl From<Vec<String>> for ErrorResponse {
-    fn from(errors: Vec<String>) -> Self {
-        ErrorResponse { errors }
+/// Automatically convert ApiErrors to external Response Errors
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::ParseError(_) => StatusCode::BAD_REQUEST,
+            ApiError::CannotDecodeJwtToken(_) | ApiError::Unauthorized(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::BlockingError(_) | ApiError::CacheError(_) | ApiError::PoolError(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ApiError::ProviderError(_) => StatusCode::BAD_GATEWAY,
+            ApiError::CannotEncodeJwtToken(_) | ApiError::InternalServerError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.to_response(None)
     }
 }
 
 /// Convert DBErrors to ApiErrors
 impl From<DBError> for ApiError {
     fn from(error: DBError) -> ApiError {
-        // Right now we just care about UniqueViolation from diesel
-        // But this would be helpful to easily map errors as our app grows
         match error {
+            DBError::NotFound => ApiError::NotFound("Record not found".into()),
             DBError::DatabaseError(kind, info) => {
-                if let DatabaseErrorKind::UniqueViolation = kind {
-                    let message = info.details().unwrap_or_else(|| info.message()).to_string();
-                    return ApiError::BadRequest(message);
+                let message = info.details().unwrap_or_else(|| info.message()).to_string();
+                match kind {
+                    DatabaseErrorKind::UniqueViolation => ApiError::Conflict(message),
+                    // Every foreign key cascades on delete, so a violation is
+                    // always a row pointing to one that doesn't exist
+                    DatabaseErrorKind::ForeignKeyViolation => ApiError::BadRequest(message),
+                    _ => ApiError::InternalServerError("Unknown database error".into()),
                 }
-                ApiError::InternalServerError("Unknown database error".into())
             }
             _ => ApiError::InternalServerError("Unknown database error".into()),
         }
@@ -118,3 +233,101 @@ impl From<BlockingError<ApiError>> for ApiError {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::managers::teams::tests::create_tournament_without_owner_and_player;
+    use crate::models::teams::Owner;
+    use crate::schema::team_owners;
+    use crate::tests::helpers::tests::get_pool;
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn it_maps_every_error_to_a_status() {
+        let statuses = vec![
+            (ApiError::BadRequest("".into()), 400),
+            (ApiError::ParseError("".into()), 400),
+            (ApiError::Unauthorized("".into()), 401),
+            (ApiError::Forbidden("".into()), 403),
+            (ApiError::NotFound("".into()), 404),
+            (ApiError::MethodNotAllowed("".into()), 405),
+            (ApiError::Conflict("".into()), 409),
+            (ApiError::PayloadTooLarge("".into()), 413),
+            (ApiError::UnsupportedMediaType("".into()), 415),
+            (ApiError::invalid_field("name", ""), 422),
+            (ApiError::InternalServerError("".into()), 500),
+            (ApiError::ProviderError("".into()), 502),
+            (ApiError::PoolError("".into()), 503),
+            (ApiError::BlockingError("".into()), 503),
+        ];
+        for (error, status) in statuses {
+            assert_eq!(error.error_response().status().as_u16(), status);
+        }
+    }
+
+    #[test]
+    fn it_describes_validation_errors_by_field() {
+        let mut fields = FieldErrors::new();
+        fields.insert("name".into(), vec!["too short".into(), "too plain".into()]);
+        let response = ErrorResponse::new(&ApiError::ValidationError(fields), Some("id".into()));
+        assert_eq!(response.code, "validation_error");
+        assert_eq!(response.status, 422);
+        assert_eq!(response.message, "too short");
+        assert_eq!(response.errors.len(), 2);
+        assert_eq!(response.fields.unwrap()["name"].len(), 2);
+        assert_eq!(response.request_id, Some("id".into()));
+    }
+
+    #[test]
+    fn it_hides_internal_details() {
+        let error = ApiError::PoolError("timed out waiting for connection".into());
+        let response = ErrorResponse::new(&error, None);
+        assert_eq!(response.code, "database_unavailable");
+        assert!(!response.message.contains("timed out"));
+    }
+
+    #[test]
+    fn it_maps_missing_rows_to_not_found() {
+        let error: ApiError = DBError::NotFound.into();
+        assert_eq!(error, ApiError::NotFound("Record not found".into()));
+    }
+
+    #[test]
+    fn it_maps_foreign_key_violations() {
+        let team = create_tournament_without_owner_and_player().unwrap();
+        let conn = get_pool().get().unwrap();
+        let error: ApiError = diesel::insert_into(team_owners::table)
+            .values(Owner {
+                id: Uuid::new_v4(),
+                user_id: Uuid::new_v4(),
+                team_id: team.id,
+            })
+            .execute(&conn)
+            .unwrap_err()
+            .into();
+        assert_eq!(error.code(), "bad_request");
+    }
+
+    #[test]
+    fn it_maps_unique_violations_to_conflicts() {
+        let team = create_tournament_without_owner_and_player().unwrap();
+        let conn = get_pool().get().unwrap();
+        let owner = Owner {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            team_id: team.id,
+        };
+        diesel::insert_into(team_owners::table)
+            .values(&owner)
+            .execute(&conn)
+            .unwrap();
+        let error: ApiError = diesel::insert_into(team_owners::table)
+            .values(&owner)
+            .execute(&conn)
+            .unwrap_err()
+            .into();
+        assert_eq!(error.code(), "conflict");
+    }
+}
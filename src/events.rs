@@ -0,0 +1,202 @@
+//! Live activity of leagues, pushed to clients as Server-Sent Events
+//!
+//! Managers publish to the bus once their writes are committed, and every
+//! stream open on the league gets a copy. The bus lives in the process, so
+//! the scheduler, the sync and every server worker share it, but instances
+//! behind a load balancer only see their own events. Nothing is stored:
+//! clients that reconnect should fetch what they show again.
+
+use crate::errors::ApiError;
+use crate::models::drafts::DraftPick;
+use crate::models::matchups::Matchup;
+use crate::models::runs::Run;
+use crate::models::scores::RoundScoresResponse;
+use actix_rt::time::interval;
+use actix_web::web::Bytes;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const MEDIA_TYPE: &str = "text/event-stream";
+
+/// Seconds between comments sent to keep idle streams open
+pub const KEEP_ALIVE_SECONDS: u64 = 15;
+
+lazy_static! {
+    pub static ref BUS: Bus = Bus::default();
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LeagueEvent {
+    RunLogged(Run),
+    ScoreChanged(RoundScoresResponse),
+    MatchupSettled(Matchup),
+    DraftPick(DraftPick),
+}
+
+impl LeagueEvent {
+    /// The event field of the message, which EventSource listeners match on
+    pub fn name(&self) -> &'static str {
+        match self {
+            LeagueEvent::RunLogged(_) => "run-logged",
+            LeagueEvent::ScoreChanged(_) => "score-changed",
+            LeagueEvent::MatchupSettled(_) => "matchup-settled",
+            LeagueEvent::DraftPick(_) => "draft-pick",
+        }
+    }
+
+    fn data(&self) -> Value {
+        let data = match self {
+            LeagueEvent::RunLogged(run) => serde_json::to_value(run),
+            LeagueEvent::ScoreChanged(scores) => serde_json::to_value(scores),
+            LeagueEvent::MatchupSettled(matchup) => serde_json::to_value(matchup),
+            LeagueEvent::DraftPick(pick) => serde_json::to_value(pick),
+        };
+        data.unwrap_or(Value::Null)
+    }
+
+    /// The event as an SSE message, its data being JSON on a single line
+    pub fn message(&self) -> String {
+        format!("event: {}\ndata: {}\n\n", self.name(), self.data())
+    }
+}
+
+/// A comment, ignored by clients, to keep proxies from closing a stream
+pub fn keep_alive() -> String {
+    ": keep-alive\n\n".into()
+}
+
+type Subscribers = HashMap<Uuid, Vec<UnboundedSender<LeagueEvent>>>;
+
+/// Streams subscribed to each league
+#[derive(Default)]
+pub struct Bus {
+    subscribers: Mutex<Subscribers>,
+}
+
+impl Bus {
+    /// Get every event of a league published from now on
+    ///
+    /// Streams of the league that were closed are dropped first, so clients
+    /// reconnecting to a quiet league don't pile them up.
+    pub fn subscribe(&self, league_id: Uuid) -> UnboundedReceiver<LeagueEvent> {
+        let (sender, receiver) = unbounded();
+        let mut subscribers = self.subscribers();
+        let senders = subscribers.entry(league_id).or_default();
+        senders.retain(|sender| !sender.is_closed());
+        senders.push(sender);
+        receiver
+    }
+
+    /// Send an event to every stream of a league
+    ///
+    /// Streams that were closed are dropped along the way.
+    pub fn publish(&self, league_id: Uuid, event: LeagueEvent) {
+        let mut subscribers = self.subscribers();
+        if let Some(senders) = subscribers.get_mut(&league_id) {
+            senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+            if senders.is_empty() {
+                subscribers.remove(&league_id);
+            }
+        }
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Subscribers> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A league's events as SSE messages, with a keep-alive comment first and
+/// every KEEP_ALIVE_SECONDS seconds
+///
+/// The stream never ends on its own, it's unsubscribed once the client goes
+/// away and the response is dropped.
+pub fn stream(league_id: Uuid) -> impl Stream<Item = Result<Bytes, ApiError>> {
+    let events = BUS.subscribe(league_id).map(|event| event.message());
+    let keep_alives = interval(Duration::from_secs(KEEP_ALIVE_SECONDS)).map(|_| keep_alive());
+    stream::select(events, keep_alives).map(|message| Ok(Bytes::from(message)))
+}
+
+/// Publish an event to the streams of a league
+pub fn publish(league_id: Uuid, event: LeagueEvent) {
+    BUS.publish(league_id, event);
+}
+
+/// Publish several events to the streams of a league, in order
+pub fn publish_all<I>(league_id: Uuid, events: I)
+where
+    I: IntoIterator<Item = LeagueEvent>,
+{
+    for event in events {
+        publish(league_id, event);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::models::matchups::tests::matchup;
+
+    fn subscriber_count(bus: &Bus, league_id: Uuid) -> usize {
+        bus.subscribers()
+            .get(&league_id)
+            .map_or(0, |senders| senders.len())
+    }
+
+    #[test]
+    fn it_sends_events_to_the_streams_of_a_league() {
+        let bus = Bus::default();
+        let league_id = Uuid::new_v4();
+        let mut first = bus.subscribe(league_id);
+        let mut second = bus.subscribe(league_id);
+        let mut other = bus.subscribe(Uuid::new_v4());
+
+        let event = LeagueEvent::MatchupSettled(matchup(Some(10.0), Some(8.5)));
+        bus.publish(league_id, event.clone());
+        assert_eq!(first.try_next().unwrap(), Some(event.clone()));
+        assert_eq!(second.try_next().unwrap(), Some(event));
+        assert!(other.try_next().is_err());
+    }
+
+    #[test]
+    fn it_drops_closed_streams() {
+        let bus = Bus::default();
+        let league_id = Uuid::new_v4();
+        let open = bus.subscribe(league_id);
+        drop(bus.subscribe(league_id));
+        assert_eq!(subscriber_count(&bus, league_id), 2);
+
+        bus.publish(league_id, LeagueEvent::MatchupSettled(matchup(None, None)));
+        assert_eq!(subscriber_count(&bus, league_id), 1);
+        drop(open);
+        bus.publish(league_id, LeagueEvent::MatchupSettled(matchup(None, None)));
+        assert_eq!(subscriber_count(&bus, league_id), 0);
+    }
+
+    #[test]
+    fn it_drops_closed_streams_when_subscribing() {
+        let bus = Bus::default();
+        let league_id = Uuid::new_v4();
+        for _ in 0..3 {
+            drop(bus.subscribe(league_id));
+        }
+        let _open = bus.subscribe(league_id);
+        assert_eq!(subscriber_count(&bus, league_id), 1);
+    }
+
+    #[test]
+    fn it_writes_sse_messages() {
+        let settled = matchup(Some(10.0), Some(8.5));
+        let message = LeagueEvent::MatchupSettled(settled.clone()).message();
+        assert!(message.starts_with("event: matchup-settled\ndata: {"));
+        assert!(message.ends_with("}\n\n"));
+        assert_eq!(message.trim_end().lines().count(), 2);
+        assert!(message.contains(&format!("\"id\":\"{}\"", settled.id)));
+    }
+}
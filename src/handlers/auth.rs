@@ -0,0 +1,119 @@
+use crate::auth::{create_jwt, PrivateClaim};
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::{respond_json, respond_ok};
+use crate::managers::users::find_by_auth;
+use crate::middleware::auth::bearer_token;
+use crate::models::sessions::{Session, SessionResponse, SessionsResponse};
+use crate::models::users::{AuthUser, LoginRequest, LoginResponse};
+use crate::pagination::ListResponse;
+use crate::sessions::Sessions;
+use crate::validate::validate;
+use actix_identity::Identity;
+use actix_web::http::header::USER_AGENT;
+use actix_web::web::{block, Data, HttpResponse, Json, Path};
+use actix_web::HttpRequest;
+use uuid::Uuid;
+
+/// Login a user
+/// Create a JWT, or a session when sessions are enabled, remember it in the
+/// identity cookie and return it for clients that send it as a bearer token
+pub async fn login(
+    id: Identity,
+    req: HttpRequest,
+    pool: Data<PoolType>,
+    sessions: Data<Sessions>,
+    params: Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    validate(&params)?;
+
+    // Validate that the email + password matches
+    let user = block(move || find_by_auth(&pool, &params.email, &params.password)).await?;
+
+    // Create a session or a JWT
+    let token = if sessions.is_enabled() {
+        let auth_user = AuthUser {
+            id: user.id,
+            email: user.email.clone(),
+        };
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|header| header.to_str().ok())
+            .map(String::from);
+        block(move || sessions.create(&auth_user, user_agent)).await?
+    } else {
+        let private_claim = PrivateClaim::new(user.id, user.email.clone());
+        create_jwt(private_claim)?
+    };
+
+    // Remember the token
+    id.remember(token.clone());
+    respond_json(LoginResponse { token, user })
+}
+
+/// Logout a user
+/// Forget the token stored in their identity cookie, and destroy its session
+pub async fn logout(
+    id: Identity,
+    req: HttpRequest,
+    sessions: Data<Sessions>,
+) -> Result<HttpResponse, ApiError> {
+    let token = bearer_token(req.headers()).or_else(|| id.identity());
+    if let (Some(token), true) = (token, sessions.is_enabled()) {
+        block(move || sessions.destroy(&token)).await?;
+    }
+    id.forget();
+    respond_ok()
+}
+
+/// Get the sessions of the logged in user
+pub async fn get_sessions(
+    auth_user: AuthUser,
+    req: HttpRequest,
+    sessions: Data<Sessions>,
+) -> Result<Json<SessionsResponse>, ApiError> {
+    let current_id = current_session_id(&req, &sessions)?;
+    let sessions = block(move || sessions.find_all(auth_user.id)).await?;
+    let total = sessions.len() as i64;
+    let data = sessions
+        .into_iter()
+        .map(|session| SessionResponse::new(session, current_id))
+        .collect();
+    respond_json(ListResponse::new(data, total, 0))
+}
+
+/// Revoke one of the logged in user's sessions
+pub async fn delete_session(
+    auth_user: AuthUser,
+    req: HttpRequest,
+    session_id: Path<Uuid>,
+    sessions: Data<Sessions>,
+) -> Result<HttpResponse, ApiError> {
+    current_session_id(&req, &sessions)?;
+    block(move || sessions.revoke(auth_user.id, *session_id)).await?;
+    respond_ok()
+}
+
+/// Log the logged in user out everywhere by destroying all of their sessions
+pub async fn delete_sessions(
+    id: Identity,
+    auth_user: AuthUser,
+    req: HttpRequest,
+    sessions: Data<Sessions>,
+) -> Result<HttpResponse, ApiError> {
+    current_session_id(&req, &sessions)?;
+    block(move || sessions.destroy_all(auth_user.id)).await?;
+    id.forget();
+    respond_ok()
+}
+
+/// Id of the session the request was made with, sessions have to be enabled
+fn current_session_id(req: &HttpRequest, sessions: &Sessions) -> Result<Option<Uuid>, ApiError> {
+    if !sessions.is_enabled() {
+        return Err(ApiError::NotFound(
+            "Sessions are only kept with AUTH_MODE=session".into(),
+        ));
+    }
+    Ok(req.extensions().get::<Session>().map(|session| session.id))
+}
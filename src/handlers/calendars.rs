@@ -0,0 +1,18 @@
+use crate::auth::decode_calendar_token;
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::ical;
+use crate::managers::leagues::find_calendar;
+use actix_web::web::{block, Data, HttpResponse, Path};
+
+/// Get the calendar feed of a link's token, no login needed
+pub async fn get_calendar(
+    token: Path<String>,
+    pool: Data<PoolType>,
+) -> Result<HttpResponse, ApiError> {
+    let claim = decode_calendar_token(&token)?;
+    let calendar = block(move || find_calendar(&pool, claim.league_id, claim.team_id)).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ical::MEDIA_TYPE)
+        .body(calendar))
+}
@@ -0,0 +1,217 @@
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::respond_json;
+use crate::managers::drafts::{create, find, get_queue, pick, set_queue, start};
+use crate::models::drafts::{
+    CreateDraftRequest, DraftDetails, DraftQueueRequest, DraftQueueResponse, PickRequest,
+};
+use crate::models::users::AuthUser;
+use crate::permissions::{ensure_commissioner, ensure_team_owner};
+use crate::validate::validate;
+use actix_web::web::{block, Data, Json, Path};
+use uuid::Uuid;
+
+/// Get a league's draft
+pub async fn get_draft(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<DraftDetails>, ApiError> {
+    let draft = block(move || find(&pool, *league_id)).await?;
+    respond_json(draft)
+}
+
+/// Create a league's draft
+/// Only the league's commissioners can create its draft
+pub async fn create_draft(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<CreateDraftRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<DraftDetails>, ApiError> {
+    validate(&params)?;
+
+    let draft = block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        create(
+            &pool,
+            *league_id,
+            &params.mode,
+            params.rounds,
+            params.pick_seconds,
+            params.order.clone(),
+        )
+    })
+    .await?;
+    respond_json(draft)
+}
+
+/// Start a league's draft
+/// Only the league's commissioners can start its draft
+pub async fn start_draft(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<DraftDetails>, ApiError> {
+    let draft = block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        start(&pool, *league_id)
+    })
+    .await?;
+    respond_json(draft)
+}
+
+/// Pick a user for the team on the clock
+/// Only the team's owners can pick for it
+pub async fn create_pick(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<PickRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<DraftDetails>, ApiError> {
+    let draft = block(move || {
+        ensure_team_owner(&pool, &auth_user, params.team_id)?;
+        pick(&pool, *league_id, params.team_id, params.user_id)
+    })
+    .await?;
+    respond_json(draft)
+}
+
+/// Get the users a team wants to pick
+/// Only the team's owners can see its queue
+pub async fn get_draft_queue(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<DraftQueueResponse>, ApiError> {
+    let (league_id, team_id) = path.into_inner();
+    let queue = block(move || {
+        ensure_team_owner(&pool, &auth_user, team_id)?;
+        get_queue(&pool, league_id, team_id)
+    })
+    .await?;
+    respond_json(queue)
+}
+
+/// Rank the users a team wants to pick, best first
+/// Only the team's owners can change its queue
+pub async fn update_draft_queue(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    params: Json<DraftQueueRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<DraftQueueResponse>, ApiError> {
+    validate(&params)?;
+
+    let (league_id, team_id) = path.into_inner();
+    let queue = block(move || {
+        ensure_team_owner(&pool, &auth_user, team_id)?;
+        set_queue(&pool, league_id, team_id, &params.user_ids)
+    })
+    .await?;
+    respond_json(queue)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::handlers::users::tests::get_auth_user;
+    use crate::managers::drafts::tests::start_draft as model_start_draft;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::managers::users::tests::create_user;
+    use crate::models::drafts::tests::create_draft_request;
+    use crate::tests::helpers::tests::get_data_pool;
+
+    fn owner_of(team: &crate::models::teams::TeamDetails) -> AuthUser {
+        get_auth_user(team.owners.as_ref().unwrap()[0].user_id)
+    }
+
+    #[actix_rt::test]
+    async fn it_creates_and_starts_a_draft_as_commissioner() {
+        let (league_id, _) = create_league_with_teams(2);
+        let path: Path<Uuid> = league_id.into();
+        create_draft(
+            path,
+            get_data_pool(),
+            Json(create_draft_request(None)),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+
+        let path: Path<Uuid> = league_id.into();
+        let response = start_draft(path, get_data_pool(), get_auth_user(Uuid::nil()))
+            .await
+            .unwrap();
+        assert!(response.into_inner().on_the_clock.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_draft_as_non_commissioner() {
+        let (league_id, _) = create_league_with_teams(2);
+        let path: Path<Uuid> = league_id.into();
+        let response = create_draft(
+            path,
+            get_data_pool(),
+            Json(create_draft_request(None)),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            league_id
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_picks_as_team_owner() {
+        let (league_id, teams) = model_start_draft();
+        let user_id = create_user().unwrap().id;
+        let params = PickRequest {
+            team_id: teams[0].id,
+            user_id,
+        };
+        let path: Path<Uuid> = league_id.into();
+        let response = create_pick(path, get_data_pool(), Json(params), owner_of(&teams[0]))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().picks[0].user_id, Some(user_id));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_pick_for_another_owners_team() {
+        let (league_id, teams) = model_start_draft();
+        let params = PickRequest {
+            team_id: teams[0].id,
+            user_id: create_user().unwrap().id,
+        };
+        let path: Path<Uuid> = league_id.into();
+        let response = create_pick(path, get_data_pool(), Json(params), owner_of(&teams[1])).await;
+        let expected_error =
+            ApiError::Forbidden(format!("Only an owner can change team {}", teams[0].id));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_updates_and_gets_a_draft_queue() {
+        let (league_id, teams) = model_start_draft();
+        let user_ids = vec![create_user().unwrap().id];
+        let path: Path<(Uuid, Uuid)> = (league_id, teams[1].id).into();
+        update_draft_queue(
+            path,
+            get_data_pool(),
+            Json(DraftQueueRequest {
+                user_ids: user_ids.clone(),
+            }),
+            owner_of(&teams[1]),
+        )
+        .await
+        .unwrap();
+
+        let path: Path<(Uuid, Uuid)> = (league_id, teams[1].id).into();
+        let response = get_draft_queue(path, get_data_pool(), owner_of(&teams[1]))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().user_ids, user_ids);
+    }
+}
@@ -1,32 +1,71 @@
+use crate::cache::{league_group, Cache, LEAGUES};
 use crate::database::PoolType;
 use crate::errors::ApiError;
+use crate::events;
 use crate::helpers::{respond_json, respond_ok};
-use crate::managers::leagues::{create, delete, find_with_details, get_all_details, update};
+use crate::ical;
+use crate::managers::leagues::{
+    close_round, create, delete, find_calendar, find_calendar_link, find_round_scores, find_rounds,
+    find_with_details, get_all_details, rescore, score_round, update,
+};
+use crate::managers::teams::get_standings;
 use crate::models::leagues::{
-    CreateLeagueRequest, League, LeagueDetails, LeaguesResponse, NewLeague, NewRuleset, Ruleset,
-    UpdateLeague, UpdateLeagueRequest, UpdateRuleset,
+    CalendarLinkResponse, Commissioner, CreateLeagueRequest, League, LeagueDetails,
+    LeaguesResponse, NewLeague, NewRuleset, RoundsResponse, Ruleset, UpdateLeague,
+    UpdateLeagueRequest, UpdateRuleset,
 };
+use crate::models::scores::RoundScoresResponse;
+use crate::models::teams::StandingsResponse;
+use crate::models::users::AuthUser;
+use crate::pagination::ListParams;
+use crate::permissions::ensure_commissioner;
+use crate::validate::validate;
 use actix_web::web::{block, Data, HttpResponse, Json, Path};
 use chrono::NaiveDateTime;
 use uuid::Uuid;
 
+/// Get a league, cached until it changes
 pub async fn get_league(
     league_id: Path<Uuid>,
     pool: Data<PoolType>,
+    cache: Data<Cache>,
 ) -> Result<Json<LeagueDetails>, ApiError> {
-    let league: LeagueDetails = block(move || find_with_details(&pool, *league_id)).await?;
+    let league: LeagueDetails = block(move || {
+        let group = league_group(*league_id);
+        cache.cached(&group, &[&group], || find_with_details(&pool, *league_id))
+    })
+    .await?;
     respond_json(league)
 }
 
-pub async fn get_leagues(pool: Data<PoolType>) -> Result<Json<LeaguesResponse>, ApiError> {
-    let leagues: LeaguesResponse = block(move || get_all_details(&pool)).await?;
+/// Get a page of leagues, cached until any league changes
+pub async fn get_leagues(
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    params: ListParams,
+) -> Result<Json<LeaguesResponse>, ApiError> {
+    let leagues: LeaguesResponse = block(move || {
+        let key = format!(
+            "{}:{}",
+            LEAGUES,
+            serde_json::to_string(&params).unwrap_or_default()
+        );
+        cache.cached(&key, &[LEAGUES], || get_all_details(&pool, &params))
+    })
+    .await?;
     respond_json(leagues)
 }
 
+/// Create a league
+/// The user creating the league becomes its commissioner
 pub async fn create_league(
     pool: Data<PoolType>,
+    cache: Data<Cache>,
     params: Json<CreateLeagueRequest>,
+    auth_user: AuthUser,
 ) -> Result<Json<LeagueDetails>, ApiError> {
+    validate(&params)?;
+
     let new_league_id = Uuid::new_v4();
     let date_time_str = [params.start.clone(), " 00:00:00".to_string()].concat();
     let league_start = NaiveDateTime::parse_from_str(&date_time_str, "%Y-%m-%d %H:%M:%S")?;
@@ -37,6 +76,8 @@ pub async fn create_league(
         start: league_start,
         rounds: params.rounds.into(),
         current_round: 0,
+        timezone: params.timezone.clone(),
+        round_days: params.round_days(),
     }
     .into();
 
@@ -45,18 +86,39 @@ pub async fn create_league(
         id: new_ruleset_id,
         league_id: new_league_id,
         points_per_mile: params.points_per_mile.into(),
+        pace_bonus_target: params.pace_bonus_target,
+        pace_bonus_per_mile: params.pace_bonus_per_mile,
+        points_per_100_feet: params.points_per_100_feet,
+        min_run_distance: params.min_run_distance,
+        daily_mileage_cap: params.daily_mileage_cap,
+        long_run_distance: params.long_run_distance,
+        long_run_multiplier: params.long_run_multiplier,
+        tie_breakers: params.tie_breakers.clone(),
     }
     .into();
 
-    let league = block(move || create(&pool, &new_league, &new_ruleset)).await?;
+    let new_commissioner = Commissioner {
+        id: Uuid::new_v4(),
+        user_id: auth_user.id,
+        league_id: new_league_id,
+    };
+
+    let league =
+        block(move || create(&pool, &cache, &new_league, &new_ruleset, &new_commissioner)).await?;
     respond_json(league)
 }
 
+/// Update a league and its ruleset, rescoring the rounds already played
+/// Only the league's commissioners can update it
 pub async fn update_league(
     league_id: Path<Uuid>,
     pool: Data<PoolType>,
+    cache: Data<Cache>,
     params: Json<UpdateLeagueRequest>,
+    auth_user: AuthUser,
 ) -> Result<Json<LeagueDetails>, ApiError> {
+    validate(&params)?;
+
     let date_time_str = [params.start.clone(), " 00:00:00".to_string()].concat();
     let league_start = NaiveDateTime::parse_from_str(&date_time_str, "%Y-%m-%d %H:%M:%S")?;
 
@@ -65,51 +127,192 @@ pub async fn update_league(
         name: params.name.to_string(),
         start: league_start,
         rounds: params.rounds.into(),
-        current_round: 0,
+        timezone: params.timezone.clone(),
+        round_days: params.round_days(),
     };
 
     let update_ruleset = UpdateRuleset {
         points_per_mile: params.points_per_mile.into(),
+        pace_bonus_target: params.pace_bonus_target,
+        pace_bonus_per_mile: params.pace_bonus_per_mile,
+        points_per_100_feet: params.points_per_100_feet,
+        min_run_distance: params.min_run_distance,
+        daily_mileage_cap: params.daily_mileage_cap,
+        long_run_distance: params.long_run_distance,
+        long_run_multiplier: params.long_run_multiplier,
+        tie_breakers: params.tie_breakers.clone(),
     };
 
-    let league = block(move || update(&pool, &update_league, &update_ruleset)).await?;
+    let league = block(move || {
+        ensure_commissioner(&pool, &auth_user, update_league.id)?;
+        let league = update(&pool, &cache, &update_league, &update_ruleset)?;
+        rescore(&pool, &cache, league.id)?;
+        Ok(league)
+    })
+    .await?;
     respond_json(league)
 }
 
+/// Delete a league
+/// Only the league's commissioners can delete it
 pub async fn delete_league(
     league_id: Path<Uuid>,
     pool: Data<PoolType>,
+    cache: Data<Cache>,
+    auth_user: AuthUser,
 ) -> Result<HttpResponse, ApiError> {
-    block(move || delete(&pool, *league_id)).await?;
+    block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        delete(&pool, &cache, *league_id)
+    })
+    .await?;
     respond_ok()
 }
 
+/// Get a league's standings, cached until its scores or records change
+pub async fn get_league_standings(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+) -> Result<Json<StandingsResponse>, ApiError> {
+    let standings: StandingsResponse = block(move || {
+        let group = league_group(*league_id);
+        let key = format!("{}:standings", group);
+        cache.cached(&key, &[&group], || get_standings(&pool, *league_id))
+    })
+    .await?;
+    respond_json(standings)
+}
+
+/// Get when each round of a league starts and ends
+pub async fn get_league_rounds(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<RoundsResponse>, ApiError> {
+    let rounds = block(move || find_rounds(&pool, *league_id)).await?;
+    respond_json(rounds)
+}
+
+/// Get a league's rounds and matchups as an iCalendar feed
+pub async fn get_league_calendar(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<HttpResponse, ApiError> {
+    let calendar = block(move || find_calendar(&pool, *league_id, None)).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ical::MEDIA_TYPE)
+        .body(calendar))
+}
+
+/// Get the link calendar apps subscribe to a league's calendar with
+pub async fn get_league_calendar_link(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<CalendarLinkResponse>, ApiError> {
+    let link = block(move || find_calendar_link(&pool, *league_id, None)).await?;
+    respond_json(link)
+}
+
+/// Stream a league's runs, scores, settled matchups and draft picks as
+/// Server-Sent Events while they happen
+pub async fn get_league_events(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<HttpResponse, ApiError> {
+    let league_id = league_id.into_inner();
+    block(move || find_with_details(&pool, league_id)).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(events::MEDIA_TYPE)
+        .header("Cache-Control", "no-cache")
+        .streaming(events::stream(league_id)))
+}
+
+/// Get the scores of a round
+pub async fn get_round_scores(
+    path: Path<(Uuid, i32)>,
+    pool: Data<PoolType>,
+) -> Result<Json<RoundScoresResponse>, ApiError> {
+    let (league_id, round) = path.into_inner();
+    let scores = block(move || find_round_scores(&pool, league_id, round)).await?;
+    respond_json(scores)
+}
+
+/// Score a round, replacing any scores it already has
+/// Only the league's commissioners can score a round
+pub async fn score_league_round(
+    path: Path<(Uuid, i32)>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    auth_user: AuthUser,
+) -> Result<Json<RoundScoresResponse>, ApiError> {
+    let (league_id, round) = path.into_inner();
+    let scores = block(move || {
+        ensure_commissioner(&pool, &auth_user, league_id)?;
+        score_round(&pool, &cache, league_id, round)
+    })
+    .await?;
+    respond_json(scores)
+}
+
+/// Close the next round of a league, settling its matchups
+/// Only the league's commissioners can close a round
+pub async fn close_league_round(
+    path: Path<(Uuid, i32)>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    auth_user: AuthUser,
+) -> Result<Json<LeagueDetails>, ApiError> {
+    let (league_id, round) = path.into_inner();
+    let league = block(move || {
+        ensure_commissioner(&pool, &auth_user, league_id)?;
+        close_round(&pool, &cache, league_id, round)
+    })
+    .await?;
+    respond_json(league)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::cache::tests::get_data_cache;
+    use crate::handlers::users::tests::get_auth_user;
+    use crate::managers::leagues::is_commissioner;
+    use crate::managers::leagues::tests::create_league_with_teams;
     use crate::tests::helpers::tests::{get_data_pool, get_pool};
+    use actix_web::body::{Body, ResponseBody};
+    use actix_web::web::Bytes;
+    use futures::StreamExt;
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M:%S").unwrap()
+    }
 
     pub fn get_all_leagues() -> LeaguesResponse {
         let pool = get_pool();
-        get_all_details(&pool).unwrap()
+        get_all_details(&pool, &ListParams::default()).unwrap()
     }
 
     pub fn get_first_leagues_id() -> Uuid {
-        get_all_leagues().0[0].id
+        get_all_leagues().data[0].id
     }
 
     #[actix_rt::test]
     async fn it_gets_all_leagues() {
-        let response = get_leagues(get_data_pool()).await;
+        let response = get_leagues(get_data_pool(), get_data_cache(), ListParams::default()).await;
         assert!(response.is_ok());
-        assert_eq!(response.unwrap().into_inner().0[0], get_all_leagues().0[0]);
+        assert_eq!(
+            response.unwrap().into_inner().data[0],
+            get_all_leagues().data[0]
+        );
     }
 
     #[actix_rt::test]
     async fn it_gets_a_league() {
-        let first_league = &get_all_leagues().0[0];
+        let first_league = &get_all_leagues().data[0];
         let league_id: Path<Uuid> = get_first_leagues_id().into();
-        let response = get_league(league_id, get_data_pool()).await.unwrap();
+        let response = get_league(league_id, get_data_pool(), get_data_cache())
+            .await
+            .unwrap();
         assert_eq!(response.into_inner(), *first_league);
     }
 
@@ -117,9 +320,479 @@ pub mod tests {
     async fn it_doesnt_find_a_league() {
         let uuid = Uuid::new_v4();
         let league_id: Path<Uuid> = uuid.into();
-        let response = get_league(league_id, get_data_pool()).await;
+        let response = get_league(league_id, get_data_pool(), get_data_cache()).await;
         let expected_error = ApiError::NotFound(format!("League {} not found", uuid.to_string()));
         assert!(response.is_err());
         assert_eq!(response.unwrap_err(), expected_error);
     }
+
+    #[actix_rt::test]
+    async fn it_caches_a_league_until_it_changes() {
+        use crate::schema::leagues;
+        use diesel::prelude::*;
+
+        let (league_id, _) = create_league_with_teams(2);
+        let cache = get_data_cache();
+        let cached = get_league(league_id.into(), get_data_pool(), cache.clone())
+            .await
+            .unwrap()
+            .into_inner();
+
+        // Changes that skip the managers aren't seen until the league changes
+        diesel::update(leagues::table.filter(leagues::id.eq(league_id)))
+            .set(leagues::name.eq("Renamed Behind The Cache"))
+            .execute(&get_pool().get().unwrap())
+            .unwrap();
+        let response = get_league(league_id.into(), get_data_pool(), cache.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner(), cached);
+
+        update_league(
+            league_id.into(),
+            get_data_pool(),
+            cache.clone(),
+            Json(update_league_request()),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        let response = get_league(league_id.into(), get_data_pool(), cache)
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().name, "Handler Test League Updated");
+    }
+
+    pub fn create_league_request() -> CreateLeagueRequest {
+        CreateLeagueRequest {
+            name: "Handler Test League".into(),
+            start: "2020-05-04".into(),
+            rounds: 8,
+            timezone: "UTC".into(),
+            round_length: "weekly".into(),
+            round_days: None,
+            points_per_mile: 10,
+            pace_bonus_target: None,
+            pace_bonus_per_mile: 0,
+            points_per_100_feet: 0,
+            min_run_distance: 0.0,
+            daily_mileage_cap: None,
+            long_run_distance: None,
+            long_run_multiplier: 1.0,
+            tie_breakers: vec!["head_to_head".into(), "total_points".into()],
+        }
+    }
+
+    pub fn update_league_request() -> UpdateLeagueRequest {
+        UpdateLeagueRequest {
+            name: "Handler Test League Updated".into(),
+            start: "2020-05-11".into(),
+            rounds: 10,
+            timezone: "UTC".into(),
+            round_length: "weekly".into(),
+            round_days: None,
+            points_per_mile: 20,
+            pace_bonus_target: Some(480),
+            pace_bonus_per_mile: 5,
+            points_per_100_feet: 2,
+            min_run_distance: 1.0,
+            daily_mileage_cap: Some(15.0),
+            long_run_distance: Some(10.0),
+            long_run_multiplier: 1.5,
+            tie_breakers: vec!["total_miles".into()],
+        }
+    }
+
+    #[actix_rt::test]
+    async fn it_creates_a_league_with_a_commissioner() {
+        let auth_user = get_auth_user(Uuid::nil());
+        let response = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(create_league_request()),
+            auth_user,
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert!(is_commissioner(&get_pool(), response.id, Uuid::nil()).unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn it_updates_a_league_as_commissioner() {
+        let created = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(create_league_request()),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        let league_id: Path<Uuid> = created.id.into();
+        let response = update_league(
+            league_id,
+            get_data_pool(),
+            get_data_cache(),
+            Json(update_league_request()),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        let updated = response.into_inner();
+        assert_eq!(updated.points_per_mile, 20);
+        assert_eq!(updated.pace_bonus_target, Some(480));
+        assert_eq!(updated.long_run_multiplier, 1.5);
+    }
+
+    #[test]
+    fn it_needs_the_calendar_of_a_league_to_update_it() {
+        for field in &["timezone", "round_length"] {
+            let mut params = serde_json::to_value(update_league_request()).unwrap();
+            params.as_object_mut().unwrap().remove(*field);
+            assert!(serde_json::from_value::<UpdateLeagueRequest>(params).is_err());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_league_with_invalid_rules() {
+        let mut params = create_league_request();
+        params.long_run_multiplier = 10.0;
+        let response = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error = ApiError::invalid_field(
+            "long_run_multiplier",
+            "long_run_multiplier must be between 1 and 5",
+        );
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_update_a_league_as_non_commissioner() {
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        let response = update_league(
+            league_id,
+            get_data_pool(),
+            get_data_cache(),
+            Json(update_league_request()),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            Uuid::nil()
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_deletes_a_league_as_commissioner() {
+        let created = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(create_league_request()),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        let league_id: Path<Uuid> = created.id.into();
+        delete_league(
+            league_id,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        assert!(find_with_details(&get_pool(), created.id).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_delete_a_league_as_non_commissioner() {
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        let response = delete_league(
+            league_id,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        assert!(response.is_err());
+        assert!(find_with_details(&get_pool(), Uuid::nil()).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn it_scores_a_round_as_commissioner() {
+        let path: Path<(Uuid, i32)> = (Uuid::nil(), 1).into();
+        let scored = score_league_round(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        let path: Path<(Uuid, i32)> = (Uuid::nil(), 1).into();
+        let response = get_round_scores(path, get_data_pool()).await.unwrap();
+        assert_eq!(response.into_inner().teams.len(), scored.teams.len());
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_score_a_round_as_non_commissioner() {
+        let path: Path<(Uuid, i32)> = (Uuid::nil(), 1).into();
+        let response = score_league_round(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            Uuid::nil()
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_closes_a_round_as_commissioner() {
+        let (league_id, _) = create_league_with_teams(3);
+        let path: Path<(Uuid, i32)> = (league_id, 1).into();
+        let response = close_league_round(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.into_inner().current_round, 1);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_close_a_round_as_non_commissioner() {
+        let (league_id, _) = create_league_with_teams(2);
+        let path: Path<(Uuid, i32)> = (league_id, 1).into();
+        let response = close_league_round(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        assert!(response.is_err());
+        assert_eq!(
+            find_with_details(&get_pool(), league_id)
+                .unwrap()
+                .current_round,
+            0
+        );
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_league_standings() {
+        let (league_id, league_teams) = create_league_with_teams(3);
+        let league_id: Path<Uuid> = league_id.into();
+        let response = get_league_standings(league_id, get_data_pool(), get_data_cache())
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().0.len(), league_teams.len());
+    }
+
+    #[actix_rt::test]
+    async fn it_caches_standings_until_a_round_is_scored() {
+        use crate::schema::teams;
+        use diesel::prelude::*;
+
+        let (league_id, league_teams) = create_league_with_teams(2);
+        let cache = get_data_cache();
+        let cached = get_league_standings(league_id.into(), get_data_pool(), cache.clone())
+            .await
+            .unwrap()
+            .into_inner();
+
+        // Changes that skip the managers aren't seen until the scores change
+        diesel::update(teams::table.filter(teams::id.eq(league_teams[0].id)))
+            .set(teams::name.eq("Renamed Behind The Cache"))
+            .execute(&get_pool().get().unwrap())
+            .unwrap();
+        let response = get_league_standings(league_id.into(), get_data_pool(), cache.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner(), cached);
+
+        let path: Path<(Uuid, i32)> = (league_id, 1).into();
+        score_league_round(
+            path,
+            get_data_pool(),
+            cache.clone(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        let standings = get_league_standings(league_id.into(), get_data_pool(), cache)
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(standings
+            .0
+            .iter()
+            .any(|standing| standing.name == "Renamed Behind The Cache"));
+    }
+
+    #[actix_rt::test]
+    async fn it_creates_a_league_with_a_calendar() {
+        let mut params = create_league_request();
+        params.timezone = "America/Denver".into();
+        params.round_length = "custom".into();
+        params.round_days = Some(10);
+        let created = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert_eq!(created.timezone, "America/Denver");
+        assert_eq!(created.round_days, 10);
+
+        let response = get_league_rounds(created.id.into(), get_data_pool())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.rounds.len(), 8);
+        let first = &response.rounds[0];
+        assert_eq!(first.start, at("2020-05-04T06:00:00"));
+        assert_eq!(first.end, at("2020-05-14T06:00:00"));
+        assert_eq!(response.rounds[1].start, first.end);
+        assert!(!first.closed);
+    }
+
+    async fn next_message(body: &mut ResponseBody<Body>) -> Bytes {
+        body.next().await.unwrap().unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn it_streams_a_leagues_events() {
+        let (league_id, _) = create_league_with_teams(3);
+        let mut response = get_league_events(league_id.into(), get_data_pool())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            events::MEDIA_TYPE
+        );
+        let mut body = response.take_body();
+        assert_eq!(next_message(&mut body).await, events::keep_alive());
+
+        let path: Path<(Uuid, i32)> = (league_id, 1).into();
+        close_league_round(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        assert!(next_message(&mut body)
+            .await
+            .starts_with(b"event: score-changed\n"));
+        assert!(next_message(&mut body)
+            .await
+            .starts_with(b"event: matchup-settled\n"));
+        assert!(next_message(&mut body)
+            .await
+            .starts_with(b"event: matchup-settled\n"));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_stream_the_events_of_a_missing_league() {
+        let league_id = Uuid::new_v4();
+        let response = get_league_events(league_id.into(), get_data_pool()).await;
+        assert!(response.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_league_calendar() {
+        let (league_id, league_teams) = create_league_with_teams(3);
+        let mut response = get_league_calendar(league_id.into(), get_data_pool())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            ical::MEDIA_TYPE
+        );
+        let calendar = match response.take_body() {
+            ResponseBody::Body(Body::Bytes(bytes)) => String::from_utf8(bytes.to_vec()).unwrap(),
+            _ => panic!("Expected a calendar"),
+        };
+        // A round and a matchup or bye per pair of teams every round
+        let events_per_round = 1 + league_teams.len().div_ceil(2);
+        assert_eq!(
+            calendar.matches("BEGIN:VEVENT").count(),
+            4 * events_per_round
+        );
+        assert!(calendar.contains(&format!("UID:round-4-{}@fantasyrustball", league_id)));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_league_in_an_unknown_timezone() {
+        let mut params = create_league_request();
+        params.timezone = "Atlantis/Capital".into();
+        let response = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error = ApiError::invalid_field(
+            "timezone",
+            "timezone must be an IANA time zone like America/New_York",
+        );
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_custom_league_without_round_days() {
+        let mut params = create_league_request();
+        params.round_length = "custom".into();
+        let response = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error =
+            ApiError::invalid_field("__all__", "round_days is needed for a custom round_length");
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_league_with_unknown_tie_breakers() {
+        let mut params = create_league_request();
+        params.tie_breakers = vec!["coin_toss".into()];
+        let response = create_league(
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error = ApiError::invalid_field(
+            "tie_breakers",
+            "tie_breakers can only be head_to_head, total_points and total_miles, each used once",
+        );
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
 }
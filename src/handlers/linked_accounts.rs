@@ -0,0 +1,80 @@
+use crate::cache::Cache;
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::{respond_json, respond_ok};
+use crate::managers::linked_accounts::{delete, find, get_all_for_user, link};
+use crate::models::linked_accounts::{
+    LinkAccountRequest, LinkedAccountResponse, LinkedAccountsResponse, SyncResponse,
+};
+use crate::models::users::AuthUser;
+use crate::permissions::ensure_self;
+use crate::providers::Providers;
+use crate::sync::sync_account;
+use crate::validate::validate;
+use actix_web::web::{block, Data, HttpResponse, Json, Path};
+use uuid::Uuid;
+
+/// Get the accounts a user linked at activity providers
+/// Users can only see their own linked accounts
+pub async fn get_linked_accounts(
+    user_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<LinkedAccountsResponse>, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
+    let accounts = block(move || get_all_for_user(&pool, *user_id)).await?;
+    respond_json(accounts)
+}
+
+/// Link an account at an activity provider
+/// Trades the authorization code the provider redirected back with for
+/// tokens, users can only link their own accounts
+pub async fn create_linked_account(
+    user_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    providers: Data<Providers>,
+    params: Json<LinkAccountRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<LinkedAccountResponse>, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
+    validate(&params)?;
+
+    let provider = providers.get(&params.provider)?;
+    let tokens = provider.exchange_code(&params.code).await?;
+    let account = block(move || link(&pool, *user_id, provider.name(), &tokens)).await?;
+    respond_json(account.into())
+}
+
+/// Sync a linked account now, instead of waiting for the background sync
+/// Users can only sync their own linked accounts
+pub async fn sync_linked_account(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    providers: Data<Providers>,
+    auth_user: AuthUser,
+) -> Result<Json<SyncResponse>, ApiError> {
+    let (user_id, account_id) = path.into_inner();
+    ensure_self(&auth_user, user_id)?;
+
+    let account = {
+        let pool = pool.clone();
+        block(move || find(&pool, user_id, account_id)).await?
+    };
+    let provider = providers.get(&account.provider)?;
+    let response = sync_account(&pool, &cache, provider.as_ref(), account).await?;
+    respond_json(response)
+}
+
+/// Unlink an account, the runs synced from it are kept
+/// Users can only unlink their own accounts
+pub async fn delete_linked_account(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    let (user_id, account_id) = path.into_inner();
+    ensure_self(&auth_user, user_id)?;
+    block(move || delete(&pool, user_id, account_id)).await?;
+    respond_ok()
+}
@@ -0,0 +1,89 @@
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::respond_json;
+use crate::managers::matchups::{generate, get_all_in_league, get_round};
+use crate::models::matchups::MatchupsResponse;
+use crate::models::users::AuthUser;
+use crate::permissions::ensure_commissioner;
+use actix_web::web::{block, Data, Json, Path};
+use uuid::Uuid;
+
+/// Get a league's schedule
+pub async fn get_schedule(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<MatchupsResponse>, ApiError> {
+    let schedule = block(move || get_all_in_league(&pool, *league_id)).await?;
+    respond_json(schedule)
+}
+
+/// Get the matchups of a round
+pub async fn get_round_matchups(
+    path: Path<(Uuid, i32)>,
+    pool: Data<PoolType>,
+) -> Result<Json<MatchupsResponse>, ApiError> {
+    let (league_id, round) = path.into_inner();
+    let matchups = block(move || get_round(&pool, league_id, round)).await?;
+    respond_json(matchups)
+}
+
+/// Generate the schedule for the rounds a league has left
+/// Only the league's commissioners can generate a schedule
+pub async fn create_schedule(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<MatchupsResponse>, ApiError> {
+    let schedule = block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        generate(&pool, *league_id)
+    })
+    .await?;
+    respond_json(schedule)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::handlers::users::tests::get_auth_user;
+    use crate::tests::helpers::tests::get_data_pool;
+
+    #[actix_rt::test]
+    async fn it_creates_and_gets_a_schedule() {
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        let created = create_schedule(league_id, get_data_pool(), get_auth_user(Uuid::nil()))
+            .await
+            .unwrap()
+            .into_inner();
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        let response = get_schedule(league_id, get_data_pool()).await.unwrap();
+        assert_eq!(response.into_inner().0.len(), created.0.len());
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_round_matchups() {
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        create_schedule(league_id, get_data_pool(), get_auth_user(Uuid::nil()))
+            .await
+            .unwrap();
+        let path: Path<(Uuid, i32)> = (Uuid::nil(), 1).into();
+        let response = get_round_matchups(path, get_data_pool()).await.unwrap();
+        assert!(response
+            .into_inner()
+            .0
+            .iter()
+            .all(|matchup| matchup.round == 1));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_schedule_as_non_commissioner() {
+        let league_id: Path<Uuid> = Uuid::nil().into();
+        let response =
+            create_schedule(league_id, get_data_pool(), get_auth_user(Uuid::new_v4())).await;
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            Uuid::nil()
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+}
@@ -1,3 +1,11 @@
+pub mod auth;
+pub mod calendars;
+pub mod drafts;
 pub mod health;
+pub mod leagues;
+pub mod linked_accounts;
+pub mod matchups;
+pub mod openapi;
+pub mod runs;
+pub mod teams;
 pub mod users;
-pub mod leagues;
\ No newline at end of file
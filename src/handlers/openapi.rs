@@ -0,0 +1,22 @@
+use crate::errors::ApiError;
+use crate::helpers::respond_json;
+use crate::openapi::SPEC;
+use actix_web::web::Json;
+use serde_json::Value;
+
+/// Get the OpenAPI 3 specification of the API
+pub async fn get_openapi() -> Result<Json<Value>, ApiError> {
+    respond_json(SPEC.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn it_gets_the_openapi_spec() {
+        let response = get_openapi().await.unwrap().into_inner();
+        assert_eq!(response["openapi"], "3.0.3");
+        assert!(response["paths"]["/api/v1/leagues/{id}"]["put"].is_object());
+    }
+}
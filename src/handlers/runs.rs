@@ -0,0 +1,292 @@
+use crate::cache::Cache;
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::{respond_json, respond_ok};
+use crate::import::{csv, parse, run_requests};
+use crate::managers::runs::{
+    create, create_all, delete, find, get_all_for_user, import_for_league, update,
+};
+use crate::models::runs::{
+    CreateRunRequest, NewRun, Run, RunDetails, RunDetailsResponse, RunLap, RunsImportParams,
+    RunsImportResponse, RunsResponse, UpdateRun, UpdateRunRequest,
+};
+use crate::models::users::AuthUser;
+use crate::pagination::{ListParams, ListResponse};
+use crate::permissions::{ensure_commissioner, ensure_self};
+use crate::validate::validate;
+use actix_web::web::{block, Bytes, Data, HttpResponse, Json, Path, Query};
+use chrono::Utc;
+use uuid::Uuid;
+
+/// Get a page of a user's runs
+pub async fn get_runs(
+    user_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: ListParams,
+) -> Result<Json<RunsResponse>, ApiError> {
+    let runs = block(move || get_all_for_user(&pool, *user_id, &params)).await?;
+    respond_json(runs)
+}
+
+/// Get one of a user's runs
+pub async fn get_run(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+) -> Result<Json<Run>, ApiError> {
+    let (user_id, run_id) = path.into_inner();
+    let run = block(move || find(&pool, user_id, run_id)).await?;
+    respond_json(run)
+}
+
+/// Log a run
+/// Users can only log their own runs
+pub async fn create_run(
+    user_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    params: Json<CreateRunRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<Run>, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
+    validate(&params)?;
+
+    let new_run: Run = NewRun {
+        id: Uuid::new_v4(),
+        user_id: *user_id,
+        distance: params.distance,
+        duration: params.duration,
+        started_at: params.started_at,
+        elevation_gain: params.elevation_gain,
+        heart_rate: params.heart_rate,
+    }
+    .into();
+    let run = block(move || create(&pool, &cache, &new_run)).await?;
+    respond_json(run)
+}
+
+/// Import runs from a GPX, TCX or FIT file sent as the request body
+/// Every activity becomes a run, users can only import their own runs
+pub async fn import_runs(
+    user_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    body: Bytes,
+    auth_user: AuthUser,
+) -> Result<Json<RunDetailsResponse>, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
+
+    let new_runs: Vec<RunDetails> = run_requests(parse(&body)?)?
+        .into_iter()
+        .map(|(params, laps)| {
+            let run: Run = NewRun {
+                id: Uuid::new_v4(),
+                user_id: *user_id,
+                distance: params.distance,
+                duration: params.duration,
+                started_at: params.started_at,
+                elevation_gain: params.elevation_gain,
+                heart_rate: params.heart_rate,
+            }
+            .into();
+            let laps = laps
+                .into_iter()
+                .zip(1..)
+                .map(|(lap, lap_number)| RunLap {
+                    id: Uuid::new_v4(),
+                    run_id: run.id,
+                    lap_number,
+                    started_at: lap.started_at,
+                    distance: lap.distance,
+                    duration: lap.duration,
+                })
+                .collect();
+            RunDetails { run, laps }
+        })
+        .collect();
+    let runs = block(move || create_all(&pool, &cache, &new_runs)).await?;
+    let total = runs.len() as i64;
+    respond_json(ListResponse::new(runs, total, 0))
+}
+
+/// Import a league's runs from a CSV file sent as the request body
+/// Only the league's commissioners can import runs, with `?dry_run=true` the
+/// file is only checked
+pub async fn import_league_runs(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    params: Query<RunsImportParams>,
+    body: Bytes,
+    auth_user: AuthUser,
+) -> Result<Json<RunsImportResponse>, ApiError> {
+    let dry_run = params.dry_run;
+    let response = block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        let (rows, errors) = csv::parse(&body)?;
+        import_for_league(&pool, &cache, *league_id, &rows, errors, dry_run)
+    })
+    .await?;
+    respond_json(response)
+}
+
+/// Update a run
+/// Users can only update their own runs
+pub async fn update_run(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    params: Json<UpdateRunRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<Run>, ApiError> {
+    let (user_id, run_id) = path.into_inner();
+    ensure_self(&auth_user, user_id)?;
+    validate(&params)?;
+
+    let update_run = UpdateRun {
+        distance: params.distance,
+        duration: params.duration,
+        started_at: params.started_at,
+        elevation_gain: params.elevation_gain,
+        heart_rate: params.heart_rate,
+        updated_at: Utc::now().naive_utc(),
+    };
+    let run = block(move || update(&pool, &cache, user_id, run_id, &update_run)).await?;
+    respond_json(run)
+}
+
+/// Delete a run
+/// Users can only delete their own runs
+pub async fn delete_run(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    cache: Data<Cache>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    let (user_id, run_id) = path.into_inner();
+    ensure_self(&auth_user, user_id)?;
+    block(move || delete(&pool, &cache, user_id, run_id)).await?;
+    respond_ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cache::tests::get_data_cache;
+    use crate::handlers::users::tests::get_auth_user;
+    use crate::managers::runs::tests::{create_run as model_create_run, player_one_id};
+    use crate::models::runs::tests::create_run_request;
+    use crate::tests::helpers::tests::{get_data_pool, get_pool};
+
+    #[actix_rt::test]
+    async fn it_gets_all_runs() {
+        let user_id: Path<Uuid> = player_one_id().into();
+        let response = get_runs(user_id, get_data_pool(), ListParams::default())
+            .await
+            .unwrap();
+        assert!(!response.into_inner().data.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_run() {
+        let created = model_create_run(player_one_id()).unwrap();
+        let path: Path<(Uuid, Uuid)> = (player_one_id(), created.id).into();
+        let response = get_run(path, get_data_pool()).await.unwrap();
+        assert_eq!(response.into_inner().id, created.id);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_find_a_run() {
+        let run_id = Uuid::new_v4();
+        let path: Path<(Uuid, Uuid)> = (player_one_id(), run_id).into();
+        let response = get_run(path, get_data_pool()).await;
+        let expected_error = ApiError::NotFound(format!("Run {} not found", run_id));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_creates_a_run() {
+        let user_id: Path<Uuid> = player_one_id().into();
+        let params = create_run_request();
+        let response = create_run(
+            user_id,
+            get_data_pool(),
+            get_data_cache(),
+            Json(params.clone()),
+            get_auth_user(player_one_id()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.into_inner().distance, params.distance);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_run_for_another_user() {
+        let user_id: Path<Uuid> = player_one_id().into();
+        let response = create_run(
+            user_id,
+            get_data_pool(),
+            get_data_cache(),
+            Json(create_run_request()),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        let expected_error = ApiError::Forbidden("You can only change your own account".into());
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_an_invalid_run() {
+        let user_id: Path<Uuid> = player_one_id().into();
+        let mut params = create_run_request();
+        params.distance = -1.0;
+        let response = create_run(
+            user_id,
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(player_one_id()),
+        )
+        .await;
+        let expected_error =
+            ApiError::invalid_field("distance", "distance must be a positive number of miles");
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_updates_a_run() {
+        let created = model_create_run(player_one_id()).unwrap();
+        let path: Path<(Uuid, Uuid)> = (player_one_id(), created.id).into();
+        let params = UpdateRunRequest {
+            distance: 4.0,
+            duration: 2000,
+            started_at: created.started_at,
+            elevation_gain: None,
+            heart_rate: None,
+        };
+        let response = update_run(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            Json(params),
+            get_auth_user(player_one_id()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.into_inner().distance, 4.0);
+    }
+
+    #[actix_rt::test]
+    async fn it_deletes_a_run() {
+        let created = model_create_run(player_one_id()).unwrap();
+        let path: Path<(Uuid, Uuid)> = (player_one_id(), created.id).into();
+        delete_run(
+            path,
+            get_data_pool(),
+            get_data_cache(),
+            get_auth_user(player_one_id()),
+        )
+        .await
+        .unwrap();
+        assert!(find(&get_pool(), player_one_id(), created.id).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::helpers::{respond_json, respond_ok};
+use crate::ical;
+use crate::managers::leagues::{find_calendar, find_calendar_link};
+use crate::managers::teams::{
+    add_owner, add_player, create, delete, find, get_all, get_all_in_league, remove_owner,
+    remove_player, update,
+};
+use crate::models::leagues::CalendarLinkResponse;
+use crate::models::teams::{
+    CreateTeamRequest, Owner, Team, TeamDetails, TeamMemberRequest, TeamsResponse, UpdateTeam,
+    UpdateTeamRequest,
+};
+use crate::models::users::AuthUser;
+use crate::pagination::ListParams;
+use crate::permissions::{ensure_commissioner, ensure_team_owner};
+use crate::validate::validate;
+use actix_web::web::{block, Data, HttpResponse, Json, Path};
+use uuid::Uuid;
+
+/// Get a page of the teams of every league
+pub async fn get_all_teams(
+    pool: Data<PoolType>,
+    params: ListParams,
+) -> Result<Json<TeamsResponse>, ApiError> {
+    let teams = block(move || get_all(&pool, &params)).await?;
+    respond_json(teams)
+}
+
+/// Get a page of a league's teams
+pub async fn get_teams(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: ListParams,
+) -> Result<Json<TeamsResponse>, ApiError> {
+    let teams = block(move || get_all_in_league(&pool, *league_id, &params)).await?;
+    respond_json(teams)
+}
+
+/// Get a team with its players and owners
+pub async fn get_team(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<TeamDetails>, ApiError> {
+    let team = block(move || find(&pool, *team_id)).await?;
+    respond_json(team)
+}
+
+/// Get a team's matchups and its league's rounds as an iCalendar feed
+pub async fn get_team_calendar(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<HttpResponse, ApiError> {
+    let calendar = block(move || {
+        let team = find(&pool, *team_id)?;
+        find_calendar(&pool, team.league_id, Some(team.id))
+    })
+    .await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ical::MEDIA_TYPE)
+        .body(calendar))
+}
+
+/// Get the link calendar apps subscribe to a team's calendar with
+pub async fn get_team_calendar_link(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+) -> Result<Json<CalendarLinkResponse>, ApiError> {
+    let link = block(move || {
+        let team = find(&pool, *team_id)?;
+        find_calendar_link(&pool, team.league_id, Some(team.id))
+    })
+    .await?;
+    respond_json(link)
+}
+
+/// Create a team in a league
+/// Only the league's commissioners can create teams, the team is owned by
+/// whoever creates it unless owner_ids are given
+pub async fn create_team(
+    league_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<CreateTeamRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    validate(&params)?;
+
+    let team_id = Uuid::new_v4();
+    let new_team = Team {
+        id: team_id,
+        name: params.name.to_string(),
+        wins: 0,
+        losses: 0,
+        ties: 0,
+        league_id: *league_id,
+    };
+    let owner_ids = if params.owner_ids.is_empty() {
+        vec![auth_user.id]
+    } else {
+        params.owner_ids.clone()
+    };
+    let new_owners: Vec<Owner> = owner_ids
+        .into_iter()
+        .map(|user_id| Owner {
+            id: Uuid::new_v4(),
+            user_id,
+            team_id,
+        })
+        .collect();
+
+    let team = block(move || {
+        ensure_commissioner(&pool, &auth_user, *league_id)?;
+        create(&pool, &new_team, None, Some(&new_owners))
+    })
+    .await?;
+    respond_json(team)
+}
+
+/// Rename a team
+/// Only the team's owners can rename it
+pub async fn update_team(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<UpdateTeamRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    validate(&params)?;
+
+    let update_team = UpdateTeam {
+        id: *team_id,
+        name: params.name.to_string(),
+    };
+    let team = block(move || {
+        ensure_team_owner(&pool, &auth_user, *team_id)?;
+        update(&pool, &update_team)
+    })
+    .await?;
+    respond_json(team)
+}
+
+/// Delete a team
+/// Only the commissioners of the team's league can delete it
+pub async fn delete_team(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    block(move || {
+        let team = find(&pool, *team_id)?;
+        ensure_commissioner(&pool, &auth_user, team.league_id)?;
+        delete(&pool, *team_id)
+    })
+    .await?;
+    respond_ok()
+}
+
+/// Add a player to a team's roster
+/// Only the team's owners can change its roster
+pub async fn add_team_player(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<TeamMemberRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    let team = block(move || {
+        ensure_team_owner(&pool, &auth_user, *team_id)?;
+        add_player(&pool, *team_id, params.user_id)
+    })
+    .await?;
+    respond_json(team)
+}
+
+/// Take a player off a team's roster
+/// Only the team's owners can change its roster
+pub async fn remove_team_player(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    let (team_id, user_id) = path.into_inner();
+    let team = block(move || {
+        ensure_team_owner(&pool, &auth_user, team_id)?;
+        remove_player(&pool, team_id, user_id)
+    })
+    .await?;
+    respond_json(team)
+}
+
+/// Add an owner to a team
+/// Only the team's owners can add other owners
+pub async fn add_team_owner(
+    team_id: Path<Uuid>,
+    pool: Data<PoolType>,
+    params: Json<TeamMemberRequest>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    let team = block(move || {
+        ensure_team_owner(&pool, &auth_user, *team_id)?;
+        add_owner(&pool, *team_id, params.user_id)
+    })
+    .await?;
+    respond_json(team)
+}
+
+/// Remove an owner from a team
+/// Only the team's owners can remove owners
+pub async fn remove_team_owner(
+    path: Path<(Uuid, Uuid)>,
+    pool: Data<PoolType>,
+    auth_user: AuthUser,
+) -> Result<Json<TeamDetails>, ApiError> {
+    let (team_id, user_id) = path.into_inner();
+    let team = block(move || {
+        ensure_team_owner(&pool, &auth_user, team_id)?;
+        remove_owner(&pool, team_id, user_id)
+    })
+    .await?;
+    respond_json(team)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::handlers::users::tests::get_auth_user;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::models::teams::tests::create_team_request;
+    use crate::tests::helpers::tests::{get_data_pool, get_pool};
+
+    async fn create_test_team(league_id: Uuid) -> TeamDetails {
+        create_team(
+            league_id.into(),
+            get_data_pool(),
+            Json(create_team_request()),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner()
+    }
+
+    #[actix_rt::test]
+    async fn it_creates_a_team_owned_by_its_creator() {
+        let (league_id, _) = create_league_with_teams(2);
+        let team = create_test_team(league_id).await;
+        assert_eq!(team.league_id, league_id);
+        assert_eq!(team.owners.unwrap()[0].user_id, Uuid::nil());
+
+        let response = get_teams(league_id.into(), get_data_pool(), ListParams::default())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.data.len(), 3);
+        assert_eq!(response.total, 3);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_team_as_non_commissioner() {
+        let (league_id, _) = create_league_with_teams(2);
+        let response = create_team(
+            league_id.into(),
+            get_data_pool(),
+            Json(create_team_request()),
+            get_auth_user(Uuid::new_v4()),
+        )
+        .await;
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            league_id
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_renames_a_team() {
+        let (league_id, _) = create_league_with_teams(2);
+        let team = create_test_team(league_id).await;
+        let params = UpdateTeamRequest {
+            name: "Renamed Team".into(),
+        };
+        let response = update_team(
+            team.id.into(),
+            get_data_pool(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.into_inner().name, "Renamed Team");
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_rename_someone_elses_team() {
+        let (_, teams) = create_league_with_teams(2);
+        let params = UpdateTeamRequest {
+            name: "Renamed Team".into(),
+        };
+        let response = update_team(
+            teams[0].id.into(),
+            get_data_pool(),
+            Json(params),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error =
+            ApiError::Forbidden(format!("Only an owner can change team {}", teams[0].id));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_manages_a_roster() {
+        let (league_id, teams) = create_league_with_teams(2);
+        let team = create_test_team(league_id).await;
+        let free_agent = Uuid::parse_str("11111111-1111-1111-1111-111111111111").unwrap();
+
+        let added = add_team_player(
+            team.id.into(),
+            get_data_pool(),
+            Json(TeamMemberRequest {
+                user_id: free_agent,
+            }),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert_eq!(added.players.unwrap()[0].user_id, free_agent);
+
+        let taken = teams[0].players.as_ref().unwrap()[0].user_id;
+        let response = add_team_player(
+            team.id.into(),
+            get_data_pool(),
+            Json(TeamMemberRequest { user_id: taken }),
+            get_auth_user(Uuid::nil()),
+        )
+        .await;
+        let expected_error = ApiError::Conflict(format!(
+            "User {} is already on a team in league {}",
+            taken, league_id
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+
+        let removed = remove_team_player(
+            (team.id, free_agent).into(),
+            get_data_pool(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert!(removed.players.unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn it_manages_owners() {
+        let (league_id, teams) = create_league_with_teams(2);
+        let team = create_test_team(league_id).await;
+        let new_owner = teams[0].owners.as_ref().unwrap()[0].user_id;
+
+        let added = add_team_owner(
+            team.id.into(),
+            get_data_pool(),
+            Json(TeamMemberRequest { user_id: new_owner }),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert_eq!(added.owners.unwrap().len(), 2);
+
+        remove_team_owner(
+            (team.id, Uuid::nil()).into(),
+            get_data_pool(),
+            get_auth_user(Uuid::nil()),
+        )
+        .await
+        .unwrap();
+
+        let response = remove_team_owner(
+            (team.id, new_owner).into(),
+            get_data_pool(),
+            get_auth_user(new_owner),
+        )
+        .await;
+        let expected_error =
+            ApiError::BadRequest(format!("Team {} has to keep at least one owner", team.id));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_deletes_a_team() {
+        let (league_id, _) = create_league_with_teams(2);
+        let team = create_test_team(league_id).await;
+        delete_team(team.id.into(), get_data_pool(), get_auth_user(Uuid::nil()))
+            .await
+            .unwrap();
+        let expected_error = ApiError::NotFound(format!("Team {} not found", team.id));
+        assert_eq!(find(&get_pool(), team.id).unwrap_err(), expected_error);
+    }
+}
@@ -3,11 +3,13 @@ use crate::errors::ApiError;
 use crate::helpers::{respond_json, respond_ok};
 use crate::managers::users::{create, delete, find, get_all, update};
 use crate::models::users::{
-    CreateUserRequest, NewUser, UpdateUser, UpdateUserRequest, User, UserResponse, UsersResponse,
+    AuthUser, CreateUserRequest, NewUser, UpdateUser, UpdateUserRequest, User, UserResponse,
+    UsersResponse,
 };
+use crate::pagination::ListParams;
+use crate::permissions::ensure_self;
 use crate::validate::validate;
 use actix_web::web::{block, Data, HttpResponse, Json, Path};
-use rayon::prelude::*;
 use uuid::Uuid;
 
 /// Get a user
@@ -19,9 +21,12 @@ pub async fn get_user(
     respond_json(user)
 }
 
-/// Get all users
-pub async fn get_users(pool: Data<PoolType>) -> Result<Json<UsersResponse>, ApiError> {
-    let users = block(move || get_all(&pool)).await?;
+/// Get a page of users
+pub async fn get_users(
+    pool: Data<PoolType>,
+    params: ListParams,
+) -> Result<Json<UsersResponse>, ApiError> {
+    let users = block(move || get_all(&pool, &params)).await?;
     respond_json(users)
 }
 
@@ -48,11 +53,14 @@ pub async fn create_user(
 }
 
 /// Update a user
+/// Users can only update themselves
 pub async fn update_user(
     user_id: Path<Uuid>,
     pool: Data<PoolType>,
     params: Json<UpdateUserRequest>,
+    auth_user: AuthUser,
 ) -> Result<Json<UserResponse>, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
     validate(&params)?;
 
     // temporarily use the user's id for updated_at
@@ -68,10 +76,13 @@ pub async fn update_user(
 }
 
 /// Delete a user
+/// Users can only delete themselves
 pub async fn delete_user(
     user_id: Path<Uuid>,
     pool: Data<PoolType>,
+    auth_user: AuthUser,
 ) -> Result<HttpResponse, ApiError> {
+    ensure_self(&auth_user, *user_id)?;
     block(move || delete(&pool, *user_id)).await?;
     respond_ok()
 }
@@ -87,12 +98,6 @@ impl From<User> for UserResponse {
     }
 }
 
-impl From<Vec<User>> for UsersResponse {
-    fn from(users: Vec<User>) -> Self {
-        UsersResponse(users.into_par_iter().map(|user| user.into()).collect())
-    }
-}
-
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -101,16 +106,23 @@ pub mod tests {
 
     pub fn get_all_users() -> UsersResponse {
         let pool = get_pool();
-        get_all(&pool).unwrap()
+        get_all(&pool, &ListParams::default()).unwrap()
     }
 
     pub fn get_first_users_id() -> Uuid {
-        get_all_users().0[0].id
+        get_all_users().data[0].id
+    }
+
+    pub fn get_auth_user(user_id: Uuid) -> AuthUser {
+        AuthUser {
+            id: user_id,
+            email: "satoshi@nakamotoinstitute.org".into(),
+        }
     }
 
     #[actix_rt::test]
     async fn it_gets_a_user() {
-        let first_user = &get_all_users().0[0];
+        let first_user = &get_all_users().data[0];
         let user_id: Path<Uuid> = get_first_users_id().into();
         let response = get_user(user_id, get_data_pool()).await.unwrap();
         assert_eq!(response.into_inner(), *first_user);
@@ -128,9 +140,12 @@ pub mod tests {
 
     #[actix_rt::test]
     async fn it_gets_all_users() {
-        let response = get_users(get_data_pool()).await;
+        let response = get_users(get_data_pool(), ListParams::default()).await;
         assert!(response.is_ok());
-        assert_eq!(response.unwrap().into_inner().0[0], get_all_users().0[0]);
+        assert_eq!(
+            response.unwrap().into_inner().data[0],
+            get_all_users().data[0]
+        );
     }
 
     #[actix_rt::test]
@@ -138,7 +153,7 @@ pub mod tests {
         let params = Json(CreateUserRequest {
             first_name: "Satoshi".into(),
             last_name: "Nakamoto".into(),
-            email: "satoshi@nakamotoinstitute.org".into(),
+            email: format!("satoshi-{}@nakamotoinstitute.org", Uuid::new_v4()),
             password: "123456".into(),
         });
         let response = create_user(get_data_pool(), Json(params.clone()))
@@ -149,19 +164,35 @@ pub mod tests {
 
     #[actix_rt::test]
     async fn it_updates_a_user() {
-        let first_user = &get_all_users().0[0];
+        let first_user = &get_all_users().data[0];
         let user_id: Path<Uuid> = get_first_users_id().into();
         let params = Json(UpdateUserRequest {
             first_name: first_user.first_name.clone(),
             last_name: first_user.last_name.clone(),
             email: first_user.email.clone(),
         });
-        let response = update_user(user_id, get_data_pool(), Json(params.clone()))
+        let auth_user = get_auth_user(first_user.id);
+        let response = update_user(user_id, get_data_pool(), Json(params.clone()), auth_user)
             .await
             .unwrap();
         assert_eq!(response.into_inner().first_name, params.first_name);
     }
 
+    #[actix_rt::test]
+    async fn it_doesnt_update_another_user() {
+        let first_user = &get_all_users().data[0];
+        let user_id: Path<Uuid> = get_first_users_id().into();
+        let params = Json(UpdateUserRequest {
+            first_name: first_user.first_name.clone(),
+            last_name: first_user.last_name.clone(),
+            email: first_user.email.clone(),
+        });
+        let auth_user = get_auth_user(Uuid::new_v4());
+        let response = update_user(user_id, get_data_pool(), params, auth_user).await;
+        let expected_error = ApiError::Forbidden("You can only change your own account".into());
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
     #[actix_rt::test]
     async fn it_deletes_a_user() {
         let created = model_create_user();
@@ -169,8 +200,22 @@ pub mod tests {
         let user_id_path: Path<Uuid> = user_id.into();
         let user = find(&get_pool(), user_id);
         assert!(user.is_ok());
-        delete_user(user_id_path, get_data_pool()).await.unwrap();
+        delete_user(user_id_path, get_data_pool(), get_auth_user(user_id))
+            .await
+            .unwrap();
         let user = find(&get_pool(), user_id);
         assert!(user.is_err());
     }
+
+    #[actix_rt::test]
+    async fn it_doesnt_delete_another_user() {
+        let created = model_create_user();
+        let user_id = created.unwrap().id;
+        let user_id_path: Path<Uuid> = user_id.into();
+        let response =
+            delete_user(user_id_path, get_data_pool(), get_auth_user(Uuid::new_v4())).await;
+        assert!(response.is_err());
+        let user = find(&get_pool(), user_id);
+        assert!(user.is_ok());
+    }
 }
@@ -0,0 +1,257 @@
+//! iCalendar (RFC 5545) feeds of a league's rounds and matchups.
+//!
+//! Every round and every matchup is an event spanning its round. Times are
+//! written in UTC so no VTIMEZONE is needed, calendar apps show them in the
+//! phone's own timezone. UIDs are made of the league, round and home team
+//! instead of row ids, which change when a schedule is generated again, so
+//! apps update events instead of adding them twice.
+
+use crate::calendar::Calendar;
+use crate::models::leagues::League;
+use crate::models::matchups::Matchup;
+use crate::models::teams::Team;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+pub const MEDIA_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Path of the feeds calendar apps subscribe to, followed by a token
+pub const FEED_PATH: &str = "/api/v1/calendars";
+
+const PRODID: &str = "-//fantasyrustball//League Calendar//EN";
+
+/// Right-hand side of every UID, to keep them globally unique
+const UID_DOMAIN: &str = "fantasyrustball";
+
+/// Lines longer than this many octets are folded
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Format of DATE-TIME values in UTC
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A VEVENT, start and end being in UTC
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// Events for every round of a league and every matchup of the schedule
+///
+/// Matchups of teams that aren't in teams are named "TBD".
+pub fn events(league: &League, teams: &[Team], schedule: &[Matchup]) -> Vec<Event> {
+    let calendar = Calendar::of(league);
+    let team_name = |team_id: Uuid| {
+        teams
+            .iter()
+            .find(|team| team.id == team_id)
+            .map_or("TBD", |team| team.name.as_str())
+    };
+
+    let rounds = (1..=league.rounds).map(|round| {
+        let (start, end) = calendar.round_window(round);
+        Event {
+            uid: format!("round-{}-{}@{}", round, league.id, UID_DOMAIN),
+            summary: format!("{}: round {}", league.name, round),
+            description: format!(
+                "Round {} closes at {}, log your runs before then.",
+                round,
+                calendar.local(end).format("%Y-%m-%d %H:%M %Z")
+            ),
+            start,
+            end,
+        }
+    });
+
+    let matchups = schedule.iter().map(|matchup| {
+        let (start, end) = calendar.round_window(matchup.round);
+        let home = team_name(matchup.home_team_id);
+        let summary = match matchup.away_team_id {
+            Some(away_team_id) => format!("{} vs {}", home, team_name(away_team_id)),
+            None => format!("{} has a bye", home),
+        };
+        let mut description = format!("Round {} of {}.", matchup.round, league.name);
+        if let (Some(home_points), Some(away_points)) = (matchup.home_points, matchup.away_points) {
+            description.push_str(&format!(
+                " Final score {:.1} - {:.1}.",
+                home_points, away_points
+            ));
+        }
+        Event {
+            uid: format!(
+                "matchup-{}-{}-{}@{}",
+                matchup.round, matchup.home_team_id, league.id, UID_DOMAIN
+            ),
+            summary,
+            description,
+            start,
+            end,
+        }
+    });
+
+    rounds.chain(matchups).collect()
+}
+
+/// Write events as an iCalendar named name, stamped at now
+pub fn render(name: &str, events: &[Event], now: NaiveDateTime) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".into(),
+        "METHOD:PUBLISH".into(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for event in events {
+        lines.extend(vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", event.uid),
+            format!("DTSTAMP:{}", now.format(UTC_FORMAT)),
+            format!("DTSTART:{}", event.start.format(UTC_FORMAT)),
+            format!("DTEND:{}", event.end.format(UTC_FORMAT)),
+            format!("SUMMARY:{}", escape(&event.summary)),
+            format!("DESCRIPTION:{}", escape(&event.description)),
+            "END:VEVENT".into(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".into());
+
+    lines
+        .iter()
+        .map(|line| fold(line) + "\r\n")
+        .collect::<String>()
+}
+
+/// Escape the characters TEXT values can't hold as they are
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split a line into lines of at most MAX_LINE_OCTETS octets, continuation
+/// lines starting with a space, without splitting a character
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for character in line.chars() {
+        if octets + character.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(character);
+        octets += character.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::calendar::{DEFAULT_ROUND_DAYS, DEFAULT_TIMEZONE};
+    use crate::models::matchups::tests::matchup;
+    use crate::scoring::tests::{league_start, team};
+
+    fn league() -> League {
+        League {
+            id: Uuid::nil(),
+            name: "Summer League".into(),
+            start: league_start(),
+            rounds: 2,
+            current_round: 1,
+            timezone: DEFAULT_TIMEZONE.into(),
+            round_days: DEFAULT_ROUND_DAYS,
+        }
+    }
+
+    fn named(name: &str) -> Team {
+        Team {
+            name: name.into(),
+            ..team(Uuid::new_v4())
+        }
+    }
+
+    #[test]
+    fn it_makes_events_for_rounds_and_matchups() {
+        let teams = vec![named("Fast Feet"), named("Slow Pokes")];
+        let settled = Matchup {
+            home_team_id: teams[0].id,
+            away_team_id: Some(teams[1].id),
+            ..matchup(Some(102.5), Some(98.0))
+        };
+        let bye = Matchup {
+            round: 2,
+            home_team_id: teams[1].id,
+            away_team_id: None,
+            ..matchup(None, None)
+        };
+
+        let events = events(&league(), &teams, &[settled, bye]);
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].summary, "Summer League: round 1");
+        assert_eq!(events[0].start, league_start());
+        assert_eq!(events[1].start, events[0].end);
+        assert_eq!(events[2].summary, "Fast Feet vs Slow Pokes");
+        assert_eq!(
+            events[2].description,
+            "Round 1 of Summer League. Final score 102.5 - 98.0."
+        );
+        assert_eq!(
+            (events[2].start, events[2].end),
+            (events[0].start, events[0].end)
+        );
+        assert_eq!(events[3].summary, "Slow Pokes has a bye");
+    }
+
+    #[test]
+    fn it_keeps_uids_when_a_schedule_is_generated_again() {
+        let teams = vec![named("Fast Feet"), named("Slow Pokes")];
+        let generated = Matchup {
+            home_team_id: teams[0].id,
+            away_team_id: Some(teams[1].id),
+            ..matchup(None, None)
+        };
+        let regenerated = Matchup {
+            id: Uuid::new_v4(),
+            ..generated.clone()
+        };
+        assert_eq!(
+            events(&league(), &teams, &[generated]),
+            events(&league(), &teams, &[regenerated])
+        );
+    }
+
+    #[test]
+    fn it_renders_a_calendar() {
+        let events = events(&league(), &[], &[]);
+        let ics = render("Summer League, 2020", &events, league_start());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("X-WR-CALNAME:Summer League\\, 2020\r\n"));
+        assert!(
+            ics.contains("UID:round-1-00000000-0000-0000-0000-000000000000@fantasyrustball\r\n")
+        );
+        assert!(ics.contains("DTSTART:20200504T000000Z\r\nDTEND:20200511T000000Z\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+    }
+
+    #[test]
+    fn it_folds_long_lines() {
+        let line = format!("SUMMARY:{}", "é".repeat(50));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn it_escapes_text() {
+        assert_eq!(escape("a;b,c\\d\ne"), "a\\;b\\,c\\\\d\\ne");
+    }
+}
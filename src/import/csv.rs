@@ -0,0 +1,261 @@
+//! CSV files of runs for a whole league, one run per row
+//!
+//! The first row names the columns, `email`, `date`, `distance` and
+//! `duration` in any order, and other columns are ignored. Dates look like
+//! 2020-05-09, distances are in miles and durations are seconds or h:mm:ss.
+//! Every row is checked like a run logged by hand, and the errors of every
+//! row are collected so a spreadsheet can be fixed in one go.
+
+use crate::errors::{ApiError, FieldErrors};
+use crate::models::runs::CreateRunRequest;
+use crate::validate::collect_errors;
+use ::csv::{ReaderBuilder, StringRecord, Trim};
+use chrono::NaiveDate;
+use validator::Validate;
+
+pub const MEDIA_TYPE: &str = "text/csv";
+
+/// Columns every file needs
+pub const COLUMNS: [&str; 4] = ["email", "date", "distance", "duration"];
+
+/// A row that's a valid run, its email still has to be matched to a player
+#[derive(Clone, Debug)]
+pub struct Row {
+    /// Path errors about the row are keyed by, e.g. `row[3]`
+    pub path: String,
+    pub line: u64,
+    pub email: String,
+    /// Day of the run, it gets a time once the league's timezone is known
+    pub date: NaiveDate,
+    pub distance: f64,
+    pub duration: i32,
+}
+
+impl Row {
+    /// An error about the row, keyed by the row and pointing at its line
+    pub fn error(&self, field: &str, message: &str) -> (String, String) {
+        row_error(&self.path, self.line, field, message)
+    }
+}
+
+/// Parse the rows of a file into the valid rows and the errors of the others
+///
+/// Only a file that can't be read at all, or that misses a column, is an
+/// error on its own.
+pub fn parse(file: &[u8]) -> Result<(Vec<Row>, FieldErrors), ApiError> {
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(file.strip_prefix("\u{feff}".as_bytes()).unwrap_or(file));
+
+    let headers = reader.headers().map_err(unreadable)?.clone();
+    let mut columns = [0; 4];
+    for (column, name) in columns.iter_mut().zip(COLUMNS.iter()) {
+        *column = headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let message = format!("line 1: the file needs a column named {}", name);
+                ApiError::invalid_field("file", &message)
+            })?;
+    }
+
+    let mut rows = vec![];
+    let mut errors = FieldErrors::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(unreadable)?;
+        let path = format!("row[{}]", index);
+        let line = record.position().map_or(0, |position| position.line());
+        match parse_row(&record, &columns, &path, line) {
+            Ok(row) => rows.push(row),
+            Err(row_errors) => {
+                for (field, message) in row_errors {
+                    errors.entry(field).or_default().push(message);
+                }
+            }
+        }
+    }
+
+    if rows.is_empty() && errors.is_empty() {
+        return Err(ApiError::invalid_field(
+            "file",
+            "the file doesn't have any run",
+        ));
+    }
+    Ok((rows, errors))
+}
+
+fn parse_row(
+    record: &StringRecord,
+    columns: &[usize; 4],
+    path: &str,
+    line: u64,
+) -> Result<Row, Vec<(String, String)>> {
+    let cell = |column: usize| record.get(columns[column]).unwrap_or_default();
+    let mut errors = vec![];
+
+    let email = cell(0);
+    if email.is_empty() {
+        errors.push(row_error(path, line, "email", "email is required"));
+    }
+    let date = NaiveDate::parse_from_str(cell(1), "%Y-%m-%d")
+        .map_err(|_| row_error(path, line, "date", "date must be a date like 2020-05-09"));
+    let distance = cell(2)
+        .parse::<f64>()
+        .map_err(|_| row_error(path, line, "distance", "distance must be a number of miles"));
+    let duration = parse_duration(cell(3)).ok_or_else(|| {
+        let message = "duration must be a number of seconds or h:mm:ss";
+        row_error(path, line, "duration", message)
+    });
+
+    let (date, distance, duration) = match (date, distance, duration) {
+        (Ok(date), Ok(distance), Ok(duration)) if errors.is_empty() => (date, distance, duration),
+        (date, distance, duration) => {
+            errors.extend(date.err());
+            errors.extend(distance.err());
+            errors.extend(duration.err());
+            return Err(errors);
+        }
+    };
+
+    // Checked as if the run started at midnight UTC of its date
+    let run = CreateRunRequest {
+        distance,
+        duration,
+        started_at: date.and_hms(0, 0, 0),
+        elevation_gain: None,
+        heart_rate: None,
+    };
+    if let Err(validation_errors) = run.validate() {
+        return Err(collect_errors(validation_errors)
+            .into_iter()
+            .flat_map(|(field, messages)| {
+                // Runs of a row start on its date
+                let field = if field == "started_at" {
+                    "date".into()
+                } else {
+                    field
+                };
+                messages
+                    .into_iter()
+                    .map(move |message| row_error(path, line, &field, &message))
+            })
+            .collect());
+    }
+
+    Ok(Row {
+        path: path.into(),
+        line,
+        email: email.into(),
+        date,
+        distance,
+        duration,
+    })
+}
+
+/// Seconds, or h:mm:ss and mm:ss like spreadsheets show them
+fn parse_duration(duration: &str) -> Option<i32> {
+    let parts = duration
+        .split(':')
+        .map(|part| part.parse::<i32>().ok().filter(|part| *part >= 0))
+        .collect::<Option<Vec<i32>>>()?;
+    match parts.as_slice() {
+        [seconds] => Some(*seconds),
+        [minutes, seconds] if *seconds < 60 => Some(minutes * 60 + seconds),
+        [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+            Some(hours * 3600 + minutes * 60 + seconds)
+        }
+        _ => None,
+    }
+}
+
+fn row_error(path: &str, line: u64, field: &str, message: &str) -> (String, String) {
+    (
+        format!("{}.{}", path, field),
+        format!("line {}: {}", line, message),
+    )
+}
+
+fn unreadable(error: ::csv::Error) -> ApiError {
+    let message = match error.position() {
+        Some(position) => format!("line {}: the file can't be read as CSV", position.line()),
+        None => "the file can't be read as CSV".into(),
+    };
+    ApiError::invalid_field("file", &message)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::import::tests::field_error;
+
+    /// A file with a row for every one of rows, which are email,date,distance,duration
+    pub fn csv_file(rows: &[&str]) -> String {
+        format!("email,date,distance,duration\n{}\n", rows.join("\n"))
+    }
+
+    #[test]
+    fn it_parses_rows() {
+        let file = csv_file(&[
+            "one@nothing.org,2020-05-09,3.1,1680",
+            "two@nothing.org,2020-05-10,6.2,0:56:00",
+        ]);
+        let (rows, errors) = parse(file.as_bytes()).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].path, "row[1]");
+        assert_eq!(rows[1].line, 3);
+        assert_eq!(rows[1].email, "two@nothing.org");
+        assert_eq!(rows[1].duration, 3360);
+        assert_eq!(rows[1].date, NaiveDate::from_ymd(2020, 5, 10));
+    }
+
+    #[test]
+    fn it_reads_columns_in_any_order() {
+        let file = "Duration,Notes,Email,Distance,Date\n28:00,easy,one@nothing.org,3.1,2020-05-09";
+        let (rows, _) = parse(file.as_bytes()).unwrap();
+        assert_eq!(rows[0].duration, 1680);
+        assert!((rows[0].distance - 3.1).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn it_collects_the_errors_of_every_row() {
+        let file = csv_file(&[
+            "one@nothing.org,2020-05-09,3.1,1680",
+            ",May 9th,3.1,1680",
+            "three@nothing.org,2020-05-09,3.1,60",
+        ]);
+        let (rows, errors) = parse(file.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(errors["row[1].email"], vec!["line 3: email is required"]);
+        assert_eq!(
+            errors["row[1].date"],
+            vec!["line 3: date must be a date like 2020-05-09"]
+        );
+        assert!(errors["row[2].__all__"][0].starts_with("line 4: pace must be"));
+    }
+
+    #[test]
+    fn it_needs_every_column() {
+        let file = "email,date,distance\none@nothing.org,2020-05-09,3.1";
+        let (field, message) = field_error(parse(file.as_bytes()).unwrap_err());
+        assert_eq!(field, "file");
+        assert_eq!(message, "line 1: the file needs a column named duration");
+    }
+
+    #[test]
+    fn it_needs_a_run() {
+        let (_, message) = field_error(parse(csv_file(&[]).as_bytes()).unwrap_err());
+        assert_eq!(message, "the file doesn't have any run");
+    }
+
+    #[test]
+    fn it_parses_durations() {
+        assert_eq!(parse_duration("1680"), Some(1680));
+        assert_eq!(parse_duration("28:00"), Some(1680));
+        assert_eq!(parse_duration("1:02:03"), Some(3723));
+        assert_eq!(parse_duration("1:60:00"), None);
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration("half an hour"), None);
+    }
+}
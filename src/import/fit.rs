@@ -0,0 +1,439 @@
+//! Garmin FIT files, https://developer.garmin.com/fit/protocol/
+//!
+//! FIT is a binary format: definition messages describe the fields of a
+//! local message type and the data messages that follow are laid out that
+//! way. Only what a run needs is read. Every session message is a run with
+//! the lap messages before it as its laps, and record messages fill in the
+//! elevation gain and heart rate when the session doesn't have them.
+
+use crate::errors::ApiError;
+use crate::import::{average, climb, Activity, Lap, METERS_PER_MILE};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+pub const MEDIA_TYPE: &str = "application/vnd.ant.fit";
+
+/// Seconds between the unix epoch and the FIT epoch, 1989-12-31T00:00:00Z
+const FIT_EPOCH: i64 = 631_065_600;
+
+// Global message numbers
+const SESSION: u16 = 18;
+const LAP: u16 = 19;
+const RECORD: u16 = 20;
+
+// Fields of every message
+const TIMESTAMP: u8 = 253;
+
+// Fields of session and lap messages
+const START_TIME: u8 = 2;
+const TOTAL_TIMER_TIME: u8 = 8;
+const TOTAL_DISTANCE: u8 = 9;
+const SESSION_AVG_HEART_RATE: u8 = 16;
+const SESSION_TOTAL_ASCENT: u8 = 22;
+
+// Fields of record messages
+const ALTITUDE: u8 = 2;
+const HEART_RATE: u8 = 3;
+const ENHANCED_ALTITUDE: u8 = 78;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+/// FIT files start with a 12 or 14 byte header ending in `.FIT`
+pub fn is_fit(file: &[u8]) -> bool {
+    file.len() >= 12 && (file[0] == 12 || file[0] == 14) && &file[8..12] == b".FIT"
+}
+
+/// Parse every session of a FIT file into an activity, keyed by its position
+pub fn parse(file: &[u8]) -> Result<Vec<(String, Activity)>, ApiError> {
+    let messages = read_messages(file)?;
+
+    let mut activities = vec![];
+    let mut laps = vec![];
+    for message in messages.iter() {
+        match message.global {
+            LAP => laps.push(message),
+            SESSION => {
+                let path = format!("session[{}]", activities.len());
+                let activity = to_activity(message, &laps, &messages, &path)?;
+                activities.push((path, activity));
+                laps.clear();
+            }
+            _ => {}
+        }
+    }
+
+    if activities.is_empty() {
+        return Err(ApiError::invalid_field(
+            "file",
+            "the file doesn't have any session",
+        ));
+    }
+    Ok(activities)
+}
+
+/// The valid integer fields of a data message
+#[derive(Debug)]
+struct Message {
+    global: u16,
+    offset: usize,
+    fields: HashMap<u8, i64>,
+}
+
+impl Message {
+    fn time(&self, field: u8) -> Option<NaiveDateTime> {
+        self.fields
+            .get(&field)
+            .map(|seconds| NaiveDateTime::from_timestamp(seconds + FIT_EPOCH, 0))
+    }
+
+    /// A field stored multiplied by its scale
+    fn scaled(&self, field: u8, scale: f64) -> Option<f64> {
+        self.fields.get(&field).map(|value| *value as f64 / scale)
+    }
+}
+
+struct Definition {
+    global: u16,
+    big_endian: bool,
+    /// Field number, size in bytes and base type
+    fields: Vec<(u8, usize, u8)>,
+    developer_size: usize,
+}
+
+fn to_activity(
+    session: &Message,
+    laps: &[&Message],
+    messages: &[Message],
+    path: &str,
+) -> Result<Activity, ApiError> {
+    let laps = match laps.is_empty() {
+        true => vec![to_lap(session, path)?],
+        false => laps
+            .iter()
+            .enumerate()
+            .map(|(index, lap)| to_lap(lap, &format!("{}.lap[{}]", path, index)))
+            .collect::<Result<Vec<Lap>, ApiError>>()?,
+    };
+
+    // Records of the session, from its start to its end
+    let start = session.fields.get(&START_TIME);
+    let end = session.fields.get(&TIMESTAMP);
+    let records: Vec<&Message> = messages
+        .iter()
+        .filter(|message| message.global == RECORD)
+        .filter(|record| match (record.fields.get(&TIMESTAMP), start, end) {
+            (Some(time), Some(start), Some(end)) => time >= start && time <= end,
+            _ => true,
+        })
+        .collect();
+
+    let elevation_gain = session.scaled(SESSION_TOTAL_ASCENT, 1.0).or_else(|| {
+        climb(records.iter().filter_map(|record| {
+            record
+                .scaled(ENHANCED_ALTITUDE, 5.0)
+                .or_else(|| record.scaled(ALTITUDE, 5.0))
+                .map(|altitude| altitude - 500.0)
+        }))
+    });
+    let heart_rate = session
+        .fields
+        .get(&SESSION_AVG_HEART_RATE)
+        .map(|bpm| *bpm as i32)
+        .or_else(|| {
+            average(
+                records
+                    .iter()
+                    .filter_map(|record| record.fields.get(&HEART_RATE).map(|bpm| *bpm as i32)),
+            )
+        });
+
+    Activity::from_laps(laps, elevation_gain, heart_rate)
+        .ok_or_else(|| ApiError::invalid_field(path, "a session needs at least one lap"))
+}
+
+/// Lap and session messages share the fields of a lap
+fn to_lap(message: &Message, path: &str) -> Result<Lap, ApiError> {
+    let missing = |field: &str| {
+        let kind = if message.global == LAP {
+            "lap"
+        } else {
+            "session"
+        };
+        let error = format!("byte {}: a {} needs its {}", message.offset, kind, field);
+        ApiError::invalid_field(path, &error)
+    };
+    let started_at = message
+        .time(START_TIME)
+        .ok_or_else(|| missing("start_time"))?;
+    let duration = message
+        .scaled(TOTAL_TIMER_TIME, 1000.0)
+        .ok_or_else(|| missing("total_timer_time"))?;
+    let distance = message
+        .scaled(TOTAL_DISTANCE, 100.0)
+        .ok_or_else(|| missing("total_distance"))?;
+
+    Ok(Lap {
+        started_at,
+        distance: distance / METERS_PER_MILE,
+        duration: duration.round() as i32,
+    })
+}
+
+/// Check the file and read its session, lap and record messages
+fn read_messages(file: &[u8]) -> Result<Vec<Message>, ApiError> {
+    let header_size = file[0] as usize;
+    let data_size = u32::from_le_bytes([file[4], file[5], file[6], file[7]]) as usize;
+    let end = header_size + data_size;
+    if file.len() < end + 2 {
+        let error = format!(
+            "byte {}: the file is shorter than its header says",
+            file.len()
+        );
+        return Err(ApiError::invalid_field("file", &error));
+    }
+    if crc(&file[..end]) != u16::from_le_bytes([file[end], file[end + 1]]) {
+        return Err(ApiError::invalid_field(
+            "file",
+            "the file's CRC doesn't match, it may be corrupted",
+        ));
+    }
+
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    let mut messages = vec![];
+    let mut position = header_size;
+    let mut last_timestamp: i64 = 0;
+    while position < end {
+        let offset = position;
+        let header = take(file, &mut position, 1, end)?[0];
+
+        // Compressed timestamp headers only have 5 bits of the timestamp
+        let (local, timestamp) = if header & 0x80 != 0 {
+            let time_offset = i64::from(header & 0x1F);
+            let mut timestamp = (last_timestamp & !0x1F) + time_offset;
+            if time_offset < last_timestamp & 0x1F {
+                timestamp += 0x20;
+            }
+            ((header >> 5) & 0x03, Some(timestamp))
+        } else {
+            (header & 0x0F, None)
+        };
+
+        if header & 0xC0 == 0x40 {
+            let definition = read_definition(file, &mut position, end, header & 0x20 != 0)?;
+            definitions.insert(local, definition);
+            continue;
+        }
+
+        let definition = definitions.get(&local).ok_or_else(|| {
+            let error = format!("byte {}: local message {} isn't defined", offset, local);
+            ApiError::invalid_field("file", &error)
+        })?;
+        let mut fields = HashMap::new();
+        for (number, size, base_type) in &definition.fields {
+            let bytes = take(file, &mut position, *size, end)?;
+            if let Some(value) = read_value(bytes, *base_type, definition.big_endian) {
+                fields.insert(*number, value);
+            }
+        }
+        take(file, &mut position, definition.developer_size, end)?;
+
+        if let Some(timestamp) = timestamp {
+            fields.insert(TIMESTAMP, timestamp);
+        }
+        if let Some(timestamp) = fields.get(&TIMESTAMP) {
+            last_timestamp = *timestamp;
+        }
+        if [SESSION, LAP, RECORD].contains(&definition.global) {
+            messages.push(Message {
+                global: definition.global,
+                offset,
+                fields,
+            });
+        }
+    }
+    Ok(messages)
+}
+
+fn read_definition(
+    file: &[u8],
+    position: &mut usize,
+    end: usize,
+    developer_data: bool,
+) -> Result<Definition, ApiError> {
+    let header = take(file, position, 5, end)?;
+    let big_endian = header[1] == 1;
+    let global = match big_endian {
+        true => u16::from_be_bytes([header[2], header[3]]),
+        false => u16::from_le_bytes([header[2], header[3]]),
+    };
+    let fields = take(file, position, header[4] as usize * 3, end)?
+        .chunks(3)
+        .map(|field| (field[0], field[1] as usize, field[2]))
+        .collect();
+
+    let mut developer_size = 0;
+    if developer_data {
+        let count = take(file, position, 1, end)?[0] as usize;
+        developer_size = take(file, position, count * 3, end)?
+            .chunks(3)
+            .map(|field| field[1] as usize)
+            .sum();
+    }
+
+    Ok(Definition {
+        global,
+        big_endian,
+        fields,
+        developer_size,
+    })
+}
+
+/// Read an integer field, None when it's the type's invalid value
+///
+/// Arrays only have their first value read, other types aren't read.
+fn read_value(bytes: &[u8], base_type: u8, big_endian: bool) -> Option<i64> {
+    let (size, signed, invalid) = match base_type & 0x1F {
+        0 | 2 => (1, false, 0xFF),
+        1 => (1, true, 0x7F),
+        3 => (2, true, 0x7FFF),
+        4 => (2, false, 0xFFFF),
+        5 => (4, true, 0x7FFF_FFFF),
+        6 => (4, false, 0xFFFF_FFFF),
+        10..=12 => (1 << ((base_type & 0x1F) - 10), false, 0),
+        _ => return None,
+    };
+    if bytes.len() < size {
+        return None;
+    }
+
+    let mut raw: u64 = 0;
+    for index in 0..size {
+        let byte = if big_endian {
+            bytes[index]
+        } else {
+            bytes[size - 1 - index]
+        };
+        raw = (raw << 8) | u64::from(byte);
+    }
+    if raw == invalid {
+        return None;
+    }
+    let value = match (signed, size) {
+        (true, 1) => i64::from(raw as u8 as i8),
+        (true, 2) => i64::from(raw as u16 as i16),
+        (true, _) => i64::from(raw as u32 as i32),
+        (false, _) => raw as i64,
+    };
+    Some(value)
+}
+
+fn take<'a>(
+    file: &'a [u8],
+    position: &mut usize,
+    size: usize,
+    end: usize,
+) -> Result<&'a [u8], ApiError> {
+    if *position + size > end {
+        let error = format!(
+            "byte {}: the file ends in the middle of a message",
+            position
+        );
+        return Err(ApiError::invalid_field("file", &error));
+    }
+    let bytes = &file[*position..*position + size];
+    *position += size;
+    Ok(bytes)
+}
+
+/// CRC-16 of the FIT protocol
+pub fn crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| {
+        let crc = (crc >> 4) ^ CRC_TABLE[(crc & 0xF) as usize] ^ CRC_TABLE[(byte & 0xF) as usize];
+        (crc >> 4) ^ CRC_TABLE[(crc & 0xF) as usize] ^ CRC_TABLE[(byte >> 4) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::tests::field_error;
+
+    const MULTI_LAP: &[u8] = include_bytes!("../tests/fixtures/multi-lap.fit");
+    const PAUSED: &[u8] = include_bytes!("../tests/fixtures/paused.fit");
+
+    #[test]
+    fn it_recognizes_fit_files() {
+        assert!(is_fit(MULTI_LAP));
+        assert!(!is_fit(b"<?xml version=\"1.0\"?>"));
+    }
+
+    #[test]
+    fn it_parses_every_lap() {
+        let activities = parse(MULTI_LAP).unwrap();
+        let (element, activity) = &activities[0];
+        assert_eq!(element, "session[0]");
+        assert_eq!(activity.started_at.to_string(), "2020-05-09 07:30:00");
+        assert_eq!(activity.laps.len(), 3);
+        assert_eq!(
+            activity.laps[2].started_at.to_string(),
+            "2020-05-09 07:46:00"
+        );
+        assert!((activity.distance - 3.0).abs() < 0.01);
+        assert_eq!(activity.duration, 1440);
+        // The session's total ascent of 42 meters
+        assert!((activity.elevation_gain.unwrap() - 137.8).abs() < 0.1);
+        assert_eq!(activity.heart_rate, Some(155));
+    }
+
+    #[test]
+    fn it_leaves_pauses_out() {
+        let activities = parse(PAUSED).unwrap();
+        let activity = &activities[0].1;
+        assert_eq!(activity.duration, 1800);
+        assert!((activity.distance - 3.728).abs() < 0.01);
+    }
+
+    #[test]
+    fn it_falls_back_to_records() {
+        // The session doesn't have a total ascent or heart rate, and its
+        // records have compressed timestamps
+        let activity = &parse(PAUSED).unwrap()[0].1;
+        assert!((activity.elevation_gain.unwrap() - 98.4).abs() < 0.1);
+        assert_eq!(activity.heart_rate, Some(150));
+    }
+
+    #[test]
+    fn it_checks_the_crc() {
+        let mut file = MULTI_LAP.to_vec();
+        file[40] ^= 0xFF;
+        let (field, message) = field_error(parse(&file).unwrap_err());
+        assert_eq!(field, "file");
+        assert!(message.contains("CRC"));
+    }
+
+    #[test]
+    fn it_points_at_a_truncated_message() {
+        // Claim less data than there is, cutting the last session short
+        let mut file = MULTI_LAP[..MULTI_LAP.len() - 4].to_vec();
+        let data_size = (file.len() - 14) as u32;
+        file[4..8].copy_from_slice(&data_size.to_le_bytes());
+        let file_crc = crc(&file);
+        file.extend_from_slice(&file_crc.to_le_bytes());
+        let (_, message) = field_error(parse(&file).unwrap_err());
+        assert!(message.starts_with("byte "));
+        assert!(message.ends_with("the file ends in the middle of a message"));
+    }
+
+    #[test]
+    fn it_needs_a_session() {
+        let mut file = MULTI_LAP[..14].to_vec();
+        file[4..8].copy_from_slice(&0u32.to_le_bytes());
+        let file_crc = crc(&file);
+        file.extend_from_slice(&file_crc.to_le_bytes());
+        let (field, _) = field_error(parse(&file).unwrap_err());
+        assert_eq!(field, "file");
+    }
+}
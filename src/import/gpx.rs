@@ -0,0 +1,206 @@
+//! GPX 1.1 files, https://www.topografix.com/GPX/1/1/
+//!
+//! Every `<trk>` is a run made of the `<trkpt>`s of its `<trkseg>`s. Points
+//! need a `lat`, a `lon` and a `<time>`, `<ele>` and the Garmin heart rate
+//! extension (`<gpxtpx:hr>`) are optional.
+
+use crate::errors::ApiError;
+use crate::import::{invalid, Activity, Point};
+use chrono::{DateTime, NaiveDateTime};
+use roxmltree::{Document, Node};
+
+pub const MEDIA_TYPE: &str = "application/gpx+xml";
+
+/// Parse every track of a GPX file into an activity, keyed by its element
+pub fn parse(document: &Document) -> Result<Vec<(String, Activity)>, ApiError> {
+    let root = document.root_element();
+    let mut activities = vec![];
+    for (index, track) in children(&root, "trk").enumerate() {
+        let element = format!("trk[{}]", index);
+        let mut segments = vec![];
+        for (segment_index, segment) in children(&track, "trkseg").enumerate() {
+            let mut points = vec![];
+            for (point_index, point) in children(&segment, "trkpt").enumerate() {
+                let path = format!(
+                    "{}.trkseg[{}].trkpt[{}]",
+                    element, segment_index, point_index
+                );
+                points.push(parse_point(&point, &path)?);
+            }
+            segments.push(points);
+        }
+        let activity = Activity::from_segments(&segments)
+            .ok_or_else(|| invalid(&track, &element, "a track needs at least two trackpoints"))?;
+        activities.push((element, activity));
+    }
+
+    if activities.is_empty() {
+        return Err(invalid(&root, "gpx", "the file doesn't have any <trk>"));
+    }
+    Ok(activities)
+}
+
+fn parse_point(point: &Node, path: &str) -> Result<Point, ApiError> {
+    let coordinate = |name: &str, max: f64| {
+        point
+            .attribute(name)
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.abs() <= max)
+            .ok_or_else(|| {
+                let message = format!("{} must be a number between -{} and {}", name, max, max);
+                invalid(point, path, &message)
+            })
+    };
+    let latitude = coordinate("lat", 90.0)?;
+    let longitude = coordinate("lon", 180.0)?;
+
+    let time = child_text(point, "time")
+        .ok_or_else(|| invalid(point, path, "a trackpoint needs a <time>"))?;
+    let time = parse_time(time).ok_or_else(|| {
+        invalid(
+            point,
+            path,
+            "<time> must be a date and time like 2020-05-09T07:30:00Z",
+        )
+    })?;
+
+    let elevation = match child_text(point, "ele") {
+        Some(elevation) => Some(
+            elevation
+                .parse::<f64>()
+                .map_err(|_| invalid(point, path, "<ele> must be a number of meters"))?,
+        ),
+        None => None,
+    };
+    let heart_rate = point
+        .descendants()
+        .find(|node| node.has_tag_name("hr"))
+        .and_then(|node| node.text())
+        .and_then(|hr| hr.trim().parse::<i32>().ok());
+
+    Ok(Point {
+        latitude,
+        longitude,
+        time,
+        elevation,
+        heart_rate,
+    })
+}
+
+/// Times are in UTC, with a Z or an offset
+pub fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.naive_utc())
+}
+
+pub fn children<'a, 'input: 'a>(
+    node: &Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.has_tag_name(name))
+}
+
+pub fn child_text<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::import::parse as parse_file;
+    use crate::import::tests::field_error;
+
+    fn parse(gpx: &str) -> Result<Vec<(String, Activity)>, ApiError> {
+        parse_file(gpx.as_bytes())
+    }
+
+    /// A GPX file with a 2 mile track, tracks are given as lists of points
+    pub fn gpx_file(tracks: &[&str]) -> String {
+        let tracks: Vec<String> = tracks
+            .iter()
+            .map(|points| format!("<trk><name>Run</name><trkseg>{}</trkseg></trk>", points))
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+{}
+</gpx>"#,
+            tracks.join("\n")
+        )
+    }
+
+    pub fn trkpt(latitude: f64, time: &str, elevation: f64) -> String {
+        format!(
+            r#"<trkpt lat="{}" lon="-105.0"><ele>{}</ele><time>{}</time><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>150</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>"#,
+            latitude, elevation, time
+        )
+    }
+
+    fn two_miles() -> String {
+        [
+            trkpt(40.0, "2020-05-09T07:30:00Z", 1600.0),
+            trkpt(40.01447, "2020-05-09T07:40:00Z", 1620.0),
+            trkpt(40.02894, "2020-05-09T07:50:00Z", 1610.0),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn it_parses_a_track() {
+        let activities = parse(&gpx_file(&[&two_miles()])).unwrap();
+        assert_eq!(activities.len(), 1);
+        let (element, activity) = &activities[0];
+        assert_eq!(element, "trk[0]");
+        assert_eq!(activity.started_at.to_string(), "2020-05-09 07:30:00");
+        assert!((activity.distance - 2.0).abs() < 0.01);
+        assert_eq!(activity.duration, 1200);
+        assert!((activity.elevation_gain.unwrap() - 65.6).abs() < 0.1);
+        assert_eq!(activity.heart_rate, Some(150));
+    }
+
+    #[test]
+    fn it_parses_every_track() {
+        let activities = parse(&gpx_file(&[&two_miles(), &two_miles()])).unwrap();
+        assert_eq!(activities[1].0, "trk[1]");
+    }
+
+    #[test]
+    fn it_points_at_an_invalid_trackpoint() {
+        let points = two_miles().replacen(r#"lat="40.01447""#, r#"lat="north""#, 1);
+        let (field, message) = field_error(parse(&gpx_file(&[&points])).unwrap_err());
+        assert_eq!(field, "trk[0].trkseg[0].trkpt[1]");
+        assert_eq!(message, "line 5: lat must be a number between -90 and 90");
+    }
+
+    #[test]
+    fn it_needs_times() {
+        let points = two_miles().replacen("<time>2020-05-09T07:30:00Z</time>", "", 1);
+        let (_, message) = field_error(parse(&gpx_file(&[&points])).unwrap_err());
+        assert!(message.ends_with("a trackpoint needs a <time>"));
+    }
+
+    #[test]
+    fn it_leaves_pauses_between_segments_out() {
+        let file = include_str!("../tests/fixtures/paused.gpx");
+        let activity = &parse(file).unwrap()[0].1;
+        assert_eq!(activity.laps.len(), 2);
+        assert_eq!(
+            activity.laps[1].started_at.to_string(),
+            "2020-05-10 07:20:00"
+        );
+        assert_eq!(activity.duration, 1800);
+        assert!((activity.distance - 3.728).abs() < 0.01);
+    }
+
+    #[test]
+    fn it_needs_a_track() {
+        let (field, _) = field_error(parse(&gpx_file(&[])).unwrap_err());
+        assert_eq!(field, "gpx");
+    }
+}
@@ -0,0 +1,375 @@
+//! Import runs from the files watches and phones record
+//!
+//! GPX, TCX and FIT files are told apart by their content rather than their
+//! name or media type. Each format is parsed into activities, normalised
+//! into distance, moving time, laps, heart rate and elevation gain, and every
+//! activity becomes a run once it passes the same validation as a run logged
+//! by hand.
+//!
+//! Commissioners can also import a whole league's runs from a spreadsheet,
+//! see `csv`.
+
+pub mod csv;
+pub mod fit;
+pub mod gpx;
+pub mod tcx;
+
+use crate::errors::{ApiError, FieldErrors};
+use crate::models::runs::CreateRunRequest;
+use crate::validate::collect_errors;
+use chrono::NaiveDateTime;
+use roxmltree::{Document, Node};
+use validator::Validate;
+
+/// Largest file accepted, in bytes
+pub const MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Media types of the files that can be imported
+pub const MEDIA_TYPES: [&str; 3] = [gpx::MEDIA_TYPE, tcx::MEDIA_TYPE, fit::MEDIA_TYPE];
+
+pub const METERS_PER_MILE: f64 = 1609.344;
+pub const FEET_PER_METER: f64 = 3.28084;
+
+/// Mean radius of the earth, in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Slower than this between two points is standing still, in meters/second
+const MIN_MOVING_SPEED: f64 = 0.5;
+
+/// Elevation changes smaller than this are GPS noise, in meters
+const ELEVATION_NOISE: f64 = 2.0;
+
+/// A recorded position
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub time: NaiveDateTime,
+    /// In meters
+    pub elevation: Option<f64>,
+    pub heart_rate: Option<i32>,
+}
+
+/// A recorded run summarized into what a run is logged with
+///
+/// distance is in miles, duration is the moving time in seconds and
+/// elevation_gain is in feet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Activity {
+    pub started_at: NaiveDateTime,
+    pub distance: f64,
+    pub duration: i32,
+    pub elevation_gain: Option<f64>,
+    pub heart_rate: Option<i32>,
+    pub laps: Vec<Lap>,
+}
+
+/// A lap of an activity, distance is in miles and duration in seconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lap {
+    pub started_at: NaiveDateTime,
+    pub distance: f64,
+    pub duration: i32,
+}
+
+impl Activity {
+    /// Summarize the segments of a track, each segment being a lap
+    ///
+    /// Time between segments is a pause and isn't counted, neither are the
+    /// stretches spent standing still. Tracks without two points are empty.
+    pub fn from_segments(segments: &[Vec<Point>]) -> Option<Activity> {
+        let points = || segments.iter().flatten();
+        if points().count() < 2 {
+            return None;
+        }
+
+        let laps = segments
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                let mut distance = 0.0;
+                let mut duration = 0;
+                for pair in segment.windows(2) {
+                    let meters = haversine(&pair[0], &pair[1]);
+                    let seconds = (pair[1].time - pair[0].time).num_seconds();
+                    distance += meters;
+                    if seconds > 0 && meters / seconds as f64 >= MIN_MOVING_SPEED {
+                        duration += seconds;
+                    }
+                }
+                Lap {
+                    started_at: segment[0].time,
+                    distance: distance / METERS_PER_MILE,
+                    duration: duration as i32,
+                }
+            })
+            .collect();
+        let elevation_gain = total_climb(
+            segments
+                .iter()
+                .map(|segment| climb(segment.iter().filter_map(|point| point.elevation))),
+        );
+        let heart_rate = average(points().filter_map(|point| point.heart_rate));
+        Activity::from_laps(laps, elevation_gain, heart_rate)
+    }
+
+    /// Add laps up into an activity, elevation_gain is in meters
+    pub fn from_laps(
+        laps: Vec<Lap>,
+        elevation_gain: Option<f64>,
+        heart_rate: Option<i32>,
+    ) -> Option<Activity> {
+        Some(Activity {
+            started_at: laps.iter().map(|lap| lap.started_at).min()?,
+            distance: laps.iter().map(|lap| lap.distance).sum(),
+            duration: laps.iter().map(|lap| lap.duration).sum(),
+            elevation_gain: elevation_gain.map(|meters| meters * FEET_PER_METER),
+            heart_rate,
+            laps,
+        })
+    }
+}
+
+impl From<Activity> for CreateRunRequest {
+    fn from(activity: Activity) -> Self {
+        CreateRunRequest {
+            distance: activity.distance,
+            duration: activity.duration,
+            started_at: activity.started_at,
+            elevation_gain: activity.elevation_gain,
+            heart_rate: activity.heart_rate,
+        }
+    }
+}
+
+/// Turn activities into runs and their laps, validated like runs logged by
+/// hand
+///
+/// Errors are keyed by the element the activity came from, e.g.
+/// `trk[1].distance`.
+pub fn run_requests(
+    activities: Vec<(String, Activity)>,
+) -> Result<Vec<(CreateRunRequest, Vec<Lap>)>, ApiError> {
+    let mut field_errors = FieldErrors::new();
+    let mut requests = vec![];
+    for (element, activity) in activities {
+        let laps = activity.laps.clone();
+        let request: CreateRunRequest = activity.into();
+        if let Err(errors) = request.validate() {
+            for (field, messages) in collect_errors(errors) {
+                field_errors.insert(format!("{}.{}", element, field), messages);
+            }
+        }
+        requests.push((request, laps));
+    }
+
+    if !field_errors.is_empty() {
+        return Err(ApiError::ValidationError(field_errors));
+    }
+    Ok(requests)
+}
+
+/// Parse a GPX, TCX or FIT file into its activities, keyed by their element
+pub fn parse(file: &[u8]) -> Result<Vec<(String, Activity)>, ApiError> {
+    if fit::is_fit(file) {
+        return fit::parse(file);
+    }
+
+    let unknown = "the file must be a GPX, TCX or FIT file";
+    let xml = std::str::from_utf8(file).map_err(|_| ApiError::invalid_field("file", unknown))?;
+    let document = Document::parse(xml.trim_start_matches('\u{feff}')).map_err(|error| {
+        ApiError::invalid_field("file", &format!("line {}: {}", error.pos().row, error))
+    })?;
+    let root = document.root_element();
+    match root.tag_name().name() {
+        "gpx" => gpx::parse(&document),
+        "TrainingCenterDatabase" => tcx::parse(&document),
+        _ => Err(invalid(&root, "file", unknown)),
+    }
+}
+
+/// Distance between two points on the surface of the earth, in meters
+pub fn haversine(from: &Point, to: &Point) -> f64 {
+    let (from_latitude, to_latitude) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let latitude_delta = to_latitude - from_latitude;
+    let longitude_delta = (to.longitude - from.longitude).to_radians();
+    let a = (latitude_delta / 2.0).sin().powi(2)
+        + from_latitude.cos() * to_latitude.cos() * (longitude_delta / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Total climb over consecutive elevations in meters, ignoring GPS noise
+pub fn climb<I: IntoIterator<Item = f64>>(elevations: I) -> Option<f64> {
+    let mut elevations = elevations.into_iter();
+    let mut reference = elevations.next()?;
+    let mut gain = 0.0;
+    for elevation in elevations {
+        if elevation - reference >= ELEVATION_NOISE {
+            gain += elevation - reference;
+            reference = elevation;
+        } else if reference - elevation >= ELEVATION_NOISE {
+            reference = elevation;
+        }
+    }
+    Some(gain)
+}
+
+/// Add up the climbs of the parts of an activity that have elevations
+pub fn total_climb<I: IntoIterator<Item = Option<f64>>>(climbs: I) -> Option<f64> {
+    climbs
+        .into_iter()
+        .flatten()
+        .fold(None, |total, climb| Some(total.unwrap_or(0.0) + climb))
+}
+
+pub fn average<I: IntoIterator<Item = i32>>(heart_rates: I) -> Option<i32> {
+    let heart_rates: Vec<i32> = heart_rates.into_iter().collect();
+    match heart_rates.len() {
+        0 => None,
+        count => Some(heart_rates.iter().sum::<i32>() / count as i32),
+    }
+}
+
+/// A validation error about an XML element, pointing at the line it's on
+pub fn invalid(node: &Node, path: &str, message: &str) -> ApiError {
+    let line = node.document().text_pos_at(node.range().start).row;
+    ApiError::invalid_field(path, &format!("line {}: {}", line, message))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    /// The field and first message of a validation error
+    pub fn field_error(error: ApiError) -> (String, String) {
+        match error {
+            ApiError::ValidationError(fields) => {
+                let (field, messages) = fields.into_iter().next().unwrap();
+                (field, messages[0].to_string())
+            }
+            _ => panic!("expected a validation error, got {:?}", error),
+        }
+    }
+
+    /// A point a number of seconds into a run an hour ago
+    pub fn point(latitude: f64, longitude: f64, seconds: i64, elevation: f64) -> Point {
+        let start = Utc::now().naive_utc() - Duration::hours(1);
+        Point {
+            latitude,
+            longitude,
+            time: start + Duration::seconds(seconds),
+            elevation: Some(elevation),
+            heart_rate: None,
+        }
+    }
+
+    #[test]
+    fn it_measures_distances() {
+        // One degree of latitude is about 69.1 miles
+        let meters = haversine(&point(40.0, -105.0, 0, 0.0), &point(41.0, -105.0, 0, 0.0));
+        assert!((meters / METERS_PER_MILE - 69.1).abs() < 0.1);
+    }
+
+    #[test]
+    fn it_summarizes_a_track() {
+        let segment = vec![
+            point(40.0, -105.0, 0, 1600.0),
+            point(40.005, -105.0, 200, 1610.0),
+            point(40.01, -105.0, 400, 1605.0),
+        ];
+        let started_at = segment[0].time;
+        let activity = Activity::from_segments(&[segment]).unwrap();
+        assert_eq!(activity.started_at, started_at);
+        assert!((activity.distance - 0.691).abs() < 0.01);
+        assert_eq!(activity.duration, 400);
+        assert!((activity.elevation_gain.unwrap() - 32.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn it_doesnt_count_standing_still_or_pauses() {
+        let first = vec![
+            point(40.0, -105.0, 0, 1600.0),
+            point(40.005, -105.0, 200, 1600.0),
+            point(40.005, -105.0, 500, 1600.0),
+        ];
+        let second = vec![
+            point(40.01, -105.0, 2000, 1600.0),
+            point(40.015, -105.0, 2200, 1600.0),
+        ];
+        let activity = Activity::from_segments(&[first, second]).unwrap();
+        assert_eq!(activity.duration, 400);
+        assert!((activity.distance - 0.691).abs() < 0.01);
+    }
+
+    #[test]
+    fn it_ignores_elevation_noise() {
+        let segment: Vec<Point> = (0..10)
+            .map(|i| {
+                point(
+                    40.0 + i as f64 * 0.001,
+                    -105.0,
+                    i * 30,
+                    1600.0 + (i % 2) as f64,
+                )
+            })
+            .collect();
+        let activity = Activity::from_segments(&[segment]).unwrap();
+        assert_eq!(activity.elevation_gain, Some(0.0));
+    }
+
+    #[test]
+    fn it_makes_a_lap_of_every_segment() {
+        let first = vec![point(40.0, -105.0, 0, 0.0), point(40.005, -105.0, 200, 0.0)];
+        let second = vec![
+            point(40.01, -105.0, 500, 0.0),
+            point(40.015, -105.0, 700, 0.0),
+        ];
+        let resumed_at = second[0].time;
+        let activity = Activity::from_segments(&[first, second]).unwrap();
+        assert_eq!(activity.laps.len(), 2);
+        assert_eq!(activity.laps[1].duration, 200);
+        assert_eq!(activity.laps[1].started_at, resumed_at);
+    }
+
+    #[test]
+    fn it_sniffs_the_format() {
+        let (_, message) = field_error(parse(b"<html></html>").unwrap_err());
+        assert_eq!(message, "line 1: the file must be a GPX, TCX or FIT file");
+        let (_, message) = field_error(parse(&[0xff, 0x00, 0x13]).unwrap_err());
+        assert_eq!(message, "the file must be a GPX, TCX or FIT file");
+    }
+
+    #[test]
+    fn it_points_at_malformed_xml() {
+        let (field, message) = field_error(parse(b"<gpx>\n<trk>\n</gpx>").unwrap_err());
+        assert_eq!(field, "file");
+        assert!(message.starts_with("line 3: "), "{}", message);
+    }
+
+    #[test]
+    fn it_needs_two_points() {
+        assert_eq!(Activity::from_segments(&[]), None);
+        assert_eq!(
+            Activity::from_segments(&[vec![point(40.0, -105.0, 0, 0.0)]]),
+            None
+        );
+    }
+
+    #[test]
+    fn it_validates_imported_runs() {
+        let activity = Activity {
+            started_at: Utc::now().naive_utc(),
+            distance: 3.1,
+            duration: 60,
+            elevation_gain: None,
+            heart_rate: None,
+            laps: vec![],
+        };
+        let error = run_requests(vec![("trk[0]".into(), activity)]).unwrap_err();
+        match error {
+            ApiError::ValidationError(fields) => assert!(fields.contains_key("trk[0].__all__")),
+            _ => panic!("expected a validation error"),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Garmin Training Center (TCX) files,
+//! https://www8.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd
+//!
+//! Every `<Activity>` is a run made of its `<Lap>`s. Laps carry the distance
+//! and the timer time the watch measured, which leaves pauses out, so they're
+//! used as they are rather than recomputed from the trackpoints. Trackpoints
+//! only add elevation gain and heart rate, when they have them.
+
+use crate::errors::ApiError;
+use crate::import::gpx::{child_text, children, parse_time};
+use crate::import::{average, climb, invalid, total_climb, Activity, Lap, METERS_PER_MILE};
+use roxmltree::{Document, Node};
+
+pub const MEDIA_TYPE: &str = "application/vnd.garmin.tcx+xml";
+
+/// Parse every activity of a TCX file, keyed by its element
+pub fn parse(document: &Document) -> Result<Vec<(String, Activity)>, ApiError> {
+    let root = document.root_element();
+    let mut activities = vec![];
+    let elements = children(&root, "Activities").flat_map(|node| children(&node, "Activity"));
+    for (index, element) in elements.enumerate() {
+        let path = format!("Activity[{}]", index);
+        let mut laps = vec![];
+        let mut climbs = vec![];
+        let mut heart_rates = vec![];
+        for (lap_index, lap) in children(&element, "Lap").enumerate() {
+            let lap_path = format!("{}.Lap[{}]", path, lap_index);
+            laps.push(parse_lap(&lap, &lap_path)?);
+
+            // A lap has a track for every stretch between pauses
+            for track in children(&lap, "Track") {
+                let trackpoints = || children(&track, "Trackpoint");
+                climbs.push(climb(
+                    trackpoints().filter_map(|trackpoint| number(&trackpoint, "AltitudeMeters")),
+                ));
+                heart_rates.extend(trackpoints().filter_map(|trackpoint| heart_rate(&trackpoint)));
+            }
+        }
+
+        let activity = Activity::from_laps(laps, total_climb(climbs), average(heart_rates))
+            .ok_or_else(|| invalid(&element, &path, "an activity needs at least one <Lap>"))?;
+        activities.push((path, activity));
+    }
+
+    if activities.is_empty() {
+        return Err(invalid(
+            &root,
+            "TrainingCenterDatabase",
+            "the file doesn't have any <Activity>",
+        ));
+    }
+    Ok(activities)
+}
+
+fn parse_lap(lap: &Node, path: &str) -> Result<Lap, ApiError> {
+    let started_at = lap
+        .attribute("StartTime")
+        .and_then(parse_time)
+        .ok_or_else(|| {
+            invalid(
+                lap,
+                path,
+                "a lap needs a StartTime like 2020-05-09T07:30:00Z",
+            )
+        })?;
+    let duration = number(lap, "TotalTimeSeconds")
+        .filter(|seconds| *seconds >= 0.0)
+        .ok_or_else(|| invalid(lap, path, "a lap needs its <TotalTimeSeconds>"))?;
+    let distance = number(lap, "DistanceMeters")
+        .filter(|meters| *meters >= 0.0)
+        .ok_or_else(|| invalid(lap, path, "a lap needs its <DistanceMeters>"))?;
+
+    Ok(Lap {
+        started_at,
+        distance: distance / METERS_PER_MILE,
+        duration: duration.round() as i32,
+    })
+}
+
+fn number(node: &Node, name: &str) -> Option<f64> {
+    child_text(node, name).and_then(|text| text.parse::<f64>().ok())
+}
+
+fn heart_rate(trackpoint: &Node) -> Option<i32> {
+    children(trackpoint, "HeartRateBpm")
+        .next()
+        .and_then(|heart_rate| number(&heart_rate, "Value"))
+        .map(|bpm| bpm.round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::parse;
+    use crate::import::tests::field_error;
+
+    const MULTI_LAP: &str = include_str!("../tests/fixtures/multi-lap.tcx");
+    const PAUSED: &str = include_str!("../tests/fixtures/paused.tcx");
+
+    #[test]
+    fn it_parses_every_lap() {
+        let activities = parse(MULTI_LAP.as_bytes()).unwrap();
+        let (element, activity) = &activities[0];
+        assert_eq!(element, "Activity[0]");
+        assert_eq!(activity.started_at.to_string(), "2020-05-09 07:30:00");
+        assert_eq!(activity.laps.len(), 3);
+        assert_eq!(
+            activity.laps[1].started_at.to_string(),
+            "2020-05-09 07:38:00"
+        );
+        assert!((activity.distance - 3.0).abs() < 0.01);
+        assert_eq!(activity.duration, 1440);
+        assert!((activity.elevation_gain.unwrap() - 98.4).abs() < 0.1);
+        assert_eq!(activity.heart_rate, Some(154));
+    }
+
+    #[test]
+    fn it_leaves_pauses_out() {
+        let activities = parse(PAUSED.as_bytes()).unwrap();
+        let activity = &activities[0].1;
+        assert_eq!(activity.laps.len(), 1);
+        assert_eq!(activity.duration, 1800);
+        assert!((activity.distance - 3.728).abs() < 0.01);
+        assert!((activity.elevation_gain.unwrap() - 98.4).abs() < 0.1);
+    }
+
+    #[test]
+    fn it_points_at_an_invalid_lap() {
+        let file = MULTI_LAP.replacen("<TotalTimeSeconds>480.0</TotalTimeSeconds>", "", 2);
+        let (field, message) = field_error(parse(file.as_bytes()).unwrap_err());
+        assert_eq!(field, "Activity[0].Lap[0]");
+        assert_eq!(message, "line 6: a lap needs its <TotalTimeSeconds>");
+    }
+
+    #[test]
+    fn it_needs_an_activity() {
+        let file = r#"<TrainingCenterDatabase><Activities/></TrainingCenterDatabase>"#;
+        let (field, _) = field_error(parse(file.as_bytes()).unwrap_err());
+        assert_eq!(field, "TrainingCenterDatabase");
+    }
+}
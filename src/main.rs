@@ -10,16 +10,33 @@ extern crate validator_derive;
 use crate::server::server;
 
 mod auth;
+mod cache;
+mod calendar;
 mod config;
 mod database;
+mod draft;
 mod errors;
+mod events;
 pub mod handlers;
 mod helpers;
+mod ical;
+mod import;
 mod managers;
+mod middleware;
 mod models;
+mod openapi;
+mod pagination;
+mod permissions;
+mod providers;
 mod routes;
+mod schedule;
+mod scheduler;
 mod schema;
+mod scoring;
 mod server;
+mod sessions;
+mod standings;
+mod sync;
 mod tests;
 mod validate;
 
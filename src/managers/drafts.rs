@@ -0,0 +1,568 @@
+use crate::database::{transaction, PoolType};
+use crate::draft::{auto_pick, pick_deadline, pick_order, COMPLETE, IN_PROGRESS, SCHEDULED};
+use crate::errors::ApiError;
+use crate::events::{publish_all, LeagueEvent};
+use crate::managers::teams::players_in_league;
+use crate::models::drafts::*;
+use crate::models::teams::Player;
+use crate::schema::{draft_picks, draft_queues, draft_slots, drafts, team_players, teams, users};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::seq::SliceRandom;
+use uuid::Uuid;
+
+/// Get a league's draft
+///
+/// Picks that ran out of time are left to the scheduler and the next pick.
+pub fn find(pool: &PoolType, league_id: Uuid) -> Result<DraftDetails, ApiError> {
+    let conn = pool.get()?;
+    let draft: Draft = drafts::table
+        .filter(drafts::league_id.eq(league_id))
+        .first(&conn)
+        .optional()?
+        .ok_or_else(|| not_found(league_id))?;
+    details(&conn, &draft)
+}
+
+/// Create a league's draft
+///
+/// The draft order has to list every team of the league once, teams are
+/// shuffled when it's left out.
+pub fn create(
+    pool: &PoolType,
+    league_id: Uuid,
+    mode: &str,
+    rounds: i32,
+    pick_seconds: i32,
+    order: Option<Vec<Uuid>>,
+) -> Result<DraftDetails, ApiError> {
+    transaction(pool, |conn| {
+        let existing: Option<Draft> = drafts::table
+            .filter(drafts::league_id.eq(league_id))
+            .first(conn)
+            .optional()?;
+        if existing.is_some() {
+            return Err(ApiError::Conflict(format!(
+                "League {} already has a draft",
+                league_id
+            )));
+        }
+
+        let mut team_ids: Vec<Uuid> = teams::table
+            .select(teams::id)
+            .filter(teams::league_id.eq(league_id))
+            .load(conn)?;
+        let order = match order {
+            Some(order) => {
+                let mut sorted_order = order.clone();
+                sorted_order.sort();
+                team_ids.sort();
+                if sorted_order != team_ids {
+                    return Err(ApiError::BadRequest(format!(
+                        "The draft order must list every team of league {} once",
+                        league_id
+                    )));
+                }
+                order
+            }
+            None => {
+                team_ids.shuffle(&mut rand::thread_rng());
+                team_ids
+            }
+        };
+
+        let now = Utc::now().naive_utc();
+        let draft = Draft {
+            id: Uuid::new_v4(),
+            league_id,
+            mode: mode.into(),
+            rounds,
+            pick_seconds,
+            status: SCHEDULED.into(),
+            current_pick: 0,
+            pick_started_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let slots: Vec<DraftSlot> = order
+            .iter()
+            .enumerate()
+            .map(|(position, team_id)| DraftSlot {
+                id: Uuid::new_v4(),
+                draft_id: draft.id,
+                team_id: *team_id,
+                position: position as i32,
+            })
+            .collect();
+
+        diesel::insert_into(drafts::table)
+            .values(&draft)
+            .execute(conn)?;
+        diesel::insert_into(draft_slots::table)
+            .values(&slots)
+            .execute(conn)?;
+
+        details(conn, &draft)
+    })
+}
+
+/// Start a draft, putting the first team on the clock
+pub fn start(pool: &PoolType, league_id: Uuid) -> Result<DraftDetails, ApiError> {
+    transaction(pool, |conn| {
+        let draft = lock(conn, league_id)?;
+        if draft.status != SCHEDULED {
+            return Err(ApiError::Conflict(format!(
+                "The draft of league {} has already started",
+                league_id
+            )));
+        }
+
+        let now = Utc::now().naive_utc();
+        let started = diesel::update(drafts::table.filter(drafts::id.eq(draft.id)))
+            .set((
+                drafts::status.eq(IN_PROGRESS),
+                drafts::pick_started_at.eq(now),
+                drafts::updated_at.eq(now),
+            ))
+            .get_result(conn)?;
+        details(conn, &started)
+    })
+}
+
+/// Pick a user for the team on the clock
+///
+/// Users already on a team of the league can't be picked again.
+pub fn pick(
+    pool: &PoolType,
+    league_id: Uuid,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> Result<DraftDetails, ApiError> {
+    let now = Utc::now().naive_utc();
+    let (picked, last_pick) = transaction(pool, |conn| {
+        let draft = lock(conn, league_id)?;
+        let last_pick = draft.current_pick;
+        let draft = run_expired_picks(conn, draft, now)?;
+        if draft.status != IN_PROGRESS {
+            return Err(ApiError::Conflict(format!(
+                "The draft of league {} isn't in progress",
+                league_id
+            )));
+        }
+        if on_the_clock(conn, &draft)? != Some(team_id) {
+            return Err(ApiError::BadRequest(format!(
+                "Team {} isn't on the clock",
+                team_id
+            )));
+        }
+
+        let user_exists: Option<Uuid> = users::table
+            .select(users::id)
+            .filter(users::id.eq(user_id))
+            .first(conn)
+            .optional()?;
+        if user_exists.is_none() {
+            return Err(ApiError::NotFound(format!("User {} not found", user_id)));
+        }
+        if players_in_league(conn, league_id)?.contains(&user_id) {
+            return Err(ApiError::Conflict(format!(
+                "User {} is already on a team in league {}",
+                user_id, league_id
+            )));
+        }
+
+        let picked = make_pick(conn, &draft, team_id, Some(user_id), false, now)?;
+        Ok((details(conn, &picked)?, last_pick))
+    })?;
+    publish_picks(&picked, last_pick);
+
+    Ok(picked)
+}
+
+/// Auto-pick for every team whose time ran out by now
+pub fn expire_picks(
+    pool: &PoolType,
+    league_id: Uuid,
+    now: NaiveDateTime,
+) -> Result<DraftDetails, ApiError> {
+    let (draft, last_pick) = transaction(pool, |conn| {
+        let draft = lock(conn, league_id)?;
+        let last_pick = draft.current_pick;
+        let draft = run_expired_picks(conn, draft, now)?;
+        Ok((details(conn, &draft)?, last_pick))
+    })?;
+    publish_picks(&draft, last_pick);
+
+    Ok(draft)
+}
+
+/// Get the users a team wants to pick, best first
+pub fn get_queue(
+    pool: &PoolType,
+    league_id: Uuid,
+    team_id: Uuid,
+) -> Result<DraftQueueResponse, ApiError> {
+    let conn = pool.get()?;
+    let draft: Draft = drafts::table
+        .filter(drafts::league_id.eq(league_id))
+        .first(&conn)
+        .optional()?
+        .ok_or_else(|| not_found(league_id))?;
+    Ok(DraftQueueResponse {
+        team_id,
+        user_ids: queue(&conn, &draft, team_id)?,
+    })
+}
+
+/// Replace the users a team wants to pick, best first
+pub fn set_queue(
+    pool: &PoolType,
+    league_id: Uuid,
+    team_id: Uuid,
+    user_ids: &[Uuid],
+) -> Result<DraftQueueResponse, ApiError> {
+    transaction(pool, |conn| {
+        let draft = lock(conn, league_id)?;
+        if !slots(conn, &draft)?.contains(&team_id) {
+            return Err(ApiError::BadRequest(format!(
+                "Team {} isn't in the draft of league {}",
+                team_id, league_id
+            )));
+        }
+
+        let queued: Vec<QueuedUser> = user_ids
+            .iter()
+            .enumerate()
+            .map(|(rank, user_id)| QueuedUser {
+                id: Uuid::new_v4(),
+                draft_id: draft.id,
+                team_id,
+                user_id: *user_id,
+                rank: rank as i32,
+            })
+            .collect();
+
+        diesel::delete(
+            draft_queues::table
+                .filter(draft_queues::draft_id.eq(draft.id))
+                .filter(draft_queues::team_id.eq(team_id)),
+        )
+        .execute(conn)?;
+        diesel::insert_into(draft_queues::table)
+            .values(&queued)
+            .execute(conn)?;
+
+        Ok(DraftQueueResponse {
+            team_id,
+            user_ids: queue(conn, &draft, team_id)?,
+        })
+    })
+}
+
+/// Tell a league's streams about the picks made after last_pick
+fn publish_picks(draft: &DraftDetails, last_pick: i32) {
+    let events = draft
+        .picks
+        .iter()
+        .filter(|pick| pick.pick_number > last_pick)
+        .map(|pick| LeagueEvent::DraftPick(pick.clone()));
+    publish_all(draft.league_id, events);
+}
+
+/// Lock a league's draft until the end of the current transaction
+fn lock(conn: &PgConnection, league_id: Uuid) -> Result<Draft, ApiError> {
+    drafts::table
+        .filter(drafts::league_id.eq(league_id))
+        .for_update()
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| not_found(league_id))
+}
+
+fn not_found(league_id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("League {} doesn't have a draft", league_id))
+}
+
+/// Auto-pick for every team whose time ran out
+///
+/// Each expired pick starts the next one at its deadline, so a draft left
+/// alone catches up on every pick it missed.
+fn run_expired_picks(
+    conn: &PgConnection,
+    mut draft: Draft,
+    now: NaiveDateTime,
+) -> Result<Draft, ApiError> {
+    while draft.status == IN_PROGRESS {
+        let deadline = match draft.pick_started_at {
+            Some(started_at) => pick_deadline(started_at, draft.pick_seconds),
+            None => break,
+        };
+        if deadline > now {
+            break;
+        }
+        let team_id = match on_the_clock(conn, &draft)? {
+            Some(team_id) => team_id,
+            None => break,
+        };
+
+        let user_id = auto_pick(
+            &queue(conn, &draft, team_id)?,
+            &players_in_league(conn, draft.league_id)?,
+        );
+        draft = make_pick(conn, &draft, team_id, user_id, true, deadline)?;
+    }
+
+    Ok(draft)
+}
+
+/// Record a pick, add the user to the team and move on to the next pick
+fn make_pick(
+    conn: &PgConnection,
+    draft: &Draft,
+    team_id: Uuid,
+    user_id: Option<Uuid>,
+    auto_picked: bool,
+    picked_at: NaiveDateTime,
+) -> Result<Draft, ApiError> {
+    let draft_pick = DraftPick {
+        id: Uuid::new_v4(),
+        draft_id: draft.id,
+        pick_number: draft.current_pick + 1,
+        team_id,
+        user_id,
+        auto_picked,
+        created_at: picked_at,
+    };
+    diesel::insert_into(draft_picks::table)
+        .values(&draft_pick)
+        .execute(conn)?;
+
+    if let Some(user_id) = user_id {
+        let player = Player {
+            id: Uuid::new_v4(),
+            user_id,
+            team_id,
+        };
+        diesel::insert_into(team_players::table)
+            .values(&player)
+            .execute(conn)?;
+    }
+
+    let current_pick = draft.current_pick + 1;
+    let slot_count = slots(conn, draft)?.len() as i32;
+    let (status, pick_started_at) = if current_pick >= slot_count * draft.rounds {
+        (COMPLETE, None)
+    } else {
+        (IN_PROGRESS, Some(picked_at))
+    };
+
+    let updated = diesel::update(drafts::table.filter(drafts::id.eq(draft.id)))
+        .set((
+            drafts::current_pick.eq(current_pick),
+            drafts::status.eq(status),
+            drafts::pick_started_at.eq(pick_started_at),
+            drafts::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result(conn)?;
+    Ok(updated)
+}
+
+/// The team ids of a draft in draft order
+fn slots(conn: &PgConnection, draft: &Draft) -> Result<Vec<Uuid>, ApiError> {
+    let team_ids = draft_slots::table
+        .select(draft_slots::team_id)
+        .filter(draft_slots::draft_id.eq(draft.id))
+        .order(draft_slots::position)
+        .load(conn)?;
+    Ok(team_ids)
+}
+
+/// The team making the current pick, None unless the draft is in progress
+fn on_the_clock(conn: &PgConnection, draft: &Draft) -> Result<Option<Uuid>, ApiError> {
+    if draft.status != IN_PROGRESS {
+        return Ok(None);
+    }
+    let order = pick_order(&slots(conn, draft)?, draft.rounds, &draft.mode);
+    Ok(order.get(draft.current_pick as usize).copied())
+}
+
+/// A team's queue, best first
+fn queue(conn: &PgConnection, draft: &Draft, team_id: Uuid) -> Result<Vec<Uuid>, ApiError> {
+    let user_ids = draft_queues::table
+        .select(draft_queues::user_id)
+        .filter(draft_queues::draft_id.eq(draft.id))
+        .filter(draft_queues::team_id.eq(team_id))
+        .order(draft_queues::rank)
+        .load(conn)?;
+    Ok(user_ids)
+}
+
+/// A draft with its order, its picks so far and who's on the clock
+fn details(conn: &PgConnection, draft: &Draft) -> Result<DraftDetails, ApiError> {
+    let picks: Vec<DraftPick> = draft_picks::table
+        .filter(draft_picks::draft_id.eq(draft.id))
+        .order(draft_picks::pick_number)
+        .load(conn)?;
+
+    Ok(DraftDetails {
+        id: draft.id,
+        league_id: draft.league_id,
+        mode: draft.mode.clone(),
+        rounds: draft.rounds,
+        pick_seconds: draft.pick_seconds,
+        status: draft.status.clone(),
+        current_pick: draft.current_pick,
+        order: slots(conn, draft)?,
+        picks,
+        on_the_clock: on_the_clock(conn, draft)?,
+        pick_deadline: draft
+            .pick_started_at
+            .map(|started_at| pick_deadline(started_at, draft.pick_seconds)),
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::events::BUS;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::managers::users::tests::create_user;
+    use crate::models::teams::TeamDetails;
+    use crate::tests::helpers::tests::get_pool;
+    use chrono::Duration;
+
+    /// Create a league of two teams and start a two round snake draft
+    pub fn start_draft() -> (Uuid, Vec<TeamDetails>) {
+        let (league_id, league_teams) = create_league_with_teams(2);
+        let order: Vec<Uuid> = league_teams.iter().map(|team| team.id).collect();
+        create(&get_pool(), league_id, "snake", 2, 60, Some(order)).unwrap();
+        start(&get_pool(), league_id).unwrap();
+        (league_id, league_teams)
+    }
+
+    #[test]
+    fn it_creates_a_draft_in_random_order() {
+        let (league_id, league_teams) = create_league_with_teams(3);
+        let draft = create(&get_pool(), league_id, "linear", 3, 60, None).unwrap();
+        assert_eq!(draft.status, SCHEDULED);
+        assert_eq!(draft.order.len(), league_teams.len());
+        assert_eq!(draft.on_the_clock, None);
+    }
+
+    #[test]
+    fn it_doesnt_create_a_draft_with_a_bad_order() {
+        let (league_id, league_teams) = create_league_with_teams(2);
+        let order = vec![league_teams[0].id, Uuid::new_v4()];
+        let draft = create(&get_pool(), league_id, "snake", 2, 60, Some(order));
+        assert!(draft.is_err());
+    }
+
+    #[test]
+    fn it_doesnt_create_two_drafts() {
+        let (league_id, _) = create_league_with_teams(2);
+        create(&get_pool(), league_id, "snake", 2, 60, None).unwrap();
+        let draft = create(&get_pool(), league_id, "snake", 2, 60, None);
+        let expected_error =
+            ApiError::Conflict(format!("League {} already has a draft", league_id));
+        assert_eq!(draft.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_picks_in_snake_order() {
+        let (league_id, teams) = start_draft();
+        for team in &[&teams[0], &teams[1], &teams[1], &teams[0]] {
+            let draft = find(&get_pool(), league_id).unwrap();
+            assert_eq!(draft.on_the_clock, Some(team.id));
+            let user_id = create_user().unwrap().id;
+            pick(&get_pool(), league_id, team.id, user_id).unwrap();
+        }
+
+        let draft = find(&get_pool(), league_id).unwrap();
+        assert_eq!(draft.status, COMPLETE);
+        assert_eq!(draft.picks.len(), 4);
+        assert_eq!(draft.on_the_clock, None);
+    }
+
+    #[test]
+    fn it_doesnt_pick_out_of_turn() {
+        let (league_id, teams) = start_draft();
+        let user_id = create_user().unwrap().id;
+        let draft = pick(&get_pool(), league_id, teams[1].id, user_id);
+        let expected_error =
+            ApiError::BadRequest(format!("Team {} isn't on the clock", teams[1].id));
+        assert_eq!(draft.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_doesnt_pick_a_user_already_on_a_team_in_the_league() {
+        let (league_id, teams) = start_draft();
+        let rostered = find_player(teams[1].id);
+        let draft = pick(&get_pool(), league_id, teams[0].id, rostered);
+        let expected_error = ApiError::Conflict(format!(
+            "User {} is already on a team in league {}",
+            rostered, league_id
+        ));
+        assert_eq!(draft.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_auto_picks_from_the_queue_when_time_runs_out() {
+        let (league_id, teams) = start_draft();
+        let rostered = find_player(teams[1].id);
+        let wanted = create_user().unwrap().id;
+        set_queue(&get_pool(), league_id, teams[0].id, &[rostered, wanted]).unwrap();
+
+        let later = Utc::now().naive_utc() + Duration::seconds(150);
+        let draft = expire_picks(&get_pool(), league_id, later).unwrap();
+        assert_eq!(draft.picks[0].user_id, Some(wanted));
+        assert!(draft.picks[0].auto_picked);
+        // teams[1] had an empty queue and its time ran out too
+        assert_eq!(draft.picks[1].user_id, None);
+        assert_eq!(draft.on_the_clock, Some(teams[1].id));
+    }
+
+    #[test]
+    fn it_doesnt_auto_pick_when_reading_a_draft() {
+        let (league_id, teams) = start_draft();
+        let long_ago = Utc::now().naive_utc() - Duration::seconds(150);
+        diesel::update(drafts::table.filter(drafts::league_id.eq(league_id)))
+            .set(drafts::pick_started_at.eq(long_ago))
+            .execute(&get_pool().get().unwrap())
+            .unwrap();
+
+        let draft = find(&get_pool(), league_id).unwrap();
+        assert!(draft.picks.is_empty());
+        assert_eq!(draft.on_the_clock, Some(teams[0].id));
+    }
+
+    #[test]
+    fn it_publishes_auto_picks() {
+        let (league_id, _) = start_draft();
+        let mut events = BUS.subscribe(league_id);
+
+        let later = Utc::now().naive_utc() + Duration::seconds(150);
+        let draft = expire_picks(&get_pool(), league_id, later).unwrap();
+        for pick in &draft.picks {
+            let event = LeagueEvent::DraftPick(pick.clone());
+            assert_eq!(events.try_next().unwrap(), Some(event));
+        }
+        assert!(events.try_next().is_err());
+    }
+
+    #[test]
+    fn it_gets_a_queue() {
+        let (league_id, teams) = start_draft();
+        let user_ids = vec![create_user().unwrap().id, create_user().unwrap().id];
+        set_queue(&get_pool(), league_id, teams[0].id, &user_ids).unwrap();
+        let queue = get_queue(&get_pool(), league_id, teams[0].id).unwrap();
+        assert_eq!(queue.user_ids, user_ids);
+    }
+
+    fn find_player(team_id: Uuid) -> Uuid {
+        crate::managers::teams::find(&get_pool(), team_id)
+            .unwrap()
+            .players
+            .unwrap()[0]
+            .user_id
+    }
+}
@@ -0,0 +1,208 @@
+use crate::calendar::Calendar;
+use crate::database::{transaction, PoolType};
+use crate::draft::{pick_deadline, IN_PROGRESS};
+use crate::errors::ApiError;
+use crate::models::drafts::Draft;
+use crate::models::jobs::Job;
+use crate::models::leagues::League;
+use crate::scheduler::{
+    CLOSE_ROUND, DONE, EXPIRE_PICKS, FAILED, LEASE_SECONDS, MAX_ATTEMPTS, PENDING, REMINDER_HOURS,
+    RETRY_SECONDS, ROUND_REMINDER, RUNNING,
+};
+use crate::schema::{drafts, jobs, leagues};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Make sure a league has jobs for its next round and the pick on the clock
+///
+/// Jobs that already exist are kept, pending ones move when the league or
+/// the draft did unless they're waiting to be retried.
+pub fn plan(pool: &PoolType, league_id: Uuid) -> Result<Vec<Job>, ApiError> {
+    transaction(pool, |conn| {
+        let league: League = leagues::table
+            .filter(leagues::id.eq(league_id))
+            .first(conn)
+            .map_err(|_| ApiError::NotFound(format!("League {} not found", league_id)))?;
+        let draft: Option<Draft> = drafts::table
+            .filter(drafts::league_id.eq(league_id))
+            .filter(drafts::status.eq(IN_PROGRESS))
+            .first(conn)
+            .optional()?;
+
+        let mut planned = vec![];
+        if league.current_round < league.rounds {
+            let round = league.current_round + 1;
+            let (_, end) = Calendar::of(&league).round_window(round);
+            planned.push((CLOSE_ROUND, round, end));
+            planned.push((ROUND_REMINDER, round, end - Duration::hours(REMINDER_HOURS)));
+        }
+        if let Some(Draft {
+            current_pick,
+            pick_seconds,
+            pick_started_at: Some(pick_started_at),
+            ..
+        }) = draft
+        {
+            let deadline = pick_deadline(pick_started_at, pick_seconds);
+            planned.push((EXPIRE_PICKS, current_pick + 1, deadline));
+        }
+
+        planned
+            .into_iter()
+            .map(|(kind, round, run_at)| schedule(conn, league_id, kind, round, run_at))
+            .collect()
+    })
+}
+
+/// Plan the jobs of every league that isn't over or is drafting
+pub fn plan_all(pool: &PoolType) -> Result<usize, ApiError> {
+    let league_ids: Vec<Uuid> = {
+        let conn = pool.get()?;
+        let mut league_ids: Vec<Uuid> = leagues::table
+            .select(leagues::id)
+            .filter(leagues::current_round.lt(leagues::rounds))
+            .load(&conn)?;
+        league_ids.extend(
+            drafts::table
+                .select(drafts::league_id)
+                .filter(drafts::status.eq(IN_PROGRESS))
+                .load::<Uuid>(&conn)?,
+        );
+        league_ids.sort();
+        league_ids.dedup();
+        league_ids
+    };
+
+    let mut planned = 0;
+    for league_id in league_ids {
+        planned += plan(pool, league_id)?.len();
+    }
+    Ok(planned)
+}
+
+/// Claim up to limit jobs that are due, for this worker only
+///
+/// Claimed jobs are running until their lease runs out. Rows other workers
+/// are claiming are skipped instead of waited for, so a job is never handed
+/// out twice.
+pub fn claim(pool: &PoolType, now: NaiveDateTime, limit: i64) -> Result<Vec<Job>, ApiError> {
+    transaction(pool, |conn| {
+        let mut due: Vec<Uuid> = jobs::table
+            .select(jobs::id)
+            .filter(jobs::status.eq(PENDING))
+            .filter(jobs::run_at.le(now))
+            .order(jobs::run_at)
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load(conn)?;
+        let stale: Vec<Uuid> = jobs::table
+            .select(jobs::id)
+            .filter(jobs::status.eq(RUNNING))
+            .filter(jobs::locked_until.lt(now))
+            .order(jobs::run_at)
+            .limit(limit - due.len() as i64)
+            .for_update()
+            .skip_locked()
+            .load(conn)?;
+        due.extend(stale);
+
+        let claimed = diesel::update(jobs::table.filter(jobs::id.eq_any(&due)))
+            .set((
+                jobs::status.eq(RUNNING),
+                jobs::attempts.eq(jobs::attempts + 1),
+                jobs::locked_until.eq(now + Duration::seconds(LEASE_SECONDS)),
+                jobs::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_results::<Job>(conn)?;
+        Ok(claimed)
+    })
+}
+
+/// Mark a job as done for good
+pub fn finish(pool: &PoolType, job_id: Uuid, now: NaiveDateTime) -> Result<Job, ApiError> {
+    let conn = pool.get()?;
+    let job = diesel::update(jobs::table.find(job_id))
+        .set((
+            jobs::status.eq(DONE),
+            jobs::locked_until.eq(None::<NaiveDateTime>),
+            jobs::finished_at.eq(now),
+            jobs::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result(&conn)?;
+
+    Ok(job)
+}
+
+/// Retry a job that failed later, or give up after MAX_ATTEMPTS
+///
+/// Each attempt waits RETRY_SECONDS longer than the previous one.
+pub fn fail(pool: &PoolType, job: &Job, error: &str, now: NaiveDateTime) -> Result<Job, ApiError> {
+    let conn = pool.get()?;
+    let (status, run_at, finished_at) = if job.attempts >= MAX_ATTEMPTS {
+        (FAILED, job.run_at, Some(now))
+    } else {
+        let delay = Duration::seconds(RETRY_SECONDS * i64::from(job.attempts));
+        (PENDING, now + delay, None)
+    };
+    let job = diesel::update(jobs::table.find(job.id))
+        .set((
+            jobs::status.eq(status),
+            jobs::run_at.eq(run_at),
+            jobs::locked_until.eq(None::<NaiveDateTime>),
+            jobs::last_error.eq(error),
+            jobs::finished_at.eq(finished_at),
+            jobs::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result(&conn)?;
+
+    Ok(job)
+}
+
+/// Add a job, or move it to run_at when it's still pending and was never
+/// tried, so failed jobs keep waiting out their retry delay
+fn schedule(
+    conn: &PgConnection,
+    league_id: Uuid,
+    kind: &str,
+    round: i32,
+    run_at: NaiveDateTime,
+) -> Result<Job, ApiError> {
+    let now = Utc::now().naive_utc();
+    let job = Job {
+        id: Uuid::new_v4(),
+        kind: kind.into(),
+        league_id,
+        round,
+        run_at,
+        status: PENDING.into(),
+        attempts: 0,
+        locked_until: None,
+        last_error: None,
+        finished_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    diesel::insert_into(jobs::table)
+        .values(&job)
+        .on_conflict((jobs::kind, jobs::league_id, jobs::round))
+        .do_nothing()
+        .execute(conn)?;
+
+    let target = jobs::table
+        .filter(jobs::kind.eq(kind))
+        .filter(jobs::league_id.eq(league_id))
+        .filter(jobs::round.eq(round));
+    diesel::update(
+        target
+            .filter(jobs::status.eq(PENDING))
+            .filter(jobs::attempts.eq(0))
+            .filter(jobs::run_at.ne(run_at)),
+    )
+    .set((jobs::run_at.eq(run_at), jobs::updated_at.eq(now)))
+    .execute(conn)?;
+
+    Ok(target.first(conn)?)
+}
@@ -1,7 +1,24 @@
-use crate::database::PoolType;
+use crate::auth::{create_calendar_token, CalendarClaim};
+use crate::cache::{league_group, Cache, LEAGUES};
+use crate::calendar::Calendar;
+use crate::database::{transaction, PoolType};
 use crate::errors::ApiError;
+use crate::events::{publish, publish_all, LeagueEvent};
+use crate::ical;
+use crate::managers::matchups::settle as settle_matchups;
+use crate::managers::teams::update_records;
 use crate::models::leagues::*;
-use crate::schema::{league_rulesets, leagues};
+use crate::models::matchups::Matchup;
+use crate::models::runs::Run;
+use crate::models::scores::{PlayerScore, RoundScoresResponse, TeamScore};
+use crate::models::teams::{Player, Team};
+use crate::pagination::{contains, ListParams, ListResponse, ASC};
+use crate::schema::{
+    league_commissioners, league_rulesets, leagues, matchups, player_scores, runs, team_players,
+    team_scores, teams,
+};
+use crate::scoring;
+use chrono::Utc;
 use diesel::prelude::*;
 use uuid::Uuid;
 
@@ -11,7 +28,17 @@ type LeagueDetailsColumns = (
     leagues::start,
     leagues::rounds,
     leagues::current_round,
+    leagues::timezone,
+    leagues::round_days,
     league_rulesets::points_per_mile,
+    league_rulesets::pace_bonus_target,
+    league_rulesets::pace_bonus_per_mile,
+    league_rulesets::points_per_100_feet,
+    league_rulesets::min_run_distance,
+    league_rulesets::daily_mileage_cap,
+    league_rulesets::long_run_distance,
+    league_rulesets::long_run_multiplier,
+    league_rulesets::tie_breakers,
 );
 
 pub const LEAGUE_DETAILS_COLUMNS: LeagueDetailsColumns = (
@@ -20,7 +47,17 @@ pub const LEAGUE_DETAILS_COLUMNS: LeagueDetailsColumns = (
     leagues::start,
     leagues::rounds,
     leagues::current_round,
+    leagues::timezone,
+    leagues::round_days,
     league_rulesets::points_per_mile,
+    league_rulesets::pace_bonus_target,
+    league_rulesets::pace_bonus_per_mile,
+    league_rulesets::points_per_100_feet,
+    league_rulesets::min_run_distance,
+    league_rulesets::daily_mileage_cap,
+    league_rulesets::long_run_distance,
+    league_rulesets::long_run_multiplier,
+    league_rulesets::tie_breakers,
 );
 
 pub fn find_with_details(pool: &PoolType, league_id: Uuid) -> Result<LeagueDetails, ApiError> {
@@ -41,116 +78,705 @@ pub fn find_with_details(pool: &PoolType, league_id: Uuid) -> Result<LeagueDetai
     }
 }
 
-pub fn get_all_details(pool: &PoolType) -> Result<LeaguesResponse, ApiError> {
+/// Get a page of leagues, filtered by name
+pub fn get_all_details(pool: &PoolType, params: &ListParams) -> Result<LeaguesResponse, ApiError> {
+    let (sort, descending) = params.sort(&["name", "start"], ASC)?;
+    let offset = params.offset()?;
     let conn = pool.get()?;
-    let query = leagues::table
+
+    let mut count_query = leagues::table.into_boxed();
+    let mut query = leagues::table
         .inner_join(league_rulesets::table)
-        .select(LEAGUE_DETAILS_COLUMNS);
-    let all_leagues = query.load(&conn)?;
+        .select(LEAGUE_DETAILS_COLUMNS)
+        .into_boxed();
+    if let Some(name) = &params.name {
+        count_query = count_query.filter(leagues::name.ilike(contains(name)));
+        query = query.filter(leagues::name.ilike(contains(name)));
+    }
+    let query = match sort {
+        "start" if descending => query.order((leagues::start.desc(), leagues::id)),
+        "start" => query.order((leagues::start, leagues::id)),
+        _ if descending => query.order((leagues::name.desc(), leagues::id)),
+        _ => query.order((leagues::name, leagues::id)),
+    };
 
-    Ok(all_leagues.into())
+    let total = count_query.count().get_result(&conn)?;
+    let found_leagues = query.limit(params.limit()).offset(offset).load(&conn)?;
+
+    Ok(ListResponse::new(found_leagues, total, offset))
 }
 
 pub fn create(
     pool: &PoolType,
+    cache: &Cache,
     new_league: &League,
     new_ruleset: &Ruleset,
+    new_commissioner: &Commissioner,
 ) -> Result<LeagueDetails, ApiError> {
-    use crate::schema::league_rulesets::dsl::league_rulesets;
-    use crate::schema::leagues::dsl::leagues;
-
-    let conn = pool.get()?;
+    transaction(pool, |conn| {
+        diesel::insert_into(leagues::table)
+            .values(new_league)
+            .execute(conn)?;
+        diesel::insert_into(league_rulesets::table)
+            .values(new_ruleset)
+            .execute(conn)?;
+        diesel::insert_into(league_commissioners::table)
+            .values(new_commissioner)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    forget(cache, new_league.id);
 
-    diesel::insert_into(leagues)
-        .values(new_league)
-        .execute(&conn)?;
-
-    diesel::insert_into(league_rulesets)
-        .values(new_ruleset)
-        .execute(&conn)?;
-
-    let created = LeagueDetails {
-        id: new_league.id,
-        name: new_league.name.clone(),
-        start: new_league.start,
-        rounds: new_league.rounds,
-        current_round: new_league.current_round,
-        points_per_mile: new_ruleset.points_per_mile,
-    };
-    Ok(created.clone().into())
+    find_with_details(pool, new_league.id)
 }
 
 pub fn update(
     pool: &PoolType,
+    cache: &Cache,
     update_league: &UpdateLeague,
     update_ruleset: &UpdateRuleset,
 ) -> Result<LeagueDetails, ApiError> {
+    transaction(pool, |conn| {
+        let league = lock(conn, update_league.id)?;
+        if update_league.rounds < league.current_round {
+            return Err(ApiError::BadRequest(format!(
+                "League {} has already closed {} rounds",
+                league.id, league.current_round
+            )));
+        }
+
+        // Update ruleset
+        let ruleset_target =
+            league_rulesets::table.filter(league_rulesets::league_id.eq(update_league.id));
+        diesel::update(ruleset_target)
+            .set(update_ruleset)
+            .execute(conn)?;
+
+        // Update league
+        let league_target = leagues::table.filter(leagues::id.eq(update_league.id));
+        diesel::update(league_target)
+            .set(update_league)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    forget(cache, update_league.id);
+
+    find_with_details(pool, update_league.id)
+}
+
+pub fn delete(pool: &PoolType, cache: &Cache, league_id: Uuid) -> Result<(), ApiError> {
+    transaction(pool, |conn| {
+        let ruleset_to_delete =
+            league_rulesets::table.filter(league_rulesets::league_id.eq(league_id));
+        diesel::delete(ruleset_to_delete).execute(conn)?;
+
+        let league_to_delete = leagues::table.filter(leagues::id.eq(league_id));
+        diesel::delete(league_to_delete).execute(conn)?;
+        Ok(())
+    })?;
+    forget(cache, league_id);
+    Ok(())
+}
+
+/// Drop the cached responses about a league and the lists it's in
+fn forget(cache: &Cache, league_id: Uuid) {
+    cache.forget(&[LEAGUES, &league_group(league_id)]);
+}
+
+/// Drop the cached responses about a league, like its standings, when its
+/// scores or runs change
+pub fn forget_league(cache: &Cache, league_id: Uuid) {
+    cache.forget(&[&league_group(league_id)]);
+}
+
+/// Lock a league's row until the end of the current transaction
+///
+/// Keeps concurrent writers (scoring, scheduling...) of the same league from
+/// replacing each other's rows halfway through.
+pub fn lock(conn: &PgConnection, league_id: Uuid) -> Result<League, ApiError> {
+    leagues::table
+        .filter(leagues::id.eq(league_id))
+        .for_update()
+        .first(conn)
+        .map_err(|_| ApiError::NotFound(format!("League {} not found", league_id)))
+}
+
+/// Get when each round of a league starts and ends
+pub fn find_rounds(pool: &PoolType, league_id: Uuid) -> Result<RoundsResponse, ApiError> {
+    let conn = pool.get()?;
+    let league: League = leagues::table
+        .filter(leagues::id.eq(league_id))
+        .first(&conn)
+        .map_err(|_| ApiError::NotFound(format!("League {} not found", league_id)))?;
+
+    let calendar = Calendar::of(&league);
+    let rounds = (1..=league.rounds)
+        .map(|round| {
+            let (start, end) = calendar.round_window(round);
+            RoundWindow {
+                round,
+                start,
+                end,
+                closed: round <= league.current_round,
+            }
+        })
+        .collect();
+
+    Ok(RoundsResponse {
+        league_id,
+        timezone: league.timezone,
+        round_days: league.round_days,
+        rounds,
+    })
+}
+
+/// Get a league's rounds and schedule as an iCalendar feed
+///
+/// With a team_id only that team's matchups are in it, along with the rounds.
+pub fn find_calendar(
+    pool: &PoolType,
+    league_id: Uuid,
+    team_id: Option<Uuid>,
+) -> Result<String, ApiError> {
     let conn = pool.get()?;
+    let league: League = leagues::table
+        .filter(leagues::id.eq(league_id))
+        .first(&conn)
+        .map_err(|_| ApiError::NotFound(format!("League {} not found", league_id)))?;
+    let league_teams: Vec<Team> = teams::table
+        .filter(teams::league_id.eq(league_id))
+        .load(&conn)?;
+    let mut schedule: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .order((matchups::round, matchups::home_team_id))
+        .load(&conn)?;
 
-    let related_ruleset: Ruleset = league_rulesets::table
-        .select(league_rulesets::all_columns)
-        .filter(league_rulesets::league_id.eq(update_league.id.clone()))
-        .first(&conn)?;
-
-    // Update ruleset
-    let ruleset_target = league_rulesets::table.filter(league_rulesets::id.eq(related_ruleset.id));
-    diesel::update(ruleset_target)
-        .set(update_ruleset)
-        .execute(&conn)?;
-
-    // Update league
-    let league_target = leagues::table.filter(leagues::id.eq(update_league.id));
-    diesel::update(league_target)
-        .set(update_league)
-        .execute(&conn)?;
-
-    // Create return obj
-    let updated = LeagueDetails {
-        id: update_league.id,
-        name: update_league.name.clone(),
-        start: update_league.start,
-        rounds: update_league.rounds,
-        current_round: update_league.current_round,
-        points_per_mile: update_ruleset.points_per_mile,
+    let name = match team_id {
+        Some(team_id) => {
+            let team = league_teams
+                .iter()
+                .find(|team| team.id == team_id)
+                .ok_or_else(|| ApiError::NotFound(format!("Team {} not found", team_id)))?;
+            schedule.retain(|matchup| {
+                matchup.home_team_id == team_id || matchup.away_team_id == Some(team_id)
+            });
+            format!("{} ({})", team.name, league.name)
+        }
+        None => league.name.clone(),
     };
+    let events = ical::events(&league, &league_teams, &schedule);
+    Ok(ical::render(&name, &events, Utc::now().naive_utc()))
+}
 
-    Ok(updated.clone().into())
+/// Get the link to a league's calendar feed, or to one of its team's
+///
+/// The link carries a token naming the league and team, so calendar apps
+/// can subscribe to it without logging in.
+pub fn find_calendar_link(
+    pool: &PoolType,
+    league_id: Uuid,
+    team_id: Option<Uuid>,
+) -> Result<CalendarLinkResponse, ApiError> {
+    find_with_details(pool, league_id)?;
+    let token = create_calendar_token(&CalendarClaim { league_id, team_id })?;
+    Ok(CalendarLinkResponse {
+        url: format!("{}/{}", ical::FEED_PATH, token),
+    })
 }
 
-pub fn delete(pool: &PoolType, league_id: Uuid) -> Result<(), ApiError> {
+/// Determine if a user is one of a league's commissioners
+pub fn is_commissioner(pool: &PoolType, league_id: Uuid, user_id: Uuid) -> Result<bool, ApiError> {
     let conn = pool.get()?;
+    let commissioner: Option<Commissioner> = league_commissioners::table
+        .filter(league_commissioners::league_id.eq(league_id))
+        .filter(league_commissioners::user_id.eq(user_id))
+        .first(&conn)
+        .optional()?;
 
-    let ruleset_to_delete =
-        league_rulesets::table.filter(league_rulesets::league_id.eq(league_id.clone()));
-    diesel::delete(ruleset_to_delete).execute(&conn)?;
+    Ok(commissioner.is_some())
+}
 
-    let league_to_delete = leagues::table.filter(leagues::id.eq(league_id.clone()));
-    diesel::delete(league_to_delete).execute(&conn)?;
+/// Score a round of a league and store the results
+///
+/// Any scores already stored for the round are replaced, so a round can be
+/// scored again after a rule change or a correction to someone's runs.
+/// Closed rounds also have their matchups settled again.
+pub fn score_round(
+    pool: &PoolType,
+    cache: &Cache,
+    league_id: Uuid,
+    round: i32,
+) -> Result<RoundScoresResponse, ApiError> {
+    let (scores, settled) = transaction(pool, |conn| {
+        let league = lock(conn, league_id)?;
+        if round <= league.current_round {
+            settle_round(conn, &league, round)
+        } else {
+            Ok((store_round_scores(conn, &league, round)?, vec![]))
+        }
+    })?;
+    forget_league(cache, league_id);
+    publish_round(&scores, settled);
 
-    Ok(())
+    Ok(scores)
+}
+
+/// Score every round of a league that has already been closed
+///
+/// Useful to replay historical rounds after the ruleset changes.
+pub fn rescore(
+    pool: &PoolType,
+    cache: &Cache,
+    league_id: Uuid,
+) -> Result<Vec<RoundScoresResponse>, ApiError> {
+    let league = find_with_details(pool, league_id)?;
+    (1..=league.current_round)
+        .map(|round| score_round(pool, cache, league_id, round))
+        .collect()
+}
+
+/// Close the next round of a league
+///
+/// Scores the round, settles its matchups, updates the teams' records and
+/// moves the league on to the next round, all or nothing. Rounds have to be
+/// closed in order and only once, use score_round to settle one again.
+pub fn close_round(
+    pool: &PoolType,
+    cache: &Cache,
+    league_id: Uuid,
+    round: i32,
+) -> Result<LeagueDetails, ApiError> {
+    let (scores, settled) = transaction(pool, |conn| {
+        let league = lock(conn, league_id)?;
+        if round <= league.current_round {
+            return Err(ApiError::Conflict(format!(
+                "Round {} of league {} is already closed",
+                round, league_id
+            )));
+        }
+        if round != league.current_round + 1 {
+            return Err(ApiError::BadRequest(format!(
+                "Round {} of league {} has to be closed first",
+                league.current_round + 1,
+                league_id
+            )));
+        }
+
+        let settled = settle_round(conn, &league, round)?;
+        diesel::update(leagues::table.filter(leagues::id.eq(league_id)))
+            .set(leagues::current_round.eq(round))
+            .execute(conn)?;
+        Ok(settled)
+    })?;
+    forget(cache, league_id);
+    publish_round(&scores, settled);
+
+    find_with_details(pool, league_id)
+}
+
+/// Score a round, then settle its matchups and recount the teams' records
+fn settle_round(
+    conn: &PgConnection,
+    league: &League,
+    round: i32,
+) -> Result<(RoundScoresResponse, Vec<Matchup>), ApiError> {
+    let scores = store_round_scores(conn, league, round)?;
+    let settled = settle_matchups(conn, league.id, round, &scores.teams)?;
+    update_records(conn, league.id)?;
+    Ok((scores, settled))
+}
+
+/// Tell a league's streams about a round's new scores and settled matchups
+fn publish_round(scores: &RoundScoresResponse, settled: Vec<Matchup>) {
+    publish(scores.league_id, LeagueEvent::ScoreChanged(scores.clone()));
+    publish_all(
+        scores.league_id,
+        settled.into_iter().map(LeagueEvent::MatchupSettled),
+    );
+}
+
+/// Score a round and replace the scores stored for it
+fn store_round_scores(
+    conn: &PgConnection,
+    league: &League,
+    round: i32,
+) -> Result<RoundScoresResponse, ApiError> {
+    let league_id = league.id;
+    if round < 1 || round > league.rounds {
+        return Err(ApiError::BadRequest(format!(
+            "League {} doesn't have a round {}",
+            league_id, round
+        )));
+    }
+
+    let ruleset: Ruleset = league_rulesets::table
+        .filter(league_rulesets::league_id.eq(league_id))
+        .first(conn)?;
+    let league_teams: Vec<Team> = teams::table
+        .filter(teams::league_id.eq(league_id))
+        .load(conn)?;
+    let team_ids: Vec<Uuid> = league_teams.iter().map(|team| team.id).collect();
+    let players: Vec<Player> = team_players::table
+        .filter(team_players::team_id.eq_any(&team_ids))
+        .load(conn)?;
+    let user_ids: Vec<Uuid> = players.iter().map(|player| player.user_id).collect();
+
+    let calendar = Calendar::of(league);
+    let window = calendar.round_window(round);
+    let round_runs: Vec<Run> = runs::table
+        .filter(runs::user_id.eq_any(&user_ids))
+        .filter(runs::started_at.ge(window.0))
+        .filter(runs::started_at.lt(window.1))
+        .load(conn)?;
+
+    let totals = scoring::score_round(
+        &ruleset,
+        &calendar,
+        round,
+        &league_teams,
+        &players,
+        &round_runs,
+    );
+    let new_team_scores: Vec<TeamScore> = totals
+        .teams
+        .into_iter()
+        .map(|total| TeamScore {
+            id: Uuid::new_v4(),
+            league_id,
+            team_id: total.team_id,
+            round,
+            miles: total.miles,
+            points: total.points,
+        })
+        .collect();
+    let new_player_scores: Vec<PlayerScore> = totals
+        .players
+        .into_iter()
+        .map(|total| PlayerScore {
+            id: Uuid::new_v4(),
+            league_id,
+            team_id: total.team_id,
+            user_id: total.user_id,
+            round,
+            miles: total.miles,
+            points: total.points,
+        })
+        .collect();
+
+    diesel::delete(
+        team_scores::table
+            .filter(team_scores::league_id.eq(league_id))
+            .filter(team_scores::round.eq(round)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        player_scores::table
+            .filter(player_scores::league_id.eq(league_id))
+            .filter(player_scores::round.eq(round)),
+    )
+    .execute(conn)?;
+
+    diesel::insert_into(team_scores::table)
+        .values(&new_team_scores)
+        .execute(conn)?;
+    diesel::insert_into(player_scores::table)
+        .values(&new_player_scores)
+        .execute(conn)?;
+
+    Ok(RoundScoresResponse {
+        league_id,
+        round,
+        teams: new_team_scores,
+        players: new_player_scores,
+    })
+}
+
+/// Get the stored scores of a round
+pub fn find_round_scores(
+    pool: &PoolType,
+    league_id: Uuid,
+    round: i32,
+) -> Result<RoundScoresResponse, ApiError> {
+    let conn = pool.get()?;
+
+    let found_team_scores: Vec<TeamScore> = team_scores::table
+        .filter(team_scores::league_id.eq(league_id))
+        .filter(team_scores::round.eq(round))
+        .order(team_scores::points.desc())
+        .load(&conn)?;
+    let found_player_scores: Vec<PlayerScore> = player_scores::table
+        .filter(player_scores::league_id.eq(league_id))
+        .filter(player_scores::round.eq(round))
+        .order(player_scores::points.desc())
+        .load(&conn)?;
+
+    Ok(RoundScoresResponse {
+        league_id,
+        round,
+        teams: found_team_scores,
+        players: found_player_scores,
+    })
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::calendar::{DEFAULT_ROUND_DAYS, DEFAULT_TIMEZONE};
+    use crate::managers::matchups::generate;
+    use crate::managers::runs::tests::create_run;
+    use crate::managers::teams::create as create_team;
+    use crate::managers::users::tests::create_user;
+    use crate::models::teams::{Owner, TeamDetails};
+    use crate::scoring::tests::ruleset;
     use crate::tests::helpers::tests::get_pool;
+    use chrono::{Duration, Utc};
+
+    /// Create a league that started yesterday with a scheduled team for each
+    /// of team_count, each team has one new user as its player and owner
+    pub fn create_league_with_teams(team_count: usize) -> (Uuid, Vec<TeamDetails>) {
+        let pool = get_pool();
+        let league_id = Uuid::new_v4();
+        let league = League {
+            id: league_id,
+            name: "Scheduled League".into(),
+            start: Utc::now().naive_utc() - Duration::days(1),
+            rounds: 4,
+            current_round: 0,
+            timezone: DEFAULT_TIMEZONE.into(),
+            round_days: DEFAULT_ROUND_DAYS,
+        };
+        let new_ruleset = Ruleset {
+            league_id,
+            ..ruleset(10)
+        };
+        let commissioner = Commissioner {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            league_id,
+        };
+        create(
+            &pool,
+            &Cache::disabled(),
+            &league,
+            &new_ruleset,
+            &commissioner,
+        )
+        .unwrap();
+
+        let league_teams = (0..team_count)
+            .map(|_| {
+                let team_id = Uuid::new_v4();
+                let team = Team {
+                    id: team_id,
+                    name: "Scheduled Team".into(),
+                    wins: 0,
+                    losses: 0,
+                    ties: 0,
+                    league_id,
+                };
+                let user_id = create_user().unwrap().id;
+                let player = Player {
+                    id: Uuid::new_v4(),
+                    user_id,
+                    team_id,
+                };
+                let owner = Owner {
+                    id: Uuid::new_v4(),
+                    user_id,
+                    team_id,
+                };
+                create_team(&pool, &team, Some(&vec![player]), Some(&vec![owner])).unwrap()
+            })
+            .collect();
+        generate(&pool, league_id).unwrap();
+
+        (league_id, league_teams)
+    }
+
+    fn player_of(team: &TeamDetails) -> Uuid {
+        team.players.as_ref().unwrap()[0].user_id
+    }
+
+    fn record_of(team: &TeamDetails) -> (i32, i32, i32) {
+        let team = crate::managers::teams::find(&get_pool(), team.id).unwrap();
+        (team.wins, team.losses, team.ties)
+    }
 
     pub fn get_all_leagues_with_details() -> Result<LeaguesResponse, ApiError> {
         let pool = get_pool();
-        get_all_details(&pool)
+        get_all_details(&pool, &ListParams::default())
     }
 
     #[test]
     fn it_finds_league_with_details() {
         let leagues = get_all_leagues_with_details().unwrap();
-        let league = &leagues.0[0];
+        let league = &leagues.data[0];
         let found_league = find_with_details(&get_pool(), league.id).unwrap();
         assert_eq!(league, &found_league);
     }
 
+    #[test]
+    fn it_finds_a_commissioner() {
+        let is_commissioner = is_commissioner(&get_pool(), Uuid::nil(), Uuid::nil()).unwrap();
+        assert!(is_commissioner);
+    }
+
+    #[test]
+    fn it_doesnt_find_a_commissioner() {
+        let is_commissioner = is_commissioner(&get_pool(), Uuid::nil(), Uuid::new_v4()).unwrap();
+        assert!(!is_commissioner);
+    }
+
+    #[test]
+    fn it_scores_a_round() {
+        let scores = score_round(&get_pool(), &Cache::disabled(), Uuid::nil(), 1).unwrap();
+        let test_team = scores
+            .teams
+            .iter()
+            .find(|score| score.team_id == Uuid::nil())
+            .unwrap();
+        let test_team_players: f64 = scores
+            .players
+            .iter()
+            .filter(|score| score.team_id == Uuid::nil())
+            .map(|score| score.points)
+            .sum();
+        assert_eq!(test_team.points, test_team_players);
+
+        let found = find_round_scores(&get_pool(), Uuid::nil(), 1).unwrap();
+        assert_eq!(found.teams.len(), scores.teams.len());
+    }
+
+    #[test]
+    fn it_doesnt_score_a_round_outside_of_the_league() {
+        let scores = score_round(&get_pool(), &Cache::disabled(), Uuid::nil(), 0);
+        assert!(scores.is_err());
+    }
+
     #[test]
     fn it_gets_all_leagues_with_details() {
         let leagues = get_all_leagues_with_details();
         assert!(leagues.is_ok());
     }
+
+    fn count_league_rows(league_id: Uuid) -> (i64, i64) {
+        let conn = get_pool().get().unwrap();
+        let league_rows = leagues::table
+            .filter(leagues::id.eq(league_id))
+            .count()
+            .get_result(&conn)
+            .unwrap();
+        let ruleset_rows = league_rulesets::table
+            .filter(league_rulesets::league_id.eq(league_id))
+            .count()
+            .get_result(&conn)
+            .unwrap();
+        (league_rows, ruleset_rows)
+    }
+
+    #[test]
+    fn it_doesnt_create_part_of_a_league() {
+        let league_id = Uuid::new_v4();
+        let league = League {
+            id: league_id,
+            name: "Half League".into(),
+            start: Utc::now().naive_utc(),
+            rounds: 4,
+            current_round: 0,
+            timezone: DEFAULT_TIMEZONE.into(),
+            round_days: DEFAULT_ROUND_DAYS,
+        };
+        let new_ruleset = Ruleset {
+            league_id,
+            ..ruleset(10)
+        };
+        // The commissioner goes in last and doesn't exist
+        let commissioner = Commissioner {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            league_id,
+        };
+
+        let created = create(
+            &get_pool(),
+            &Cache::disabled(),
+            &league,
+            &new_ruleset,
+            &commissioner,
+        );
+        assert!(created.is_err());
+        assert_eq!(count_league_rows(league_id), (0, 0));
+    }
+
+    #[test]
+    fn it_deletes_a_league_and_its_ruleset() {
+        let (league_id, _) = create_league_with_teams(2);
+        delete(&get_pool(), &Cache::disabled(), league_id).unwrap();
+        assert_eq!(count_league_rows(league_id), (0, 0));
+    }
+
+    #[test]
+    fn it_forgets_a_cached_league_when_it_changes() {
+        let (league_id, _) = create_league_with_teams(2);
+        let cache = Cache::memory(60);
+        let group = league_group(league_id);
+        let load = || find_with_details(&get_pool(), league_id);
+        cache.cached(&group, &[&group], load).unwrap();
+        cache.cached(LEAGUES, &[LEAGUES], load).unwrap();
+
+        close_round(&get_pool(), &cache, league_id, 1).unwrap();
+        assert_eq!(cache.get(&group).unwrap(), None);
+        assert_eq!(cache.get(LEAGUES).unwrap(), None);
+
+        cache.cached(&group, &[&group], load).unwrap();
+        delete(&get_pool(), &cache, league_id).unwrap();
+        assert_eq!(cache.get(&group).unwrap(), None);
+    }
+
+    #[test]
+    fn it_closes_a_round() {
+        let (league_id, league_teams) = create_league_with_teams(2);
+        create_run(player_of(&league_teams[0])).unwrap();
+
+        let league = close_round(&get_pool(), &Cache::disabled(), league_id, 1).unwrap();
+        assert_eq!(league.current_round, 1);
+        assert_eq!(record_of(&league_teams[0]), (1, 0, 0));
+        assert_eq!(record_of(&league_teams[1]), (0, 1, 0));
+    }
+
+    #[test]
+    fn it_doesnt_close_a_round_twice() {
+        let (league_id, _) = create_league_with_teams(2);
+        close_round(&get_pool(), &Cache::disabled(), league_id, 1).unwrap();
+        let closed_again = close_round(&get_pool(), &Cache::disabled(), league_id, 1);
+        let expected_error =
+            ApiError::Conflict(format!("Round 1 of league {} is already closed", league_id));
+        assert_eq!(closed_again.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_doesnt_close_rounds_out_of_order() {
+        let (league_id, _) = create_league_with_teams(2);
+        let closed = close_round(&get_pool(), &Cache::disabled(), league_id, 2);
+        assert!(closed.is_err());
+        assert_eq!(
+            find_with_details(&get_pool(), league_id)
+                .unwrap()
+                .current_round,
+            0
+        );
+    }
+
+    #[test]
+    fn it_settles_a_closed_round_again() {
+        let (league_id, league_teams) = create_league_with_teams(2);
+        create_run(player_of(&league_teams[0])).unwrap();
+        close_round(&get_pool(), &Cache::disabled(), league_id, 1).unwrap();
+
+        create_run(player_of(&league_teams[1])).unwrap();
+        create_run(player_of(&league_teams[1])).unwrap();
+        score_round(&get_pool(), &Cache::disabled(), league_id, 1).unwrap();
+        assert_eq!(record_of(&league_teams[0]), (0, 1, 0));
+        assert_eq!(record_of(&league_teams[1]), (1, 0, 0));
+    }
 }
@@ -0,0 +1,355 @@
+use crate::cache::Cache;
+use crate::database::{transaction, PoolType};
+use crate::errors::ApiError;
+use crate::managers::runs::{forget_leagues_of, publish_runs};
+use crate::models::linked_accounts::*;
+use crate::models::runs::Run;
+use crate::pagination::ListResponse;
+use crate::schema::{linked_accounts, runs};
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::sql;
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Get every account a user linked, by provider
+pub fn get_all_for_user(
+    pool: &PoolType,
+    user_id: Uuid,
+) -> Result<LinkedAccountsResponse, ApiError> {
+    let conn = pool.get()?;
+    let accounts: Vec<LinkedAccountResponse> = linked_accounts::table
+        .filter(linked_accounts::user_id.eq(user_id))
+        .order((linked_accounts::provider, linked_accounts::id))
+        .load::<LinkedAccount>(&conn)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    let total = accounts.len() as i64;
+    Ok(ListResponse::new(accounts, total, 0))
+}
+
+/// Get every linked account, least recently synced first
+pub fn get_all(pool: &PoolType) -> Result<Vec<LinkedAccount>, ApiError> {
+    let conn = pool.get()?;
+    let accounts = linked_accounts::table
+        .order((
+            linked_accounts::synced_at.asc().nulls_first(),
+            linked_accounts::id,
+        ))
+        .load(&conn)?;
+
+    Ok(accounts)
+}
+
+/// Find one of a user's linked accounts or error out
+pub fn find(pool: &PoolType, user_id: Uuid, account_id: Uuid) -> Result<LinkedAccount, ApiError> {
+    let not_found = format!("Linked account {} not found", account_id);
+    let conn = pool.get()?;
+    linked_accounts::table
+        .filter(linked_accounts::id.eq(account_id))
+        .filter(linked_accounts::user_id.eq(user_id))
+        .first::<LinkedAccount>(&conn)
+        .map_err(|_| ApiError::NotFound(not_found))
+}
+
+/// Link a user's account at a provider
+///
+/// Linking the same account again only replaces the tokens, so it keeps its
+/// cursor. Linking another account at the provider starts syncing over, from
+/// its first activity. An account at a provider can only be linked to one
+/// user.
+pub fn link(
+    pool: &PoolType,
+    user_id: Uuid,
+    provider: &str,
+    tokens: &Tokens,
+) -> Result<LinkedAccount, ApiError> {
+    let conn = pool.get()?;
+    let now = Utc::now().naive_utc();
+    let account = LinkedAccount {
+        id: Uuid::new_v4(),
+        user_id,
+        provider: provider.into(),
+        provider_user_id: tokens.provider_user_id.clone(),
+        access_token: tokens.access_token.clone(),
+        refresh_token: tokens.refresh_token.clone(),
+        expires_at: tokens.expires_at,
+        sync_cursor: None,
+        synced_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let account = diesel::insert_into(linked_accounts::table)
+        .values(&account)
+        .on_conflict((linked_accounts::user_id, linked_accounts::provider))
+        .do_update()
+        .set((
+            linked_accounts::provider_user_id.eq(excluded(linked_accounts::provider_user_id)),
+            linked_accounts::access_token.eq(excluded(linked_accounts::access_token)),
+            linked_accounts::refresh_token.eq(excluded(linked_accounts::refresh_token)),
+            linked_accounts::expires_at.eq(excluded(linked_accounts::expires_at)),
+            linked_accounts::sync_cursor.eq(sql(&keep_if_same_account("sync_cursor"))),
+            linked_accounts::synced_at.eq(sql(&keep_if_same_account("synced_at"))),
+            linked_accounts::updated_at.eq(now),
+        ))
+        .get_result(&conn)?;
+
+    Ok(account)
+}
+
+/// A column of the linked account being upserted, NULL when it's another
+/// account at the provider
+fn keep_if_same_account(column: &str) -> String {
+    format!(
+        "CASE WHEN linked_accounts.provider_user_id = excluded.provider_user_id \
+         THEN linked_accounts.{} END",
+        column
+    )
+}
+
+/// Replace the tokens of a linked account
+pub fn update_tokens(
+    pool: &PoolType,
+    account_id: Uuid,
+    tokens: &Tokens,
+) -> Result<LinkedAccount, ApiError> {
+    let conn = pool.get()?;
+    let account = diesel::update(linked_accounts::table.find(account_id))
+        .set((
+            linked_accounts::access_token.eq(&tokens.access_token),
+            linked_accounts::refresh_token.eq(&tokens.refresh_token),
+            linked_accounts::expires_at.eq(tokens.expires_at),
+            linked_accounts::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result(&conn)?;
+
+    Ok(account)
+}
+
+/// Save the runs of a sync and move the account's cursor, all in one go
+///
+/// Runs are upserted by provider and activity id, so syncing an activity
+/// again updates its run instead of adding another one. Only the runs of
+/// activities that weren't synced before are published to the user's
+/// leagues, once saved, and the leagues' cached responses are dropped.
+pub fn save_sync(
+    pool: &PoolType,
+    cache: &Cache,
+    account_id: Uuid,
+    synced_runs: &[Run],
+    sync_cursor: Option<NaiveDateTime>,
+) -> Result<LinkedAccount, ApiError> {
+    let (account, logged_runs) = transaction(pool, |conn| {
+        let mut logged_runs = vec![];
+        if !synced_runs.is_empty() {
+            let saved_runs: Vec<Run> = diesel::insert_into(runs::table)
+                .values(synced_runs)
+                .on_conflict((runs::provider, runs::provider_activity_id))
+                .do_update()
+                .set((
+                    runs::distance.eq(excluded(runs::distance)),
+                    runs::duration.eq(excluded(runs::duration)),
+                    runs::started_at.eq(excluded(runs::started_at)),
+                    runs::elevation_gain.eq(excluded(runs::elevation_gain)),
+                    runs::heart_rate.eq(excluded(runs::heart_rate)),
+                    runs::updated_at.eq(excluded(runs::updated_at)),
+                ))
+                .get_results(conn)?;
+            // Runs of activities synced before keep their id, new ones get theirs
+            logged_runs = saved_runs
+                .into_iter()
+                .filter(|saved| synced_runs.iter().any(|synced| synced.id == saved.id))
+                .collect();
+        }
+        let account: LinkedAccount = diesel::update(linked_accounts::table.find(account_id))
+            .set((
+                linked_accounts::sync_cursor.eq(sync_cursor),
+                linked_accounts::synced_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(conn)?;
+
+        Ok((account, logged_runs))
+    })?;
+    if !synced_runs.is_empty() {
+        forget_leagues_of(pool, cache, account.user_id);
+    }
+    publish_runs(pool, cache, &logged_runs);
+
+    Ok(account)
+}
+
+/// Unlink one of a user's accounts, the runs synced from it are kept
+pub fn delete(pool: &PoolType, user_id: Uuid, account_id: Uuid) -> Result<(), ApiError> {
+    let conn = pool.get()?;
+    let target = linked_accounts::table
+        .filter(linked_accounts::id.eq(account_id))
+        .filter(linked_accounts::user_id.eq(user_id));
+    let deleted = diesel::delete(target).execute(&conn)?;
+
+    if deleted == 0 {
+        return Err(ApiError::NotFound(format!(
+            "Linked account {} not found",
+            account_id
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::events::{LeagueEvent, BUS};
+    use crate::managers::runs::tests::create_league_with_player;
+    use crate::managers::users::tests::create_user;
+    use crate::models::runs::NewRun;
+    use crate::tests::helpers::tests::get_pool;
+    use chrono::Duration;
+
+    pub fn tokens(provider_user_id: &str) -> Tokens {
+        Tokens {
+            provider_user_id: provider_user_id.into(),
+            access_token: "access".into(),
+            refresh_token: "refresh".into(),
+            expires_at: Utc::now().naive_utc() + Duration::hours(6),
+        }
+    }
+
+    #[test]
+    fn it_links_an_account() {
+        let user = create_user().unwrap();
+        let provider_user_id = Uuid::new_v4().to_string();
+        let account = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        assert_eq!(account.provider_user_id, provider_user_id);
+
+        let accounts = get_all_for_user(&get_pool(), user.id).unwrap();
+        assert_eq!(accounts.total, 1);
+        assert_eq!(accounts.data[0], account.into());
+    }
+
+    #[test]
+    fn it_replaces_the_tokens_of_an_account_linked_again() {
+        let user = create_user().unwrap();
+        let provider_user_id = Uuid::new_v4().to_string();
+        let first = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        let new_tokens = Tokens {
+            access_token: "new-access".into(),
+            ..tokens(&provider_user_id)
+        };
+        let second = link(&get_pool(), user.id, "strava", &new_tokens).unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.access_token, "new-access");
+    }
+
+    #[test]
+    fn it_keeps_the_cursor_only_when_the_same_account_is_linked_again() {
+        let user = create_user().unwrap();
+        let provider_user_id = Uuid::new_v4().to_string();
+        let account = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        let cursor = NaiveDateTime::from_timestamp(1_589_009_400, 0);
+        save_sync(
+            &get_pool(),
+            &Cache::disabled(),
+            account.id,
+            &[],
+            Some(cursor),
+        )
+        .unwrap();
+
+        let again = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        assert_eq!(again.sync_cursor, Some(cursor));
+        assert!(again.synced_at.is_some());
+
+        let other_provider_user_id = Uuid::new_v4().to_string();
+        let other = link(
+            &get_pool(),
+            user.id,
+            "strava",
+            &tokens(&other_provider_user_id),
+        )
+        .unwrap();
+        assert_eq!(other.id, account.id);
+        assert_eq!(other.provider_user_id, other_provider_user_id);
+        assert_eq!(other.sync_cursor, None);
+        assert_eq!(other.synced_at, None);
+    }
+
+    #[test]
+    fn it_doesnt_link_an_account_to_two_users() {
+        let provider_user_id = Uuid::new_v4().to_string();
+        let first_user = create_user().unwrap();
+        link(
+            &get_pool(),
+            first_user.id,
+            "strava",
+            &tokens(&provider_user_id),
+        )
+        .unwrap();
+
+        let second_user = create_user().unwrap();
+        let response = link(
+            &get_pool(),
+            second_user.id,
+            "strava",
+            &tokens(&provider_user_id),
+        );
+        assert!(matches!(response, Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn it_publishes_only_newly_synced_runs() {
+        let (league_id, user) = create_league_with_player();
+        let provider_user_id = Uuid::new_v4().to_string();
+        let account = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        let synced_run = || Run {
+            provider: Some("strava".into()),
+            provider_activity_id: Some(provider_user_id.clone()),
+            ..NewRun {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                distance: 5.0,
+                duration: 2700,
+                started_at: Utc::now().naive_utc() - Duration::hours(3),
+                elevation_gain: None,
+                heart_rate: None,
+            }
+            .into()
+        };
+        let mut events = BUS.subscribe(league_id);
+
+        let first = synced_run();
+        save_sync(
+            &get_pool(),
+            &Cache::disabled(),
+            account.id,
+            std::slice::from_ref(&first),
+            None,
+        )
+        .unwrap();
+        match events.try_next().unwrap() {
+            Some(LeagueEvent::RunLogged(run)) => assert_eq!(run.id, first.id),
+            event => panic!("Expected a logged run, got {:?}", event),
+        }
+
+        save_sync(
+            &get_pool(),
+            &Cache::disabled(),
+            account.id,
+            &[synced_run()],
+            None,
+        )
+        .unwrap();
+        assert!(events.try_next().is_err());
+    }
+
+    #[test]
+    fn it_deletes_a_linked_account() {
+        let user = create_user().unwrap();
+        let provider_user_id = Uuid::new_v4().to_string();
+        let account = link(&get_pool(), user.id, "strava", &tokens(&provider_user_id)).unwrap();
+        delete(&get_pool(), user.id, account.id).unwrap();
+        assert!(find(&get_pool(), user.id, account.id).is_err());
+        assert!(delete(&get_pool(), user.id, account.id).is_err());
+    }
+}
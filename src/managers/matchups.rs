@@ -0,0 +1,146 @@
+use crate::database::{transaction, PoolType};
+use crate::errors::ApiError;
+use crate::managers::leagues::{find_with_details, lock};
+use crate::managers::teams::get_league_teams;
+use crate::models::matchups::*;
+use crate::models::scores::TeamScore;
+use crate::schedule::round_robin;
+use crate::schema::matchups;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Get every matchup of a league ordered by round
+pub fn get_all_in_league(pool: &PoolType, league_id: Uuid) -> Result<MatchupsResponse, ApiError> {
+    let conn = pool.get()?;
+    let found_matchups: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .order((matchups::round, matchups::home_team_id))
+        .load(&conn)?;
+
+    Ok(found_matchups.into())
+}
+
+/// Get the matchups of a single round
+pub fn get_round(
+    pool: &PoolType,
+    league_id: Uuid,
+    round: i32,
+) -> Result<MatchupsResponse, ApiError> {
+    let conn = pool.get()?;
+    let found_matchups: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .filter(matchups::round.eq(round))
+        .order(matchups::home_team_id)
+        .load(&conn)?;
+
+    Ok(found_matchups.into())
+}
+
+/// Generate a round-robin schedule for the rounds a league has left
+///
+/// Rounds that were already played keep their matchups, so teams added or
+/// removed mid-season only change the rest of the schedule.
+pub fn generate(pool: &PoolType, league_id: Uuid) -> Result<MatchupsResponse, ApiError> {
+    let league = find_with_details(pool, league_id)?;
+    let mut team_ids: Vec<Uuid> = get_league_teams(pool, league_id)?
+        .into_iter()
+        .map(|team| team.id)
+        .collect();
+    team_ids.sort();
+
+    let new_matchups: Vec<Matchup> = round_robin(&team_ids, league.rounds)
+        .into_iter()
+        .filter(|pairing| pairing.round > league.current_round)
+        .map(|pairing| Matchup {
+            id: Uuid::new_v4(),
+            league_id,
+            round: pairing.round,
+            home_team_id: pairing.home_team_id,
+            away_team_id: pairing.away_team_id,
+            home_points: None,
+            away_points: None,
+        })
+        .collect();
+
+    transaction(pool, |conn| {
+        lock(conn, league_id)?;
+        diesel::delete(
+            matchups::table
+                .filter(matchups::league_id.eq(league_id))
+                .filter(matchups::round.gt(league.current_round)),
+        )
+        .execute(conn)?;
+        diesel::insert_into(matchups::table)
+            .values(&new_matchups)
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    get_all_in_league(pool, league_id)
+}
+
+/// Record the points each side scored in a round's matchups
+///
+/// Teams without a score get 0 points. Returns the settled matchups.
+pub fn settle(
+    conn: &PgConnection,
+    league_id: Uuid,
+    round: i32,
+    team_scores: &[TeamScore],
+) -> Result<Vec<Matchup>, ApiError> {
+    let points_for = |team_id: Uuid| {
+        team_scores
+            .iter()
+            .find(|score| score.team_id == team_id)
+            .map_or(0.0, |score| score.points)
+    };
+
+    let round_matchups: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .filter(matchups::round.eq(round))
+        .load(conn)?;
+    let mut settled = Vec::with_capacity(round_matchups.len());
+    for matchup in round_matchups {
+        settled.push(
+            diesel::update(matchups::table.filter(matchups::id.eq(matchup.id)))
+                .set((
+                    matchups::home_points.eq(points_for(matchup.home_team_id)),
+                    matchups::away_points.eq(matchup.away_team_id.map(points_for)),
+                ))
+                .get_result(conn)?,
+        );
+    }
+
+    Ok(settled)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tests::helpers::tests::get_pool;
+
+    #[test]
+    fn it_generates_a_schedule() {
+        let schedule = generate(&get_pool(), Uuid::nil()).unwrap();
+        let league = find_with_details(&get_pool(), Uuid::nil()).unwrap();
+        let rounds: Vec<i32> = schedule.0.iter().map(|matchup| matchup.round).collect();
+        assert_eq!(rounds.iter().max(), Some(&league.rounds));
+        assert!(rounds.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn it_gets_a_round() {
+        generate(&get_pool(), Uuid::nil()).unwrap();
+        let round = get_round(&get_pool(), Uuid::nil(), 2).unwrap();
+        assert!(round.0.iter().all(|matchup| matchup.round == 2));
+        assert!(!round.0.is_empty());
+    }
+
+    #[test]
+    fn it_doesnt_generate_a_schedule_for_a_missing_league() {
+        let league_id = Uuid::new_v4();
+        let schedule = generate(&get_pool(), league_id);
+        let expected_error = ApiError::NotFound(format!("League {} not found", league_id));
+        assert_eq!(schedule.unwrap_err(), expected_error);
+    }
+}
@@ -1,3 +1,9 @@
+pub mod drafts;
+pub mod jobs;
 pub mod leagues;
+pub mod linked_accounts;
+pub mod matchups;
+pub mod notifications;
+pub mod runs;
 pub mod teams;
 pub mod users;
@@ -0,0 +1,66 @@
+use crate::calendar::Calendar;
+use crate::database::{transaction, PoolType};
+use crate::errors::ApiError;
+use crate::managers::leagues::lock;
+use crate::managers::teams::players_in_league;
+use crate::models::notifications::Notification;
+use crate::scheduler::ROUND_REMINDER;
+use crate::schema::notifications;
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Queue a reminder to log their runs for every player of a league
+///
+/// Players are only reminded once per round, and not at all once the round
+/// is closed. Returns the notifications queued.
+pub fn queue_round_reminders(
+    pool: &PoolType,
+    league_id: Uuid,
+    round: i32,
+) -> Result<Vec<Notification>, ApiError> {
+    transaction(pool, |conn| {
+        let league = lock(conn, league_id)?;
+        if round <= league.current_round {
+            return Ok(vec![]);
+        }
+
+        let calendar = Calendar::of(&league);
+        let (_, end) = calendar.round_window(round);
+        let message = format!(
+            "Round {} of {} closes at {}, log your runs before then",
+            round,
+            league.name,
+            calendar.local(end).format("%Y-%m-%d %H:%M %Z")
+        );
+        let mut user_ids = players_in_league(conn, league_id)?;
+        user_ids.sort();
+        user_ids.dedup();
+        let now = Utc::now().naive_utc();
+        let reminders: Vec<Notification> = user_ids
+            .into_iter()
+            .map(|user_id| Notification {
+                id: Uuid::new_v4(),
+                user_id,
+                league_id,
+                kind: ROUND_REMINDER.into(),
+                round,
+                message: message.clone(),
+                sent_at: None,
+                created_at: now,
+            })
+            .collect();
+
+        let queued = diesel::insert_into(notifications::table)
+            .values(&reminders)
+            .on_conflict((
+                notifications::user_id,
+                notifications::league_id,
+                notifications::kind,
+                notifications::round,
+            ))
+            .do_nothing()
+            .get_results(conn)?;
+        Ok(queued)
+    })
+}
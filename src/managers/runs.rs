@@ -0,0 +1,499 @@
+use crate::cache::Cache;
+use crate::calendar::Calendar;
+use crate::database::{transaction, PoolType};
+use crate::errors::{ApiError, FieldErrors};
+use crate::events::{publish_all, LeagueEvent};
+use crate::import::csv::Row;
+use crate::managers::leagues::{forget_league, lock};
+use crate::managers::teams::{leagues_of_players, player_emails_in_league};
+use crate::models::runs::*;
+use crate::pagination::{ListParams, ListResponse, DESC};
+use crate::schema::{run_laps, runs};
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Get a page of a user's runs, most recent first unless sorted otherwise
+pub fn get_all_for_user(
+    pool: &PoolType,
+    user_id: Uuid,
+    params: &ListParams,
+) -> Result<RunsResponse, ApiError> {
+    let (sort, descending) = params.sort(&["started_at", "distance", "duration"], DESC)?;
+    let offset = params.offset()?;
+    let conn = pool.get()?;
+
+    let total = runs::table
+        .filter(runs::user_id.eq(user_id))
+        .count()
+        .get_result(&conn)?;
+    let query = runs::table.filter(runs::user_id.eq(user_id)).into_boxed();
+    let query = match sort {
+        "distance" if descending => query.order((runs::distance.desc(), runs::id)),
+        "distance" => query.order((runs::distance, runs::id)),
+        "duration" if descending => query.order((runs::duration.desc(), runs::id)),
+        "duration" => query.order((runs::duration, runs::id)),
+        _ if descending => query.order((runs::started_at.desc(), runs::id)),
+        _ => query.order((runs::started_at, runs::id)),
+    };
+    let user_runs = query
+        .limit(params.limit())
+        .offset(offset)
+        .load::<Run>(&conn)?;
+
+    Ok(ListResponse::new(user_runs, total, offset))
+}
+
+/// Find one of a user's runs or error out
+pub fn find(pool: &PoolType, user_id: Uuid, run_id: Uuid) -> Result<Run, ApiError> {
+    let not_found = format!("Run {} not found", run_id);
+    let conn = pool.get()?;
+    runs::table
+        .filter(runs::id.eq(run_id))
+        .filter(runs::user_id.eq(user_id))
+        .first::<Run>(&conn)
+        .map_err(|_| ApiError::NotFound(not_found))
+}
+
+/// Create a new run
+pub fn create(pool: &PoolType, cache: &Cache, new_run: &Run) -> Result<Run, ApiError> {
+    let conn = pool.get()?;
+    diesel::insert_into(runs::table)
+        .values(new_run)
+        .execute(&conn)?;
+    publish_runs(pool, cache, std::slice::from_ref(new_run));
+    Ok(new_run.clone())
+}
+
+/// Create several runs with their laps at once, all of them or none of them
+pub fn create_all(
+    pool: &PoolType,
+    cache: &Cache,
+    new_runs: &[RunDetails],
+) -> Result<Vec<RunDetails>, ApiError> {
+    let runs: Vec<Run> = new_runs.iter().map(|details| details.run.clone()).collect();
+    let laps: Vec<&RunLap> = new_runs.iter().flat_map(|details| &details.laps).collect();
+    transaction(pool, |conn| {
+        diesel::insert_into(runs::table)
+            .values(&runs)
+            .execute(conn)?;
+        diesel::insert_into(run_laps::table)
+            .values(laps)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    publish_runs(pool, cache, &runs);
+    Ok(new_runs.to_vec())
+}
+
+/// Create the runs of a league's CSV file, matching emails to its players
+///
+/// Emails are matched to the league's players ignoring case, errors of rows
+/// that don't match are added to the errors the file already had. Runs start
+/// at noon of their date in the league's timezone. They're only created when
+/// every row is valid and it isn't a dry run, all of them in one transaction.
+pub fn import_for_league(
+    pool: &PoolType,
+    cache: &Cache,
+    league_id: Uuid,
+    rows: &[Row],
+    mut errors: FieldErrors,
+    dry_run: bool,
+) -> Result<RunsImportResponse, ApiError> {
+    let response = transaction(pool, |conn| {
+        let calendar = Calendar::of(&lock(conn, league_id)?);
+        let players = player_emails_in_league(conn, league_id)?;
+        let now = Utc::now().naive_utc();
+
+        let mut new_runs: Vec<Run> = vec![];
+        for row in rows {
+            let player = players
+                .iter()
+                .find(|(_, email)| email.eq_ignore_ascii_case(&row.email));
+            match player {
+                Some((user_id, _)) => new_runs.push(
+                    NewRun {
+                        id: Uuid::new_v4(),
+                        user_id: *user_id,
+                        distance: row.distance,
+                        duration: row.duration,
+                        // Runs of today may not have reached noon yet
+                        started_at: calendar.noon(row.date).min(now),
+                        elevation_gain: None,
+                        heart_rate: None,
+                    }
+                    .into(),
+                ),
+                None => {
+                    let message =
+                        format!("{} isn't the email of a player in the league", row.email);
+                    let (field, message) = row.error("email", &message);
+                    errors.entry(field).or_default().push(message);
+                }
+            }
+        }
+
+        if !dry_run {
+            if !errors.is_empty() {
+                return Err(ApiError::ValidationError(errors));
+            }
+            diesel::insert_into(runs::table)
+                .values(&new_runs)
+                .execute(conn)?;
+        }
+        Ok(RunsImportResponse {
+            dry_run,
+            total: new_runs.len() as i64,
+            data: new_runs,
+            errors,
+        })
+    })?;
+    if !dry_run {
+        publish_runs(pool, cache, &response.data);
+    }
+    Ok(response)
+}
+
+/// Update one of a user's runs
+pub fn update(
+    pool: &PoolType,
+    cache: &Cache,
+    user_id: Uuid,
+    run_id: Uuid,
+    update_run: &UpdateRun,
+) -> Result<Run, ApiError> {
+    let conn = pool.get()?;
+    let target = runs::table
+        .filter(runs::id.eq(run_id))
+        .filter(runs::user_id.eq(user_id));
+    let updated = diesel::update(target).set(update_run).execute(&conn)?;
+
+    if updated == 0 {
+        return Err(ApiError::NotFound(format!("Run {} not found", run_id)));
+    }
+    forget_leagues_of(pool, cache, user_id);
+    find(pool, user_id, run_id)
+}
+
+/// Delete one of a user's runs
+pub fn delete(pool: &PoolType, cache: &Cache, user_id: Uuid, run_id: Uuid) -> Result<(), ApiError> {
+    let conn = pool.get()?;
+    let target = runs::table
+        .filter(runs::id.eq(run_id))
+        .filter(runs::user_id.eq(user_id));
+    let deleted = diesel::delete(target).execute(&conn)?;
+
+    if deleted == 0 {
+        return Err(ApiError::NotFound(format!("Run {} not found", run_id)));
+    }
+    forget_leagues_of(pool, cache, user_id);
+    Ok(())
+}
+
+/// Tell every league the users of runs that were just logged play in
+///
+/// The leagues' cached responses are dropped and the runs published to their
+/// streams.
+pub fn publish_runs(pool: &PoolType, cache: &Cache, logged_runs: &[Run]) {
+    if logged_runs.is_empty() {
+        return;
+    }
+    let user_ids: Vec<Uuid> = logged_runs.iter().map(|run| run.user_id).collect();
+    for (user_id, league_id) in leagues_of(pool, &user_ids) {
+        forget_league(cache, league_id);
+        let events = logged_runs
+            .iter()
+            .filter(|run| run.user_id == user_id)
+            .map(|run| LeagueEvent::RunLogged(run.clone()));
+        publish_all(league_id, events);
+    }
+}
+
+/// Drop the cached responses of every league a user whose runs changed plays in
+pub fn forget_leagues_of(pool: &PoolType, cache: &Cache, user_id: Uuid) {
+    for (_, league_id) in leagues_of(pool, &[user_id]) {
+        forget_league(cache, league_id);
+    }
+}
+
+/// The leagues users play in, as user and league ids
+///
+/// The runs are saved already, so failing to find the leagues is only logged.
+fn leagues_of(pool: &PoolType, user_ids: &[Uuid]) -> Vec<(Uuid, Uuid)> {
+    pool.get()
+        .map_err(ApiError::from)
+        .and_then(|conn| leagues_of_players(&conn, user_ids))
+        .unwrap_or_else(|error| {
+            log::warn!(
+                "Finding the leagues of {} users failed: {:?}",
+                user_ids.len(),
+                error
+            );
+            vec![]
+        })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cache::{league_group, DEFAULT_TTL};
+    use crate::events::BUS;
+    use crate::import::csv::parse;
+    use crate::import::csv::tests::csv_file;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::managers::teams::add_player;
+    use crate::managers::users::create as create_account;
+    use crate::managers::users::tests::create_user;
+    use crate::models::users::{NewUser, UserResponse};
+    use crate::schema::leagues;
+    use crate::tests::helpers::tests::get_pool;
+    use chrono::{Duration, NaiveDate, Utc};
+
+    pub fn player_one_id() -> Uuid {
+        Uuid::parse_str("11111111-1111-1111-1111-111111111111").unwrap()
+    }
+
+    pub fn create_run(user_id: Uuid) -> Result<Run, ApiError> {
+        let new_run: Run = NewRun {
+            id: Uuid::new_v4(),
+            user_id,
+            distance: 5.0,
+            duration: 2700,
+            started_at: Utc::now().naive_utc() - Duration::hours(3),
+            elevation_gain: None,
+            heart_rate: Some(148),
+        }
+        .into();
+        create(&get_pool(), &Cache::disabled(), &new_run)
+    }
+
+    #[test]
+    fn it_creates_a_run() {
+        let created = create_run(player_one_id()).unwrap();
+        let found = find(&get_pool(), player_one_id(), created.id).unwrap();
+        assert_eq!(created.id, found.id);
+        assert_eq!(created.distance, found.distance);
+    }
+
+    #[test]
+    fn it_publishes_a_run_to_the_users_leagues() {
+        let (league_id, user) = create_league_with_player();
+        let mut events = BUS.subscribe(league_id);
+        let created = create_run(user.id).unwrap();
+        assert_eq!(
+            events.try_next().unwrap(),
+            Some(LeagueEvent::RunLogged(created))
+        );
+    }
+
+    #[test]
+    fn it_gets_all_runs_for_a_user() {
+        let created = create_run(player_one_id()).unwrap();
+        let runs = get_all_for_user(&get_pool(), player_one_id(), &ListParams::default()).unwrap();
+        assert!(runs.data.iter().any(|run| run.id == created.id));
+        assert!(runs.data.iter().all(|run| run.user_id == player_one_id()));
+    }
+
+    #[test]
+    fn it_pages_through_a_users_runs() {
+        let user_id = create_user().unwrap().id;
+        for _ in 0..3 {
+            create_run(user_id).unwrap();
+        }
+        let params = ListParams {
+            limit: Some(2),
+            sort: Some("distance".into()),
+            ..ListParams::default()
+        };
+
+        let first_page = get_all_for_user(&get_pool(), user_id, &params).unwrap();
+        assert_eq!(first_page.data.len(), 2);
+        assert_eq!(first_page.total, 3);
+
+        let params = ListParams {
+            cursor: first_page.next_cursor,
+            ..params
+        };
+        let last_page = get_all_for_user(&get_pool(), user_id, &params).unwrap();
+        assert_eq!(last_page.data.len(), 1);
+        assert_eq!(last_page.next_cursor, None);
+    }
+
+    #[test]
+    fn it_doesnt_find_another_users_run() {
+        let created = create_run(player_one_id()).unwrap();
+        let found = find(&get_pool(), Uuid::nil(), created.id);
+        assert!(found.is_err());
+    }
+
+    #[test]
+    fn it_updates_a_run() {
+        let created = create_run(player_one_id()).unwrap();
+        let update_run = UpdateRun {
+            distance: 6.0,
+            duration: 3000,
+            started_at: created.started_at,
+            elevation_gain: Some(50.0),
+            heart_rate: None,
+            updated_at: Utc::now().naive_utc(),
+        };
+        let updated = update(
+            &get_pool(),
+            &Cache::disabled(),
+            player_one_id(),
+            created.id,
+            &update_run,
+        )
+        .unwrap();
+        assert_eq!(updated.distance, 6.0);
+        assert_eq!(updated.heart_rate, None);
+    }
+
+    #[test]
+    fn it_fails_to_update_a_nonexistent_run() {
+        let update_run = UpdateRun {
+            distance: 6.0,
+            duration: 3000,
+            started_at: Utc::now().naive_utc(),
+            elevation_gain: None,
+            heart_rate: None,
+            updated_at: Utc::now().naive_utc(),
+        };
+        let updated = update(
+            &get_pool(),
+            &Cache::disabled(),
+            player_one_id(),
+            Uuid::new_v4(),
+            &update_run,
+        );
+        assert!(updated.is_err());
+    }
+
+    #[test]
+    fn it_forgets_the_cached_responses_of_a_players_leagues() {
+        let (league_id, user) = create_league_with_player();
+        let cache = Cache::memory(DEFAULT_TTL);
+        let group = league_group(league_id);
+        let new_run: Run = NewRun {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            distance: 5.0,
+            duration: 2700,
+            started_at: Utc::now().naive_utc() - Duration::hours(3),
+            elevation_gain: None,
+            heart_rate: None,
+        }
+        .into();
+
+        cache.set(&group, "standings", &[&group]).unwrap();
+        create(&get_pool(), &cache, &new_run).unwrap();
+        assert_eq!(cache.get(&group).unwrap(), None);
+
+        cache.set(&group, "standings", &[&group]).unwrap();
+        delete(&get_pool(), &cache, user.id, new_run.id).unwrap();
+        assert_eq!(cache.get(&group).unwrap(), None);
+    }
+
+    #[test]
+    fn it_deletes_a_run() {
+        let created = create_run(player_one_id()).unwrap();
+        delete(&get_pool(), &Cache::disabled(), player_one_id(), created.id).unwrap();
+        let found = find(&get_pool(), player_one_id(), created.id);
+        assert!(found.is_err());
+    }
+
+    /// A league with one team, its player has an email of their own
+    pub fn create_league_with_player() -> (Uuid, UserResponse) {
+        let (league_id, teams) = create_league_with_teams(1);
+        let new_user = NewUser {
+            id: Uuid::new_v4(),
+            first_name: "Import".into(),
+            last_name: "Test".into(),
+            email: format!("import-test-{}@nothing.org", Uuid::new_v4()),
+            password: "123456".into(),
+        };
+        let user = create_account(&get_pool(), &new_user.into()).unwrap();
+        add_player(&get_pool(), teams[0].id, user.id).unwrap();
+        (league_id, user)
+    }
+
+    fn import(league_id: Uuid, file: &str, dry_run: bool) -> Result<RunsImportResponse, ApiError> {
+        let (rows, errors) = parse(file.as_bytes()).unwrap();
+        import_for_league(
+            &get_pool(),
+            &Cache::disabled(),
+            league_id,
+            &rows,
+            errors,
+            dry_run,
+        )
+    }
+
+    fn run_count(user_id: Uuid) -> i64 {
+        runs::table
+            .filter(runs::user_id.eq(user_id))
+            .count()
+            .get_result(&get_pool().get().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn it_imports_a_leagues_runs() {
+        let (league_id, user) = create_league_with_player();
+        let email = user.email.to_uppercase();
+        let file = csv_file(&[
+            &format!("{},2020-05-09,3.1,1680", email),
+            &format!("{},2020-05-10,6.2,0:56:00", email),
+        ]);
+        let response = import(league_id, &file, false).unwrap();
+        assert!(!response.dry_run);
+        assert_eq!(response.total, 2);
+        assert!(response.errors.is_empty());
+        assert_eq!(response.data[0].user_id, user.id);
+        assert_eq!(run_count(user.id), 2);
+    }
+
+    #[test]
+    fn it_imports_runs_on_their_date_in_the_leagues_timezone() {
+        let (league_id, user) = create_league_with_player();
+        diesel::update(leagues::table.filter(leagues::id.eq(league_id)))
+            .set(leagues::timezone.eq("America/Los_Angeles"))
+            .execute(&get_pool().get().unwrap())
+            .unwrap();
+        let file = csv_file(&[&format!("{},2020-05-09,3.1,1680", user.email)]);
+        let response = import(league_id, &file, false).unwrap();
+        let started_at = NaiveDate::from_ymd(2020, 5, 9).and_hms(19, 0, 0);
+        assert_eq!(response.data[0].started_at, started_at);
+    }
+
+    #[test]
+    fn it_doesnt_import_anything_in_a_dry_run() {
+        let (league_id, user) = create_league_with_player();
+        let file = csv_file(&[
+            &format!("{},2020-05-09,3.1,1680", user.email),
+            "stranger@nothing.org,2020-05-09,3.1,1680",
+        ]);
+        let response = import(league_id, &file, true).unwrap();
+        assert!(response.dry_run);
+        assert_eq!(response.total, 1);
+        assert_eq!(
+            response.errors["row[1].email"],
+            vec!["line 3: stranger@nothing.org isn't the email of a player in the league"]
+        );
+        assert_eq!(run_count(user.id), 0);
+    }
+
+    #[test]
+    fn it_imports_nothing_unless_every_row_is_valid() {
+        let (league_id, user) = create_league_with_player();
+        let file = csv_file(&[
+            &format!("{},2020-05-09,3.1,1680", user.email),
+            &format!("{},2020-05-10,3.1,60", user.email),
+        ]);
+        match import(league_id, &file, false).unwrap_err() {
+            ApiError::ValidationError(errors) => assert!(errors.contains_key("row[1].__all__")),
+            error => panic!("expected a validation error, got {:?}", error),
+        }
+        assert_eq!(run_count(user.id), 0);
+    }
+}
@@ -1,25 +1,73 @@
-use crate::database::PoolType;
+use crate::database::{transaction, PoolType};
 use crate::errors::ApiError;
+use crate::managers::leagues::{find_with_details as find_league, lock};
+use crate::models::matchups::Matchup;
+use crate::models::scores::TeamScore;
 use crate::models::teams::*;
-use crate::schema::{leagues, team_owners, team_players, teams};
+use crate::pagination::{contains, ListParams, ListResponse, ASC};
+use crate::schema::{matchups, team_owners, team_players, team_scores, teams, users};
+use crate::standings::rank;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use std::cmp::Ordering;
 use uuid::Uuid;
 
-pub fn get_all(pool: &PoolType) -> Result<TeamsResponse, ApiError> {
-    let conn = pool.get()?;
-    let all_teams = teams::table.load(&conn)?;
+/// Get a page of teams, sorted by name or wins
+pub fn get_all(pool: &PoolType, params: &ListParams) -> Result<TeamsResponse, ApiError> {
+    get_page(pool, None, params)
+}
 
-    Ok(all_teams.into())
+/// Get a page of a league's teams, sorted by name or wins
+pub fn get_all_in_league(
+    pool: &PoolType,
+    league_id: Uuid,
+    params: &ListParams,
+) -> Result<TeamsResponse, ApiError> {
+    get_page(pool, Some(league_id), params)
 }
 
-pub fn get_all_in_league(pool: &PoolType, league_id: Uuid) -> Result<TeamsResponse, ApiError> {
+/// Get every team of a league, ordered by name
+pub fn get_league_teams(pool: &PoolType, league_id: Uuid) -> Result<Vec<Team>, ApiError> {
     let conn = pool.get()?;
     let teams_found = teams::table
-        .select(teams::all_columns)
-        .filter(teams::league_id.eq(league_id.clone()))
+        .filter(teams::league_id.eq(league_id))
+        .order((teams::name, teams::id))
         .load(&conn)?;
 
-    Ok(teams_found.into())
+    Ok(teams_found)
+}
+
+fn get_page(
+    pool: &PoolType,
+    league_id: Option<Uuid>,
+    params: &ListParams,
+) -> Result<TeamsResponse, ApiError> {
+    let (sort, descending) = params.sort(&["name", "wins"], ASC)?;
+    let offset = params.offset()?;
+    let conn = pool.get()?;
+
+    let total = filtered(league_id, params).count().get_result(&conn)?;
+    let query = filtered(league_id, params);
+    let query = match sort {
+        "wins" if descending => query.order((teams::wins.desc(), teams::id)),
+        "wins" => query.order((teams::wins, teams::id)),
+        _ if descending => query.order((teams::name.desc(), teams::id)),
+        _ => query.order((teams::name, teams::id)),
+    };
+    let teams_found = query.limit(params.limit()).offset(offset).load(&conn)?;
+
+    Ok(ListResponse::new(teams_found, total, offset))
+}
+
+fn filtered(league_id: Option<Uuid>, params: &ListParams) -> teams::BoxedQuery<'_, Pg> {
+    let mut query = teams::table.into_boxed();
+    if let Some(league_id) = league_id {
+        query = query.filter(teams::league_id.eq(league_id));
+    }
+    if let Some(name) = &params.name {
+        query = query.filter(teams::name.ilike(contains(name)));
+    }
+    query
 }
 
 pub fn find(pool: &PoolType, team_id: Uuid) -> Result<TeamDetails, ApiError> {
@@ -28,7 +76,8 @@ pub fn find(pool: &PoolType, team_id: Uuid) -> Result<TeamDetails, ApiError> {
     let team: Team = teams::table
         .select(teams::all_columns)
         .filter(teams::id.eq(team_id.clone()))
-        .first(&conn)?;
+        .first(&conn)
+        .map_err(|_| not_found(team_id))?;
 
     let found_owners: Option<Vec<Owner>> = team_owners::table
         .select(team_owners::all_columns)
@@ -62,58 +111,356 @@ pub fn create(
     new_players: Option<&Vec<Player>>,
     new_owners: Option<&Vec<Owner>>,
 ) -> Result<TeamDetails, ApiError> {
+    transaction(pool, |conn| {
+        new_team.insert_into(teams::table).execute(conn)?;
+
+        let mut inserted_players = None;
+        if let Some(new_players) = new_players {
+            inserted_players = diesel::insert_into(team_players::table)
+                .values(new_players)
+                .get_results(conn)
+                .optional()?;
+        }
+
+        let mut inserted_owners = None;
+        if let Some(new_owners) = new_owners {
+            inserted_owners = diesel::insert_into(team_owners::table)
+                .values(new_owners)
+                .get_results(conn)
+                .optional()?;
+        }
+
+        Ok(TeamDetails {
+            id: new_team.id,
+            league_id: new_team.league_id,
+            name: new_team.name.to_string(),
+            wins: new_team.wins,
+            losses: new_team.losses,
+            ties: new_team.ties,
+            players: inserted_players,
+            owners: inserted_owners,
+        })
+    })
+}
+
+/// Rename a team
+pub fn update(pool: &PoolType, update_team: &UpdateTeam) -> Result<TeamDetails, ApiError> {
     let conn = pool.get()?;
+    let updated = diesel::update(teams::table.filter(teams::id.eq(update_team.id)))
+        .set(update_team)
+        .execute(&conn)?;
 
-    new_team.insert_into(teams::table).execute(&conn)?;
-
-    let mut inserted_players = None;
-    if let Some(new_players) = new_players {
-        inserted_players = diesel::insert_into(team_players::table)
-            .values(new_players)
-            .get_results(&conn)
-            .optional()?;
-    }
-
-    let mut inserted_owners = None;
-    if let Some(new_owners) = new_owners {
-        inserted_owners = diesel::insert_into(team_owners::table)
-            .values(new_owners)
-            .get_results(&conn)
-            .optional()?;
-    }
-
-    let created = TeamDetails {
-        id: new_team.id,
-        league_id: new_team.league_id,
-        name: new_team.name.to_string(),
-        wins: new_team.wins,
-        losses: new_team.losses,
-        ties: new_team.ties,
-        players: inserted_players,
-        owners: inserted_owners,
-    };
+    if updated == 0 {
+        return Err(not_found(update_team.id));
+    }
+    find(pool, update_team.id)
+}
+
+/// Delete a team along with its roster, matchups and scores
+pub fn delete(pool: &PoolType, team_id: Uuid) -> Result<(), ApiError> {
+    let conn = pool.get()?;
+    let deleted = diesel::delete(teams::table.filter(teams::id.eq(team_id))).execute(&conn)?;
+
+    if deleted == 0 {
+        return Err(not_found(team_id));
+    }
+    Ok(())
+}
 
-    Ok(created)
+/// Add a user to a team's roster
+///
+/// Users can only play for one team per league.
+pub fn add_player(pool: &PoolType, team_id: Uuid, user_id: Uuid) -> Result<TeamDetails, ApiError> {
+    transaction(pool, |conn| {
+        let team = lock_team(conn, team_id)?;
+        ensure_user_exists(conn, user_id)?;
+        if players_in_league(conn, team.league_id)?.contains(&user_id) {
+            return Err(ApiError::Conflict(format!(
+                "User {} is already on a team in league {}",
+                user_id, team.league_id
+            )));
+        }
+
+        diesel::insert_into(team_players::table)
+            .values(Player {
+                id: Uuid::new_v4(),
+                user_id,
+                team_id,
+            })
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    find(pool, team_id)
+}
+
+/// Take a user off a team's roster
+pub fn remove_player(
+    pool: &PoolType,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> Result<TeamDetails, ApiError> {
+    let conn = pool.get()?;
+    let target = team_players::table
+        .filter(team_players::team_id.eq(team_id))
+        .filter(team_players::user_id.eq(user_id));
+    let deleted = diesel::delete(target).execute(&conn)?;
+
+    if deleted == 0 {
+        return Err(ApiError::NotFound(format!(
+            "User {} doesn't play for team {}",
+            user_id, team_id
+        )));
+    }
+    find(pool, team_id)
+}
+
+/// Make a user one of a team's owners
+pub fn add_owner(pool: &PoolType, team_id: Uuid, user_id: Uuid) -> Result<TeamDetails, ApiError> {
+    transaction(pool, |conn| {
+        lock_team(conn, team_id)?;
+        ensure_user_exists(conn, user_id)?;
+        if owners(conn, team_id)?.contains(&user_id) {
+            return Err(ApiError::Conflict(format!(
+                "User {} already owns team {}",
+                user_id, team_id
+            )));
+        }
+
+        diesel::insert_into(team_owners::table)
+            .values(Owner {
+                id: Uuid::new_v4(),
+                user_id,
+                team_id,
+            })
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    find(pool, team_id)
+}
+
+/// Remove one of a team's owners
+///
+/// Teams always keep at least one owner.
+pub fn remove_owner(
+    pool: &PoolType,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> Result<TeamDetails, ApiError> {
+    transaction(pool, |conn| {
+        lock_team(conn, team_id)?;
+        let team_owners = owners(conn, team_id)?;
+        if !team_owners.contains(&user_id) {
+            return Err(ApiError::NotFound(format!(
+                "User {} doesn't own team {}",
+                user_id, team_id
+            )));
+        }
+        if team_owners.len() == 1 {
+            return Err(ApiError::BadRequest(format!(
+                "Team {} has to keep at least one owner",
+                team_id
+            )));
+        }
+
+        let target = team_owners::table
+            .filter(team_owners::team_id.eq(team_id))
+            .filter(team_owners::user_id.eq(user_id));
+        diesel::delete(target).execute(conn)?;
+        Ok(())
+    })?;
+
+    find(pool, team_id)
+}
+
+/// Get every user playing for a team in a league
+pub fn players_in_league(conn: &PgConnection, league_id: Uuid) -> Result<Vec<Uuid>, ApiError> {
+    let user_ids = team_players::table
+        .inner_join(teams::table)
+        .select(team_players::user_id)
+        .filter(teams::league_id.eq(league_id))
+        .load(conn)?;
+    Ok(user_ids)
+}
+
+/// Get the user and league ids of every league the users play in
+pub fn leagues_of_players(
+    conn: &PgConnection,
+    user_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Uuid)>, ApiError> {
+    let leagues = team_players::table
+        .inner_join(teams::table)
+        .select((team_players::user_id, teams::league_id))
+        .filter(team_players::user_id.eq_any(user_ids))
+        .load(conn)?;
+    Ok(leagues)
+}
+
+/// Get the id and email of every user playing for a team in a league
+pub fn player_emails_in_league(
+    conn: &PgConnection,
+    league_id: Uuid,
+) -> Result<Vec<(Uuid, String)>, ApiError> {
+    let players = team_players::table
+        .inner_join(teams::table)
+        .inner_join(users::table)
+        .select((users::id, users::email))
+        .filter(teams::league_id.eq(league_id))
+        .load(conn)?;
+    Ok(players)
+}
+
+/// Lock a team's league so roster changes in it don't race each other
+fn lock_team(conn: &PgConnection, team_id: Uuid) -> Result<Team, ApiError> {
+    let team: Team = teams::table
+        .filter(teams::id.eq(team_id))
+        .first(conn)
+        .map_err(|_| not_found(team_id))?;
+    lock(conn, team.league_id)?;
+    Ok(team)
+}
+
+fn owners(conn: &PgConnection, team_id: Uuid) -> Result<Vec<Uuid>, ApiError> {
+    let user_ids = team_owners::table
+        .select(team_owners::user_id)
+        .filter(team_owners::team_id.eq(team_id))
+        .load(conn)?;
+    Ok(user_ids)
+}
+
+fn ensure_user_exists(conn: &PgConnection, user_id: Uuid) -> Result<(), ApiError> {
+    let user_exists: Option<Uuid> = users::table
+        .select(users::id)
+        .filter(users::id.eq(user_id))
+        .first(conn)
+        .optional()?;
+    match user_exists {
+        Some(_) => Ok(()),
+        None => Err(ApiError::NotFound(format!("User {} not found", user_id))),
+    }
+}
+
+fn not_found(team_id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("Team {} not found", team_id))
+}
+
+/// Recount the wins, losses and ties of every team in a league
+///
+/// Records are worked out from the settled matchups every time, so settling a
+/// round again never counts its results twice.
+pub fn update_records(conn: &PgConnection, league_id: Uuid) -> Result<(), ApiError> {
+    let settled: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .filter(matchups::home_points.is_not_null())
+        .load(conn)?;
+    let league_teams: Vec<Team> = teams::table
+        .filter(teams::league_id.eq(league_id))
+        .load(conn)?;
+
+    for team in league_teams {
+        let results: Vec<Ordering> = settled
+            .iter()
+            .filter_map(|matchup| matchup.result_for(team.id))
+            .collect();
+        let count = |ordering| results.iter().filter(|result| **result == ordering).count() as i32;
+        diesel::update(teams::table.filter(teams::id.eq(team.id)))
+            .set((
+                teams::wins.eq(count(Ordering::Greater)),
+                teams::losses.eq(count(Ordering::Less)),
+                teams::ties.eq(count(Ordering::Equal)),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Rank the teams of a league using its tie-breakers
+pub fn get_standings(pool: &PoolType, league_id: Uuid) -> Result<StandingsResponse, ApiError> {
+    let league = find_league(pool, league_id)?;
+    let league_teams = get_league_teams(pool, league_id)?;
+
+    let conn = pool.get()?;
+    let league_matchups: Vec<Matchup> = matchups::table
+        .filter(matchups::league_id.eq(league_id))
+        .load(&conn)?;
+    let closed_scores: Vec<TeamScore> = team_scores::table
+        .filter(team_scores::league_id.eq(league_id))
+        .filter(team_scores::round.le(league.current_round))
+        .load(&conn)?;
+
+    Ok(StandingsResponse(rank(
+        &league_teams,
+        &league_matchups,
+        &closed_scores,
+        &league.tie_breakers,
+    )))
+}
+
+/// Determine if a user is one of a team's owners
+pub fn is_owner(pool: &PoolType, team_id: Uuid, user_id: Uuid) -> Result<bool, ApiError> {
+    let conn = pool.get()?;
+    let owner: Option<Owner> = team_owners::table
+        .filter(team_owners::team_id.eq(team_id))
+        .filter(team_owners::user_id.eq(user_id))
+        .first(&conn)
+        .optional()?;
+
+    Ok(owner.is_some())
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::cache::Cache;
+    use crate::managers::leagues::close_round;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::managers::runs::tests::create_run;
     use crate::tests::helpers::tests::get_pool;
 
     pub fn get_all_teams() -> Result<TeamsResponse, ApiError> {
         let pool = get_pool();
-        get_all(&pool)
+        get_all(&pool, &ListParams::default())
     }
 
     #[test]
     fn it_finds_team() {
         let teams = get_all_teams().unwrap();
-        let team = &teams.0[0];
+        let team = &teams.data[0];
         let found_team = find(&get_pool(), team.id).unwrap();
         assert_eq!(team.id, found_team.id);
     }
 
+    #[test]
+    fn it_finds_an_owner() {
+        let owner_id = Uuid::parse_str("33333333-3333-3333-3333-333333333333").unwrap();
+        let is_owner = is_owner(&get_pool(), Uuid::nil(), owner_id).unwrap();
+        assert!(is_owner);
+    }
+
+    #[test]
+    fn it_doesnt_find_a_player_as_owner() {
+        let player_id = Uuid::parse_str("11111111-1111-1111-1111-111111111111").unwrap();
+        let is_owner = is_owner(&get_pool(), Uuid::nil(), player_id).unwrap();
+        assert!(!is_owner);
+    }
+
+    #[test]
+    fn it_gets_standings() {
+        let (league_id, league_teams) = create_league_with_teams(2);
+        let runner = league_teams[1].players.as_ref().unwrap()[0].user_id;
+        create_run(runner).unwrap();
+        close_round(&get_pool(), &Cache::disabled(), league_id, 1).unwrap();
+
+        let standings = get_standings(&get_pool(), league_id).unwrap().0;
+        assert_eq!(standings[0].team_id, league_teams[1].id);
+        assert_eq!(standings[0].streak, "W1");
+        assert_eq!(standings[0].miles, 5.0);
+        assert_eq!(standings[1].streak, "L1");
+        assert_eq!(standings[1].games_back, 1.0);
+    }
+
     #[test]
     fn it_gets_all_teams() {
         let teams = get_all_teams();
@@ -173,6 +520,44 @@ pub mod tests {
 
         create(&pool, &team, None, None)
     }
+
+    #[test]
+    fn it_doesnt_create_part_of_a_team() {
+        let team = Team {
+            id: Uuid::new_v4(),
+            name: "Half Team".into(),
+            wins: 0,
+            losses: 0,
+            ties: 0,
+            league_id: Uuid::nil(),
+        };
+        let player = Player {
+            id: Uuid::new_v4(),
+            team_id: team.id,
+            user_id: Uuid::nil(),
+        };
+        // The owners go in last and this one doesn't exist
+        let owner = Owner {
+            id: Uuid::new_v4(),
+            team_id: team.id,
+            user_id: Uuid::new_v4(),
+        };
+
+        let created = create(&get_pool(), &team, Some(&vec![player]), Some(&vec![owner]));
+        assert!(created.is_err());
+        assert_eq!(
+            find(&get_pool(), team.id).unwrap_err(),
+            ApiError::NotFound(format!("Team {} not found", team.id))
+        );
+        let conn = get_pool().get().unwrap();
+        let players: i64 = team_players::table
+            .filter(team_players::team_id.eq(team.id))
+            .count()
+            .get_result(&conn)
+            .unwrap();
+        assert_eq!(players, 0);
+    }
+
     #[test]
     fn it_creates_team_alone() {
         let created = create_tournament_without_owner_and_player();
@@ -1,16 +1,63 @@
+use crate::auth::{hash, needs_rehash, verify};
 use crate::database::PoolType;
 use crate::errors::ApiError;
 use crate::models::users::*;
+use crate::pagination::{contains, ListParams, ListResponse, ASC};
+use crate::schema::users;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::Text;
+use rayon::prelude::*;
 use uuid::Uuid;
 
-pub fn get_all(pool: &PoolType) -> Result<UsersResponse, ApiError> {
-    use crate::schema::users::dsl::users;
+// Emails are unique by their lowercase form
+sql_function!(fn lower(x: Text) -> Text);
 
+/// Get a page of users, filtered by email or name
+pub fn get_all(pool: &PoolType, params: &ListParams) -> Result<UsersResponse, ApiError> {
+    let (sort, descending) =
+        params.sort(&["created_at", "email", "first_name", "last_name"], ASC)?;
+    let offset = params.offset()?;
     let conn = pool.get()?;
-    let all_users = users.load(&conn)?;
 
-    Ok(all_users.into())
+    let total = filtered(params).count().get_result(&conn)?;
+    let query = filtered(params);
+    let query = match sort {
+        "email" if descending => query.order((users::email.desc(), users::id)),
+        "email" => query.order((users::email, users::id)),
+        "first_name" if descending => query.order((users::first_name.desc(), users::id)),
+        "first_name" => query.order((users::first_name, users::id)),
+        "last_name" if descending => query.order((users::last_name.desc(), users::id)),
+        "last_name" => query.order((users::last_name, users::id)),
+        _ if descending => query.order((users::created_at.desc(), users::id)),
+        _ => query.order((users::created_at, users::id)),
+    };
+    let found_users: Vec<User> = query.limit(params.limit()).offset(offset).load(&conn)?;
+
+    Ok(ListResponse::new(
+        found_users
+            .into_par_iter()
+            .map(|user| user.into())
+            .collect(),
+        total,
+        offset,
+    ))
+}
+
+fn filtered(params: &ListParams) -> users::BoxedQuery<'_, Pg> {
+    let mut query = users::table.into_boxed();
+    if let Some(email) = &params.email {
+        query = query.filter(users::email.ilike(contains(email)));
+    }
+    if let Some(name) = &params.name {
+        let pattern = contains(name);
+        query = query.filter(
+            users::first_name
+                .ilike(pattern.clone())
+                .or(users::last_name.ilike(pattern)),
+        );
+    }
+    query
 }
 
 /// Find a user by the user's id or error out
@@ -29,21 +76,36 @@ pub fn find(pool: &PoolType, user_id: Uuid) -> Result<UserResponse, ApiError> {
 
 /// Find a user by the user's authentication information (email + password)
 /// Return an Unauthorized error if it doesn't match
-// pub fn find_by_auth(
-//     pool: &PoolType,
-//     user_email: &str,
-//     user_password: &str,
-// ) -> Result<UserResponse, ApiError> {
-//     use crate::schema::users::dsl::{email, password, users};
-
-//     let conn = pool.get()?;
-//     let user = users
-//         .filter(email.eq(user_email.to_string()))
-//         .filter(password.eq(user_password.to_string()))
-//         .first::<User>(&conn)
-//         .map_err(|_| ApiError::Unauthorized("Invalid login".into()))?;
-//     Ok(user.into())
-// }
+///
+/// Emails are unique ignoring case, so there's at most one user to check the
+/// password of.
+///
+/// Password hashes in an outdated format are transparently replaced with a
+/// fresh hash once the password has been verified.
+pub fn find_by_auth(
+    pool: &PoolType,
+    user_email: &str,
+    user_password: &str,
+) -> Result<UserResponse, ApiError> {
+    use crate::schema::users::dsl::{email, id, password, users};
+
+    let conn = pool.get()?;
+    let user = users
+        .filter(lower(email).eq(lower(user_email)))
+        .first::<User>(&conn)
+        .optional()?
+        .filter(|user| verify(user_password, &user.password))
+        .ok_or_else(|| ApiError::Unauthorized("Invalid login".into()))?;
+
+    if needs_rehash(&user.password) {
+        diesel::update(users)
+            .filter(id.eq(user.id))
+            .set(password.eq(hash(user_password)))
+            .execute(&conn)?;
+    }
+
+    Ok(user.into())
+}
 
 /// Create a new user
 pub fn create(pool: &PoolType, new_user: &User) -> Result<UserResponse, ApiError> {
@@ -80,11 +142,12 @@ pub fn delete(pool: &PoolType, user_id: Uuid) -> Result<(), ApiError> {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::auth::legacy_hash;
     use crate::tests::helpers::tests::get_pool;
 
     pub fn get_all_users() -> Result<UsersResponse, ApiError> {
         let pool = get_pool();
-        get_all(&pool)
+        get_all(&pool, &ListParams::default())
     }
 
     pub fn create_user() -> Result<UserResponse, ApiError> {
@@ -93,13 +156,83 @@ pub mod tests {
             id: user_id,
             first_name: "Model".to_string(),
             last_name: "Test".to_string(),
-            email: "model-test@nothing.org".to_string(),
+            email: format!("model-test-{}@nothing.org", user_id),
             password: "123456".to_string(),
         };
         let user: User = new_user.into();
         create(&get_pool(), &user)
     }
 
+    #[test]
+    fn it_finds_a_user_by_auth() {
+        let created = create_user().unwrap();
+        let found_user = find_by_auth(&get_pool(), &created.email, "123456").unwrap();
+        assert_eq!(found_user.email, created.email);
+    }
+
+    #[test]
+    fn it_upgrades_a_legacy_password_hash() {
+        use crate::schema::users::dsl::{id, password, users};
+
+        let user: User = NewUser {
+            id: Uuid::new_v4(),
+            first_name: "Legacy".to_string(),
+            last_name: "Test".to_string(),
+            email: format!("legacy-test-{}@nothing.org", Uuid::new_v4()),
+            password: "123456".to_string(),
+        }
+        .into();
+        let created = create(&get_pool(), &user).unwrap();
+        let conn = get_pool().get().unwrap();
+        diesel::update(users)
+            .filter(id.eq(created.id))
+            .set(password.eq(legacy_hash("123456")))
+            .execute(&conn)
+            .unwrap();
+
+        find_by_auth(&get_pool(), &created.email, "123456").unwrap();
+        let upgraded: String = users
+            .filter(id.eq(created.id))
+            .select(password)
+            .first(&conn)
+            .unwrap();
+        assert!(upgraded.starts_with("$argon2i$"));
+        assert!(verify("123456", &upgraded));
+    }
+
+    #[test]
+    fn it_finds_a_user_by_auth_ignoring_the_case_of_the_email() {
+        let created = create_user().unwrap();
+        let found_user =
+            find_by_auth(&get_pool(), &created.email.to_uppercase(), "123456").unwrap();
+        assert_eq!(found_user.id, created.id);
+    }
+
+    #[test]
+    fn it_doesnt_create_a_user_with_a_taken_email() {
+        let created = create_user().unwrap();
+        let user: User = NewUser {
+            id: Uuid::new_v4(),
+            first_name: "Model".to_string(),
+            last_name: "Test".to_string(),
+            email: created.email.to_uppercase(),
+            password: "123456".to_string(),
+        }
+        .into();
+        match create(&get_pool(), &user).unwrap_err() {
+            ApiError::Conflict(_) => (),
+            error => panic!("expected a conflict, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn it_doesnt_find_a_user_with_a_bad_password() {
+        let created = create_user().unwrap();
+        let found_user = find_by_auth(&get_pool(), &created.email, "654321");
+        let expected_error = ApiError::Unauthorized("Invalid login".into());
+        assert_eq!(found_user.unwrap_err(), expected_error);
+    }
+
     #[test]
     fn it_gets_a_user() {
         let users = get_all_users();
@@ -109,7 +242,7 @@ pub mod tests {
     #[test]
     fn test_find() {
         let users = get_all_users().unwrap();
-        let user = &users.0[0];
+        let user = &users.data[0];
         let found_user = find(&get_pool(), user.id).unwrap();
         assert_eq!(user, &found_user);
     }
@@ -133,12 +266,12 @@ pub mod tests {
     #[test]
     fn it_updates_a_user() {
         let users = get_all_users().unwrap();
-        let user = &users.0[1];
+        let user = &users.data[1];
         let update_user = UpdateUser {
             id: user.id,
             first_name: "ModelUpdate".to_string(),
             last_name: "TestUpdate".to_string(),
-            email: "model-update-test@nothing.org".to_string(),
+            email: format!("model-update-test-{}@nothing.org", Uuid::new_v4()),
         };
         let updated = update(&get_pool(), &update_user);
         println!("{:?}", updated);
@@ -0,0 +1,145 @@
+//! Auth middleware
+//!
+//! Looks for a token in the `Authorization: Bearer` header, falling back to
+//! the identity cookie set at login. The token is a JWT, or the token of a
+//! session when sessions are enabled. A valid token stores the AuthUser (and
+//! its Session) on the request so handlers can extract it, anything else is
+//! rejected with a 401.
+
+use crate::auth::decode_jwt;
+use crate::errors::ApiError;
+use crate::models::users::AuthUser;
+use crate::sessions::Sessions;
+use actix_identity::RequestIdentity;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, AUTHORIZATION},
+    web::block,
+    Error, HttpMessage,
+};
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+pub struct Auth;
+
+impl<S, B> Transform<S> for Auth
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AuthMiddleware {
+            service: Rc::new(RefCell::new(service)),
+        })
+    }
+}
+
+pub struct AuthMiddleware<S> {
+    service: Rc<RefCell<S>>,
+}
+
+impl<S, B> Service for AuthMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let token = bearer_token(req.headers()).or_else(|| req.get_identity());
+        let sessions = req.app_data::<Sessions>();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let unauthorized = || ApiError::Unauthorized("Unauthorized".into());
+            let auth_user = match (token, sessions) {
+                (Some(token), Some(sessions)) if sessions.is_enabled() => {
+                    match block(move || sessions.find(&token)).await {
+                        Ok(Some(session)) => {
+                            req.extensions_mut().insert(session.clone());
+                            Ok(session.into())
+                        }
+                        Ok(None) => Err(unauthorized()),
+                        Err(error) => Err(error.into()),
+                    }
+                }
+                (Some(token), _) => decode_jwt(&token)
+                    .map(AuthUser::from)
+                    .map_err(|_| unauthorized()),
+                (None, _) => Err(unauthorized()),
+            };
+
+            match auth_user {
+                Ok(auth_user) => {
+                    req.extensions_mut().insert::<AuthUser>(auth_user);
+                    let response = service.borrow_mut().call(req);
+                    response.await
+                }
+                Err(error) => Ok(req.error_response(error)),
+            }
+        })
+    }
+}
+
+/// Get the token from an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| {
+            let mut parts = header.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                    Some(token.trim().to_string())
+                }
+                _ => None,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[test]
+    fn it_gets_a_bearer_token() {
+        let req = test::TestRequest::default()
+            .header(AUTHORIZATION, "Bearer abc.def.ghi")
+            .to_srv_request();
+        assert_eq!(bearer_token(req.headers()), Some("abc.def.ghi".to_string()));
+    }
+
+    #[test]
+    fn it_ignores_other_authorization_schemes() {
+        let req = test::TestRequest::default()
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .to_srv_request();
+        assert_eq!(bearer_token(req.headers()), None);
+    }
+}
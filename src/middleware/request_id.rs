@@ -0,0 +1,155 @@
+//! Request id middleware
+//!
+//! Tags every request with the id from its `X-Request-Id` header, or a new
+//! one when it doesn't have a usable one, and sends it back in the response's
+//! `X-Request-Id` header. Error responses are rebuilt with the id in their
+//! body, and errors that didn't come from an ApiError (bad JSON, unknown
+//! paths...) are turned into the same JSON error schema.
+
+use crate::errors::ApiError;
+use actix_service::{Service, Transform};
+use actix_web::{
+    body::{Body, MessageBody, ResponseBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderName, HeaderValue},
+        StatusCode,
+    },
+    Error, HttpMessage,
+};
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The id of the current request, stored in the request's extensions
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestId(pub String);
+
+pub struct RequestIds;
+
+impl<S, B> Transform<S> for RequestIds
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdMiddleware { service })
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestIdMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let request_id = incoming_request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut()
+            .insert::<RequestId>(RequestId(request_id.clone()));
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            let api_error =
+                response
+                    .response()
+                    .error()
+                    .and_then(|error| match error.as_error::<ApiError>() {
+                        Some(api_error) => Some(api_error.to_response(Some(request_id.clone()))),
+                        None => from_status(response.status(), error.to_string())
+                            .map(|api_error| api_error.to_response(Some(request_id.clone()))),
+                    });
+            let mut response = match api_error {
+                Some(error_response) => response.into_response(error_response),
+                None => response.map_body(|_, body| ResponseBody::Body(Body::from_message(body))),
+            };
+
+            if let Ok(header) = HeaderValue::from_str(&request_id) {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), header);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Use the caller's request id if it's short and printable
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .map(|header| header.trim().to_string())
+        .filter(|header| {
+            !header.is_empty()
+                && header.len() <= 128
+                && header.chars().all(|c| c.is_ascii_graphic())
+        })
+}
+
+/// Turn errors actix responded with on its own into ApiErrors
+fn from_status(status: StatusCode, message: String) -> Option<ApiError> {
+    match status {
+        StatusCode::BAD_REQUEST => Some(ApiError::BadRequest(message)),
+        StatusCode::UNAUTHORIZED => Some(ApiError::Unauthorized(message)),
+        StatusCode::FORBIDDEN => Some(ApiError::Forbidden(message)),
+        StatusCode::NOT_FOUND => Some(ApiError::NotFound(message)),
+        StatusCode::METHOD_NOT_ALLOWED => Some(ApiError::MethodNotAllowed(message)),
+        StatusCode::CONFLICT => Some(ApiError::Conflict(message)),
+        StatusCode::PAYLOAD_TOO_LARGE => Some(ApiError::PayloadTooLarge(message)),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => Some(ApiError::UnsupportedMediaType(message)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[test]
+    fn it_keeps_an_incoming_request_id() {
+        let req = test::TestRequest::default()
+            .header(REQUEST_ID_HEADER, "abc-123")
+            .to_srv_request();
+        assert_eq!(incoming_request_id(&req), Some("abc-123".into()));
+    }
+
+    #[test]
+    fn it_ignores_unusable_request_ids() {
+        let req = test::TestRequest::default()
+            .header(REQUEST_ID_HEADER, "two words")
+            .to_srv_request();
+        assert_eq!(incoming_request_id(&req), None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::draft::MODES;
+use crate::models::leagues::League;
+use crate::schema::{draft_picks, draft_queues, draft_slots, drafts};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// A league's draft
+///
+/// current_pick counts the picks made so far and pick_started_at is when the
+/// team on the clock started picking.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(League)]
+pub struct Draft {
+    pub id: Uuid,
+    pub league_id: Uuid,
+    pub mode: String,
+    pub rounds: i32,
+    pub pick_seconds: i32,
+    pub status: String,
+    pub current_pick: i32,
+    pub pick_started_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A team's place in the draft order
+#[derive(Clone, Debug, Queryable, Identifiable, Associations, Insertable)]
+#[belongs_to(Draft)]
+pub struct DraftSlot {
+    pub id: Uuid,
+    pub draft_id: Uuid,
+    pub team_id: Uuid,
+    pub position: i32,
+}
+
+/// A pick, user_id is None when time ran out with nobody left to auto-pick
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(Draft)]
+pub struct DraftPick {
+    pub id: Uuid,
+    pub draft_id: Uuid,
+    pub pick_number: i32,
+    pub team_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub auto_picked: bool,
+    pub created_at: NaiveDateTime,
+}
+
+/// A user a team wants to pick, lowest rank first
+#[derive(Clone, Debug, Queryable, Identifiable, Associations, Insertable)]
+#[belongs_to(Draft)]
+#[table_name = "draft_queues"]
+pub struct QueuedUser {
+    pub id: Uuid,
+    pub draft_id: Uuid,
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+    pub rank: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DraftDetails {
+    pub id: Uuid,
+    pub league_id: Uuid,
+    pub mode: String,
+    pub rounds: i32,
+    pub pick_seconds: i32,
+    pub status: String,
+    pub current_pick: i32,
+    pub order: Vec<Uuid>,
+    pub picks: Vec<DraftPick>,
+    pub on_the_clock: Option<Uuid>,
+    pub pick_deadline: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct DraftQueueResponse {
+    pub team_id: Uuid,
+    pub user_ids: Vec<Uuid>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct CreateDraftRequest {
+    #[validate(custom(function = "validate_mode", message = "mode must be snake or linear"))]
+    pub mode: String,
+
+    #[validate(range(min = 1, max = 50, message = "rounds must be between 1 and 50"))]
+    pub rounds: i32,
+
+    #[validate(range(
+        min = 10,
+        max = 86400,
+        message = "pick_seconds must be between 10 seconds and a day"
+    ))]
+    pub pick_seconds: i32,
+
+    /// Team ids in draft order, the order is random when left out
+    pub order: Option<Vec<Uuid>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct PickRequest {
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct DraftQueueRequest {
+    #[validate(length(max = 500, message = "user_ids can't have more than 500 users"))]
+    pub user_ids: Vec<Uuid>,
+}
+
+fn validate_mode(mode: &str) -> Result<(), ValidationError> {
+    if !MODES.contains(&mode) {
+        return Err(ValidationError::new("mode"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn create_draft_request(order: Option<Vec<Uuid>>) -> CreateDraftRequest {
+        CreateDraftRequest {
+            mode: "snake".into(),
+            rounds: 2,
+            pick_seconds: 60,
+            order,
+        }
+    }
+
+    #[test]
+    fn it_validates_a_draft() {
+        assert!(create_draft_request(None).validate().is_ok());
+    }
+
+    #[test]
+    fn it_rejects_an_unknown_mode() {
+        let mut draft = create_draft_request(None);
+        draft.mode = "auction".into();
+        let errors = draft.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("mode"));
+    }
+}
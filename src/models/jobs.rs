@@ -0,0 +1,36 @@
+use crate::models::leagues::League;
+use crate::schema::jobs;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// Work the scheduler does for a league once run_at has passed
+///
+/// Jobs are unique by kind, league and round, round being the pick for
+/// expire_picks jobs. A running job is leased until locked_until, and
+/// claimed again when the worker running it didn't finish by then.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(League)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    pub league_id: Uuid,
+    pub round: i32,
+    pub run_at: NaiveDateTime,
+    pub status: String,
+    pub attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -1,10 +1,17 @@
-use crate::schema::{league_rulesets, leagues};
+use crate::calendar::{self, CUSTOM, DEFAULT_ROUND_DAYS, DEFAULT_TIMEZONE, ROUND_LENGTHS, WEEKLY};
+use crate::pagination::ListResponse;
+use crate::schema::{league_commissioners, league_rulesets, leagues};
+use crate::standings::TIE_BREAKERS;
 use chrono::NaiveDateTime;
-use rayon::prelude::*;
 use serde::Serialize;
+use std::collections::HashSet;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
+/// A league, start is midnight of its first day in its timezone
+///
+/// Rounds last round_days days and start at midnight local time, see
+/// `calendar::Calendar` for their windows in UTC.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Queryable, Identifiable, Insertable)]
 pub struct League {
     pub id: Uuid,
@@ -12,6 +19,8 @@ pub struct League {
     pub start: NaiveDateTime,
     pub rounds: i32,
     pub current_round: i32,
+    pub timezone: String,
+    pub round_days: i32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -21,6 +30,8 @@ pub struct NewLeague {
     pub start: NaiveDateTime,
     pub rounds: i32,
     pub current_round: i32,
+    pub timezone: String,
+    pub round_days: i32,
 }
 
 impl From<NewLeague> for League {
@@ -31,6 +42,8 @@ impl From<NewLeague> for League {
             start: league.start,
             rounds: league.rounds,
             current_round: league.current_round,
+            timezone: league.timezone,
+            round_days: league.round_days,
         }
     }
 }
@@ -42,9 +55,18 @@ pub struct UpdateLeague {
     pub name: String,
     pub start: NaiveDateTime,
     pub rounds: i32,
-    pub current_round: i32,
+    pub timezone: String,
+    pub round_days: i32,
 }
 
+/// How runs are turned into points in a league
+///
+/// pace_bonus_target is in seconds per mile, runs at or under it earn
+/// pace_bonus_per_mile on top of points_per_mile. Runs shorter than
+/// min_run_distance don't count, only daily_mileage_cap miles count per day
+/// and runs of at least long_run_distance have their points multiplied by
+/// long_run_multiplier. tie_breakers rank teams with the same record, in
+/// order.
 #[derive(
     Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, Insertable,
 )]
@@ -53,6 +75,14 @@ pub struct Ruleset {
     pub id: Uuid,
     pub points_per_mile: i32,
     pub league_id: Uuid,
+    pub pace_bonus_target: Option<i32>,
+    pub pace_bonus_per_mile: i32,
+    pub points_per_100_feet: i32,
+    pub min_run_distance: f64,
+    pub daily_mileage_cap: Option<f64>,
+    pub long_run_distance: Option<f64>,
+    pub long_run_multiplier: f64,
+    pub tie_breakers: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -60,6 +90,14 @@ pub struct NewRuleset {
     pub id: Uuid,
     pub points_per_mile: i32,
     pub league_id: Uuid,
+    pub pace_bonus_target: Option<i32>,
+    pub pace_bonus_per_mile: i32,
+    pub points_per_100_feet: i32,
+    pub min_run_distance: f64,
+    pub daily_mileage_cap: Option<f64>,
+    pub long_run_distance: Option<f64>,
+    pub long_run_multiplier: f64,
+    pub tie_breakers: Vec<String>,
 }
 
 impl From<NewRuleset> for Ruleset {
@@ -68,14 +106,40 @@ impl From<NewRuleset> for Ruleset {
             id: ruleset.id,
             league_id: ruleset.league_id,
             points_per_mile: ruleset.points_per_mile,
+            pace_bonus_target: ruleset.pace_bonus_target,
+            pace_bonus_per_mile: ruleset.pace_bonus_per_mile,
+            points_per_100_feet: ruleset.points_per_100_feet,
+            min_run_distance: ruleset.min_run_distance,
+            daily_mileage_cap: ruleset.daily_mileage_cap,
+            long_run_distance: ruleset.long_run_distance,
+            long_run_multiplier: ruleset.long_run_multiplier,
+            tie_breakers: ruleset.tie_breakers,
         }
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, AsChangeset)]
 #[table_name = "league_rulesets"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct UpdateRuleset {
     pub points_per_mile: i32,
+    pub pace_bonus_target: Option<i32>,
+    pub pace_bonus_per_mile: i32,
+    pub points_per_100_feet: i32,
+    pub min_run_distance: f64,
+    pub daily_mileage_cap: Option<f64>,
+    pub long_run_distance: Option<f64>,
+    pub long_run_multiplier: f64,
+    pub tie_breakers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations, Insertable)]
+#[belongs_to(League)]
+#[table_name = "league_commissioners"]
+pub struct Commissioner {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub league_id: Uuid,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Queryable)]
@@ -85,30 +149,309 @@ pub struct LeagueDetails {
     pub start: NaiveDateTime,
     pub rounds: i32,
     pub current_round: i32,
+    pub timezone: String,
+    pub round_days: i32,
     pub points_per_mile: i32,
+    pub pace_bonus_target: Option<i32>,
+    pub pace_bonus_per_mile: i32,
+    pub points_per_100_feet: i32,
+    pub min_run_distance: f64,
+    pub daily_mileage_cap: Option<f64>,
+    pub long_run_distance: Option<f64>,
+    pub long_run_multiplier: f64,
+    pub tie_breakers: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
-pub struct LeaguesResponse(pub Vec<LeagueDetails>);
+pub type LeaguesResponse = ListResponse<LeagueDetails>;
+
+/// When a round starts and ends, in UTC
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RoundWindow {
+    pub round: i32,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub closed: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct RoundsResponse {
+    pub league_id: Uuid,
+    pub timezone: String,
+    pub round_days: i32,
+    pub rounds: Vec<RoundWindow>,
+}
+
+/// URL of a calendar feed, which calendar apps subscribe to without logging in
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct CalendarLinkResponse {
+    pub url: String,
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[validate(schema(
+    function = "validate_create_round_days",
+    message = "round_days is needed for a custom round_length"
+))]
 pub struct CreateLeagueRequest {
     pub name: String,
     pub start: String,
+
+    #[validate(range(min = 1, max = 52, message = "rounds must be between 1 and 52"))]
     pub rounds: i32,
+
+    #[serde(default = "default_timezone")]
+    #[validate(custom(
+        function = "validate_timezone",
+        message = "timezone must be an IANA time zone like America/New_York"
+    ))]
+    pub timezone: String,
+
+    #[serde(default = "default_round_length")]
+    #[validate(custom(
+        function = "validate_round_length",
+        message = "round_length must be weekly, biweekly or custom"
+    ))]
+    pub round_length: String,
+
+    /// Days in a round, only for a custom round_length
+    #[validate(range(min = 1, max = 28, message = "round_days must be between 1 and 28"))]
+    pub round_days: Option<i32>,
+
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "points_per_mile must be between 0 and 1000"
+    ))]
     pub points_per_mile: i32,
+
+    #[validate(range(
+        min = 180,
+        max = 1800,
+        message = "pace_bonus_target must be between 180 and 1800 seconds per mile"
+    ))]
+    pub pace_bonus_target: Option<i32>,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "pace_bonus_per_mile must be between 0 and 1000"
+    ))]
+    pub pace_bonus_per_mile: i32,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "points_per_100_feet must be between 0 and 1000"
+    ))]
+    pub points_per_100_feet: i32,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0.0,
+        max = 50.0,
+        message = "min_run_distance must be between 0 and 50 miles"
+    ))]
+    pub min_run_distance: f64,
+
+    #[validate(range(
+        min = 1.0,
+        max = 200.0,
+        message = "daily_mileage_cap must be between 1 and 200 miles"
+    ))]
+    pub daily_mileage_cap: Option<f64>,
+
+    #[validate(range(
+        min = 1.0,
+        max = 200.0,
+        message = "long_run_distance must be between 1 and 200 miles"
+    ))]
+    pub long_run_distance: Option<f64>,
+
+    #[serde(default = "default_long_run_multiplier")]
+    #[validate(range(
+        min = 1.0,
+        max = 5.0,
+        message = "long_run_multiplier must be between 1 and 5"
+    ))]
+    pub long_run_multiplier: f64,
+
+    #[serde(default = "default_tie_breakers")]
+    #[validate(custom(
+        function = "validate_tie_breakers",
+        message = "tie_breakers can only be head_to_head, total_points and total_miles, each used once"
+    ))]
+    pub tie_breakers: Vec<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[validate(schema(
+    function = "validate_update_round_days",
+    message = "round_days is needed for a custom round_length"
+))]
 pub struct UpdateLeagueRequest {
     pub name: String,
     pub start: String,
+
+    #[validate(range(min = 1, max = 52, message = "rounds must be between 1 and 52"))]
     pub rounds: i32,
+
+    #[validate(custom(
+        function = "validate_timezone",
+        message = "timezone must be an IANA time zone like America/New_York"
+    ))]
+    pub timezone: String,
+
+    #[validate(custom(
+        function = "validate_round_length",
+        message = "round_length must be weekly, biweekly or custom"
+    ))]
+    pub round_length: String,
+
+    /// Days in a round, only for a custom round_length
+    #[validate(range(min = 1, max = 28, message = "round_days must be between 1 and 28"))]
+    pub round_days: Option<i32>,
+
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "points_per_mile must be between 0 and 1000"
+    ))]
     pub points_per_mile: i32,
+
+    #[validate(range(
+        min = 180,
+        max = 1800,
+        message = "pace_bonus_target must be between 180 and 1800 seconds per mile"
+    ))]
+    pub pace_bonus_target: Option<i32>,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "pace_bonus_per_mile must be between 0 and 1000"
+    ))]
+    pub pace_bonus_per_mile: i32,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "points_per_100_feet must be between 0 and 1000"
+    ))]
+    pub points_per_100_feet: i32,
+
+    #[serde(default)]
+    #[validate(range(
+        min = 0.0,
+        max = 50.0,
+        message = "min_run_distance must be between 0 and 50 miles"
+    ))]
+    pub min_run_distance: f64,
+
+    #[validate(range(
+        min = 1.0,
+        max = 200.0,
+        message = "daily_mileage_cap must be between 1 and 200 miles"
+    ))]
+    pub daily_mileage_cap: Option<f64>,
+
+    #[validate(range(
+        min = 1.0,
+        max = 200.0,
+        message = "long_run_distance must be between 1 and 200 miles"
+    ))]
+    pub long_run_distance: Option<f64>,
+
+    #[serde(default = "default_long_run_multiplier")]
+    #[validate(range(
+        min = 1.0,
+        max = 5.0,
+        message = "long_run_multiplier must be between 1 and 5"
+    ))]
+    pub long_run_multiplier: f64,
+
+    #[serde(default = "default_tie_breakers")]
+    #[validate(custom(
+        function = "validate_tie_breakers",
+        message = "tie_breakers can only be head_to_head, total_points and total_miles, each used once"
+    ))]
+    pub tie_breakers: Vec<String>,
 }
 
-impl From<Vec<LeagueDetails>> for LeaguesResponse {
-    fn from(leagues: Vec<LeagueDetails>) -> Self {
-        LeaguesResponse(leagues.into_par_iter().map(|league| league).collect())
+impl CreateLeagueRequest {
+    /// Days in a round of the league, once validated
+    pub fn round_days(&self) -> i32 {
+        calendar::round_days(&self.round_length, self.round_days).unwrap_or(DEFAULT_ROUND_DAYS)
     }
 }
+
+impl UpdateLeagueRequest {
+    /// Days in a round of the league, once validated
+    pub fn round_days(&self) -> i32 {
+        calendar::round_days(&self.round_length, self.round_days).unwrap_or(DEFAULT_ROUND_DAYS)
+    }
+}
+
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.into()
+}
+
+fn default_round_length() -> String {
+    WEEKLY.into()
+}
+
+fn default_long_run_multiplier() -> f64 {
+    1.0
+}
+
+fn default_tie_breakers() -> Vec<String> {
+    TIE_BREAKERS
+        .iter()
+        .map(|tie_breaker| tie_breaker.to_string())
+        .collect()
+}
+
+/// Reject unknown or repeated tie-breakers
+fn validate_tie_breakers(tie_breakers: &[String]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+    for tie_breaker in tie_breakers {
+        if !TIE_BREAKERS.contains(&tie_breaker.as_str()) || !seen.insert(tie_breaker) {
+            return Err(ValidationError::new("tie_breakers"));
+        }
+    }
+    Ok(())
+}
+
+/// Reject timezones that aren't in the IANA database
+fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    if calendar::parse_timezone(timezone).is_none() {
+        return Err(ValidationError::new("timezone"));
+    }
+    Ok(())
+}
+
+fn validate_round_length(round_length: &str) -> Result<(), ValidationError> {
+    if !ROUND_LENGTHS.contains(&round_length) {
+        return Err(ValidationError::new("round_length"));
+    }
+    Ok(())
+}
+
+/// Reject custom round lengths without a number of days
+fn has_round_days(round_length: &str, round_days: Option<i32>) -> Result<(), ValidationError> {
+    if round_length == CUSTOM && round_days.is_none() {
+        return Err(ValidationError::new("round_days"));
+    }
+    Ok(())
+}
+
+fn validate_create_round_days(league: &CreateLeagueRequest) -> Result<(), ValidationError> {
+    has_round_days(&league.round_length, league.round_days)
+}
+
+fn validate_update_round_days(league: &UpdateLeagueRequest) -> Result<(), ValidationError> {
+    has_round_days(&league.round_length, league.round_days)
+}
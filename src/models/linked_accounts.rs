@@ -0,0 +1,92 @@
+use crate::models::users::User;
+use crate::pagination::ListResponse;
+use crate::schema::linked_accounts;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+use validator::Validate;
+
+/// A user's account at an activity provider, and the tokens to read it
+///
+/// sync_cursor is when the newest activity synced so far started, the next
+/// sync only asks the provider for activities that started after it.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(User)]
+pub struct LinkedAccount {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: NaiveDateTime,
+    pub sync_cursor: Option<NaiveDateTime>,
+    pub synced_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Tokens of an account, as handed out by its provider
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Tokens {
+    pub provider_user_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// A linked account without its tokens
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct LinkedAccountResponse {
+    pub id: Uuid,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub sync_cursor: Option<NaiveDateTime>,
+    pub synced_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+pub type LinkedAccountsResponse = ListResponse<LinkedAccountResponse>;
+
+impl From<LinkedAccount> for LinkedAccountResponse {
+    fn from(account: LinkedAccount) -> Self {
+        LinkedAccountResponse {
+            id: account.id,
+            provider: account.provider,
+            provider_user_id: account.provider_user_id,
+            sync_cursor: account.sync_cursor,
+            synced_at: account.synced_at,
+            created_at: account.created_at,
+        }
+    }
+}
+
+/// Link an account with the authorization code its provider redirected back with
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct LinkAccountRequest {
+    #[validate(length(min = 1, max = 50, message = "provider is required"))]
+    pub provider: String,
+
+    #[validate(length(min = 1, message = "code is required"))]
+    pub code: String,
+}
+
+/// What syncing an account did
+///
+/// synced counts the runs created or updated, skipped the activities that
+/// didn't pass the validation of a run logged by hand.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct SyncResponse {
+    pub synced: i64,
+    pub skipped: i64,
+    pub sync_cursor: Option<NaiveDateTime>,
+}
@@ -0,0 +1,99 @@
+use crate::models::leagues::League;
+use crate::schema::matchups;
+use serde::Serialize;
+use std::cmp::Ordering;
+use uuid::Uuid;
+
+/// Two teams facing each other in a round of a league
+///
+/// away_team_id is None when the home team has a bye. The points are set
+/// once the round is closed.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(League)]
+pub struct Matchup {
+    pub id: Uuid,
+    pub league_id: Uuid,
+    pub round: i32,
+    pub home_team_id: Uuid,
+    pub away_team_id: Option<Uuid>,
+    pub home_points: Option<f64>,
+    pub away_points: Option<f64>,
+}
+
+impl Matchup {
+    /// How a team did in a closed matchup
+    ///
+    /// None when the matchup isn't settled, was a bye or the team isn't in it.
+    pub fn result_for(&self, team_id: Uuid) -> Option<Ordering> {
+        let away_team_id = self.away_team_id?;
+        let (home_points, away_points) = (self.home_points?, self.away_points?);
+        if team_id == self.home_team_id {
+            home_points.partial_cmp(&away_points)
+        } else if team_id == away_team_id {
+            away_points.partial_cmp(&home_points)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct MatchupsResponse(pub Vec<Matchup>);
+
+impl From<Vec<Matchup>> for MatchupsResponse {
+    fn from(matchups: Vec<Matchup>) -> Self {
+        MatchupsResponse(matchups)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn matchup(home_points: Option<f64>, away_points: Option<f64>) -> Matchup {
+        Matchup {
+            id: Uuid::new_v4(),
+            league_id: Uuid::nil(),
+            round: 1,
+            home_team_id: Uuid::new_v4(),
+            away_team_id: Some(Uuid::new_v4()),
+            home_points,
+            away_points,
+        }
+    }
+
+    #[test]
+    fn it_gets_results() {
+        let settled = matchup(Some(30.0), Some(20.0));
+        let away_team_id = settled.away_team_id.unwrap();
+        assert_eq!(
+            settled.result_for(settled.home_team_id),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(settled.result_for(away_team_id), Some(Ordering::Less));
+        assert_eq!(settled.result_for(Uuid::new_v4()), None);
+
+        let tied = matchup(Some(20.0), Some(20.0));
+        assert_eq!(tied.result_for(tied.home_team_id), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn it_doesnt_get_results_for_open_matchups_or_byes() {
+        let open = matchup(None, None);
+        assert_eq!(open.result_for(open.home_team_id), None);
+
+        let mut bye = matchup(Some(20.0), None);
+        bye.away_team_id = None;
+        assert_eq!(bye.result_for(bye.home_team_id), None);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::models::users::User;
+use crate::schema::notifications;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// A message queued for a user, sent_at is None until it's delivered
+///
+/// Users get one notification of a kind per league and round.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(User)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub league_id: Uuid,
+    pub kind: String,
+    pub round: i32,
+    pub message: String,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,312 @@
+use crate::errors::FieldErrors;
+use crate::models::users::User;
+use crate::pagination::ListResponse;
+use crate::schema::{run_laps, runs};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Fastest pace accepted for a run, in seconds per mile (3:00/mi)
+pub const MIN_SECONDS_PER_MILE: f64 = 180.0;
+
+/// Slowest pace accepted for a run, in seconds per mile (30:00/mi)
+pub const MAX_SECONDS_PER_MILE: f64 = 1800.0;
+
+/// A logged run
+///
+/// distance is in miles, duration in seconds and elevation_gain in feet.
+/// heart_rate is the average beats per minute over the run. Runs synced from
+/// an activity provider have its name and the id of the activity.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(User)]
+pub struct Run {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub distance: f64,
+    pub duration: i32,
+    pub started_at: NaiveDateTime,
+    pub elevation_gain: Option<f64>,
+    pub heart_rate: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub provider: Option<String>,
+    pub provider_activity_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NewRun {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub distance: f64,
+    pub duration: i32,
+    pub started_at: NaiveDateTime,
+    pub elevation_gain: Option<f64>,
+    pub heart_rate: Option<i32>,
+}
+
+impl From<NewRun> for Run {
+    fn from(run: NewRun) -> Self {
+        Run {
+            id: run.id,
+            user_id: run.user_id,
+            distance: run.distance,
+            duration: run.duration,
+            started_at: run.started_at,
+            elevation_gain: run.elevation_gain,
+            heart_rate: run.heart_rate,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            provider: None,
+            provider_activity_id: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, AsChangeset)]
+#[table_name = "runs"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UpdateRun {
+    pub distance: f64,
+    pub duration: i32,
+    pub started_at: NaiveDateTime,
+    pub elevation_gain: Option<f64>,
+    pub heart_rate: Option<i32>,
+    pub updated_at: NaiveDateTime,
+}
+
+pub type RunsResponse = ListResponse<Run>;
+
+/// A lap of a run imported from a file
+///
+/// Laps are numbered from 1 in the order they were run, distance is in miles
+/// and duration is the moving time in seconds.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(Run)]
+pub struct RunLap {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub lap_number: i32,
+    pub started_at: NaiveDateTime,
+    pub distance: f64,
+    pub duration: i32,
+}
+
+/// A run with its laps
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RunDetails {
+    #[serde(flatten)]
+    pub run: Run,
+    pub laps: Vec<RunLap>,
+}
+
+pub type RunDetailsResponse = ListResponse<RunDetails>;
+
+/// What importing a league's CSV file did, or would do in a dry run
+///
+/// data holds the runs of the valid rows and errors the problems of the
+/// others, keyed by row and field like `row[3].distance`. Nothing is created
+/// unless every row is valid.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct RunsImportResponse {
+    pub dry_run: bool,
+    pub data: Vec<Run>,
+    pub total: i64,
+    pub errors: FieldErrors,
+}
+
+/// Query parameters of a league's CSV import
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RunsImportParams {
+    /// Check the file and report what would be imported without importing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[validate(schema(
+    function = "validate_pace",
+    message = "pace must be between 3:00 and 30:00 minutes per mile"
+))]
+pub struct CreateRunRequest {
+    #[validate(range(
+        min = 0.01,
+        max = 500.0,
+        message = "distance must be a positive number of miles"
+    ))]
+    pub distance: f64,
+
+    #[validate(range(
+        min = 1,
+        max = 172800,
+        message = "duration must be a positive number of seconds"
+    ))]
+    pub duration: i32,
+
+    #[validate(custom(
+        function = "validate_not_in_future",
+        message = "started_at can't be in the future"
+    ))]
+    pub started_at: NaiveDateTime,
+
+    #[validate(range(
+        min = 0.0,
+        max = 100000.0,
+        message = "elevation_gain must be a positive number of feet"
+    ))]
+    pub elevation_gain: Option<f64>,
+
+    #[validate(range(
+        min = 30,
+        max = 250,
+        message = "heart_rate must be between 30 and 250 beats per minute"
+    ))]
+    pub heart_rate: Option<i32>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[validate(schema(
+    function = "validate_update_pace",
+    message = "pace must be between 3:00 and 30:00 minutes per mile"
+))]
+pub struct UpdateRunRequest {
+    #[validate(range(
+        min = 0.01,
+        max = 500.0,
+        message = "distance must be a positive number of miles"
+    ))]
+    pub distance: f64,
+
+    #[validate(range(
+        min = 1,
+        max = 172800,
+        message = "duration must be a positive number of seconds"
+    ))]
+    pub duration: i32,
+
+    #[validate(custom(
+        function = "validate_not_in_future",
+        message = "started_at can't be in the future"
+    ))]
+    pub started_at: NaiveDateTime,
+
+    #[validate(range(
+        min = 0.0,
+        max = 100000.0,
+        message = "elevation_gain must be a positive number of feet"
+    ))]
+    pub elevation_gain: Option<f64>,
+
+    #[validate(range(
+        min = 30,
+        max = 250,
+        message = "heart_rate must be between 30 and 250 beats per minute"
+    ))]
+    pub heart_rate: Option<i32>,
+}
+
+/// Reject runs that start in the future
+fn validate_not_in_future(started_at: &NaiveDateTime) -> Result<(), ValidationError> {
+    if *started_at > Utc::now().naive_utc() {
+        return Err(ValidationError::new("started_at_in_future"));
+    }
+    Ok(())
+}
+
+/// Reject runs with a pace no runner could (or would) have
+fn is_sane_pace(distance: f64, duration: i32) -> Result<(), ValidationError> {
+    let seconds_per_mile = f64::from(duration) / distance;
+    if !(MIN_SECONDS_PER_MILE..=MAX_SECONDS_PER_MILE).contains(&seconds_per_mile) {
+        return Err(ValidationError::new("pace"));
+    }
+    Ok(())
+}
+
+fn validate_pace(run: &CreateRunRequest) -> Result<(), ValidationError> {
+    is_sane_pace(run.distance, run.duration)
+}
+
+fn validate_update_pace(run: &UpdateRunRequest) -> Result<(), ValidationError> {
+    is_sane_pace(run.distance, run.duration)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    pub fn create_run_request() -> CreateRunRequest {
+        CreateRunRequest {
+            distance: 3.1,
+            duration: 1680,
+            started_at: Utc::now().naive_utc() - Duration::hours(2),
+            elevation_gain: Some(120.0),
+            heart_rate: Some(150),
+        }
+    }
+
+    #[test]
+    fn it_validates_a_run() {
+        assert!(create_run_request().validate().is_ok());
+    }
+
+    #[test]
+    fn it_rejects_a_negative_distance() {
+        let mut run = create_run_request();
+        run.distance = -3.1;
+        let errors = run.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("distance"));
+    }
+
+    #[test]
+    fn it_rejects_a_run_in_the_future() {
+        let mut run = create_run_request();
+        run.started_at = Utc::now().naive_utc() + Duration::days(1);
+        let errors = run.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("started_at"));
+    }
+
+    #[test]
+    fn it_rejects_an_impossible_pace() {
+        let mut run = create_run_request();
+        run.duration = 300;
+        let errors = run.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("__all__"));
+    }
+
+    #[test]
+    fn it_rejects_a_bad_heart_rate() {
+        let mut run = create_run_request();
+        run.heart_rate = Some(400);
+        let errors = run.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("heart_rate"));
+    }
+
+    #[test]
+    fn it_allows_missing_optional_fields() {
+        let mut run = create_run_request();
+        run.elevation_gain = None;
+        run.heart_rate = None;
+        assert!(run.validate().is_ok());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::models::leagues::League;
+use crate::models::teams::Team;
+use crate::schema::{player_scores, team_scores};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A team's total for a single round of a league
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(League)]
+#[belongs_to(Team)]
+pub struct TeamScore {
+    pub id: Uuid,
+    pub league_id: Uuid,
+    pub team_id: Uuid,
+    pub round: i32,
+    pub miles: f64,
+    pub points: f64,
+}
+
+/// A player's contribution to their team for a single round of a league
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+)]
+#[belongs_to(League)]
+#[belongs_to(Team)]
+pub struct PlayerScore {
+    pub id: Uuid,
+    pub league_id: Uuid,
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+    pub round: i32,
+    pub miles: f64,
+    pub points: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RoundScoresResponse {
+    pub league_id: Uuid,
+    pub round: i32,
+    pub teams: Vec<TeamScore>,
+    pub players: Vec<PlayerScore>,
+}
@@ -0,0 +1,45 @@
+use crate::models::users::AuthUser;
+use crate::pagination::ListResponse;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// A logged in user, kept in Redis under the session's token
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub current: bool,
+}
+
+pub type SessionsResponse = ListResponse<SessionResponse>;
+
+impl SessionResponse {
+    /// `current` is true for the session the request was made with
+    pub fn new(session: Session, current_id: Option<Uuid>) -> Self {
+        SessionResponse {
+            current: current_id == Some(session.id),
+            id: session.id,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+        }
+    }
+}
+
+impl From<Session> for AuthUser {
+    fn from(session: Session) -> Self {
+        AuthUser {
+            id: session.user_id,
+            email: session.email,
+        }
+    }
+}
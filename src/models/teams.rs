@@ -1,6 +1,5 @@
-use crate::models::users::User;
+use crate::pagination::ListResponse;
 use crate::schema::{team_owners, team_players, teams};
-use rayon::prelude::*;
 use serde::Serialize;
 use uuid::Uuid;
 use validator::Validate;
@@ -15,7 +14,18 @@ pub struct Team {
     pub league_id: Uuid,
 }
 
-#[derive(Debug, Queryable, Identifiable, Associations, AsChangeset, Insertable)]
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    AsChangeset,
+    Insertable,
+)]
 #[belongs_to(Team)]
 #[table_name = "team_owners"]
 pub struct Owner {
@@ -24,7 +34,18 @@ pub struct Owner {
     pub team_id: Uuid,
 }
 
-#[derive(Debug, Queryable, Identifiable, Associations, AsChangeset, Insertable)]
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Queryable,
+    Identifiable,
+    Associations,
+    AsChangeset,
+    Insertable,
+)]
 #[belongs_to(Team)]
 #[table_name = "team_players"]
 pub struct Player {
@@ -33,7 +54,14 @@ pub struct Player {
     pub team_id: Uuid,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize, AsChangeset)]
+#[table_name = "teams"]
+pub struct UpdateTeam {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct TeamDetails {
     pub id: Uuid,
     pub league_id: Uuid,
@@ -45,10 +73,83 @@ pub struct TeamDetails {
     pub owners: Option<Vec<Owner>>,
 }
 
-pub struct TeamsResponse(pub Vec<Team>);
+pub type TeamsResponse = ListResponse<Team>;
+
+/// A team's place in its league
+///
+/// streak is the latest run of results, like "W3", and games_back how far the
+/// team is behind the leader.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Standing {
+    pub rank: i32,
+    pub team_id: Uuid,
+    pub name: String,
+    pub wins: i32,
+    pub losses: i32,
+    pub ties: i32,
+    pub win_percentage: f64,
+    pub points_for: f64,
+    pub points_against: f64,
+    pub miles: f64,
+    pub streak: String,
+    pub games_back: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct StandingsResponse(pub Vec<Standing>);
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct CreateTeamRequest {
+    #[validate(length(
+        min = 3,
+        max = 100,
+        message = "name is required and must be between 3 and 100 characters"
+    ))]
+    pub name: String,
+
+    /// Defaults to the user creating the team
+    #[serde(default)]
+    #[validate(length(max = 10, message = "owner_ids can't have more than 10 owners"))]
+    pub owner_ids: Vec<Uuid>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct UpdateTeamRequest {
+    #[validate(length(
+        min = 3,
+        max = 100,
+        message = "name is required and must be between 3 and 100 characters"
+    ))]
+    pub name: String,
+}
+
+/// Add a user to a team as a player or an owner
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct TeamMemberRequest {
+    pub user_id: Uuid,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn create_team_request() -> CreateTeamRequest {
+        CreateTeamRequest {
+            name: "Test Team".into(),
+            owner_ids: vec![],
+        }
+    }
+
+    #[test]
+    fn it_validates_a_team() {
+        assert!(create_team_request().validate().is_ok());
+    }
 
-impl From<Vec<Team>> for TeamsResponse {
-    fn from(team: Vec<Team>) -> Self {
-        TeamsResponse(team.into_par_iter().map(|team| team).collect())
+    #[test]
+    fn it_rejects_a_short_name() {
+        let mut team = create_team_request();
+        team.name = "A".into();
+        let errors = team.validate().unwrap_err().field_errors();
+        assert!(errors.contains_key("name"));
     }
 }
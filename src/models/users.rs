@@ -1,4 +1,5 @@
 use crate::auth::hash;
+use crate::pagination::ListResponse;
 use crate::schema::users;
 use chrono::{NaiveDateTime, Utc};
 use serde::Serialize;
@@ -62,8 +63,7 @@ pub struct UserResponse {
     pub email: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
-pub struct UsersResponse(pub Vec<UserResponse>);
+pub type UsersResponse = ListResponse<UserResponse>;
 
 #[derive(Clone, Debug, Deserialize, Serialize, Validate)]
 pub struct CreateUserRequest {
@@ -106,3 +106,21 @@ pub struct UpdateUserRequest {
     #[validate(email(message = "email must be a valid email"))]
     pub email: String,
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct LoginRequest {
+    #[validate(email(message = "email must be a valid email"))]
+    pub email: String,
+
+    #[validate(length(
+        min = 6,
+        message = "password is required and must be at least 6 characters"
+    ))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct LoginResponse {
+    pub token: String,
+    pub user: UserResponse,
+}
@@ -0,0 +1,1294 @@
+//! OpenAPI 3 specification of the API, served at `/api/v1/openapi.json`.
+//!
+//! Schemas are derived from the request and response types themselves. Each
+//! type has an `Example` that's serialized to find out its JSON types, then
+//! deserialized again with every field left out or set to null to find out
+//! which fields are required and which are nullable. Adding, renaming or
+//! retyping a field breaks the example, so the spec can't silently fall
+//! behind the models.
+//!
+//! Operations are listed in `operations()` and mirror `routes.rs`, the tests
+//! fail when a route is added or removed without the spec following.
+
+use crate::calendar::CUSTOM;
+use crate::draft::{SCHEDULED, SNAKE};
+use crate::errors::{ApiError, ErrorResponse, FieldErrors};
+use crate::events;
+use crate::handlers::health::HealthResponse;
+use crate::ical;
+use crate::import;
+use crate::models::drafts::{
+    CreateDraftRequest, DraftDetails, DraftPick, DraftQueueRequest, DraftQueueResponse, PickRequest,
+};
+use crate::models::leagues::LeaguesResponse;
+use crate::models::leagues::{
+    CalendarLinkResponse, CreateLeagueRequest, LeagueDetails, RoundWindow, RoundsResponse,
+    UpdateLeagueRequest,
+};
+use crate::models::linked_accounts::{
+    LinkAccountRequest, LinkedAccountResponse, LinkedAccountsResponse, SyncResponse,
+};
+use crate::models::matchups::{Matchup, MatchupsResponse};
+use crate::models::runs::{CreateRunRequest, Run, UpdateRunRequest};
+use crate::models::runs::{
+    RunDetails, RunDetailsResponse, RunLap, RunsImportResponse, RunsResponse,
+};
+use crate::models::scores::{PlayerScore, RoundScoresResponse, TeamScore};
+use crate::models::sessions::{SessionResponse, SessionsResponse};
+use crate::models::teams::{
+    CreateTeamRequest, Owner, Player, Standing, StandingsResponse, Team, TeamDetails,
+    TeamMemberRequest, TeamsResponse, UpdateTeamRequest,
+};
+use crate::models::users::{
+    CreateUserRequest, LoginRequest, LoginResponse, UpdateUserRequest, UserResponse, UsersResponse,
+};
+use crate::pagination::{ListParams, ListResponse, ASC};
+use crate::standings::TIE_BREAKERS;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+/// Marks an array's items in a field path
+const ITEMS: &str = "[]";
+
+lazy_static! {
+    pub static ref SPEC: Value = spec();
+}
+
+/// A type the API sends or receives
+pub trait Example: Serialize + DeserializeOwned {
+    /// A value with every optional field set and every list non-empty
+    fn example() -> Self;
+
+    /// JSON schema of the type, inferred from its example
+    fn schema() -> Value {
+        infer::<Self>()
+    }
+}
+
+/// A named schema with an example
+pub struct Schema {
+    pub name: &'static str,
+    pub schema: Value,
+    pub example: Value,
+}
+
+impl Schema {
+    pub fn of<T: Example>(name: &'static str) -> Self {
+        Schema {
+            name,
+            schema: T::schema(),
+            example: serde_json::to_value(T::example()).unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// An endpoint of the API
+pub struct Operation {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub request: Option<Schema>,
+    pub uploads: Vec<&'static str>,
+    pub query: Vec<Value>,
+    pub response: Option<Schema>,
+    pub download: Option<&'static str>,
+    pub list: bool,
+    pub public: bool,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &'static str, summary: &'static str) -> Self {
+        Operation {
+            method,
+            path,
+            summary,
+            request: None,
+            uploads: vec![],
+            query: vec![],
+            response: None,
+            download: None,
+            list: false,
+            public: false,
+        }
+    }
+
+    fn request<T: Example>(mut self, name: &'static str) -> Self {
+        self.request = Some(Schema::of::<T>(name));
+        self
+    }
+
+    /// Takes a file of any of the media types as its body
+    fn upload(mut self, media_types: &[&'static str]) -> Self {
+        self.uploads.extend_from_slice(media_types);
+        self
+    }
+
+    /// Takes an optional query parameter
+    fn query(mut self, name: &'static str, schema: Value) -> Self {
+        self.query
+            .push(json!({"name": name, "in": "query", "required": false, "schema": schema}));
+        self
+    }
+
+    fn response<T: Example>(mut self, name: &'static str) -> Self {
+        self.response = Some(Schema::of::<T>(name));
+        self
+    }
+
+    /// Responds with a body of the media type instead of JSON
+    fn download(mut self, media_type: &'static str) -> Self {
+        self.download = Some(media_type);
+        self
+    }
+
+    /// Takes the pagination, sorting and filtering query parameters
+    fn list(mut self) -> Self {
+        self.list = true;
+        self
+    }
+
+    /// Doesn't need a token
+    fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+
+    /// The first segment after /api/v1 groups operations together
+    fn tag(&self) -> &'static str {
+        self.path
+            .trim_start_matches("/api/v1")
+            .split('/')
+            .nth(1)
+            .unwrap_or_default()
+            .trim_end_matches(".json")
+    }
+
+    /// Names in {braces} in the path, rounds are numbers, tokens are strings
+    /// and the rest are ids
+    fn path_parameters(&self) -> Vec<Value> {
+        self.path
+            .split('/')
+            .filter(|segment| segment.starts_with('{') && segment.ends_with('}'))
+            .map(|segment| {
+                let name = segment.trim_start_matches('{').trim_end_matches('}');
+                let schema = if name == "round" {
+                    json!({"type": "integer"})
+                } else if name == "token" {
+                    json!({"type": "string"})
+                } else {
+                    json!({"type": "string", "format": "uuid"})
+                };
+                json!({"name": name, "in": "path", "required": true, "schema": schema})
+            })
+            .collect()
+    }
+}
+
+/// Every endpoint, in the order routes.rs registers them
+pub fn operations() -> Vec<Operation> {
+    vec![
+        Operation::new("get", "/health", "Check that the service is up")
+            .response::<HealthResponse>("HealthResponse")
+            .public(),
+        Operation::new("get", "/api/v1/openapi.json", "Get this specification").public(),
+        // Auth
+        Operation::new("post", "/api/v1/auth/login", "Log in and get a token")
+            .request::<LoginRequest>("LoginRequest")
+            .response::<LoginResponse>("LoginResponse")
+            .public(),
+        Operation::new("get", "/api/v1/auth/logout", "Log out").public(),
+        Operation::new("post", "/api/v1/auth/register", "Sign up")
+            .request::<CreateUserRequest>("CreateUserRequest")
+            .response::<UserResponse>("UserResponse")
+            .public(),
+        Operation::new("delete", "/api/v1/auth/sessions/{id}", "Revoke a session"),
+        Operation::new("get", "/api/v1/auth/sessions", "List your sessions")
+            .response::<SessionsResponse>("SessionsResponse"),
+        Operation::new("delete", "/api/v1/auth/sessions", "Log out everywhere"),
+        // Linked accounts
+        Operation::new(
+            "post",
+            "/api/v1/users/{id}/linked-accounts/{account_id}/sync",
+            "Sync a linked account's runs",
+        )
+        .response::<SyncResponse>("SyncResponse"),
+        Operation::new(
+            "delete",
+            "/api/v1/users/{id}/linked-accounts/{account_id}",
+            "Unlink an account",
+        ),
+        Operation::new(
+            "get",
+            "/api/v1/users/{id}/linked-accounts",
+            "List a user's linked accounts",
+        )
+        .response::<LinkedAccountsResponse>("LinkedAccountsResponse"),
+        Operation::new(
+            "post",
+            "/api/v1/users/{id}/linked-accounts",
+            "Link an account at an activity provider",
+        )
+        .request::<LinkAccountRequest>("LinkAccountRequest")
+        .response::<LinkedAccountResponse>("LinkedAccountResponse"),
+        // Runs
+        Operation::new(
+            "post",
+            "/api/v1/users/{id}/runs/import",
+            "Import runs from a file",
+        )
+        .upload(&import::MEDIA_TYPES)
+        .response::<RunDetailsResponse>("RunDetailsResponse"),
+        Operation::new("get", "/api/v1/users/{id}/runs/{run_id}", "Get a run")
+            .response::<Run>("Run"),
+        Operation::new("put", "/api/v1/users/{id}/runs/{run_id}", "Change a run")
+            .request::<UpdateRunRequest>("UpdateRunRequest")
+            .response::<Run>("Run"),
+        Operation::new("delete", "/api/v1/users/{id}/runs/{run_id}", "Delete a run"),
+        Operation::new("get", "/api/v1/users/{id}/runs", "List a user's runs")
+            .response::<RunsResponse>("RunsResponse")
+            .list(),
+        Operation::new("post", "/api/v1/users/{id}/runs", "Log a run")
+            .request::<CreateRunRequest>("CreateRunRequest")
+            .response::<Run>("Run"),
+        // Users
+        Operation::new("get", "/api/v1/users/{id}", "Get a user")
+            .response::<UserResponse>("UserResponse"),
+        Operation::new("put", "/api/v1/users/{id}", "Change a user")
+            .request::<UpdateUserRequest>("UpdateUserRequest")
+            .response::<UserResponse>("UserResponse"),
+        Operation::new("delete", "/api/v1/users/{id}", "Delete a user"),
+        Operation::new("get", "/api/v1/users", "List users")
+            .response::<UsersResponse>("UsersResponse")
+            .list(),
+        Operation::new("post", "/api/v1/users", "Create a user")
+            .request::<CreateUserRequest>("CreateUserRequest")
+            .response::<UserResponse>("UserResponse"),
+        // Drafts
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/draft/queues/{team_id}",
+            "Get a team's auto-pick queue",
+        )
+        .response::<DraftQueueResponse>("DraftQueueResponse"),
+        Operation::new(
+            "put",
+            "/api/v1/leagues/{id}/draft/queues/{team_id}",
+            "Replace a team's auto-pick queue",
+        )
+        .request::<DraftQueueRequest>("DraftQueueRequest")
+        .response::<DraftQueueResponse>("DraftQueueResponse"),
+        Operation::new("post", "/api/v1/leagues/{id}/draft/picks", "Make a pick")
+            .request::<PickRequest>("PickRequest")
+            .response::<DraftDetails>("DraftDetails"),
+        Operation::new("post", "/api/v1/leagues/{id}/draft/start", "Start a draft")
+            .response::<DraftDetails>("DraftDetails"),
+        Operation::new("get", "/api/v1/leagues/{id}/draft", "Get a league's draft")
+            .response::<DraftDetails>("DraftDetails"),
+        Operation::new("post", "/api/v1/leagues/{id}/draft", "Schedule a draft")
+            .request::<CreateDraftRequest>("CreateDraftRequest")
+            .response::<DraftDetails>("DraftDetails"),
+        // Matchups
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/rounds/{round}/matchups",
+            "Get a round's matchups",
+        )
+        .response::<MatchupsResponse>("MatchupsResponse"),
+        Operation::new(
+            "post",
+            "/api/v1/leagues/{id}/rounds/{round}/close",
+            "Close a round",
+        )
+        .response::<LeagueDetails>("LeagueDetails"),
+        // Runs in a league
+        Operation::new(
+            "post",
+            "/api/v1/leagues/{id}/runs/import",
+            "Import a league's runs from a CSV file",
+        )
+        .upload(&[import::csv::MEDIA_TYPE])
+        .query("dry_run", json!({"type": "boolean"}))
+        .response::<RunsImportResponse>("RunsImportResponse"),
+        // Teams in a league
+        Operation::new("get", "/api/v1/leagues/{id}/teams", "List a league's teams")
+            .response::<TeamsResponse>("TeamsResponse")
+            .list(),
+        Operation::new("post", "/api/v1/leagues/{id}/teams", "Create a team")
+            .request::<CreateTeamRequest>("CreateTeamRequest")
+            .response::<TeamDetails>("TeamDetails"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/schedule",
+            "Get a league's schedule",
+        )
+        .response::<MatchupsResponse>("MatchupsResponse"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/standings",
+            "Get a league's standings",
+        )
+        .response::<StandingsResponse>("StandingsResponse"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/calendar.ics",
+            "Get a league's rounds and matchups as an iCalendar feed",
+        )
+        .download(ical::MEDIA_TYPE),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/calendar-link",
+            "Get the link calendar apps subscribe to a league's calendar with",
+        )
+        .response::<CalendarLinkResponse>("CalendarLinkResponse"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/events",
+            "Stream a league's run-logged, score-changed, matchup-settled and draft-pick events",
+        )
+        .download(events::MEDIA_TYPE),
+        Operation::new(
+            "post",
+            "/api/v1/leagues/{id}/schedule",
+            "Generate a schedule",
+        )
+        .response::<MatchupsResponse>("MatchupsResponse"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/rounds",
+            "Get when a league's rounds start and end",
+        )
+        .response::<RoundsResponse>("RoundsResponse"),
+        Operation::new(
+            "get",
+            "/api/v1/leagues/{id}/rounds/{round}/scores",
+            "Get a round's scores",
+        )
+        .response::<RoundScoresResponse>("RoundScoresResponse"),
+        Operation::new(
+            "post",
+            "/api/v1/leagues/{id}/rounds/{round}/scores",
+            "Score a round",
+        )
+        .response::<RoundScoresResponse>("RoundScoresResponse"),
+        // Leagues
+        Operation::new("get", "/api/v1/leagues/{id}", "Get a league")
+            .response::<LeagueDetails>("LeagueDetails"),
+        Operation::new("put", "/api/v1/leagues/{id}", "Change a league")
+            .request::<UpdateLeagueRequest>("UpdateLeagueRequest")
+            .response::<LeagueDetails>("LeagueDetails"),
+        Operation::new("delete", "/api/v1/leagues/{id}", "Delete a league"),
+        Operation::new("get", "/api/v1/leagues", "List leagues")
+            .response::<LeaguesResponse>("LeaguesResponse")
+            .list(),
+        Operation::new("post", "/api/v1/leagues", "Create a league")
+            .request::<CreateLeagueRequest>("CreateLeagueRequest")
+            .response::<LeagueDetails>("LeagueDetails"),
+        // Teams
+        Operation::new(
+            "delete",
+            "/api/v1/teams/{id}/players/{user_id}",
+            "Take a player off a team",
+        )
+        .response::<TeamDetails>("TeamDetails"),
+        Operation::new(
+            "post",
+            "/api/v1/teams/{id}/players",
+            "Add a player to a team",
+        )
+        .request::<TeamMemberRequest>("TeamMemberRequest")
+        .response::<TeamDetails>("TeamDetails"),
+        Operation::new(
+            "delete",
+            "/api/v1/teams/{id}/owners/{user_id}",
+            "Remove an owner from a team",
+        )
+        .response::<TeamDetails>("TeamDetails"),
+        Operation::new(
+            "post",
+            "/api/v1/teams/{id}/owners",
+            "Add an owner to a team",
+        )
+        .request::<TeamMemberRequest>("TeamMemberRequest")
+        .response::<TeamDetails>("TeamDetails"),
+        Operation::new(
+            "get",
+            "/api/v1/teams/{id}/calendar.ics",
+            "Get a team's matchups and rounds as an iCalendar feed",
+        )
+        .download(ical::MEDIA_TYPE),
+        Operation::new(
+            "get",
+            "/api/v1/teams/{id}/calendar-link",
+            "Get the link calendar apps subscribe to a team's calendar with",
+        )
+        .response::<CalendarLinkResponse>("CalendarLinkResponse"),
+        Operation::new("get", "/api/v1/teams/{id}", "Get a team")
+            .response::<TeamDetails>("TeamDetails"),
+        Operation::new("put", "/api/v1/teams/{id}", "Rename a team")
+            .request::<UpdateTeamRequest>("UpdateTeamRequest")
+            .response::<TeamDetails>("TeamDetails"),
+        Operation::new("delete", "/api/v1/teams/{id}", "Delete a team"),
+        Operation::new("get", "/api/v1/teams", "List teams")
+            .response::<TeamsResponse>("TeamsResponse")
+            .list(),
+        // Calendars
+        Operation::new(
+            "get",
+            "/api/v1/calendars/{token}",
+            "Get the iCalendar feed of a calendar link",
+        )
+        .download(ical::MEDIA_TYPE)
+        .public(),
+    ]
+}
+
+/// Build the OpenAPI document
+pub fn spec() -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    let error = Schema::of::<ErrorResponse>("ErrorResponse");
+    schemas.insert(error.name.into(), with_example(error.schema, error.example));
+
+    let list_parameters: Vec<Value> = ListParams::schema()["properties"]
+        .as_object()
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, schema)| {
+                    json!({"name": name, "in": "query", "required": false, "schema": schema})
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for operation in operations() {
+        let tag = operation.tag();
+        let mut parameters = operation.path_parameters();
+        if operation.list {
+            parameters.extend(list_parameters.iter().cloned());
+        }
+        parameters.extend(operation.query);
+
+        let success = match (operation.response, operation.download) {
+            (_, Some(media_type)) => json!({
+                "description": "OK",
+                "content": {media_type: {"schema": {"type": "string"}}}
+            }),
+            (Some(response), None) => {
+                let description = json!({
+                    "description": "OK",
+                    "content": {"application/json": {"schema": reference(response.name)}}
+                });
+                schemas.insert(
+                    response.name.into(),
+                    with_example(response.schema, response.example),
+                );
+                description
+            }
+            (None, None) => json!({"description": "OK"}),
+        };
+        let mut description = json!({
+            "summary": operation.summary,
+            "tags": [tag],
+            "responses": {
+                "200": success,
+                "default": {
+                    "description": "Error",
+                    "content": {"application/json": {"schema": reference("ErrorResponse")}}
+                }
+            }
+        });
+        if !parameters.is_empty() {
+            description["parameters"] = json!(parameters);
+        }
+        if let Some(request) = operation.request {
+            description["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": reference(request.name)}}
+            });
+            schemas.insert(
+                request.name.into(),
+                with_example(request.schema, request.example),
+            );
+        }
+        if !operation.uploads.is_empty() {
+            let file = json!({"schema": {"type": "string", "format": "binary"}});
+            let content: Map<String, Value> = operation
+                .uploads
+                .iter()
+                .map(|media_type| (media_type.to_string(), file.clone()))
+                .collect();
+            description["requestBody"] = json!({"required": true, "content": content});
+        }
+        if !operation.public {
+            description["security"] = json!([{"bearerAuth": []}]);
+        }
+
+        let path = paths.entry(operation.path).or_insert_with(|| json!({}));
+        path[operation.method] = description;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer", "bearerFormat": "JWT"}
+            }
+        }
+    })
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn with_example(mut schema: Value, example: Value) -> Value {
+    schema["example"] = example;
+    schema
+}
+
+/// Infer the schema of a type from its example
+pub fn infer<T: Example>() -> Value {
+    let example = serde_json::to_value(T::example()).unwrap_or(Value::Null);
+    describe::<T>(&example, &example, &mut vec![])
+}
+
+fn describe<T: DeserializeOwned>(root: &Value, value: &Value, path: &mut Vec<String>) -> Value {
+    match value {
+        Value::Null => json!({"nullable": true}),
+        Value::Bool(_) => json!({"type": "boolean"}),
+        Value::Number(number) if number.is_f64() => json!({"type": "number", "format": "double"}),
+        Value::Number(_) => json!({"type": "integer"}),
+        Value::String(string) => describe_string(string),
+        Value::Array(items) => {
+            path.push(ITEMS.into());
+            let items = items
+                .first()
+                .map_or_else(|| json!({}), |item| describe::<T>(root, item, path));
+            path.pop();
+            json!({"type": "array", "items": items})
+        }
+        Value::Object(fields) => {
+            let mut properties = Map::new();
+            let mut required = vec![];
+            for (name, field) in fields {
+                path.push(name.to_string());
+                let mut property = describe::<T>(root, field, path);
+                if !accepts::<T>(root, path, None) {
+                    required.push(name.to_string());
+                }
+                if accepts::<T>(root, path, Some(&Value::Null)) {
+                    property["nullable"] = json!(true);
+                }
+                path.pop();
+                properties.insert(name.to_string(), property);
+            }
+            let mut schema = json!({"type": "object", "properties": properties});
+            if !required.is_empty() {
+                schema["required"] = json!(required);
+            }
+            schema
+        }
+    }
+}
+
+fn describe_string(string: &str) -> Value {
+    if Uuid::parse_str(string).is_ok() {
+        json!({"type": "string", "format": "uuid"})
+    } else if NaiveDateTime::parse_from_str(string, "%Y-%m-%dT%H:%M:%S%.f").is_ok() {
+        json!({"type": "string", "format": "date-time"})
+    } else if NaiveDate::parse_from_str(string, "%Y-%m-%d").is_ok() {
+        json!({"type": "string", "format": "date"})
+    } else {
+        json!({"type": "string"})
+    }
+}
+
+/// Whether T still deserializes with the field at path left out (None) or
+/// replaced
+fn accepts<T: DeserializeOwned>(
+    root: &Value,
+    path: &[String],
+    replacement: Option<&Value>,
+) -> bool {
+    let mut value = root.clone();
+    change(&mut value, path, replacement);
+    serde_json::from_value::<T>(value).is_ok()
+}
+
+fn change(value: &mut Value, path: &[String], replacement: Option<&Value>) {
+    match (path, value) {
+        ([field], Value::Object(fields)) => {
+            match replacement {
+                Some(replacement) => fields.insert(field.to_string(), replacement.clone()),
+                None => fields.remove(field),
+            };
+        }
+        ([step, rest @ ..], Value::Array(items)) if step == ITEMS => {
+            for item in items {
+                change(item, rest, replacement);
+            }
+        }
+        ([field, rest @ ..], Value::Object(fields)) => {
+            if let Some(value) = fields.get_mut(field) {
+                change(value, rest, replacement);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn id() -> Uuid {
+    Uuid::parse_str("6a2f41a3-c54c-4fce-832d-0324e1c32e22").expect("Example id")
+}
+
+fn at(datetime: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M:%S").expect("Example date")
+}
+
+impl<T: Example> Example for ListResponse<T> {
+    fn example() -> Self {
+        ListResponse::new(vec![T::example()], 2, 0)
+    }
+}
+
+impl Example for ListParams {
+    fn example() -> Self {
+        ListParams {
+            limit: Some(25),
+            offset: Some(0),
+            cursor: Some("o25".into()),
+            sort: Some("name".into()),
+            direction: Some(ASC.into()),
+            name: Some("run".into()),
+            email: Some("satoshi".into()),
+        }
+    }
+}
+
+impl Example for ErrorResponse {
+    fn example() -> Self {
+        let error = ApiError::invalid_field("name", "name is required");
+        ErrorResponse::new(&error, Some(id().to_string()))
+    }
+
+    /// fields is a map of field paths to messages
+    fn schema() -> Value {
+        let mut schema = infer::<Self>();
+        schema["properties"]["fields"] = json!({
+            "type": "object",
+            "additionalProperties": {"type": "array", "items": {"type": "string"}}
+        });
+        schema
+    }
+}
+
+impl Example for HealthResponse {
+    fn example() -> Self {
+        HealthResponse {
+            status: "ok".into(),
+            version: env!("CARGO_PKG_VERSION").into(),
+        }
+    }
+}
+
+impl Example for LoginRequest {
+    fn example() -> Self {
+        LoginRequest {
+            email: "satoshi@nakamotoinstitute.org".into(),
+            password: "123456".into(),
+        }
+    }
+}
+
+impl Example for LoginResponse {
+    fn example() -> Self {
+        LoginResponse {
+            token: "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9...".into(),
+            user: UserResponse::example(),
+        }
+    }
+}
+
+impl Example for SessionResponse {
+    fn example() -> Self {
+        SessionResponse {
+            id: id(),
+            user_agent: Some("Mozilla/5.0 (X11; Linux x86_64)".into()),
+            created_at: at("2020-05-09T07:30:00"),
+            current: true,
+        }
+    }
+}
+
+impl Example for LinkedAccountResponse {
+    fn example() -> Self {
+        LinkedAccountResponse {
+            id: id(),
+            provider: "strava".into(),
+            provider_user_id: "134815".into(),
+            sync_cursor: Some(at("2020-05-09T07:30:00")),
+            synced_at: Some(at("2020-05-09T09:00:00")),
+            created_at: at("2020-05-01T18:00:00"),
+        }
+    }
+}
+
+impl Example for LinkAccountRequest {
+    fn example() -> Self {
+        LinkAccountRequest {
+            provider: "strava".into(),
+            code: "4f2a8c1d9e7b3a6f".into(),
+        }
+    }
+}
+
+impl Example for SyncResponse {
+    fn example() -> Self {
+        SyncResponse {
+            synced: 3,
+            skipped: 1,
+            sync_cursor: Some(at("2020-05-09T07:30:00")),
+        }
+    }
+}
+
+impl Example for UserResponse {
+    fn example() -> Self {
+        UserResponse {
+            id: id(),
+            first_name: "Satoshi".into(),
+            last_name: "Nakamoto".into(),
+            email: "satoshi@nakamotoinstitute.org".into(),
+        }
+    }
+}
+
+impl Example for CreateUserRequest {
+    fn example() -> Self {
+        CreateUserRequest {
+            first_name: "Satoshi".into(),
+            last_name: "Nakamoto".into(),
+            email: "satoshi@nakamotoinstitute.org".into(),
+            password: "123456".into(),
+        }
+    }
+}
+
+impl Example for UpdateUserRequest {
+    fn example() -> Self {
+        UpdateUserRequest {
+            first_name: "Satoshi".into(),
+            last_name: "Nakamoto".into(),
+            email: "satoshi@nakamotoinstitute.org".into(),
+        }
+    }
+}
+
+impl Example for Run {
+    fn example() -> Self {
+        Run {
+            id: id(),
+            user_id: id(),
+            distance: 3.1,
+            duration: 1680,
+            started_at: at("2020-05-09T07:30:00"),
+            elevation_gain: Some(120.0),
+            heart_rate: Some(152),
+            created_at: at("2020-05-09T08:00:00"),
+            updated_at: at("2020-05-09T08:00:00"),
+            provider: Some("strava".into()),
+            provider_activity_id: Some("3456789012".into()),
+        }
+    }
+}
+
+impl Example for RunDetails {
+    fn example() -> Self {
+        let run = Run::example();
+        let laps = (1..=3)
+            .map(|lap_number| RunLap {
+                id: id(),
+                run_id: run.id,
+                lap_number,
+                started_at: run.started_at + Duration::seconds(560 * (lap_number as i64 - 1)),
+                distance: 1.0,
+                duration: 560,
+            })
+            .collect();
+        RunDetails { run, laps }
+    }
+}
+
+impl Example for RunsImportResponse {
+    fn example() -> Self {
+        let mut errors = FieldErrors::new();
+        errors.insert(
+            "row[3].distance".into(),
+            vec!["line 5: distance must be a number of miles".into()],
+        );
+        RunsImportResponse {
+            dry_run: true,
+            data: vec![Run::example()],
+            total: 1,
+            errors,
+        }
+    }
+
+    /// errors is a map of row and field paths to messages
+    fn schema() -> Value {
+        let mut schema = infer::<Self>();
+        schema["properties"]["errors"] = json!({
+            "type": "object",
+            "additionalProperties": {"type": "array", "items": {"type": "string"}}
+        });
+        schema
+    }
+}
+
+impl Example for CreateRunRequest {
+    fn example() -> Self {
+        CreateRunRequest {
+            distance: 3.1,
+            duration: 1680,
+            started_at: at("2020-05-09T07:30:00"),
+            elevation_gain: Some(120.0),
+            heart_rate: Some(152),
+        }
+    }
+}
+
+impl Example for UpdateRunRequest {
+    fn example() -> Self {
+        UpdateRunRequest {
+            distance: 3.1,
+            duration: 1680,
+            started_at: at("2020-05-09T07:30:00"),
+            elevation_gain: Some(120.0),
+            heart_rate: Some(152),
+        }
+    }
+}
+
+impl Example for LeagueDetails {
+    fn example() -> Self {
+        LeagueDetails {
+            id: id(),
+            name: "Summer League".into(),
+            start: at("2020-06-01T00:00:00"),
+            rounds: 10,
+            current_round: 1,
+            timezone: "America/Chicago".into(),
+            round_days: 7,
+            points_per_mile: 10,
+            pace_bonus_target: Some(480),
+            pace_bonus_per_mile: 2,
+            points_per_100_feet: 1,
+            min_run_distance: 1.0,
+            daily_mileage_cap: Some(20.0),
+            long_run_distance: Some(13.1),
+            long_run_multiplier: 1.5,
+            tie_breakers: TIE_BREAKERS
+                .iter()
+                .map(|tie_breaker| tie_breaker.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Example for CreateLeagueRequest {
+    fn example() -> Self {
+        CreateLeagueRequest {
+            name: "Summer League".into(),
+            start: "2020-06-01".into(),
+            rounds: 10,
+            timezone: "America/Chicago".into(),
+            round_length: CUSTOM.into(),
+            round_days: Some(7),
+            points_per_mile: 10,
+            pace_bonus_target: Some(480),
+            pace_bonus_per_mile: 2,
+            points_per_100_feet: 1,
+            min_run_distance: 1.0,
+            daily_mileage_cap: Some(20.0),
+            long_run_distance: Some(13.1),
+            long_run_multiplier: 1.5,
+            tie_breakers: TIE_BREAKERS
+                .iter()
+                .map(|tie_breaker| tie_breaker.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Example for UpdateLeagueRequest {
+    fn example() -> Self {
+        UpdateLeagueRequest {
+            name: "Summer League".into(),
+            start: "2020-06-01".into(),
+            rounds: 10,
+            timezone: "America/Chicago".into(),
+            round_length: CUSTOM.into(),
+            round_days: Some(7),
+            points_per_mile: 10,
+            pace_bonus_target: Some(480),
+            pace_bonus_per_mile: 2,
+            points_per_100_feet: 1,
+            min_run_distance: 1.0,
+            daily_mileage_cap: Some(20.0),
+            long_run_distance: Some(13.1),
+            long_run_multiplier: 1.5,
+            tie_breakers: TIE_BREAKERS
+                .iter()
+                .map(|tie_breaker| tie_breaker.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Example for CalendarLinkResponse {
+    fn example() -> Self {
+        CalendarLinkResponse {
+            url: format!("{}/eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9", ical::FEED_PATH),
+        }
+    }
+}
+
+impl Example for RoundsResponse {
+    fn example() -> Self {
+        RoundsResponse {
+            league_id: id(),
+            timezone: "America/Chicago".into(),
+            round_days: 7,
+            rounds: vec![RoundWindow {
+                round: 1,
+                start: at("2020-06-01T05:00:00"),
+                end: at("2020-06-08T05:00:00"),
+                closed: true,
+            }],
+        }
+    }
+}
+
+impl Example for StandingsResponse {
+    fn example() -> Self {
+        StandingsResponse(vec![Standing {
+            rank: 1,
+            team_id: id(),
+            name: "Fast Feet".into(),
+            wins: 3,
+            losses: 1,
+            ties: 0,
+            win_percentage: 0.75,
+            points_for: 412.5,
+            points_against: 380.0,
+            miles: 41.2,
+            streak: "W2".into(),
+            games_back: 0.0,
+        }])
+    }
+}
+
+impl Example for MatchupsResponse {
+    fn example() -> Self {
+        MatchupsResponse(vec![Matchup {
+            id: id(),
+            league_id: id(),
+            round: 1,
+            home_team_id: id(),
+            away_team_id: Some(id()),
+            home_points: Some(102.5),
+            away_points: Some(98.0),
+        }])
+    }
+}
+
+impl Example for RoundScoresResponse {
+    fn example() -> Self {
+        RoundScoresResponse {
+            league_id: id(),
+            round: 1,
+            teams: vec![TeamScore {
+                id: id(),
+                league_id: id(),
+                team_id: id(),
+                round: 1,
+                miles: 10.5,
+                points: 102.5,
+            }],
+            players: vec![PlayerScore {
+                id: id(),
+                league_id: id(),
+                team_id: id(),
+                user_id: id(),
+                round: 1,
+                miles: 10.5,
+                points: 102.5,
+            }],
+        }
+    }
+}
+
+impl Example for Team {
+    fn example() -> Self {
+        Team {
+            id: id(),
+            name: "Fast Feet".into(),
+            wins: 3,
+            losses: 1,
+            ties: 0,
+            league_id: id(),
+        }
+    }
+}
+
+impl Example for TeamDetails {
+    fn example() -> Self {
+        TeamDetails {
+            id: id(),
+            league_id: id(),
+            name: "Fast Feet".into(),
+            wins: 3,
+            losses: 1,
+            ties: 0,
+            players: Some(vec![Player {
+                id: id(),
+                user_id: id(),
+                team_id: id(),
+            }]),
+            owners: Some(vec![Owner {
+                id: id(),
+                user_id: id(),
+                team_id: id(),
+            }]),
+        }
+    }
+}
+
+impl Example for CreateTeamRequest {
+    fn example() -> Self {
+        CreateTeamRequest {
+            name: "Fast Feet".into(),
+            owner_ids: vec![id()],
+        }
+    }
+}
+
+impl Example for UpdateTeamRequest {
+    fn example() -> Self {
+        UpdateTeamRequest {
+            name: "Fast Feet".into(),
+        }
+    }
+}
+
+impl Example for TeamMemberRequest {
+    fn example() -> Self {
+        TeamMemberRequest { user_id: id() }
+    }
+}
+
+impl Example for DraftDetails {
+    fn example() -> Self {
+        DraftDetails {
+            id: id(),
+            league_id: id(),
+            mode: SNAKE.into(),
+            rounds: 5,
+            pick_seconds: 90,
+            status: SCHEDULED.into(),
+            current_pick: 1,
+            order: vec![id()],
+            picks: vec![DraftPick {
+                id: id(),
+                draft_id: id(),
+                pick_number: 1,
+                team_id: id(),
+                user_id: Some(id()),
+                auto_picked: false,
+                created_at: at("2020-05-30T18:00:00"),
+            }],
+            on_the_clock: Some(id()),
+            pick_deadline: Some(at("2020-05-30T18:01:30")),
+        }
+    }
+}
+
+impl Example for CreateDraftRequest {
+    fn example() -> Self {
+        CreateDraftRequest {
+            mode: SNAKE.into(),
+            rounds: 5,
+            pick_seconds: 90,
+            order: Some(vec![id()]),
+        }
+    }
+}
+
+impl Example for PickRequest {
+    fn example() -> Self {
+        PickRequest {
+            team_id: id(),
+            user_id: id(),
+        }
+    }
+}
+
+impl Example for DraftQueueRequest {
+    fn example() -> Self {
+        DraftQueueRequest {
+            user_ids: vec![id()],
+        }
+    }
+}
+
+impl Example for DraftQueueResponse {
+    fn example() -> Self {
+        DraftQueueResponse {
+            team_id: id(),
+            user_ids: vec![id()],
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Method and path of every route registered in routes.rs
+    fn registered_routes() -> BTreeSet<(String, String)> {
+        let source: String = include_str!("routes.rs")
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect::<String>()
+            .split_whitespace()
+            .collect();
+
+        let mut routes = BTreeSet::new();
+        let mut scopes: Vec<(String, usize)> = vec![];
+        let mut depth = 0;
+        let mut rest = source.as_str();
+        while let Some(next) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix("web::scope(\"") {
+                let scope = after.split('"').next().unwrap_or_default();
+                scopes.push((scope.into(), depth));
+            } else if let Some(after) = rest.strip_prefix(".route(\"") {
+                let mut parts = after.splitn(2, "\",web::");
+                let path = parts.next().unwrap_or_default();
+                let method = parts
+                    .next()
+                    .and_then(|method| method.split('(').next())
+                    .unwrap_or_default();
+                let prefix: String = scopes.iter().map(|(scope, _)| scope.as_str()).collect();
+                let separator = if path.is_empty() || path.starts_with('/') {
+                    ""
+                } else {
+                    "/"
+                };
+                routes.insert((method.into(), format!("{}{}{}", prefix, separator, path)));
+            }
+            match next {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    while scopes
+                        .last()
+                        .is_some_and(|(_, scope_depth)| depth < *scope_depth)
+                    {
+                        scopes.pop();
+                    }
+                }
+                _ => {}
+            }
+            rest = &rest[next.len_utf8()..];
+        }
+        routes
+    }
+
+    #[test]
+    fn it_documents_every_route() {
+        let documented: BTreeSet<(String, String)> = operations()
+            .into_iter()
+            .map(|operation| (operation.method.into(), operation.path.into()))
+            .collect();
+        let registered = registered_routes();
+        assert!(registered.len() > 40);
+
+        let undocumented: Vec<_> = registered.difference(&documented).collect();
+        assert!(
+            undocumented.is_empty(),
+            "Missing from the spec: {:?}",
+            undocumented
+        );
+        let unrouted: Vec<_> = documented.difference(&registered).collect();
+        assert!(unrouted.is_empty(), "Not in routes.rs: {:?}", unrouted);
+    }
+
+    #[test]
+    fn it_resolves_every_reference() {
+        let spec = spec().to_string();
+        let schemas = spec_schemas();
+        for reference in spec.split("#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(schemas.contains_key(name), "{} has no schema", name);
+        }
+    }
+
+    fn spec_schemas() -> Map<String, Value> {
+        spec()["components"]["schemas"]
+            .as_object()
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn it_infers_required_and_nullable_fields() {
+        let schema = &spec_schemas()["CreateLeagueRequest"];
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&json!("name")));
+        assert!(required.contains(&json!("points_per_mile")));
+        assert!(!required.contains(&json!("tie_breakers")));
+        assert!(!required.contains(&json!("pace_bonus_target")));
+
+        let properties = &schema["properties"];
+        assert_eq!(properties["pace_bonus_target"]["nullable"], json!(true));
+        assert_eq!(properties["rounds"]["type"], json!("integer"));
+        assert_eq!(properties["min_run_distance"]["type"], json!("number"));
+        assert_eq!(properties["start"]["format"], json!("date"));
+        assert_eq!(properties["tie_breakers"]["items"]["type"], json!("string"));
+    }
+
+    #[test]
+    fn it_infers_nested_schemas() {
+        let schema = &spec_schemas()["TeamDetails"];
+        let owner = &schema["properties"]["owners"]["items"];
+        assert_eq!(owner["properties"]["user_id"]["format"], json!("uuid"));
+        assert!(owner["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("user_id")));
+    }
+
+    #[test]
+    fn it_documents_list_parameters_and_security() {
+        let spec = spec();
+        let get_leagues = &spec["paths"]["/api/v1/leagues"]["get"];
+        let parameters = get_leagues["parameters"].as_array().unwrap();
+        assert!(parameters
+            .iter()
+            .any(|parameter| parameter["name"] == "cursor"));
+        assert!(get_leagues["security"].is_array());
+
+        let login = &spec["paths"]["/api/v1/auth/login"]["post"];
+        assert!(login["security"].is_null());
+    }
+}
@@ -0,0 +1,249 @@
+//! Pagination, sorting and filtering for list endpoints.
+//!
+//! Every list endpoint takes the same query parameters:
+//!
+//! - `limit`: how many items to return, 25 by default and 100 at most
+//! - `offset` or `cursor`: where to start, `cursor` being the `next_cursor`
+//!   of the previous page
+//! - `sort` and `direction` (`asc` or `desc`): what to order by, each list
+//!   has its own sort fields
+//! - `name` and `email`: case-insensitive "contains" filters, for the lists
+//!   that have those fields
+//!
+//! Lists respond with a page of `data`, the `total` number of matches and a
+//! `next_cursor` that's `null` on the last page.
+
+use crate::errors::ApiError;
+use crate::validate::collect_errors;
+use actix_web::{dev::Payload, web::Query, FromRequest, HttpRequest};
+use futures::future::{err, ok, Ready};
+use validator::{Validate, ValidationError};
+
+pub const DEFAULT_LIMIT: i64 = 25;
+
+pub const ASC: &str = "asc";
+pub const DESC: &str = "desc";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Validate)]
+pub struct ListParams {
+    #[validate(range(min = 1, max = 100, message = "limit must be between 1 and 100"))]
+    pub limit: Option<i64>,
+
+    #[validate(range(
+        min = 0,
+        max = 1_000_000_000,
+        message = "offset must be between 0 and 1000000000"
+    ))]
+    pub offset: Option<i64>,
+
+    pub cursor: Option<String>,
+
+    pub sort: Option<String>,
+
+    #[validate(custom(
+        function = "validate_direction",
+        message = "direction must be asc or desc"
+    ))]
+    pub direction: Option<String>,
+
+    pub name: Option<String>,
+
+    pub email: Option<String>,
+}
+
+impl ListParams {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+
+    /// Where the page starts, a cursor takes precedence over an offset
+    pub fn offset(&self) -> Result<i64, ApiError> {
+        match &self.cursor {
+            Some(cursor) => decode_cursor(cursor),
+            None => Ok(self.offset.unwrap_or(0)),
+        }
+    }
+
+    /// Get the field to sort by and whether it's sorted in descending order
+    ///
+    /// `fields` lists what a list can be sorted by, the first one being the
+    /// default.
+    pub fn sort<'a>(
+        &self,
+        fields: &[&'a str],
+        default_direction: &str,
+    ) -> Result<(&'a str, bool), ApiError> {
+        let field = match &self.sort {
+            Some(sort) => *fields.iter().find(|field| *field == sort).ok_or_else(|| {
+                ApiError::BadRequest(format!("sort must be one of {}", fields.join(", ")))
+            })?,
+            None => fields[0],
+        };
+        let direction = self.direction.as_deref().unwrap_or(default_direction);
+        Ok((field, direction == DESC))
+    }
+}
+
+/// Turn a filter into an ILIKE pattern matching anything that contains it
+pub fn contains(filter: &str) -> String {
+    let escaped = filter
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Parse and validate list parameters from the query string
+impl FromRequest for ListParams {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let params = match Query::<ListParams>::from_query(req.query_string()) {
+            Ok(query) => query.into_inner(),
+            Err(error) => return err(ApiError::BadRequest(error.to_string())),
+        };
+        match params.validate() {
+            Ok(()) => ok(params),
+            Err(errors) => err(ApiError::ValidationError(collect_errors(errors))),
+        }
+    }
+}
+
+/// A page of a list
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct ListResponse<T> {
+    pub data: Vec<T>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(data: Vec<T>, total: i64, offset: i64) -> Self {
+        let next_offset = offset + data.len() as i64;
+        let next_cursor = if !data.is_empty() && next_offset < total {
+            Some(encode_cursor(next_offset))
+        } else {
+            None
+        };
+        ListResponse {
+            data,
+            total,
+            next_cursor,
+        }
+    }
+}
+
+fn encode_cursor(offset: i64) -> String {
+    format!("o{}", offset)
+}
+
+fn decode_cursor(cursor: &str) -> Result<i64, ApiError> {
+    cursor
+        .strip_prefix('o')
+        .and_then(|offset| offset.parse::<i64>().ok())
+        .filter(|offset| *offset >= 0)
+        .ok_or_else(|| ApiError::BadRequest(format!("cursor {} is invalid", cursor)))
+}
+
+fn validate_direction(direction: &str) -> Result<(), ValidationError> {
+    if direction != ASC && direction != DESC {
+        return Err(ValidationError::new("direction"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    async fn params(query: &str) -> Result<ListParams, ApiError> {
+        let (req, mut payload) = TestRequest::with_uri(&format!("/?{}", query)).to_http_parts();
+        ListParams::from_request(&req, &mut payload).await
+    }
+
+    #[actix_rt::test]
+    async fn it_defaults_to_the_first_page() {
+        let params = params("").await.unwrap();
+        assert_eq!(params.limit(), DEFAULT_LIMIT);
+        assert_eq!(params.offset().unwrap(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn it_extracts_list_params() {
+        let params = params("limit=10&offset=20&sort=name&direction=desc&name=run")
+            .await
+            .unwrap();
+        assert_eq!(params.limit(), 10);
+        assert_eq!(params.offset().unwrap(), 20);
+        assert_eq!(
+            params.sort(&["start", "name"], ASC).unwrap(),
+            ("name", true)
+        );
+        assert_eq!(params.name, Some("run".into()));
+    }
+
+    #[actix_rt::test]
+    async fn it_rejects_a_limit_over_the_max() {
+        let response = params("limit=101").await;
+        let expected_error = ApiError::invalid_field("limit", "limit must be between 1 and 100");
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_rejects_an_offset_over_the_max() {
+        let response = params("offset=1000000001").await;
+        let expected_error =
+            ApiError::invalid_field("offset", "offset must be between 0 and 1000000000");
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[actix_rt::test]
+    async fn it_rejects_an_unknown_direction() {
+        assert!(params("direction=sideways").await.is_err());
+    }
+
+    #[test]
+    fn it_rejects_an_unknown_sort_field() {
+        let params = ListParams {
+            sort: Some("password".into()),
+            ..ListParams::default()
+        };
+        let expected_error = ApiError::BadRequest("sort must be one of name, start".into());
+        assert_eq!(
+            params.sort(&["name", "start"], ASC).unwrap_err(),
+            expected_error
+        );
+    }
+
+    #[test]
+    fn it_pages_with_cursors() {
+        let first_page = ListResponse::new(vec![1, 2], 3, 0);
+        let cursor = first_page.next_cursor.unwrap();
+        let params = ListParams {
+            cursor: Some(cursor),
+            offset: Some(50),
+            ..ListParams::default()
+        };
+        assert_eq!(params.offset().unwrap(), 2);
+
+        let last_page = ListResponse::new(vec![3], 3, 2);
+        assert_eq!(last_page.next_cursor, None);
+    }
+
+    #[test]
+    fn it_rejects_an_invalid_cursor() {
+        let params = ListParams {
+            cursor: Some("nope".into()),
+            ..ListParams::default()
+        };
+        assert!(params.offset().is_err());
+    }
+
+    #[test]
+    fn it_escapes_contains_filters() {
+        assert_eq!(contains("5%_off"), "%5\\%\\_off%");
+    }
+}
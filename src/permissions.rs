@@ -0,0 +1,110 @@
+//! Authorization checks for the authenticated user.
+//!
+//! League commissioners manage their league, team owners manage their team's
+//! roster and every user manages their own account. Checks that touch the
+//! database should be run inside of `block`, like any other manager call.
+
+use crate::database::PoolType;
+use crate::errors::ApiError;
+use crate::managers::{leagues::is_commissioner, teams::is_owner};
+use crate::models::users::AuthUser;
+use uuid::Uuid;
+
+/// Only allow a user to act on their own account
+pub fn ensure_self(auth_user: &AuthUser, user_id: Uuid) -> Result<(), ApiError> {
+    if auth_user.id == user_id {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "You can only change your own account".into(),
+        ))
+    }
+}
+
+/// Only allow a league's commissioners to act on the league
+pub fn ensure_commissioner(
+    pool: &PoolType,
+    auth_user: &AuthUser,
+    league_id: Uuid,
+) -> Result<(), ApiError> {
+    if is_commissioner(pool, league_id, auth_user.id)? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            league_id
+        )))
+    }
+}
+
+/// Only allow a team's owners to act on the team
+pub fn ensure_team_owner(
+    pool: &PoolType,
+    auth_user: &AuthUser,
+    team_id: Uuid,
+) -> Result<(), ApiError> {
+    if is_owner(pool, team_id, auth_user.id)? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "Only an owner can change team {}",
+            team_id
+        )))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tests::helpers::tests::get_pool;
+
+    fn auth_user(id: Uuid) -> AuthUser {
+        AuthUser {
+            id,
+            email: "permissions-test@nothing.org".into(),
+        }
+    }
+
+    #[test]
+    fn it_allows_a_user_to_change_themselves() {
+        let user_id = Uuid::new_v4();
+        assert!(ensure_self(&auth_user(user_id), user_id).is_ok());
+    }
+
+    #[test]
+    fn it_forbids_a_user_from_changing_someone_else() {
+        let response = ensure_self(&auth_user(Uuid::new_v4()), Uuid::new_v4());
+        let expected_error = ApiError::Forbidden("You can only change your own account".into());
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_allows_a_commissioner() {
+        let response = ensure_commissioner(&get_pool(), &auth_user(Uuid::nil()), Uuid::nil());
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    fn it_forbids_a_non_commissioner() {
+        let response = ensure_commissioner(&get_pool(), &auth_user(Uuid::new_v4()), Uuid::nil());
+        let expected_error = ApiError::Forbidden(format!(
+            "Only a commissioner can change league {}",
+            Uuid::nil()
+        ));
+        assert_eq!(response.unwrap_err(), expected_error);
+    }
+
+    #[test]
+    fn it_allows_a_team_owner() {
+        let owner_id = Uuid::parse_str("33333333-3333-3333-3333-333333333333").unwrap();
+        let response = ensure_team_owner(&get_pool(), &auth_user(owner_id), Uuid::nil());
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    fn it_forbids_a_team_player_from_acting_as_owner() {
+        let player_id = Uuid::parse_str("11111111-1111-1111-1111-111111111111").unwrap();
+        let response = ensure_team_owner(&get_pool(), &auth_user(player_id), Uuid::nil());
+        assert!(response.is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! A fake Strava for tests, so linking and syncing accounts works offline
+//!
+//! It serves the parts of Strava's API the Strava provider uses, from
+//! activities added by the tests. Any code but REFUSED_CODE is authorized,
+//! and refreshing tokens hands out a new access token every time.
+
+use crate::providers::strava::Strava;
+use actix_web::test::{start, TestServer};
+use actix_web::web::{get, post, Data, Form, Query};
+use actix_web::{App, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub const CLIENT_ID: &str = "client-id";
+pub const CLIENT_SECRET: &str = "client-secret";
+
+/// The authorization code Strava refuses
+pub const REFUSED_CODE: &str = "refused";
+
+pub struct MockStrava {
+    server: TestServer,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    athlete_id: i64,
+    access_token: String,
+    refresh_token: String,
+    refreshes: u32,
+    activities: Vec<Value>,
+}
+
+type Params = HashMap<String, String>;
+
+impl MockStrava {
+    /// Start a server without activities, for an athlete of its own
+    pub fn start() -> Self {
+        let athlete_id = rand::random::<u32>() as i64;
+        let state = Arc::new(Mutex::new(State {
+            athlete_id,
+            access_token: format!("access-{}", athlete_id),
+            refresh_token: format!("refresh-{}", athlete_id),
+            refreshes: 0,
+            activities: vec![],
+        }));
+        let data = state.clone();
+        let server = start(move || {
+            App::new()
+                .data(data.clone())
+                .route("/oauth/token", post().to(token))
+                .route("/api/v3/athlete/activities", get().to(activities))
+        });
+        MockStrava { server, state }
+    }
+
+    /// The Strava provider, talking to this server
+    pub fn provider(&self) -> Strava {
+        let url = format!("http://{}", self.server.addr());
+        Strava::new(&url, CLIENT_ID, CLIENT_SECRET)
+    }
+
+    pub fn athlete_id(&self) -> i64 {
+        self.state.lock().unwrap().athlete_id
+    }
+
+    /// The access token currently accepted
+    pub fn access_token(&self) -> String {
+        self.state.lock().unwrap().access_token.clone()
+    }
+
+    /// How many times tokens were refreshed
+    pub fn refreshes(&self) -> u32 {
+        self.state.lock().unwrap().refreshes
+    }
+
+    /// Record an activity, start_date is like 2020-06-01T07:00:00Z
+    pub fn add_activity(&self, id: i64, kind: &str, start_date: &str, meters: f64, seconds: i32) {
+        self.state.lock().unwrap().activities.push(json!({
+            "id": id,
+            "type": kind,
+            "start_date": start_date,
+            "distance": meters,
+            "moving_time": seconds,
+            "total_elevation_gain": 12.5,
+            "average_heartrate": 151.4,
+        }));
+    }
+}
+
+async fn token(state: Data<Arc<Mutex<State>>>, params: Form<Params>) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    let param = |name: &str| params.get(name).map(String::as_str).unwrap_or_default();
+    if param("client_id") != CLIENT_ID || param("client_secret") != CLIENT_SECRET {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let athlete = match (param("grant_type"), param("code"), param("refresh_token")) {
+        ("authorization_code", code, _) if code != REFUSED_CODE => {
+            Some(json!({ "id": state.athlete_id }))
+        }
+        ("refresh_token", _, refresh_token) if refresh_token == state.refresh_token => {
+            state.refreshes += 1;
+            state.access_token = format!("access-{}-{}", state.athlete_id, state.refreshes);
+            None
+        }
+        _ => return HttpResponse::BadRequest().finish(),
+    };
+    HttpResponse::Ok().json(json!({
+        "access_token": state.access_token,
+        "refresh_token": state.refresh_token,
+        "expires_at": (Utc::now() + Duration::hours(6)).timestamp(),
+        "athlete": athlete,
+    }))
+}
+
+async fn activities(
+    state: Data<Arc<Mutex<State>>>,
+    request: HttpRequest,
+    params: Query<Params>,
+) -> HttpResponse {
+    let state = state.lock().unwrap();
+    let authorization = request
+        .headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok());
+    if authorization != Some(&format!("Bearer {}", state.access_token)) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let param = |name: &str, default: i64| {
+        params
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    let (after, page, per_page) = (param("after", 0), param("page", 1), param("per_page", 30));
+    let started = |activity: &Value| {
+        DateTime::parse_from_rfc3339(activity["start_date"].as_str().unwrap())
+            .unwrap()
+            .timestamp()
+    };
+
+    let mut activities: Vec<&Value> = state
+        .activities
+        .iter()
+        .filter(|activity| started(activity) > after)
+        .collect();
+    activities.sort_by_key(|activity| started(activity));
+    let page: Vec<&Value> = activities
+        .into_iter()
+        .skip(((page - 1) * per_page) as usize)
+        .take(per_page as usize)
+        .collect();
+    HttpResponse::Ok().json(page)
+}
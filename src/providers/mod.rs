@@ -0,0 +1,140 @@
+//! Activity providers runs are synced from, like Strava
+//!
+//! Users link their account at a provider through OAuth: the provider
+//! redirects back with an authorization code, which is traded for tokens
+//! kept in a linked account. Syncing then lists the activities of the
+//! account, see `crate::sync`.
+//!
+//! Providers are configured from the environment, a provider missing its
+//! client id and secret isn't available.
+
+use crate::config::{Config, CONFIG};
+use crate::errors::ApiError;
+use crate::models::linked_accounts::{LinkedAccount, Tokens};
+use crate::models::runs::CreateRunRequest;
+use actix_web::web::ServiceConfig;
+use chrono::NaiveDateTime;
+use futures::future::LocalBoxFuture;
+use std::sync::Arc;
+
+#[cfg(test)]
+pub mod mock;
+pub mod strava;
+
+/// A service users record their runs with
+pub trait ActivityProvider: Send + Sync {
+    /// Name of the provider, which linked accounts and synced runs keep
+    fn name(&self) -> &'static str;
+
+    /// Trade the authorization code of an OAuth redirect for tokens
+    fn exchange_code<'a>(&'a self, code: &'a str) -> LocalBoxFuture<'a, Result<Tokens, ApiError>>;
+
+    /// Get new tokens for an account whose access token expired
+    fn refresh<'a>(
+        &'a self,
+        account: &'a LinkedAccount,
+    ) -> LocalBoxFuture<'a, Result<Tokens, ApiError>>;
+
+    /// Get a page of the runs that started after a time, oldest first
+    ///
+    /// Pages are numbered from 1, an empty page is past the last one.
+    fn activities<'a>(
+        &'a self,
+        access_token: &'a str,
+        after: Option<NaiveDateTime>,
+        page: u32,
+    ) -> LocalBoxFuture<'a, Result<Vec<ProviderActivity>, ApiError>>;
+}
+
+/// A run recorded at a provider
+///
+/// distance is in miles, duration in seconds and elevation_gain in feet, like
+/// the runs it becomes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderActivity {
+    /// Id of the activity at the provider
+    pub id: String,
+    pub started_at: NaiveDateTime,
+    pub distance: f64,
+    pub duration: i32,
+    pub elevation_gain: Option<f64>,
+    pub heart_rate: Option<i32>,
+}
+
+impl From<&ProviderActivity> for CreateRunRequest {
+    fn from(activity: &ProviderActivity) -> Self {
+        CreateRunRequest {
+            distance: activity.distance,
+            duration: activity.duration,
+            started_at: activity.started_at,
+            elevation_gain: activity.elevation_gain,
+            heart_rate: activity.heart_rate,
+        }
+    }
+}
+
+/// The providers accounts can be linked to
+#[derive(Clone, Default)]
+pub struct Providers {
+    providers: Vec<Arc<dyn ActivityProvider>>,
+}
+
+impl Providers {
+    pub fn new(providers: Vec<Arc<dyn ActivityProvider>>) -> Self {
+        Providers { providers }
+    }
+
+    /// Every provider with a client id and secret in the environment
+    pub fn from_config(config: &Config) -> Self {
+        let mut providers: Vec<Arc<dyn ActivityProvider>> = vec![];
+        if let Some(strava) = strava::Strava::from_config(config) {
+            providers.push(Arc::new(strava));
+        }
+        Providers::new(providers)
+    }
+
+    /// Find a provider by name or error out
+    pub fn get(&self, name: &str) -> Result<Arc<dyn ActivityProvider>, ApiError> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("Provider {} isn't available", name)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+/// Add the providers to the app's data, see Providers::from_config
+pub fn add_providers(cfg: &mut ServiceConfig) {
+    cfg.data(Providers::from_config(&CONFIG));
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::providers::strava::Strava;
+
+    #[test]
+    fn it_finds_a_provider_by_name() {
+        let strava = Strava::new("http://localhost", "id", "secret");
+        let providers = Providers::new(vec![Arc::new(strava)]);
+        assert_eq!(providers.get("strava").unwrap().name(), "strava");
+        assert_eq!(
+            providers.get("garmin").err().unwrap(),
+            ApiError::NotFound("Provider garmin isn't available".into())
+        );
+    }
+
+    #[test]
+    fn it_has_no_provider_without_credentials() {
+        let config = Config {
+            strava_client_id: None,
+            strava_client_secret: None,
+            ..CONFIG.clone()
+        };
+        assert!(Providers::from_config(&config).is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+//! Strava, see https://developers.strava.com/docs/reference/
+//!
+//! Needs STRAVA_CLIENT_ID and STRAVA_CLIENT_SECRET, STRAVA_URL points at
+//! another server speaking Strava's API, like the mock one of tests.
+
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::linked_accounts::{LinkedAccount, Tokens};
+use crate::providers::{ActivityProvider, ProviderActivity};
+use actix_web::client::{Client, ClientResponse};
+use actix_web::dev::{Decompress, Payload};
+use chrono::{DateTime, NaiveDateTime};
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+pub const NAME: &str = "strava";
+
+const DEFAULT_URL: &str = "https://www.strava.com";
+
+/// Activities asked for per page, Strava allows up to 200
+pub const PER_PAGE: u32 = 50;
+
+/// Longest wait for an answer from Strava
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest response read from Strava, in bytes
+const MAX_RESPONSE_SIZE: usize = 2_097_152;
+
+const METERS_PER_MILE: f64 = 1609.344;
+const FEET_PER_METER: f64 = 3.28084;
+
+pub struct Strava {
+    url: String,
+    client_id: String,
+    client_secret: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    /// Unix time
+    expires_at: i64,
+    /// Only sent when trading an authorization code
+    athlete: Option<Athlete>,
+}
+
+#[derive(Deserialize)]
+struct Athlete {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct Activity {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    start_date: String,
+    /// Meters
+    distance: f64,
+    /// Seconds
+    moving_time: i32,
+    /// Meters
+    total_elevation_gain: Option<f64>,
+    average_heartrate: Option<f64>,
+}
+
+impl Strava {
+    pub fn new(url: &str, client_id: &str, client_secret: &str) -> Self {
+        Strava {
+            url: url.trim_end_matches('/').into(),
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+        }
+    }
+
+    /// Strava with the client id and secret of the environment, if there are
+    pub fn from_config(config: &Config) -> Option<Self> {
+        match (&config.strava_client_id, &config.strava_client_secret) {
+            (Some(client_id), Some(client_secret))
+                if !client_id.is_empty() && !client_secret.is_empty() =>
+            {
+                let url = config.strava_url.as_deref().unwrap_or(DEFAULT_URL);
+                Some(Strava::new(url, client_id, client_secret))
+            }
+            _ => None,
+        }
+    }
+
+    async fn token(&self, params: &[(&str, &str)]) -> Result<TokenResponse, ApiError> {
+        let response = Client::default()
+            .post(format!("{}/oauth/token", self.url))
+            .timeout(TIMEOUT)
+            .send_form(&params)
+            .await
+            .map_err(unavailable)?;
+        read(response).await
+    }
+
+    async fn get_activities(
+        &self,
+        access_token: &str,
+        after: Option<NaiveDateTime>,
+        page: u32,
+    ) -> Result<Vec<ProviderActivity>, ApiError> {
+        let after = after.map_or(0, |after| after.timestamp());
+        let response = Client::default()
+            .get(format!("{}/api/v3/athlete/activities", self.url))
+            .bearer_auth(access_token)
+            .timeout(TIMEOUT)
+            .query(&[
+                ("after", after),
+                ("page", page.into()),
+                ("per_page", PER_PAGE.into()),
+            ])
+            .map_err(|error| ApiError::InternalServerError(error.to_string()))?
+            .send()
+            .await
+            .map_err(unavailable)?;
+        let activities: Vec<Activity> = read(response).await?;
+
+        activities
+            .into_iter()
+            .filter(|activity| activity.kind == "Run")
+            .map(|activity| {
+                let started_at = DateTime::parse_from_rfc3339(&activity.start_date)
+                    .map_err(|_| unreadable(&activity.start_date))?
+                    .naive_utc();
+                Ok(ProviderActivity {
+                    id: activity.id.to_string(),
+                    started_at,
+                    distance: activity.distance / METERS_PER_MILE,
+                    duration: activity.moving_time,
+                    elevation_gain: activity
+                        .total_elevation_gain
+                        .map(|meters| meters * FEET_PER_METER),
+                    heart_rate: activity
+                        .average_heartrate
+                        .map(|heart_rate| heart_rate.round() as i32),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ActivityProvider for Strava {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn exchange_code<'a>(&'a self, code: &'a str) -> LocalBoxFuture<'a, Result<Tokens, ApiError>> {
+        async move {
+            let response = self
+                .token(&[
+                    ("client_id", &self.client_id),
+                    ("client_secret", &self.client_secret),
+                    ("code", code),
+                    ("grant_type", "authorization_code"),
+                ])
+                .await?;
+            let athlete = response
+                .athlete
+                .as_ref()
+                .ok_or_else(|| unreadable("the token without an athlete"))?;
+            Ok(tokens(athlete.id.to_string(), response))
+        }
+        .boxed_local()
+    }
+
+    fn refresh<'a>(
+        &'a self,
+        account: &'a LinkedAccount,
+    ) -> LocalBoxFuture<'a, Result<Tokens, ApiError>> {
+        async move {
+            let response = self
+                .token(&[
+                    ("client_id", &self.client_id),
+                    ("client_secret", &self.client_secret),
+                    ("refresh_token", &account.refresh_token),
+                    ("grant_type", "refresh_token"),
+                ])
+                .await?;
+            Ok(tokens(account.provider_user_id.clone(), response))
+        }
+        .boxed_local()
+    }
+
+    fn activities<'a>(
+        &'a self,
+        access_token: &'a str,
+        after: Option<NaiveDateTime>,
+        page: u32,
+    ) -> LocalBoxFuture<'a, Result<Vec<ProviderActivity>, ApiError>> {
+        self.get_activities(access_token, after, page).boxed_local()
+    }
+}
+
+fn tokens(provider_user_id: String, response: TokenResponse) -> Tokens {
+    Tokens {
+        provider_user_id,
+        access_token: response.access_token,
+        refresh_token: response.refresh_token,
+        expires_at: NaiveDateTime::from_timestamp(response.expires_at, 0),
+    }
+}
+
+/// Read the JSON body of a successful response
+async fn read<T: DeserializeOwned>(
+    mut response: ClientResponse<Decompress<Payload>>,
+) -> Result<T, ApiError> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ApiError::ProviderError(format!(
+            "Strava refused the request ({})",
+            status
+        )));
+    }
+    response
+        .json::<T>()
+        .limit(MAX_RESPONSE_SIZE)
+        .await
+        .map_err(|error| unreadable(&error.to_string()))
+}
+
+fn unavailable<E: std::fmt::Display>(error: E) -> ApiError {
+    ApiError::ProviderError(format!("Strava can't be reached: {}", error))
+}
+
+fn unreadable(what: &str) -> ApiError {
+    ApiError::ProviderError(format!("Strava's answer can't be read: {}", what))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::providers::mock::{MockStrava, REFUSED_CODE};
+
+    #[actix_rt::test]
+    async fn it_exchanges_a_code_for_tokens() {
+        let strava = MockStrava::start();
+        let tokens = strava.provider().exchange_code("code").await.unwrap();
+        assert_eq!(tokens.provider_user_id, strava.athlete_id().to_string());
+        assert_eq!(tokens.access_token, strava.access_token());
+    }
+
+    #[actix_rt::test]
+    async fn it_refuses_a_bad_code() {
+        let strava = MockStrava::start();
+        let error = strava
+            .provider()
+            .exchange_code(REFUSED_CODE)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ApiError::ProviderError("Strava refused the request (400 Bad Request)".into())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn it_converts_runs_and_skips_other_activities() {
+        let strava = MockStrava::start();
+        strava.add_activity(1, "Ride", "2020-06-01T07:00:00Z", 20000.0, 3600);
+        strava.add_activity(2, "Run", "2020-06-02T07:00:00Z", 8046.72, 2400);
+        let activities = strava
+            .provider()
+            .activities(&strava.access_token(), None, 1)
+            .await
+            .unwrap();
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].id, "2");
+        assert!((activities[0].distance - 5.0).abs() < 0.0001);
+        assert_eq!(activities[0].duration, 2400);
+        assert_eq!(activities[0].started_at.to_string(), "2020-06-02 07:00:00");
+    }
+}
@@ -2,10 +2,34 @@
 //! combined.
 
 use crate::handlers::{
+    auth::{delete_session, delete_sessions, get_sessions, login, logout},
+    calendars::get_calendar,
+    drafts::{
+        create_draft, create_pick, get_draft, get_draft_queue, start_draft, update_draft_queue,
+    },
     health::get_health,
-    leagues::{create_league, delete_league, get_league, get_leagues, update_league},
+    leagues::{
+        close_league_round, create_league, delete_league, get_league, get_league_calendar,
+        get_league_calendar_link, get_league_events, get_league_rounds, get_league_standings,
+        get_leagues, get_round_scores, score_league_round, update_league,
+    },
+    linked_accounts::{
+        create_linked_account, delete_linked_account, get_linked_accounts, sync_linked_account,
+    },
+    matchups::{create_schedule, get_round_matchups, get_schedule},
+    openapi::get_openapi,
+    runs::{
+        create_run, delete_run, get_run, get_runs, import_league_runs, import_runs, update_run,
+    },
+    teams::{
+        add_team_owner, add_team_player, create_team, delete_team, get_all_teams, get_team,
+        get_team_calendar, get_team_calendar_link, get_teams, remove_team_owner,
+        remove_team_player, update_team,
+    },
     users::{create_user, delete_user, get_user, get_users, update_user},
 };
+use crate::import::MAX_FILE_SIZE;
+use crate::middleware::auth::Auth as AuthMiddleware;
 use actix_web::web;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
@@ -15,9 +39,50 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
         // /api/v1 routes
         .service(
             web::scope("/api/v1")
+                // OpenAPI specification
+                .route("/openapi.json", web::get().to(get_openapi))
+                // AUTH routes
+                .service(
+                    web::scope("/auth")
+                        .route("/login", web::post().to(login))
+                        .route("/logout", web::get().to(logout))
+                        .route("/register", web::post().to(create_user))
+                        // SESSION routes
+                        .service(
+                            web::scope("/sessions")
+                                .wrap(AuthMiddleware)
+                                .route("/{id}", web::delete().to(delete_session))
+                                .route("", web::get().to(get_sessions))
+                                .route("", web::delete().to(delete_sessions)),
+                        ),
+                )
                 // USER routes
                 .service(
                     web::scope("/users")
+                        .wrap(AuthMiddleware)
+                        // Size limit of imported files
+                        .data(web::PayloadConfig::new(MAX_FILE_SIZE))
+                        // LINKED ACCOUNT routes
+                        .route(
+                            "/{id}/linked-accounts/{account_id}/sync",
+                            web::post().to(sync_linked_account),
+                        )
+                        .route(
+                            "/{id}/linked-accounts/{account_id}",
+                            web::delete().to(delete_linked_account),
+                        )
+                        .route("/{id}/linked-accounts", web::get().to(get_linked_accounts))
+                        .route(
+                            "/{id}/linked-accounts",
+                            web::post().to(create_linked_account),
+                        )
+                        // RUN routes
+                        .route("/{id}/runs/import", web::post().to(import_runs))
+                        .route("/{id}/runs/{run_id}", web::get().to(get_run))
+                        .route("/{id}/runs/{run_id}", web::put().to(update_run))
+                        .route("/{id}/runs/{run_id}", web::delete().to(delete_run))
+                        .route("/{id}/runs", web::get().to(get_runs))
+                        .route("/{id}/runs", web::post().to(create_run))
                         .route("/{id}", web::get().to(get_user))
                         .route("/{id}", web::put().to(update_user))
                         .route("/{id}", web::delete().to(delete_user))
@@ -27,11 +92,82 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
                 // LEAGUE routes
                 .service(
                     web::scope("/leagues")
+                        .wrap(AuthMiddleware)
+                        // Size limit of imported files
+                        .data(web::PayloadConfig::new(MAX_FILE_SIZE))
+                        // DRAFT routes
+                        .route(
+                            "/{id}/draft/queues/{team_id}",
+                            web::get().to(get_draft_queue),
+                        )
+                        .route(
+                            "/{id}/draft/queues/{team_id}",
+                            web::put().to(update_draft_queue),
+                        )
+                        .route("/{id}/draft/picks", web::post().to(create_pick))
+                        .route("/{id}/draft/start", web::post().to(start_draft))
+                        .route("/{id}/draft", web::get().to(get_draft))
+                        .route("/{id}/draft", web::post().to(create_draft))
+                        // MATCHUP routes
+                        .route(
+                            "/{id}/rounds/{round}/matchups",
+                            web::get().to(get_round_matchups),
+                        )
+                        .route(
+                            "/{id}/rounds/{round}/close",
+                            web::post().to(close_league_round),
+                        )
+                        // RUN routes
+                        .route("/{id}/runs/import", web::post().to(import_league_runs))
+                        // TEAM routes
+                        .route("/{id}/teams", web::get().to(get_teams))
+                        .route("/{id}/teams", web::post().to(create_team))
+                        .route("/{id}/schedule", web::get().to(get_schedule))
+                        .route("/{id}/standings", web::get().to(get_league_standings))
+                        .route("/{id}/calendar.ics", web::get().to(get_league_calendar))
+                        .route(
+                            "/{id}/calendar-link",
+                            web::get().to(get_league_calendar_link),
+                        )
+                        .route("/{id}/events", web::get().to(get_league_events))
+                        .route("/{id}/schedule", web::post().to(create_schedule))
+                        .route("/{id}/rounds", web::get().to(get_league_rounds))
+                        .route(
+                            "/{id}/rounds/{round}/scores",
+                            web::get().to(get_round_scores),
+                        )
+                        .route(
+                            "/{id}/rounds/{round}/scores",
+                            web::post().to(score_league_round),
+                        )
                         .route("/{id}", web::get().to(get_league))
                         .route("/{id}", web::put().to(update_league))
                         .route("{id}", web::delete().to(delete_league))
                         .route("", web::get().to(get_leagues))
                         .route("", web::post().to(create_league)),
-                ),
+                )
+                // TEAM routes
+                .service(
+                    web::scope("/teams")
+                        .wrap(AuthMiddleware)
+                        .route(
+                            "/{id}/players/{user_id}",
+                            web::delete().to(remove_team_player),
+                        )
+                        .route("/{id}/players", web::post().to(add_team_player))
+                        .route(
+                            "/{id}/owners/{user_id}",
+                            web::delete().to(remove_team_owner),
+                        )
+                        .route("/{id}/owners", web::post().to(add_team_owner))
+                        .route("/{id}/calendar.ics", web::get().to(get_team_calendar))
+                        .route("/{id}/calendar-link", web::get().to(get_team_calendar_link))
+                        .route("/{id}", web::get().to(get_team))
+                        .route("/{id}", web::put().to(update_team))
+                        .route("/{id}", web::delete().to(delete_team))
+                        .route("", web::get().to(get_all_teams)),
+                )
+                // CALENDAR routes, for calendar apps that can't log in
+                .route("/calendars/{token}", web::get().to(get_calendar)),
         );
 }
@@ -0,0 +1,173 @@
+//! Round-robin schedule generation.
+//!
+//! Uses the circle method: one team stays put while the others rotate around
+//! it, so every team meets every other team once per cycle of rounds. Odd
+//! numbers of teams get an empty slot, and whoever is paired with it has a
+//! bye. Leagues with more rounds than a cycle start the cycle over, swapping
+//! home and away.
+
+use uuid::Uuid;
+
+/// A pairing of two teams in a round, away_team_id is None for a bye
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pairing {
+    pub round: i32,
+    pub home_team_id: Uuid,
+    pub away_team_id: Option<Uuid>,
+}
+
+/// Pair up teams for every round of a league
+///
+/// Rounds are numbered from 1. The schedule only depends on the order of
+/// team_ids, so the same teams always get the same schedule.
+pub fn round_robin(team_ids: &[Uuid], rounds: i32) -> Vec<Pairing> {
+    let mut slots: Vec<Option<Uuid>> = team_ids.iter().copied().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.push(None);
+    }
+    if slots.len() < 2 {
+        return vec![];
+    }
+
+    let slot_count = slots.len();
+    let rounds_per_cycle = slot_count - 1;
+    let mut pairings = vec![];
+
+    for round in 0..rounds.max(0) as usize {
+        let cycle = round / rounds_per_cycle;
+        let turn = round % rounds_per_cycle;
+
+        let mut rotated = slots[1..].to_vec();
+        rotated.rotate_right(turn);
+        let mut order = vec![slots[0]];
+        order.extend(rotated);
+
+        for index in 0..slot_count / 2 {
+            let (mut home, mut away) = (order[index], order[slot_count - 1 - index]);
+            // Keep the fixed team from always playing at home
+            if index == 0 && turn % 2 == 1 {
+                std::mem::swap(&mut home, &mut away);
+            }
+            if cycle % 2 == 1 {
+                std::mem::swap(&mut home, &mut away);
+            }
+
+            let (home_team_id, away_team_id) = match (home, away) {
+                (Some(home), away) => (home, away),
+                (None, Some(away)) => (away, None),
+                (None, None) => continue,
+            };
+            pairings.push(Pairing {
+                round: round as i32 + 1,
+                home_team_id,
+                away_team_id,
+            });
+        }
+    }
+
+    pairings
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    pub fn team_ids(count: usize) -> Vec<Uuid> {
+        (0..count).map(|_| Uuid::new_v4()).collect()
+    }
+
+    fn in_round(pairings: &[Pairing], round: i32) -> Vec<&Pairing> {
+        pairings
+            .iter()
+            .filter(|pairing| pairing.round == round)
+            .collect()
+    }
+
+    fn opponents(pairings: &[Pairing], team_id: Uuid) -> Vec<Option<Uuid>> {
+        pairings
+            .iter()
+            .filter_map(|pairing| {
+                if pairing.home_team_id == team_id {
+                    Some(pairing.away_team_id)
+                } else if pairing.away_team_id == Some(team_id) {
+                    Some(Some(pairing.home_team_id))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_plays_every_team_once_per_cycle() {
+        let teams = team_ids(6);
+        let pairings = round_robin(&teams, 5);
+        for team_id in &teams {
+            let opponents: HashSet<Option<Uuid>> =
+                opponents(&pairings, *team_id).into_iter().collect();
+            assert_eq!(opponents.len(), 5);
+            assert!(!opponents.contains(&None));
+            assert!(!opponents.contains(&Some(*team_id)));
+        }
+    }
+
+    #[test]
+    fn it_schedules_every_team_once_per_round() {
+        let teams = team_ids(6);
+        let pairings = round_robin(&teams, 5);
+        for round in 1..=5 {
+            let mut playing = HashSet::new();
+            for pairing in in_round(&pairings, round) {
+                assert!(playing.insert(pairing.home_team_id));
+                assert!(playing.insert(pairing.away_team_id.unwrap()));
+            }
+            assert_eq!(playing.len(), 6);
+        }
+    }
+
+    #[test]
+    fn it_gives_byes_to_odd_numbers_of_teams() {
+        let teams = team_ids(5);
+        let pairings = round_robin(&teams, 5);
+        for round in 1..=5 {
+            let byes = in_round(&pairings, round)
+                .into_iter()
+                .filter(|pairing| pairing.away_team_id.is_none())
+                .count();
+            assert_eq!(byes, 1);
+        }
+        for team_id in &teams {
+            let byes = opponents(&pairings, *team_id)
+                .into_iter()
+                .filter(Option::is_none)
+                .count();
+            assert_eq!(byes, 1);
+        }
+    }
+
+    #[test]
+    fn it_repeats_cycles_with_home_and_away_swapped() {
+        let teams = team_ids(4);
+        let pairings = round_robin(&teams, 6);
+        assert_eq!(pairings.len(), 12);
+        for (first, second) in in_round(&pairings, 1)
+            .into_iter()
+            .zip(in_round(&pairings, 4))
+        {
+            assert_eq!(first.home_team_id, second.away_team_id.unwrap());
+            assert_eq!(first.away_team_id.unwrap(), second.home_team_id);
+        }
+    }
+
+    #[test]
+    fn it_is_deterministic() {
+        let teams = team_ids(7);
+        assert_eq!(round_robin(&teams, 10), round_robin(&teams, 10));
+    }
+
+    #[test]
+    fn it_doesnt_schedule_without_teams() {
+        assert!(round_robin(&[], 4).is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+//! Jobs that run on their own, outside of any request
+//!
+//! Every SCHEDULER_INTERVAL seconds the scheduler plans the jobs of every
+//! league into the jobs table, then runs the ones that are due: closing a
+//! round once it ended, which scores it too, queueing reminders for the
+//! players a day before, and auto-picking for teams that ran out of time in
+//! a draft.
+//!
+//! Jobs live in the database so they survive restarts. Each one is claimed
+//! by a single worker, with `FOR UPDATE SKIP LOCKED`, for a lease of
+//! LEASE_SECONDS: several workers or instances can run schedulers without
+//! running a job twice, and a job whose worker died is picked up again once
+//! its lease runs out. Failed jobs are retried up to MAX_ATTEMPTS times.
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::database::{init_pool, PoolType};
+use crate::errors::ApiError;
+use crate::managers::drafts::expire_picks;
+use crate::managers::jobs::{claim, fail, finish, plan_all};
+use crate::managers::leagues::close_round;
+use crate::managers::notifications::queue_round_reminders;
+use crate::models::jobs::Job;
+use actix::{Actor, AsyncContext, Context};
+use actix_web::web::block;
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use std::time::Duration;
+
+pub const CLOSE_ROUND: &str = "close_round";
+pub const ROUND_REMINDER: &str = "round_reminder";
+pub const EXPIRE_PICKS: &str = "expire_picks";
+
+pub const PENDING: &str = "pending";
+pub const RUNNING: &str = "running";
+pub const DONE: &str = "done";
+pub const FAILED: &str = "failed";
+
+/// Seconds between two runs of the scheduler when SCHEDULER_INTERVAL isn't set
+pub const DEFAULT_INTERVAL: u64 = 60;
+
+/// Hours before a round closes that its players are reminded
+pub const REMINDER_HOURS: i64 = 24;
+
+/// Seconds a worker has to finish a job before another one claims it
+pub const LEASE_SECONDS: i64 = 600;
+
+/// Attempts at a job before it's given up on
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Seconds to wait before retrying a failed job, times the attempts so far
+pub const RETRY_SECONDS: i64 = 60;
+
+/// Jobs claimed at once by a worker
+const BATCH_SIZE: i64 = 20;
+
+pub struct Scheduler {
+    pool: PoolType,
+    cache: Cache,
+    interval: Duration,
+}
+
+impl Actor for Scheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.interval, |scheduler, ctx| {
+            let pool = scheduler.pool.clone();
+            let cache = scheduler.cache.clone();
+            ctx.spawn(actix::fut::wrap_future(async move {
+                if let Err(error) = block(move || tick(&pool, &cache)).await {
+                    log::error!("Running scheduled jobs failed: {:?}", error);
+                }
+            }));
+        });
+    }
+}
+
+/// Start the scheduler, unless SCHEDULER_INTERVAL is 0
+pub fn start(config: &Config) {
+    let seconds = config.scheduler_interval.unwrap_or(DEFAULT_INTERVAL);
+    if seconds == 0 {
+        return;
+    }
+    let pool = init_pool::<PgConnection>(config.clone()).expect("Failed to create connection pool");
+    let scheduler = Scheduler {
+        pool,
+        cache: Cache::from_config(config),
+        interval: Duration::from_secs(seconds),
+    };
+    scheduler.start();
+}
+
+/// Plan every league's jobs, then run the ones that are due
+pub fn tick(pool: &PoolType, cache: &Cache) -> Result<Vec<Job>, ApiError> {
+    plan_all(pool)?;
+    run_due(pool, cache, Utc::now().naive_utc())
+}
+
+/// Run the jobs due by now, returns them as they ended up
+pub fn run_due(pool: &PoolType, cache: &Cache, now: NaiveDateTime) -> Result<Vec<Job>, ApiError> {
+    claim(pool, now, BATCH_SIZE)?
+        .into_iter()
+        .map(|job| match run(pool, cache, &job, now) {
+            Ok(()) => finish(pool, job.id, now),
+            Err(error) => {
+                log::warn!("Job {} {} failed: {:?}", job.kind, job.id, error);
+                fail(pool, &job, &format!("{:?}", error), now)
+            }
+        })
+        .collect()
+}
+
+fn run(pool: &PoolType, cache: &Cache, job: &Job, now: NaiveDateTime) -> Result<(), ApiError> {
+    match job.kind.as_str() {
+        CLOSE_ROUND => match close_round(pool, cache, job.league_id, job.round) {
+            // Closed by a commissioner in the meantime
+            Ok(_) | Err(ApiError::Conflict(_)) => Ok(()),
+            Err(error) => Err(error),
+        },
+        ROUND_REMINDER => queue_round_reminders(pool, job.league_id, job.round).map(|_| ()),
+        EXPIRE_PICKS => expire_picks(pool, job.league_id, now).map(|_| ()),
+        kind => Err(ApiError::InternalServerError(format!(
+            "Unknown kind of job {}",
+            kind
+        ))),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::managers::drafts::tests::start_draft;
+    use crate::managers::jobs::plan;
+    use crate::managers::leagues::find_with_details;
+    use crate::managers::leagues::tests::create_league_with_teams;
+    use crate::models::notifications::Notification;
+    use crate::schema::{jobs, leagues, notifications};
+    use crate::tests::helpers::tests::get_pool;
+    use chrono::{Duration, SubsecRound};
+    use diesel::prelude::*;
+    use std::sync::{Mutex, MutexGuard};
+    use uuid::Uuid;
+
+    lazy_static! {
+        /// Claiming takes every due job, so tests that claim run one at a time
+        static ref CLAIMING: Mutex<()> = Mutex::new(());
+    }
+
+    fn claiming() -> MutexGuard<'static, ()> {
+        CLAIMING
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The current time, to the microsecond Postgres keeps timestamps to
+    fn now() -> NaiveDateTime {
+        Utc::now().naive_utc().trunc_subsecs(6)
+    }
+
+    /// Move a league's start, so its first round ends at round_end
+    fn end_first_round_at(league_id: Uuid, round_end: NaiveDateTime) {
+        let conn = get_pool().get().unwrap();
+        diesel::update(leagues::table.find(league_id))
+            .set(leagues::start.eq(round_end - Duration::days(7)))
+            .execute(&conn)
+            .unwrap();
+    }
+
+    fn league_jobs(league_id: Uuid) -> Vec<Job> {
+        let conn = get_pool().get().unwrap();
+        jobs::table
+            .filter(jobs::league_id.eq(league_id))
+            .order((jobs::round, jobs::kind))
+            .load(&conn)
+            .unwrap()
+    }
+
+    fn reminders(league_id: Uuid) -> Vec<Notification> {
+        let conn = get_pool().get().unwrap();
+        notifications::table
+            .filter(notifications::league_id.eq(league_id))
+            .load(&conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn it_plans_the_next_round_once() {
+        let (league_id, _) = create_league_with_teams(2);
+        let planned = plan(&get_pool(), league_id).unwrap();
+        let kinds: Vec<&str> = planned.iter().map(|job| job.kind.as_str()).collect();
+        assert_eq!(kinds, vec![CLOSE_ROUND, ROUND_REMINDER]);
+        assert_eq!(planned[0].round, 1);
+        assert_eq!(
+            planned[0].run_at - planned[1].run_at,
+            Duration::hours(REMINDER_HOURS)
+        );
+
+        let replanned = plan(&get_pool(), league_id).unwrap();
+        assert_eq!(replanned, planned);
+        assert_eq!(league_jobs(league_id).len(), 2);
+    }
+
+    #[test]
+    fn it_moves_pending_jobs_with_the_league() {
+        let (league_id, _) = create_league_with_teams(2);
+        plan(&get_pool(), league_id).unwrap();
+        let round_end = now() + Duration::days(3);
+        end_first_round_at(league_id, round_end);
+
+        let planned = plan(&get_pool(), league_id).unwrap();
+        assert_eq!(planned[0].run_at, round_end);
+        assert_eq!(league_jobs(league_id).len(), 2);
+    }
+
+    #[test]
+    fn it_closes_rounds_that_ended_and_reminds_players() {
+        let _claiming = claiming();
+        let (league_id, teams) = create_league_with_teams(2);
+        end_first_round_at(league_id, now() - Duration::minutes(5));
+        plan(&get_pool(), league_id).unwrap();
+
+        // Reminders go out before the round is closed
+        let reminded = now() - Duration::minutes(10);
+        run_due(&get_pool(), &Cache::disabled(), reminded).unwrap();
+        assert_eq!(reminders(league_id).len(), teams.len());
+        assert!(reminders(league_id)[0].message.starts_with("Round 1 of"));
+
+        run_due(&get_pool(), &Cache::disabled(), now()).unwrap();
+        let league = find_with_details(&get_pool(), league_id).unwrap();
+        assert_eq!(league.current_round, 1);
+        let jobs = league_jobs(league_id);
+        assert!(jobs
+            .iter()
+            .all(|job| job.status == DONE && job.attempts == 1));
+
+        // The next round is planned, and nothing runs twice
+        plan(&get_pool(), league_id).unwrap();
+        run_due(&get_pool(), &Cache::disabled(), now()).unwrap();
+        let jobs = league_jobs(league_id);
+        assert_eq!(jobs.len(), 4);
+        assert_eq!(jobs[2].round, 2);
+        assert_eq!(jobs[2].status, PENDING);
+        assert_eq!(reminders(league_id).len(), teams.len());
+    }
+
+    #[test]
+    fn it_doesnt_hand_out_a_job_twice() {
+        let _claiming = claiming();
+        let (league_id, _) = create_league_with_teams(2);
+        end_first_round_at(league_id, now() - Duration::minutes(5));
+        let planned = plan(&get_pool(), league_id).unwrap();
+        let now = now();
+
+        let claimed = claim(&get_pool(), now, 100).unwrap();
+        assert!(claimed.iter().any(|job| job.id == planned[0].id));
+        let claimed_again = claim(&get_pool(), now, 100).unwrap();
+        assert!(claimed_again.iter().all(|job| job.id != planned[0].id));
+
+        // Until the lease of the worker that claimed it runs out
+        let later = now + Duration::seconds(LEASE_SECONDS + 1);
+        let reclaimed = claim(&get_pool(), later, 100).unwrap();
+        let job = reclaimed
+            .iter()
+            .find(|job| job.id == planned[0].id)
+            .unwrap();
+        assert_eq!(job.attempts, 2);
+        for job in reclaimed {
+            finish(&get_pool(), job.id, later).unwrap();
+        }
+    }
+
+    #[test]
+    fn it_retries_failed_jobs_then_gives_up() {
+        let _claiming = claiming();
+        let (league_id, _) = create_league_with_teams(2);
+        let now = now();
+        let unknown = Job {
+            kind: "unknown".into(),
+            run_at: now - Duration::minutes(1),
+            ..plan(&get_pool(), league_id).unwrap()[0].clone()
+        };
+        let conn = get_pool().get().unwrap();
+        diesel::insert_into(jobs::table)
+            .values(&Job {
+                id: Uuid::new_v4(),
+                ..unknown
+            })
+            .execute(&conn)
+            .unwrap();
+
+        run_due(&get_pool(), &Cache::disabled(), now).unwrap();
+        let job = league_jobs(league_id).pop().unwrap();
+        assert_eq!(job.status, PENDING);
+        assert_eq!(job.run_at, now + Duration::seconds(RETRY_SECONDS));
+        assert_eq!(
+            job.last_error.as_deref(),
+            Some("InternalServerError(\"Unknown kind of job unknown\")")
+        );
+
+        diesel::update(jobs::table.find(job.id))
+            .set((jobs::attempts.eq(MAX_ATTEMPTS - 1), jobs::run_at.eq(now)))
+            .execute(&conn)
+            .unwrap();
+        run_due(&get_pool(), &Cache::disabled(), now).unwrap();
+        let job = league_jobs(league_id).pop().unwrap();
+        assert_eq!(job.status, FAILED);
+        assert_eq!(job.attempts, MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn it_keeps_the_delay_of_a_retry_when_planning_again() {
+        let (league_id, _) = create_league_with_teams(2);
+        let now = now();
+        let job = plan(&get_pool(), league_id).unwrap()[0].clone();
+        let conn = get_pool().get().unwrap();
+        let tried: Job = diesel::update(jobs::table.find(job.id))
+            .set((jobs::status.eq(RUNNING), jobs::attempts.eq(1)))
+            .get_result(&conn)
+            .unwrap();
+        let failed = fail(&get_pool(), &tried, "boom", now).unwrap();
+        assert_eq!(failed.run_at, now + Duration::seconds(RETRY_SECONDS));
+
+        let replanned = plan(&get_pool(), league_id).unwrap()[0].clone();
+        assert_eq!(replanned.id, job.id);
+        assert_eq!(replanned.run_at, failed.run_at);
+    }
+
+    #[test]
+    fn it_expires_picks_when_time_runs_out() {
+        let _claiming = claiming();
+        let (league_id, _) = start_draft();
+        let planned = plan(&get_pool(), league_id).unwrap();
+        let expire = planned.iter().find(|job| job.kind == EXPIRE_PICKS).unwrap();
+        assert_eq!(expire.round, 1);
+
+        let later = expire.run_at + Duration::seconds(1);
+        run_due(&get_pool(), &Cache::disabled(), later).unwrap();
+        let draft = crate::managers::drafts::find(&get_pool(), league_id).unwrap();
+        assert_eq!(draft.current_pick, 1);
+        assert!(draft.picks[0].auto_picked);
+
+        let replanned = plan(&get_pool(), league_id).unwrap();
+        assert!(replanned
+            .iter()
+            .any(|job| job.kind == EXPIRE_PICKS && job.round == 2));
+    }
+}
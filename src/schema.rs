@@ -1,8 +1,87 @@
+table! {
+    draft_picks (id) {
+        id -> Uuid,
+        draft_id -> Uuid,
+        pick_number -> Int4,
+        team_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        auto_picked -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    draft_queues (id) {
+        id -> Uuid,
+        draft_id -> Uuid,
+        team_id -> Uuid,
+        user_id -> Uuid,
+        rank -> Int4,
+    }
+}
+
+table! {
+    draft_slots (id) {
+        id -> Uuid,
+        draft_id -> Uuid,
+        team_id -> Uuid,
+        position -> Int4,
+    }
+}
+
+table! {
+    drafts (id) {
+        id -> Uuid,
+        league_id -> Uuid,
+        mode -> Varchar,
+        rounds -> Int4,
+        pick_seconds -> Int4,
+        status -> Varchar,
+        current_pick -> Int4,
+        pick_started_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    jobs (id) {
+        id -> Uuid,
+        kind -> Varchar,
+        league_id -> Uuid,
+        round -> Int4,
+        run_at -> Timestamp,
+        status -> Varchar,
+        attempts -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        finished_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    league_commissioners (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        league_id -> Uuid,
+    }
+}
+
 table! {
     league_rulesets (id) {
         id -> Uuid,
         points_per_mile -> Int4,
         league_id -> Uuid,
+        pace_bonus_target -> Nullable<Int4>,
+        pace_bonus_per_mile -> Int4,
+        points_per_100_feet -> Int4,
+        min_run_distance -> Float8,
+        daily_mileage_cap -> Nullable<Float8>,
+        long_run_distance -> Nullable<Float8>,
+        long_run_multiplier -> Float8,
+        tie_breakers -> Array<Text>,
     }
 }
 
@@ -13,6 +92,88 @@ table! {
         start -> Timestamp,
         rounds -> Int4,
         current_round -> Int4,
+        timezone -> Varchar,
+        round_days -> Int4,
+    }
+}
+
+table! {
+    linked_accounts (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Varchar,
+        provider_user_id -> Varchar,
+        access_token -> Text,
+        refresh_token -> Text,
+        expires_at -> Timestamp,
+        sync_cursor -> Nullable<Timestamp>,
+        synced_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    matchups (id) {
+        id -> Uuid,
+        league_id -> Uuid,
+        round -> Int4,
+        home_team_id -> Uuid,
+        away_team_id -> Nullable<Uuid>,
+        home_points -> Nullable<Float8>,
+        away_points -> Nullable<Float8>,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        league_id -> Uuid,
+        kind -> Varchar,
+        round -> Int4,
+        message -> Text,
+        sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    player_scores (id) {
+        id -> Uuid,
+        league_id -> Uuid,
+        team_id -> Uuid,
+        user_id -> Uuid,
+        round -> Int4,
+        miles -> Float8,
+        points -> Float8,
+    }
+}
+
+table! {
+    run_laps (id) {
+        id -> Uuid,
+        run_id -> Uuid,
+        lap_number -> Int4,
+        started_at -> Timestamp,
+        distance -> Float8,
+        duration -> Int4,
+    }
+}
+
+table! {
+    runs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        distance -> Float8,
+        duration -> Int4,
+        started_at -> Timestamp,
+        elevation_gain -> Nullable<Float8>,
+        heart_rate -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        provider -> Nullable<Varchar>,
+        provider_activity_id -> Nullable<Varchar>,
     }
 }
 
@@ -32,6 +193,17 @@ table! {
     }
 }
 
+table! {
+    team_scores (id) {
+        id -> Uuid,
+        league_id -> Uuid,
+        team_id -> Uuid,
+        round -> Int4,
+        miles -> Float8,
+        points -> Float8,
+    }
+}
+
 table! {
     teams (id) {
         id -> Uuid,
@@ -55,18 +227,54 @@ table! {
     }
 }
 
+joinable!(draft_picks -> drafts (draft_id));
+joinable!(draft_picks -> teams (team_id));
+joinable!(draft_picks -> users (user_id));
+joinable!(draft_queues -> drafts (draft_id));
+joinable!(draft_queues -> teams (team_id));
+joinable!(draft_queues -> users (user_id));
+joinable!(draft_slots -> drafts (draft_id));
+joinable!(draft_slots -> teams (team_id));
+joinable!(drafts -> leagues (league_id));
+joinable!(jobs -> leagues (league_id));
+joinable!(league_commissioners -> leagues (league_id));
+joinable!(league_commissioners -> users (user_id));
 joinable!(league_rulesets -> leagues (league_id));
+joinable!(linked_accounts -> users (user_id));
+joinable!(matchups -> leagues (league_id));
+joinable!(notifications -> leagues (league_id));
+joinable!(notifications -> users (user_id));
+joinable!(player_scores -> leagues (league_id));
+joinable!(player_scores -> teams (team_id));
+joinable!(player_scores -> users (user_id));
+joinable!(run_laps -> runs (run_id));
+joinable!(runs -> users (user_id));
 joinable!(team_owners -> teams (team_id));
 joinable!(team_owners -> users (user_id));
 joinable!(team_players -> teams (team_id));
 joinable!(team_players -> users (user_id));
+joinable!(team_scores -> leagues (league_id));
+joinable!(team_scores -> teams (team_id));
 joinable!(teams -> leagues (league_id));
 
 allow_tables_to_appear_in_same_query!(
+    draft_picks,
+    draft_queues,
+    draft_slots,
+    drafts,
+    jobs,
+    league_commissioners,
     league_rulesets,
     leagues,
+    linked_accounts,
+    matchups,
+    notifications,
+    player_scores,
+    run_laps,
+    runs,
     team_owners,
     team_players,
+    team_scores,
     teams,
     users,
 );
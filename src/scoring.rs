@@ -0,0 +1,357 @@
+//! Scoring rules that turn runs into points.
+//!
+//! Everything in here is a pure function of the league's ruleset, calendar,
+//! rosters and runs so a round can be replayed at any time and always give
+//! the same result. Loading the inputs and persisting the results is left to
+//! `managers::leagues`.
+
+use crate::calendar::Calendar;
+use crate::models::leagues::Ruleset;
+use crate::models::runs::Run;
+use crate::models::teams::{Player, Team};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A player's total for a round
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerTotal {
+    pub user_id: Uuid,
+    pub team_id: Uuid,
+    pub miles: f64,
+    pub points: f64,
+}
+
+/// A team's total for a round
+#[derive(Clone, Debug, PartialEq)]
+pub struct TeamTotal {
+    pub team_id: Uuid,
+    pub miles: f64,
+    pub points: f64,
+}
+
+/// Everything scored in a round
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundTotals {
+    pub teams: Vec<TeamTotal>,
+    pub players: Vec<PlayerTotal>,
+}
+
+/// Points earned for a single run, before the daily mileage cap
+///
+/// Runs shorter than the ruleset's minimum distance earn nothing.
+pub fn score_run(ruleset: &Ruleset, run: &Run) -> f64 {
+    if run.distance < ruleset.min_run_distance {
+        return 0.0;
+    }
+
+    let mut points_per_mile = f64::from(ruleset.points_per_mile);
+    if let Some(target) = ruleset.pace_bonus_target {
+        if f64::from(run.duration) / run.distance <= f64::from(target) {
+            points_per_mile += f64::from(ruleset.pace_bonus_per_mile);
+        }
+    }
+    let elevation_points =
+        run.elevation_gain.unwrap_or(0.0) / 100.0 * f64::from(ruleset.points_per_100_feet);
+    let points = run.distance * points_per_mile + elevation_points;
+
+    match ruleset.long_run_distance {
+        Some(long_run_distance) if run.distance >= long_run_distance => {
+            points * ruleset.long_run_multiplier
+        }
+        _ => points,
+    }
+}
+
+/// Score all of a player's runs, returning the miles and points that count
+///
+/// Only the first daily_mileage_cap miles run each day, in the league's
+/// timezone, count. A run that crosses the cap earns points for the share of
+/// it under the cap.
+pub fn score_runs(ruleset: &Ruleset, calendar: &Calendar, runs: &[&Run]) -> (f64, f64) {
+    let mut counted_runs: Vec<&Run> = runs
+        .iter()
+        .copied()
+        .filter(|run| run.distance >= ruleset.min_run_distance)
+        .collect();
+    counted_runs.sort_by_key(|run| (run.started_at, run.id));
+
+    let mut miles_by_day: HashMap<NaiveDate, f64> = HashMap::new();
+    counted_runs
+        .iter()
+        .fold((0.0, 0.0), |(miles, points), run| {
+            let day = calendar.local(run.started_at).naive_local().date();
+            let day_miles = miles_by_day.entry(day).or_insert(0.0);
+            let counted_miles = match ruleset.daily_mileage_cap {
+                Some(cap) => run.distance.min((cap - *day_miles).max(0.0)),
+                None => run.distance,
+            };
+            *day_miles += counted_miles;
+            let counted_points = score_run(ruleset, run) * counted_miles / run.distance;
+            (miles + counted_miles, points + counted_points)
+        })
+}
+
+/// Score every rostered player and team for a round of a league's calendar
+///
+/// Only runs inside of the round's window count towards it. Miles and points
+/// are the ones left after applying the ruleset. Runs by users that aren't on
+/// a roster are ignored, and every rostered player and team is included even
+/// if they didn't run at all. Results are sorted by id so they're stable.
+pub fn score_round(
+    ruleset: &Ruleset,
+    calendar: &Calendar,
+    round: i32,
+    teams: &[Team],
+    players: &[Player],
+    runs: &[Run],
+) -> RoundTotals {
+    let (start, end) = calendar.round_window(round);
+    let mut runs_by_user: HashMap<Uuid, Vec<&Run>> = HashMap::new();
+    for run in runs
+        .iter()
+        .filter(|run| run.started_at >= start && run.started_at < end)
+    {
+        runs_by_user.entry(run.user_id).or_default().push(run);
+    }
+
+    let mut player_totals: Vec<PlayerTotal> = players
+        .iter()
+        .map(|player| {
+            let player_runs = runs_by_user
+                .get(&player.user_id)
+                .map(Vec::as_slice)
+                .unwrap_or(&[]);
+            let (miles, points) = score_runs(ruleset, calendar, player_runs);
+            PlayerTotal {
+                user_id: player.user_id,
+                team_id: player.team_id,
+                miles,
+                points,
+            }
+        })
+        .collect();
+    player_totals.sort_by_key(|total| (total.team_id, total.user_id));
+
+    let mut team_totals: Vec<TeamTotal> = teams
+        .iter()
+        .map(|team| {
+            let roster = player_totals
+                .iter()
+                .filter(|total| total.team_id == team.id);
+            TeamTotal {
+                team_id: team.id,
+                miles: roster.clone().map(|total| total.miles).sum(),
+                points: roster.map(|total| total.points).sum(),
+            }
+        })
+        .collect();
+    team_totals.sort_by_key(|total| total.team_id);
+
+    RoundTotals {
+        teams: team_totals,
+        players: player_totals,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::calendar::tests::calendar;
+    use crate::calendar::{DEFAULT_ROUND_DAYS, DEFAULT_TIMEZONE};
+    use chrono::{Duration, NaiveDateTime, Utc};
+
+    pub fn league_start() -> NaiveDateTime {
+        NaiveDate::from_ymd(2020, 5, 4).and_hms(0, 0, 0)
+    }
+
+    /// The calendar of a weekly league in UTC
+    fn weekly() -> Calendar {
+        Calendar {
+            start: league_start(),
+            ..calendar(DEFAULT_TIMEZONE, DEFAULT_ROUND_DAYS)
+        }
+    }
+
+    pub fn ruleset(points_per_mile: i32) -> Ruleset {
+        Ruleset {
+            id: Uuid::new_v4(),
+            points_per_mile,
+            league_id: Uuid::nil(),
+            pace_bonus_target: None,
+            pace_bonus_per_mile: 0,
+            points_per_100_feet: 0,
+            min_run_distance: 0.0,
+            daily_mileage_cap: None,
+            long_run_distance: None,
+            long_run_multiplier: 1.0,
+            tie_breakers: vec![],
+        }
+    }
+
+    pub fn team(id: Uuid) -> Team {
+        Team {
+            id,
+            name: "Scoring Team".into(),
+            wins: 0,
+            losses: 0,
+            ties: 0,
+            league_id: Uuid::nil(),
+        }
+    }
+
+    pub fn player(team_id: Uuid, user_id: Uuid) -> Player {
+        Player {
+            id: Uuid::new_v4(),
+            user_id,
+            team_id,
+        }
+    }
+
+    pub fn run(user_id: Uuid, distance: f64, started_at: NaiveDateTime) -> Run {
+        Run {
+            id: Uuid::new_v4(),
+            user_id,
+            distance,
+            duration: (distance * 540.0) as i32,
+            started_at,
+            elevation_gain: None,
+            heart_rate: None,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            provider: None,
+            provider_activity_id: None,
+        }
+    }
+
+    #[test]
+    fn it_scores_a_run() {
+        let run = run(Uuid::new_v4(), 3.5, league_start());
+        assert_eq!(score_run(&ruleset(10), &run), 35.0);
+    }
+
+    #[test]
+    fn it_adds_a_pace_bonus() {
+        let mut rules = ruleset(10);
+        rules.pace_bonus_target = Some(480);
+        rules.pace_bonus_per_mile = 5;
+        let mut fast = run(Uuid::new_v4(), 2.0, league_start());
+        fast.duration = 900;
+        let mut slow = fast.clone();
+        slow.duration = 1200;
+        assert_eq!(score_run(&rules, &fast), 30.0);
+        assert_eq!(score_run(&rules, &slow), 20.0);
+    }
+
+    #[test]
+    fn it_adds_elevation_points() {
+        let mut rules = ruleset(10);
+        rules.points_per_100_feet = 2;
+        let mut hilly = run(Uuid::new_v4(), 2.0, league_start());
+        hilly.elevation_gain = Some(350.0);
+        assert_eq!(score_run(&rules, &hilly), 27.0);
+    }
+
+    #[test]
+    fn it_ignores_runs_under_the_minimum_distance() {
+        let mut rules = ruleset(10);
+        rules.min_run_distance = 1.0;
+        let short = run(Uuid::new_v4(), 0.5, league_start());
+        assert_eq!(score_run(&rules, &short), 0.0);
+        assert_eq!(score_runs(&rules, &weekly(), &[&short]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn it_multiplies_long_runs() {
+        let mut rules = ruleset(10);
+        rules.long_run_distance = Some(10.0);
+        rules.long_run_multiplier = 1.5;
+        let long = run(Uuid::new_v4(), 10.0, league_start());
+        let short = run(Uuid::new_v4(), 9.0, league_start());
+        assert_eq!(score_run(&rules, &long), 150.0);
+        assert_eq!(score_run(&rules, &short), 90.0);
+    }
+
+    #[test]
+    fn it_caps_daily_mileage() {
+        let mut rules = ruleset(10);
+        rules.daily_mileage_cap = Some(10.0);
+        let runner = Uuid::new_v4();
+        let morning = run(runner, 6.0, league_start() + Duration::hours(7));
+        let evening = run(runner, 6.0, league_start() + Duration::hours(18));
+        let next_day = run(runner, 6.0, league_start() + Duration::hours(31));
+        let (miles, points) = score_runs(&rules, &weekly(), &[&evening, &next_day, &morning]);
+        assert_eq!(miles, 16.0);
+        assert_eq!(points, 160.0);
+    }
+
+    #[test]
+    fn it_caps_daily_mileage_in_the_leagues_timezone() {
+        let mut rules = ruleset(10);
+        rules.daily_mileage_cap = Some(10.0);
+        let denver = Calendar {
+            start: league_start(),
+            ..calendar("America/Denver", DEFAULT_ROUND_DAYS)
+        };
+        let runner = Uuid::new_v4();
+        // 7am and 7pm in Denver, the evening run is on the next day in UTC
+        let morning = run(runner, 6.0, league_start() + Duration::hours(13));
+        let evening = run(runner, 6.0, league_start() + Duration::hours(25));
+        assert_eq!(score_runs(&rules, &denver, &[&morning, &evening]).0, 10.0);
+        assert_eq!(score_runs(&rules, &weekly(), &[&morning, &evening]).0, 12.0);
+    }
+
+    #[test]
+    fn it_scores_a_round() {
+        let (team_a, team_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (runner_1, runner_2, runner_3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let teams = vec![team(team_a), team(team_b)];
+        let players = vec![
+            player(team_a, runner_1),
+            player(team_a, runner_2),
+            player(team_b, runner_3),
+        ];
+        let day = |days| league_start() + Duration::days(days);
+        let runs = vec![
+            run(runner_1, 3.0, day(0)),
+            run(runner_1, 5.0, day(3)),
+            run(runner_2, 2.0, day(6)),
+            // outside of round 1
+            run(runner_3, 10.0, day(7)),
+            // not on a roster
+            run(Uuid::new_v4(), 20.0, day(1)),
+        ];
+
+        let totals = score_round(&ruleset(10), &weekly(), 1, &teams, &players, &runs);
+
+        let team_a_total = totals.teams.iter().find(|t| t.team_id == team_a).unwrap();
+        let team_b_total = totals.teams.iter().find(|t| t.team_id == team_b).unwrap();
+        assert_eq!(team_a_total.miles, 10.0);
+        assert_eq!(team_a_total.points, 100.0);
+        assert_eq!(team_b_total.points, 0.0);
+        assert_eq!(totals.players.len(), 3);
+        let runner_1_total = totals
+            .players
+            .iter()
+            .find(|p| p.user_id == runner_1)
+            .unwrap();
+        assert_eq!(runner_1_total.points, 80.0);
+    }
+
+    #[test]
+    fn it_replays_a_round_deterministically() {
+        let team_id = Uuid::new_v4();
+        let runner = Uuid::new_v4();
+        let teams = vec![team(team_id)];
+        let players = vec![player(team_id, runner)];
+        let runs = vec![run(runner, 4.0, league_start() + Duration::hours(8))];
+        let weekly = weekly();
+
+        let first = score_round(&ruleset(10), &weekly, 1, &teams, &players, &runs);
+        let replayed = score_round(&ruleset(10), &weekly, 1, &teams, &players, &runs);
+        assert_eq!(first, replayed);
+
+        let rescored = score_round(&ruleset(25), &weekly, 1, &teams, &players, &runs);
+        assert_eq!(rescored.teams[0].points, 100.0);
+    }
+}
@@ -1,8 +1,15 @@
 //! Spin up a HTTPServer
 
+use crate::auth::get_identity_service;
+use crate::cache::add_cache;
 use crate::config::CONFIG;
 use crate::database::add_pool;
+use crate::middleware::request_id::RequestIds;
+use crate::providers::add_providers;
 use crate::routes::routes;
+use crate::scheduler;
+use crate::sessions::add_sessions;
+use crate::sync;
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, App, HttpServer};
 use listenfd::ListenFd;
@@ -13,11 +20,20 @@ pub async fn server() -> std::io::Result<()> {
 
     let mut listenfd = ListenFd::from_env();
 
+    // Run scheduled jobs and sync linked accounts in the background
+    scheduler::start(&CONFIG);
+    sync::start(&CONFIG);
+
     let app = move || {
         App::new()
             .wrap(Cors::new().finish())
             .wrap(Logger::default())
+            .wrap(get_identity_service())
+            .wrap(RequestIds)
             .configure(add_pool)
+            .configure(add_cache)
+            .configure(add_sessions)
+            .configure(add_providers)
             .configure(routes)
     };
     // wraps on new app are ordered from most internal to most external
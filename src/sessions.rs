@@ -0,0 +1,356 @@
+//! Server-side sessions, an alternative to JWTs
+//!
+//! With AUTH_MODE=session, logging in stores the user in a session in Redis
+//! instead of signing a JWT. The token handed out, and kept in the identity
+//! cookie, is the session's random key: logging out destroys the session for
+//! good, and users can list their sessions and revoke any of them. Sessions
+//! live for SESSION_TIMEOUT minutes after they were last used.
+//!
+//! Like the cache, Redis is called synchronously so sessions have to be used
+//! from inside of `web::block`.
+
+use crate::cache::{send, start_redis};
+use crate::config::{Config, CONFIG};
+use crate::errors::ApiError;
+use crate::models::sessions::Session;
+use crate::models::users::AuthUser;
+use actix::Addr;
+use actix_redis::RedisActor;
+use actix_web::web::ServiceConfig;
+use chrono::Utc;
+use rand::Rng;
+use redis_async::resp::RespValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Random bytes in a session token
+const TOKEN_LENGTH: usize = 32;
+
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    Jwt,
+    Session,
+}
+
+#[derive(Clone)]
+pub struct Sessions {
+    backend: SessionBackend,
+    ttl: Duration,
+}
+
+#[derive(Clone)]
+pub enum SessionBackend {
+    Redis(Addr<RedisActor>),
+    /// In-process store keyed by token, for tests
+    Memory(Arc<Mutex<SessionStore>>),
+    /// Logins hand out JWTs instead
+    Disabled,
+}
+
+impl Sessions {
+    /// Keep sessions in Redis when AUTH_MODE=session, use JWTs otherwise
+    pub fn from_config(config: &Config) -> Self {
+        let ttl = (config.session_timeout * 60) as u64;
+        match (config.auth_mode, &config.redis_url) {
+            (Some(AuthMode::Session), Some(redis_url)) if !redis_url.is_empty() => {
+                Sessions::new(SessionBackend::Redis(start_redis(redis_url)), ttl)
+            }
+            (Some(AuthMode::Session), _) => {
+                panic!("Configuration Error: AUTH_MODE=session needs a REDIS_URL")
+            }
+            _ => Sessions::disabled(),
+        }
+    }
+
+    pub fn new(backend: SessionBackend, ttl: u64) -> Self {
+        Sessions {
+            backend,
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn memory(ttl: u64) -> Self {
+        Sessions::new(SessionBackend::Memory(Arc::default()), ttl)
+    }
+
+    pub fn disabled() -> Self {
+        Sessions::new(SessionBackend::Disabled, 0)
+    }
+
+    /// Whether logins create sessions rather than JWTs
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.backend, SessionBackend::Disabled)
+    }
+
+    /// Start a session for a user and get its token
+    pub fn create(&self, user: &AuthUser, user_agent: Option<String>) -> Result<String, ApiError> {
+        let token = new_token();
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            email: user.email.to_string(),
+            user_agent,
+            created_at: Utc::now().naive_utc(),
+        };
+        match &self.backend {
+            SessionBackend::Redis(redis) => {
+                let value = serde_json::to_string(&session)
+                    .map_err(|error| ApiError::CacheError(error.to_string()))?;
+                let ttl = self.ttl.as_secs().to_string();
+                let user_key = user_key(user.id);
+                send(redis, vec!["SET", &session_key(&token), &value, "EX", &ttl])?;
+                send(redis, vec!["SADD", &user_key, &token])?;
+                send(redis, vec!["EXPIRE", &user_key, &ttl])?;
+            }
+            SessionBackend::Memory(store) => {
+                let expires_at = Instant::now() + self.ttl;
+                lock_store(store)?.insert(token.to_string(), (session, expires_at));
+            }
+            SessionBackend::Disabled => {
+                return Err(ApiError::InternalServerError(
+                    "Sessions are disabled".into(),
+                ))
+            }
+        }
+        Ok(token)
+    }
+
+    /// Find the session of a token, keeping it alive for another TTL
+    pub fn find(&self, token: &str) -> Result<Option<Session>, ApiError> {
+        match &self.backend {
+            SessionBackend::Redis(redis) => {
+                let session = get(redis, token)?;
+                if let Some(session) = &session {
+                    let ttl = self.ttl.as_secs().to_string();
+                    send(redis, vec!["EXPIRE", &session_key(token), &ttl])?;
+                    send(redis, vec!["EXPIRE", &user_key(session.user_id), &ttl])?;
+                }
+                Ok(session)
+            }
+            SessionBackend::Memory(store) => {
+                let mut store = lock_store(store)?;
+                let now = Instant::now();
+                Ok(match store.get_mut(token) {
+                    Some((session, expires_at)) if *expires_at > now => {
+                        *expires_at = now + self.ttl;
+                        Some(session.clone())
+                    }
+                    _ => None,
+                })
+            }
+            SessionBackend::Disabled => Ok(None),
+        }
+    }
+
+    /// Every live session of a user, oldest first
+    pub fn find_all(&self, user_id: Uuid) -> Result<Vec<Session>, ApiError> {
+        let mut sessions: Vec<Session> = self
+            .tokens(user_id)?
+            .into_iter()
+            .map(|(_, session)| session)
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    /// End the session of a token, e.g. when logging out
+    pub fn destroy(&self, token: &str) -> Result<(), ApiError> {
+        match &self.backend {
+            SessionBackend::Redis(redis) => {
+                if let Some(session) = get(redis, token)? {
+                    send(redis, vec!["SREM", &user_key(session.user_id), token])?;
+                }
+                send(redis, vec!["DEL", &session_key(token)])?;
+            }
+            SessionBackend::Memory(store) => {
+                lock_store(store)?.remove(token);
+            }
+            SessionBackend::Disabled => {}
+        }
+        Ok(())
+    }
+
+    /// End one of a user's sessions, by its id
+    pub fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<(), ApiError> {
+        let token = self
+            .tokens(user_id)?
+            .into_iter()
+            .find(|(_, session)| session.id == session_id)
+            .map(|(token, _)| token)
+            .ok_or_else(|| ApiError::NotFound(format!("Session {} not found", session_id)))?;
+        self.destroy(&token)
+    }
+
+    /// End every session of a user, logging them out everywhere
+    pub fn destroy_all(&self, user_id: Uuid) -> Result<(), ApiError> {
+        for (token, _) in self.tokens(user_id)? {
+            self.destroy(&token)?;
+        }
+        Ok(())
+    }
+
+    /// Tokens and sessions of a user, dropping the ones that expired
+    fn tokens(&self, user_id: Uuid) -> Result<Vec<(String, Session)>, ApiError> {
+        match &self.backend {
+            SessionBackend::Redis(redis) => {
+                let user_key = user_key(user_id);
+                let tokens = match send(redis, vec!["SMEMBERS", &user_key])? {
+                    RespValue::Array(members) => members
+                        .into_iter()
+                        .filter_map(|member| match member {
+                            RespValue::BulkString(bytes) => String::from_utf8(bytes).ok(),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                let mut sessions = vec![];
+                for token in tokens {
+                    match get(redis, &token)? {
+                        Some(session) => sessions.push((token, session)),
+                        None => {
+                            send(redis, vec!["SREM", &user_key, &token])?;
+                        }
+                    }
+                }
+                Ok(sessions)
+            }
+            SessionBackend::Memory(store) => {
+                let mut store = lock_store(store)?;
+                let now = Instant::now();
+                store.retain(|_, (_, expires_at)| *expires_at > now);
+                Ok(store
+                    .iter()
+                    .filter(|(_, (session, _))| session.user_id == user_id)
+                    .map(|(token, (session, _))| (token.to_string(), session.clone()))
+                    .collect())
+            }
+            SessionBackend::Disabled => Ok(vec![]),
+        }
+    }
+}
+
+/// Add the session store to the app's data, see Sessions::from_config
+pub fn add_sessions(cfg: &mut ServiceConfig) {
+    cfg.data(Sessions::from_config(&CONFIG));
+}
+
+fn new_token() -> String {
+    let bytes: [u8; TOKEN_LENGTH] = rand::thread_rng().gen();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn session_key(token: &str) -> String {
+    format!("session:{}", token)
+}
+
+fn user_key(user_id: Uuid) -> String {
+    format!("user_sessions:{}", user_id)
+}
+
+fn get(redis: &Addr<RedisActor>, token: &str) -> Result<Option<Session>, ApiError> {
+    match send(redis, vec!["GET", &session_key(token)])? {
+        RespValue::BulkString(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+        _ => Ok(None),
+    }
+}
+
+type SessionStore = HashMap<String, (Session, Instant)>;
+
+fn lock_store(store: &Mutex<SessionStore>) -> Result<MutexGuard<'_, SessionStore>, ApiError> {
+    store
+        .lock()
+        .map_err(|_| ApiError::CacheError("The in-process session store is poisoned".into()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use actix_web::web::Data;
+
+    /// An in-process session store wrapped in Actix Application Data
+    pub fn get_data_sessions() -> Data<Sessions> {
+        Data::new(Sessions::memory(60))
+    }
+
+    fn user() -> AuthUser {
+        AuthUser {
+            id: Uuid::new_v4(),
+            email: "sessions@nothing.org".into(),
+        }
+    }
+
+    #[test]
+    fn it_creates_and_finds_a_session() {
+        let sessions = Sessions::memory(60);
+        let user = user();
+        let token = sessions.create(&user, Some("curl".into())).unwrap();
+        let session = sessions.find(&token).unwrap().unwrap();
+        assert_eq!(session.user_id, user.id);
+        assert_eq!(session.user_agent, Some("curl".into()));
+        assert_eq!(sessions.find("not-a-token").unwrap(), None);
+    }
+
+    #[test]
+    fn it_destroys_a_session() {
+        let sessions = Sessions::memory(60);
+        let token = sessions.create(&user(), None).unwrap();
+        sessions.destroy(&token).unwrap();
+        assert_eq!(sessions.find(&token).unwrap(), None);
+    }
+
+    #[test]
+    fn it_expires_sessions() {
+        let sessions = Sessions::memory(0);
+        let user = user();
+        let token = sessions.create(&user, None).unwrap();
+        assert_eq!(sessions.find(&token).unwrap(), None);
+        assert!(sessions.find_all(user.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_revokes_one_of_a_users_sessions() {
+        let sessions = Sessions::memory(60);
+        let user = user();
+        let first = sessions.create(&user, None).unwrap();
+        let second = sessions.create(&user, None).unwrap();
+        sessions.create(&self::user(), None).unwrap();
+        assert_eq!(sessions.find_all(user.id).unwrap().len(), 2);
+
+        let session_id = sessions.find(&first).unwrap().unwrap().id;
+        sessions.revoke(user.id, session_id).unwrap();
+        assert_eq!(sessions.find(&first).unwrap(), None);
+        assert!(sessions.find(&second).unwrap().is_some());
+    }
+
+    #[test]
+    fn it_doesnt_revoke_another_users_session() {
+        let sessions = Sessions::memory(60);
+        let token = sessions.create(&user(), None).unwrap();
+        let session_id = sessions.find(&token).unwrap().unwrap().id;
+        assert!(sessions.revoke(Uuid::new_v4(), session_id).is_err());
+        assert!(sessions.find(&token).unwrap().is_some());
+    }
+
+    #[test]
+    fn it_destroys_all_of_a_users_sessions() {
+        let sessions = Sessions::memory(60);
+        let user = user();
+        sessions.create(&user, None).unwrap();
+        sessions.create(&user, None).unwrap();
+        let other = sessions.create(&self::user(), None).unwrap();
+        sessions.destroy_all(user.id).unwrap();
+        assert!(sessions.find_all(user.id).unwrap().is_empty());
+        assert!(sessions.find(&other).unwrap().is_some());
+    }
+
+    #[test]
+    fn it_uses_jwts_by_default() {
+        let mut config = CONFIG.clone();
+        config.auth_mode = None;
+        assert!(!Sessions::from_config(&config).is_enabled());
+    }
+}
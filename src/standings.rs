@@ -0,0 +1,377 @@
+//! Ranks the teams of a league from their settled matchups.
+//!
+//! Teams are ordered by win percentage, ties count as half a win. Teams with
+//! the same win percentage are split by the league's tie-breakers, in order:
+//!
+//! - `head_to_head`: win percentage in the matchups between the tied teams
+//! - `total_points`: points scored in every settled matchup
+//! - `total_miles`: miles run in every closed round
+//!
+//! Anything still tied is ordered by name so standings never shuffle.
+
+use crate::models::matchups::Matchup;
+use crate::models::scores::TeamScore;
+use crate::models::teams::{Standing, Team};
+use std::cmp::Ordering;
+use uuid::Uuid;
+
+pub const HEAD_TO_HEAD: &str = "head_to_head";
+pub const TOTAL_POINTS: &str = "total_points";
+pub const TOTAL_MILES: &str = "total_miles";
+
+/// Every tie-breaker, in the order leagues use them by default
+pub const TIE_BREAKERS: [&str; 3] = [HEAD_TO_HEAD, TOTAL_POINTS, TOTAL_MILES];
+
+/// A team's results, collected before ranking
+struct Record<'a> {
+    team: &'a Team,
+    wins: i32,
+    losses: i32,
+    ties: i32,
+    points_for: f64,
+    points_against: f64,
+    miles: f64,
+    results: Vec<(i32, Ordering)>,
+}
+
+impl<'a> Record<'a> {
+    fn new(team: &'a Team, matchups: &[Matchup], scores: &[TeamScore]) -> Self {
+        let mut record = Record {
+            team,
+            wins: 0,
+            losses: 0,
+            ties: 0,
+            points_for: 0.0,
+            points_against: 0.0,
+            miles: scores
+                .iter()
+                .filter(|score| score.team_id == team.id)
+                .map(|score| score.miles)
+                .sum(),
+            results: vec![],
+        };
+
+        for matchup in matchups {
+            let (points_for, points_against) = if matchup.home_team_id == team.id {
+                (matchup.home_points, matchup.away_points)
+            } else if matchup.away_team_id == Some(team.id) {
+                (matchup.away_points, matchup.home_points)
+            } else {
+                continue;
+            };
+            record.points_for += points_for.unwrap_or(0.0);
+            record.points_against += points_against.unwrap_or(0.0);
+
+            let result = match matchup.result_for(team.id) {
+                Some(result) => result,
+                None => continue,
+            };
+            match result {
+                Ordering::Greater => record.wins += 1,
+                Ordering::Less => record.losses += 1,
+                Ordering::Equal => record.ties += 1,
+            }
+            record.results.push((matchup.round, result));
+        }
+        record.results.sort_by_key(|(round, _)| -round);
+
+        record
+    }
+
+    fn win_percentage(&self) -> f64 {
+        win_percentage(self.wins, self.losses, self.ties)
+    }
+
+    fn streak(&self) -> String {
+        let latest = match self.results.first() {
+            Some((_, result)) => *result,
+            None => return "".into(),
+        };
+        let length = self
+            .results
+            .iter()
+            .take_while(|(_, result)| *result == latest)
+            .count();
+        let letter = match latest {
+            Ordering::Greater => "W",
+            Ordering::Less => "L",
+            Ordering::Equal => "T",
+        };
+        format!("{}{}", letter, length)
+    }
+}
+
+/// Share of games won, counting ties as half a win
+pub fn win_percentage(wins: i32, losses: i32, ties: i32) -> f64 {
+    let games = wins + losses + ties;
+    if games == 0 {
+        return 0.0;
+    }
+    (f64::from(wins) + f64::from(ties) / 2.0) / f64::from(games)
+}
+
+/// Rank the teams of a league
+///
+/// matchups should be the league's matchups (unsettled ones are ignored) and
+/// scores the team scores of its closed rounds.
+pub fn rank(
+    teams: &[Team],
+    matchups: &[Matchup],
+    scores: &[TeamScore],
+    tie_breakers: &[String],
+) -> Vec<Standing> {
+    let settled: Vec<Matchup> = matchups
+        .iter()
+        .filter(|matchup| matchup.home_points.is_some())
+        .cloned()
+        .collect();
+    let mut records: Vec<Record> = teams
+        .iter()
+        .map(|team| Record::new(team, &settled, scores))
+        .collect();
+
+    records.sort_by(|a, b| {
+        descending(a.win_percentage(), b.win_percentage())
+            .then_with(|| a.team.name.cmp(&b.team.name))
+            .then_with(|| a.team.id.cmp(&b.team.id))
+    });
+
+    let mut start = 0;
+    while start < records.len() {
+        let win_percentage = records[start].win_percentage();
+        let end = start
+            + records[start..]
+                .iter()
+                .take_while(|record| record.win_percentage() == win_percentage)
+                .count();
+        break_ties(&mut records[start..end], &settled, tie_breakers);
+        start = end;
+    }
+
+    let (leader_wins, leader_losses) = records
+        .first()
+        .map_or((0, 0), |leader| (leader.wins, leader.losses));
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| Standing {
+            rank: index as i32 + 1,
+            team_id: record.team.id,
+            name: record.team.name.clone(),
+            wins: record.wins,
+            losses: record.losses,
+            ties: record.ties,
+            win_percentage: record.win_percentage(),
+            points_for: record.points_for,
+            points_against: record.points_against,
+            miles: record.miles,
+            streak: record.streak(),
+            games_back: f64::from((leader_wins - record.wins) + (record.losses - leader_losses))
+                / 2.0,
+        })
+        .collect()
+}
+
+/// Order a group of teams with the same win percentage
+fn break_ties(tied: &mut [Record], matchups: &[Matchup], tie_breakers: &[String]) {
+    if tied.len() < 2 {
+        return;
+    }
+
+    let tied_ids: Vec<Uuid> = tied.iter().map(|record| record.team.id).collect();
+    let keys = |record: &Record| -> Vec<f64> {
+        tie_breakers
+            .iter()
+            .map(|tie_breaker| match tie_breaker.as_str() {
+                HEAD_TO_HEAD => head_to_head(record.team.id, &tied_ids, matchups),
+                TOTAL_POINTS => record.points_for,
+                TOTAL_MILES => record.miles,
+                _ => 0.0,
+            })
+            .collect()
+    };
+
+    tied.sort_by(|a, b| {
+        keys(a)
+            .into_iter()
+            .zip(keys(b))
+            .map(|(a, b)| descending(a, b))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Win percentage of a team in its matchups against the other tied teams
+fn head_to_head(team_id: Uuid, tied_ids: &[Uuid], matchups: &[Matchup]) -> f64 {
+    let results: Vec<Ordering> = matchups
+        .iter()
+        .filter(|matchup| match matchup.away_team_id {
+            Some(away_team_id) => {
+                tied_ids.contains(&matchup.home_team_id) && tied_ids.contains(&away_team_id)
+            }
+            None => false,
+        })
+        .filter_map(|matchup| matchup.result_for(team_id))
+        .collect();
+    let count = |ordering| results.iter().filter(|result| **result == ordering).count() as i32;
+    win_percentage(
+        count(Ordering::Greater),
+        count(Ordering::Less),
+        count(Ordering::Equal),
+    )
+}
+
+fn descending(a: f64, b: f64) -> Ordering {
+    b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::scoring::tests::team;
+
+    fn named_team(name: &str) -> Team {
+        Team {
+            name: name.into(),
+            ..team(Uuid::new_v4())
+        }
+    }
+
+    fn settled(
+        round: i32,
+        home: &Team,
+        away: &Team,
+        home_points: f64,
+        away_points: f64,
+    ) -> Matchup {
+        Matchup {
+            id: Uuid::new_v4(),
+            league_id: Uuid::nil(),
+            round,
+            home_team_id: home.id,
+            away_team_id: Some(away.id),
+            home_points: Some(home_points),
+            away_points: Some(away_points),
+        }
+    }
+
+    fn score(team: &Team, round: i32, miles: f64) -> TeamScore {
+        TeamScore {
+            id: Uuid::new_v4(),
+            league_id: Uuid::nil(),
+            team_id: team.id,
+            round,
+            miles,
+            points: miles * 10.0,
+        }
+    }
+
+    fn tie_breakers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn it_gets_win_percentages() {
+        assert_eq!(win_percentage(0, 0, 0), 0.0);
+        assert_eq!(win_percentage(3, 1, 0), 0.75);
+        assert_eq!(win_percentage(1, 0, 1), 0.75);
+    }
+
+    #[test]
+    fn it_ranks_by_win_percentage() {
+        let (a, b, c) = (named_team("A"), named_team("B"), named_team("C"));
+        let matchups = vec![
+            settled(1, &c, &a, 30.0, 20.0),
+            settled(2, &c, &b, 30.0, 20.0),
+            settled(3, &b, &a, 30.0, 20.0),
+        ];
+        let standings = rank(
+            &[a.clone(), b.clone(), c.clone()],
+            &matchups,
+            &[],
+            &tie_breakers(&TIE_BREAKERS),
+        );
+
+        let order: Vec<Uuid> = standings.iter().map(|standing| standing.team_id).collect();
+        assert_eq!(order, vec![c.id, b.id, a.id]);
+        assert_eq!(standings[0].rank, 1);
+        assert_eq!(standings[0].win_percentage, 1.0);
+        assert_eq!(standings[0].points_for, 60.0);
+        assert_eq!(standings[0].points_against, 40.0);
+        assert_eq!(standings[2].games_back, 2.0);
+    }
+
+    #[test]
+    fn it_gets_streaks() {
+        let (a, b) = (named_team("A"), named_team("B"));
+        let matchups = vec![
+            settled(1, &a, &b, 10.0, 20.0),
+            settled(2, &a, &b, 30.0, 20.0),
+            settled(3, &b, &a, 10.0, 20.0),
+        ];
+        let standings = rank(&[a.clone(), b], &matchups, &[], &[]);
+        let a_standing = standings.iter().find(|s| s.team_id == a.id).unwrap();
+        assert_eq!(a_standing.streak, "W2");
+        assert_eq!(a_standing.wins, 2);
+    }
+
+    #[test]
+    fn it_ignores_unsettled_matchups() {
+        let (a, b) = (named_team("A"), named_team("B"));
+        let mut unsettled = settled(1, &a, &b, 0.0, 0.0);
+        unsettled.home_points = None;
+        unsettled.away_points = None;
+        let standings = rank(&[a, b], &[unsettled], &[], &[]);
+        assert!(standings.iter().all(|standing| standing.streak.is_empty()));
+        assert!(standings.iter().all(|standing| standing.ties == 0));
+    }
+
+    #[test]
+    fn it_breaks_ties_head_to_head() {
+        let (a, b, c, d) = (
+            named_team("A"),
+            named_team("B"),
+            named_team("C"),
+            named_team("D"),
+        );
+        // A and B both go 1-1, B beat A head to head but A scored more
+        let matchups = vec![
+            settled(1, &b, &a, 20.0, 10.0),
+            settled(1, &c, &d, 20.0, 10.0),
+            settled(2, &a, &d, 90.0, 10.0),
+            settled(2, &c, &b, 30.0, 20.0),
+        ];
+        let teams = vec![a.clone(), b.clone(), c, d];
+
+        let standings = rank(&teams, &matchups, &[], &tie_breakers(&[HEAD_TO_HEAD]));
+        assert_eq!(standings[1].team_id, b.id);
+        assert_eq!(standings[2].team_id, a.id);
+
+        let standings = rank(&teams, &matchups, &[], &tie_breakers(&[TOTAL_POINTS]));
+        assert_eq!(standings[1].team_id, a.id);
+        assert_eq!(standings[2].team_id, b.id);
+    }
+
+    #[test]
+    fn it_breaks_ties_by_miles() {
+        let (a, b) = (named_team("A"), named_team("B"));
+        let matchups = vec![settled(1, &a, &b, 20.0, 20.0)];
+        let scores = vec![score(&a, 1, 2.0), score(&b, 1, 4.0)];
+        let standings = rank(
+            &[a, b.clone()],
+            &matchups,
+            &scores,
+            &tie_breakers(&[TOTAL_MILES]),
+        );
+        assert_eq!(standings[0].team_id, b.id);
+        assert_eq!(standings[0].games_back, 0.0);
+        assert_eq!(standings[1].games_back, 0.0);
+    }
+
+    #[test]
+    fn it_orders_remaining_ties_by_name() {
+        let (a, b) = (named_team("A"), named_team("B"));
+        let standings = rank(&[b, a.clone()], &[], &[], &tie_breakers(&TIE_BREAKERS));
+        assert_eq!(standings[0].team_id, a.id);
+    }
+}
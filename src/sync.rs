@@ -0,0 +1,197 @@
+//! Syncing linked accounts' activities into runs
+//!
+//! A sync refreshes the account's tokens when they're about to expire, then
+//! pulls the activities that started after the account's cursor a page at a
+//! time. Activities become runs of the account's user keyed by the
+//! provider's activity id, so an activity synced twice is only updated.
+//! Activities that wouldn't pass as a run logged by hand are skipped. The
+//! runs are saved and the cursor moved in one transaction, a sync that fails
+//! halfway is picked up again by the next one.
+//!
+//! With SYNC_INTERVAL set, every linked account is synced every that many
+//! minutes in the background.
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::database::{init_pool, PoolType};
+use crate::errors::ApiError;
+use crate::managers::linked_accounts::{get_all, save_sync, update_tokens};
+use crate::models::linked_accounts::{LinkedAccount, SyncResponse};
+use crate::models::runs::{CreateRunRequest, NewRun, Run};
+use crate::providers::{ActivityProvider, ProviderActivity, Providers};
+use actix_web::web::block;
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Tokens expiring within this many seconds are refreshed before syncing
+const REFRESH_MARGIN: i64 = 300;
+
+/// Most pages pulled by one sync, the next sync carries on from the cursor
+pub const MAX_PAGES: u32 = 20;
+
+/// Sync one linked account with its provider
+pub async fn sync_account(
+    pool: &PoolType,
+    cache: &Cache,
+    provider: &dyn ActivityProvider,
+    account: LinkedAccount,
+) -> Result<SyncResponse, ApiError> {
+    let mut account = account;
+    if account.expires_at <= Utc::now().naive_utc() + Duration::seconds(REFRESH_MARGIN) {
+        let tokens = provider.refresh(&account).await?;
+        let pool = pool.clone();
+        account = block(move || update_tokens(&pool, account.id, &tokens)).await?;
+    }
+
+    let mut activities = vec![];
+    for page in 1..=MAX_PAGES {
+        let page = provider
+            .activities(&account.access_token, account.sync_cursor, page)
+            .await?;
+        if page.is_empty() {
+            break;
+        }
+        activities.extend(page);
+    }
+
+    let sync_cursor = activities
+        .iter()
+        .map(|activity| activity.started_at)
+        .chain(account.sync_cursor)
+        .max();
+    let (synced_runs, skipped) = to_runs(&account, activities);
+    let synced = synced_runs.len() as i64;
+
+    let pool = pool.clone();
+    let cache = cache.clone();
+    let account =
+        block(move || save_sync(&pool, &cache, account.id, &synced_runs, sync_cursor)).await?;
+    Ok(SyncResponse {
+        synced,
+        skipped,
+        sync_cursor: account.sync_cursor,
+    })
+}
+
+/// Sync every linked account whose provider is available, logging failures
+pub async fn sync_all(pool: &PoolType, cache: &Cache, providers: &Providers) {
+    let accounts = {
+        let pool = pool.clone();
+        match block(move || get_all(&pool)).await {
+            Ok(accounts) => accounts,
+            Err(error) => {
+                log::error!("Getting the linked accounts to sync failed: {:?}", error);
+                return;
+            }
+        }
+    };
+
+    for account in accounts {
+        let provider = match providers.get(&account.provider) {
+            Ok(provider) => provider,
+            Err(_) => continue,
+        };
+        let account_id = account.id;
+        if let Err(error) = sync_account(pool, cache, provider.as_ref(), account).await {
+            log::error!("Syncing linked account {} failed: {:?}", account_id, error);
+        }
+    }
+}
+
+/// Sync every linked account every SYNC_INTERVAL minutes, in the background
+///
+/// Does nothing without SYNC_INTERVAL or a provider to sync from.
+pub fn start(config: &Config) {
+    let minutes = match config.sync_interval {
+        Some(minutes) if minutes > 0 => minutes,
+        _ => return,
+    };
+    let providers = Providers::from_config(config);
+    if providers.is_empty() {
+        return;
+    }
+    let pool = init_pool::<PgConnection>(config.clone()).expect("Failed to create connection pool");
+    let cache = Cache::from_config(config);
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(std::time::Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            sync_all(&pool, &cache, &providers).await;
+        }
+    });
+}
+
+/// The runs of a user's activities and how many activities were skipped
+///
+/// An activity listed twice only becomes one run, with its latest values.
+fn to_runs(account: &LinkedAccount, activities: Vec<ProviderActivity>) -> (Vec<Run>, i64) {
+    let mut skipped = 0;
+    let mut runs = BTreeMap::new();
+    for activity in activities {
+        let params = CreateRunRequest::from(&activity);
+        if params.validate().is_err() {
+            skipped += 1;
+            continue;
+        }
+        let run = Run {
+            provider: Some(account.provider.clone()),
+            provider_activity_id: Some(activity.id.clone()),
+            ..NewRun {
+                id: Uuid::new_v4(),
+                user_id: account.user_id,
+                distance: params.distance,
+                duration: params.duration,
+                started_at: params.started_at,
+                elevation_gain: params.elevation_gain,
+                heart_rate: params.heart_rate,
+            }
+            .into()
+        };
+        runs.insert(activity.id, run);
+    }
+    (runs.into_values().collect(), skipped)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::managers::linked_accounts::link;
+    use crate::managers::linked_accounts::tests::tokens;
+    use crate::managers::users::tests::create_user;
+    use crate::tests::helpers::tests::get_pool;
+
+    fn activity(id: &str, distance: f64) -> ProviderActivity {
+        ProviderActivity {
+            id: id.into(),
+            started_at: Utc::now().naive_utc(),
+            distance,
+            duration: 2700,
+            elevation_gain: None,
+            heart_rate: Some(150),
+        }
+    }
+
+    #[test]
+    fn it_turns_activities_into_runs_once() {
+        let user = create_user().unwrap();
+        let account = link(
+            &get_pool(),
+            user.id,
+            "strava",
+            &tokens(&Uuid::new_v4().to_string()),
+        );
+        let account = account.unwrap();
+        let activities = vec![activity("1", 5.0), activity("2", 100.0), activity("1", 5.5)];
+        let (runs, skipped) = to_runs(&account, activities);
+        assert_eq!(skipped, 1);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].user_id, user.id);
+        assert_eq!(runs[0].provider.as_deref(), Some("strava"));
+        assert_eq!(runs[0].provider_activity_id.as_deref(), Some("1"));
+        assert!((runs[0].distance - 5.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::get_identity_service;
+    use crate::cache::add_cache;
+    use crate::database::add_pool;
+    use crate::models::users::LoginRequest;
+    use crate::providers::add_providers;
+    use crate::routes::routes;
+    use crate::sessions::add_sessions;
+    use crate::tests::helpers::tests::{
+        assert_get, login_request, test_get_anonymous, LOGIN_EMAIL,
+    };
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn it_logs_in() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+        let response = test::call_service(&mut app, login_request()).await;
+        assert!(response.status().is_success());
+        assert!(response.response().cookies().next().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_log_in_with_a_bad_password() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+        let request = test::TestRequest::post()
+            .set_json(&LoginRequest {
+                email: LOGIN_EMAIL.into(),
+                password: "not-the-password".into(),
+            })
+            .uri("/api/v1/auth/login")
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn it_authenticates_with_the_identity_cookie() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+        let response = test::call_service(&mut app, login_request()).await;
+        let cookie = response.response().cookies().next().unwrap().into_owned();
+        let request = test::TestRequest::get()
+            .cookie(cookie)
+            .uri("/api/v1/leagues")
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn it_logs_out() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+        let response = test::call_service(&mut app, login_request()).await;
+        let cookie = response.response().cookies().next().unwrap().into_owned();
+        let request = test::TestRequest::get()
+            .cookie(cookie)
+            .uri("/api/v1/auth/logout")
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert!(response.status().is_success());
+        let removal = response.response().cookies().next().unwrap();
+        assert_eq!(removal.value(), "");
+    }
+
+    #[actix_rt::test]
+    async fn it_allows_authenticated_requests() {
+        assert_get("/api/v1/leagues").await;
+    }
+
+    #[actix_rt::test]
+    async fn it_rejects_unauthenticated_requests() {
+        let response = test_get_anonymous("/api/v1/users").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = test_get_anonymous("/api/v1/leagues").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
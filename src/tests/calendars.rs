@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::leagues::CalendarLinkResponse;
+    use crate::tests::helpers::tests::{assert_get, test_get_anonymous};
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use uuid::Uuid;
+
+    async fn calendar_link(url: &str) -> String {
+        let response = assert_get(url).await;
+        let link: CalendarLinkResponse =
+            serde_json::from_slice(&test::read_body(response).await).unwrap();
+        link.url
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_league_calendar_without_logging_in() {
+        let url = calendar_link(&format!("/api/v1/leagues/{}/calendar-link", Uuid::nil())).await;
+        let response = test_get_anonymous(&url).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/calendar; charset=utf-8"
+        );
+        let body = test::read_body(response).await;
+        assert!(body.starts_with(b"BEGIN:VCALENDAR\r\n"));
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_team_calendar_without_logging_in() {
+        let league_url =
+            calendar_link(&format!("/api/v1/leagues/{}/calendar-link", Uuid::nil())).await;
+        let response = assert_get(&format!("/api/v1/leagues/{}/teams", Uuid::nil())).await;
+        let teams: serde_json::Value =
+            serde_json::from_slice(&test::read_body(response).await).unwrap();
+        let team_id = teams["data"][0]["id"].as_str().unwrap().to_string();
+
+        let url = calendar_link(&format!("/api/v1/teams/{}/calendar-link", team_id)).await;
+        assert_ne!(url, league_url);
+        let response = test_get_anonymous(&url).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_get_a_calendar_with_a_bad_token() {
+        let url = calendar_link(&format!("/api/v1/leagues/{}/calendar-link", Uuid::nil())).await;
+        let response = test_get_anonymous(&format!("{}x", url)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_get_a_calendar_ics_without_logging_in() {
+        let url = format!("/api/v1/leagues/{}/calendar.ics", Uuid::nil());
+        let response = test_get_anonymous(&url).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::errors::ErrorResponse;
+    use crate::import::MAX_FILE_SIZE;
+    use crate::models::users::CreateUserRequest;
+    use crate::tests::helpers::tests::{get_login_user, test_get, test_post, test_upload};
+    use actix_web::dev::ServiceResponse;
+    use actix_web::http::StatusCode;
+    use actix_web::test::read_body;
+    use uuid::Uuid;
+
+    async fn error_body(response: ServiceResponse) -> (Option<String>, ErrorResponse) {
+        let request_id = response
+            .headers()
+            .get("x-request-id")
+            .map(|header| header.to_str().unwrap().to_string());
+        let body = serde_json::from_slice(&read_body(response).await).unwrap();
+        (request_id, body)
+    }
+
+    #[actix_rt::test]
+    async fn it_describes_a_missing_record() {
+        let url = format!("/api/v1/users/{}", Uuid::new_v4());
+        let response = test_get(&url).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (request_id, error) = error_body(response).await;
+        assert_eq!(error.code, "not_found");
+        assert_eq!(error.status, 404);
+        assert!(request_id.is_some());
+        assert_eq!(error.request_id, request_id);
+    }
+
+    #[actix_rt::test]
+    async fn it_describes_invalid_fields() {
+        let params = CreateUserRequest {
+            first_name: "S".into(),
+            last_name: "N".into(),
+            email: "satoshi".into(),
+            password: "123456".into(),
+        };
+        let response = test_post("/api/v1/users", params).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, error) = error_body(response).await;
+        assert_eq!(error.code, "validation_error");
+        let fields = error.fields.unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields["email"], vec!["email must be a valid email"]);
+    }
+
+    #[actix_rt::test]
+    async fn it_describes_errors_actix_responds_with() {
+        let response = test_get("/api/v1/users/not-a-uuid").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (_, error) = error_body(response).await;
+        assert_eq!(error.code, "not_found");
+        assert!(error.request_id.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn it_describes_a_body_over_the_limit() {
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let response = test_upload(&url, "text/csv", vec![b'a'; MAX_FILE_SIZE + 1]).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (request_id, error) = error_body(response).await;
+        assert_eq!(error.code, "payload_too_large");
+        assert_eq!(error.status, 413);
+        assert_eq!(error.request_id, request_id);
+    }
+}
@@ -1,25 +1,45 @@
 #[cfg(test)]
 pub mod tests {
+    use crate::auth::get_identity_service;
+    use crate::cache::add_cache;
     use crate::config::CONFIG;
     use crate::database::{add_pool, init_pool, Pool};
+    use crate::managers::users::{create, find_by_auth};
+    use crate::middleware::request_id::RequestIds;
+    use crate::models::users::{LoginRequest, LoginResponse, NewUser, UserResponse};
+    use crate::providers::add_providers;
     use crate::routes::routes;
-    use actix_web::dev::ServiceResponse;
-    use actix_web::{test, web::Data, App};
+    use crate::sessions::add_sessions;
+    use actix_http::Request;
+    use actix_web::dev::{MessageBody, Service, ServiceResponse};
+    use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE};
+    use actix_web::{test, web::Data, App, Error};
     use diesel::pg::PgConnection;
     use serde::Serialize;
+    use uuid::Uuid;
+
+    pub static LOGIN_EMAIL: &str = "login-test@nothing.org";
+    pub static LOGIN_PASSWORD: &str = "123456";
 
     /// Helper for HTTP GET integration tests
     pub async fn test_get(route: &str) -> ServiceResponse {
         let mut app = test::init_service(
             App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
                 .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
                 .configure(routes),
         )
         .await;
 
+        let token = login(&mut app).await;
         test::call_service(
             &mut app,
             test::TestRequest::get()
+                .header(AUTHORIZATION, format!("Bearer {}", token))
                 .uri(route)
                 .to_request(),
         )
@@ -30,14 +50,21 @@ pub mod tests {
     pub async fn test_post<T: Serialize>(route: &str, params: T) -> ServiceResponse {
         let mut app = test::init_service(
             App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
                 .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
                 .configure(routes),
         )
         .await;
 
+        let token = login(&mut app).await;
         test::call_service(
             &mut app,
             test::TestRequest::post()
+                .header(AUTHORIZATION, format!("Bearer {}", token))
                 .set_json(&params)
                 .uri(route)
                 .to_request(),
@@ -45,17 +72,92 @@ pub mod tests {
         .await
     }
 
+    /// Helper for integration tests uploading a file as the body of a POST
+    pub async fn test_upload(route: &str, content_type: &str, file: Vec<u8>) -> ServiceResponse {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+
+        let token = login(&mut app).await;
+        test::call_service(
+            &mut app,
+            test::TestRequest::post()
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, content_type)
+                .set_payload(file)
+                .uri(route)
+                .to_request(),
+        )
+        .await
+    }
+
+    /// Helper for HTTP GET integration tests without logging in first
+    pub async fn test_get_anonymous(route: &str) -> ServiceResponse {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+
+        test::call_service(&mut app, test::TestRequest::get().uri(route).to_request()).await
+    }
+
+    /// Makes sure the user used for logging in exists
+    ///
+    /// Another test may create it first, then it's found again.
+    pub fn get_login_user() -> UserResponse {
+        let pool = get_pool();
+        find_by_auth(&pool, LOGIN_EMAIL, LOGIN_PASSWORD)
+            .or_else(|_| {
+                let new_user = NewUser {
+                    id: Uuid::new_v4(),
+                    first_name: "Login".into(),
+                    last_name: "Test".into(),
+                    email: LOGIN_EMAIL.into(),
+                    password: LOGIN_PASSWORD.into(),
+                };
+                create(&pool, &new_user.into())
+            })
+            .or_else(|_| find_by_auth(&pool, LOGIN_EMAIL, LOGIN_PASSWORD))
+            .unwrap()
+    }
+
     /// Helper to login for tests
-    // pub fn login_request() -> Request {
-    //     let login_request = LoginRequest {
-    //         email: "satoshi@nakamotoinstitute.org".into(),
-    //         password: "123456".into(),
-    //     };
-    //     test::TestRequest::post()
-    //         .set_json(&login_request)
-    //         .uri("/api/v1/auth/login")
-    //         .to_request()
-    // }
+    pub fn login_request() -> Request {
+        get_login_user();
+        let login_request = LoginRequest {
+            email: LOGIN_EMAIL.into(),
+            password: LOGIN_PASSWORD.into(),
+        };
+        test::TestRequest::post()
+            .set_json(&login_request)
+            .uri("/api/v1/auth/login")
+            .to_request()
+    }
+
+    /// Login and return the issued JWT
+    pub async fn login<S, B>(app: &mut S) -> String
+    where
+        S: Service<Request = Request, Response = ServiceResponse<B>, Error = Error>,
+        B: MessageBody,
+    {
+        let response: LoginResponse = test::read_response_json(app, login_request()).await;
+        response.token
+    }
 
     /// Assert that a route is successful for HTTP GET requests
     pub async fn assert_get(route: &str) -> ServiceResponse {
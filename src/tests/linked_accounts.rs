@@ -0,0 +1,265 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::{create_jwt, get_identity_service, PrivateClaim};
+    use crate::cache::add_cache;
+    use crate::database::add_pool;
+    use crate::managers::linked_accounts::find;
+    use crate::managers::users::tests::create_user;
+    use crate::middleware::request_id::RequestIds;
+    use crate::models::linked_accounts::{
+        LinkAccountRequest, LinkedAccountResponse, LinkedAccountsResponse, SyncResponse,
+    };
+    use crate::models::runs::RunsResponse;
+    use crate::models::users::UserResponse;
+    use crate::providers::mock::{MockStrava, REFUSED_CODE};
+    use crate::providers::Providers;
+    use crate::routes::routes;
+    use crate::schema::linked_accounts;
+    use crate::sessions::add_sessions;
+    use crate::tests::helpers::tests::get_pool;
+    use actix_http::Request;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::http::{header::AUTHORIZATION, StatusCode};
+    use actix_web::{test, App};
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use serde::de::DeserializeOwned;
+    use std::sync::Arc;
+
+    /// Call the API as a new user, with Strava served by the mock
+    struct Client {
+        strava: MockStrava,
+        user: UserResponse,
+        token: String,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let user = create_user().unwrap();
+            let token = create_jwt(PrivateClaim::new(user.id, user.email.clone())).unwrap();
+            Client {
+                strava: MockStrava::start(),
+                user,
+                token,
+            }
+        }
+
+        fn request(&self, request: test::TestRequest, path: &str) -> Request {
+            request
+                .header(AUTHORIZATION, format!("Bearer {}", self.token))
+                .uri(&format!("/api/v1/users/{}{}", self.user.id, path))
+                .to_request()
+        }
+
+        async fn call(&self, request: Request) -> ServiceResponse {
+            let providers = Providers::new(vec![Arc::new(self.strava.provider())]);
+            let mut app = test::init_service(
+                App::new()
+                    .wrap(get_identity_service())
+                    .wrap(RequestIds)
+                    .configure(add_pool)
+                    .configure(add_cache)
+                    .configure(add_sessions)
+                    .data(providers)
+                    .configure(routes),
+            )
+            .await;
+            test::call_service(&mut app, request).await
+        }
+
+        async fn json<T: DeserializeOwned>(&self, request: Request) -> T {
+            let response = self.call(request).await;
+            assert!(response.status().is_success());
+            serde_json::from_slice(&test::read_body(response).await).unwrap()
+        }
+
+        async fn link(&self) -> LinkedAccountResponse {
+            let params = LinkAccountRequest {
+                provider: "strava".into(),
+                code: "authorization-code".into(),
+            };
+            let request = self.request(
+                test::TestRequest::post().set_json(&params),
+                "/linked-accounts",
+            );
+            self.json(request).await
+        }
+
+        async fn sync(&self, account: &LinkedAccountResponse) -> SyncResponse {
+            let path = format!("/linked-accounts/{}/sync", account.id);
+            self.json(self.request(test::TestRequest::post(), &path))
+                .await
+        }
+
+        async fn runs(&self) -> RunsResponse {
+            self.json(self.request(test::TestRequest::get(), "/runs?direction=asc"))
+                .await
+        }
+    }
+
+    #[actix_rt::test]
+    async fn it_links_an_account() {
+        let client = Client::new();
+        let account = client.link().await;
+        assert_eq!(account.provider, "strava");
+        assert_eq!(
+            account.provider_user_id,
+            client.strava.athlete_id().to_string()
+        );
+        assert_eq!(account.sync_cursor, None);
+
+        let accounts: LinkedAccountsResponse = client
+            .json(client.request(test::TestRequest::get(), "/linked-accounts"))
+            .await;
+        assert_eq!(accounts.data, vec![account]);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_link_an_account_with_a_refused_code() {
+        let client = Client::new();
+        let params = LinkAccountRequest {
+            provider: "strava".into(),
+            code: REFUSED_CODE.into(),
+        };
+        let request = client.request(
+            test::TestRequest::post().set_json(&params),
+            "/linked-accounts",
+        );
+        let response = client.call(request).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_link_an_unknown_provider() {
+        let client = Client::new();
+        let params = LinkAccountRequest {
+            provider: "garmin".into(),
+            code: "authorization-code".into(),
+        };
+        let request = client.request(
+            test::TestRequest::post().set_json(&params),
+            "/linked-accounts",
+        );
+        let response = client.call(request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn it_syncs_runs_without_duplicates() {
+        let client = Client::new();
+        let account = client.link().await;
+        let id = client.strava.athlete_id() * 10;
+        client
+            .strava
+            .add_activity(id, "Run", "2020-06-01T07:00:00Z", 8046.72, 2400);
+        client
+            .strava
+            .add_activity(id + 1, "Ride", "2020-06-02T07:00:00Z", 30000.0, 3600);
+        client
+            .strava
+            .add_activity(id + 2, "Run", "2020-06-03T07:00:00Z", 100.0, 3600);
+
+        let sync = client.sync(&account).await;
+        assert_eq!(sync.synced, 1);
+        assert_eq!(sync.skipped, 1);
+        assert_eq!(sync.sync_cursor.unwrap().to_string(), "2020-06-03 07:00:00");
+
+        let sync = client.sync(&account).await;
+        assert_eq!(sync.synced, 0);
+        assert_eq!(sync.sync_cursor.unwrap().to_string(), "2020-06-03 07:00:00");
+
+        let runs = client.runs().await;
+        assert_eq!(runs.total, 1);
+        assert_eq!(runs.data[0].provider.as_deref(), Some("strava"));
+        assert_eq!(runs.data[0].provider_activity_id, Some(id.to_string()));
+        assert!((runs.data[0].distance - 5.0).abs() < 0.0001);
+        assert_eq!(runs.data[0].heart_rate, Some(151));
+    }
+
+    #[actix_rt::test]
+    async fn it_syncs_new_activities_from_the_cursor() {
+        let client = Client::new();
+        let account = client.link().await;
+        let id = client.strava.athlete_id() * 10;
+        client
+            .strava
+            .add_activity(id, "Run", "2020-06-01T07:00:00Z", 8046.72, 2400);
+        assert_eq!(client.sync(&account).await.synced, 1);
+
+        client
+            .strava
+            .add_activity(id + 1, "Run", "2020-06-04T07:00:00Z", 10000.0, 3000);
+        let sync = client.sync(&account).await;
+        assert_eq!(sync.synced, 1);
+        assert_eq!(sync.sync_cursor.unwrap().to_string(), "2020-06-04 07:00:00");
+
+        let runs = client.runs().await;
+        assert_eq!(runs.total, 2);
+        assert_eq!(
+            runs.data[1].provider_activity_id,
+            Some((id + 1).to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn it_refreshes_expired_tokens_before_syncing() {
+        let client = Client::new();
+        let account = client.link().await;
+        let id = client.strava.athlete_id() * 10;
+        client
+            .strava
+            .add_activity(id, "Run", "2020-06-01T07:00:00Z", 8046.72, 2400);
+
+        let conn = get_pool().get().unwrap();
+        diesel::update(linked_accounts::table.find(account.id))
+            .set(linked_accounts::expires_at.eq(Utc::now().naive_utc()))
+            .execute(&conn)
+            .unwrap();
+
+        assert_eq!(client.sync(&account).await.synced, 1);
+        assert_eq!(client.strava.refreshes(), 1);
+        let refreshed = find(&get_pool(), client.user.id, account.id).unwrap();
+        assert_eq!(refreshed.access_token, client.strava.access_token());
+        assert!(refreshed.expires_at > Utc::now().naive_utc());
+    }
+
+    #[actix_rt::test]
+    async fn it_unlinks_an_account_and_keeps_its_runs() {
+        let client = Client::new();
+        let account = client.link().await;
+        let id = client.strava.athlete_id() * 10;
+        client
+            .strava
+            .add_activity(id, "Run", "2020-06-01T07:00:00Z", 8046.72, 2400);
+        client.sync(&account).await;
+
+        let path = format!("/linked-accounts/{}", account.id);
+        let response = client
+            .call(client.request(test::TestRequest::delete(), &path))
+            .await;
+        assert!(response.status().is_success());
+
+        let accounts: LinkedAccountsResponse = client
+            .json(client.request(test::TestRequest::get(), "/linked-accounts"))
+            .await;
+        assert_eq!(accounts.total, 0);
+        assert_eq!(client.runs().await.total, 1);
+    }
+
+    #[actix_rt::test]
+    async fn it_only_syncs_your_own_accounts() {
+        let client = Client::new();
+        let account = client.link().await;
+        let other = Client::new();
+        let path = format!(
+            "/api/v1/users/{}/linked-accounts/{}/sync",
+            client.user.id, account.id
+        );
+        let request = test::TestRequest::post()
+            .header(AUTHORIZATION, format!("Bearer {}", other.token))
+            .uri(&path)
+            .to_request();
+        let response = other.call(request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
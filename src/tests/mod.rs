@@ -1,4 +1,12 @@
 //! Integration tests
+pub mod auth;
+pub mod calendars;
+pub mod errors;
 pub mod helpers;
 pub mod health;
+pub mod linked_accounts;
+pub mod openapi;
+pub mod runs;
+pub mod sessions;
+pub mod teams;
 pub mod users;
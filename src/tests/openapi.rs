@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::get_identity_service;
+    use crate::cache::add_cache;
+    use crate::database::add_pool;
+    use crate::errors::ErrorResponse;
+    use crate::middleware::request_id::RequestIds;
+    use crate::openapi::operations;
+    use crate::providers::add_providers;
+    use crate::routes::routes;
+    use crate::sessions::add_sessions;
+    use crate::tests::helpers::tests::{assert_get, login};
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::{self, read_body};
+    use actix_web::App;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn it_serves_the_spec() {
+        let response = assert_get("/api/v1/openapi.json").await;
+        let spec: Value = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert!(spec["components"]["schemas"]["CreateLeagueRequest"].is_object());
+    }
+
+    /// Call every documented operation with the example of its request body,
+    /// each one has to be routed and accept the body
+    #[actix_rt::test]
+    async fn it_matches_the_handlers() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
+                .configure(add_pool)
+                .configure(add_cache)
+                .configure(add_sessions)
+                .configure(add_providers)
+                .configure(routes),
+        )
+        .await;
+        let token = login(&mut app).await;
+
+        for operation in operations() {
+            let path = operation
+                .path
+                .replace("{round}", "1")
+                .split('/')
+                .map(|segment| match segment.starts_with('{') {
+                    true => Uuid::new_v4().to_string(),
+                    false => segment.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join("/");
+            let method = Method::from_bytes(operation.method.to_uppercase().as_bytes()).unwrap();
+            let mut request = test::TestRequest::with_uri(&path)
+                .method(method)
+                .header(AUTHORIZATION, format!("Bearer {}", token));
+            if let Some(body) = &operation.request {
+                request = request.set_json(&body.example);
+            }
+
+            let response = test::call_service(&mut app, request.to_request()).await;
+            let status = response.status();
+            let body = read_body(response).await;
+            let route = format!("{} {}", operation.method, operation.path);
+            assert!(
+                !(body.is_empty()
+                    && (status == StatusCode::NOT_FOUND
+                        || status == StatusCode::METHOD_NOT_ALLOWED)),
+                "{} isn't routed",
+                route
+            );
+            if status == StatusCode::BAD_REQUEST {
+                let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+                assert!(
+                    !error.message.starts_with("Json"),
+                    "{} doesn't accept its documented body: {}",
+                    route,
+                    error.message
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use crate::errors::ErrorResponse;
+    use crate::import::csv::tests::csv_file;
+    use crate::import::gpx::tests::{gpx_file, trkpt};
+    use crate::import::gpx::MEDIA_TYPE;
+    use crate::import::{csv, fit, tcx};
+    use crate::managers::runs::tests::{create_league_with_player, player_one_id};
+    use crate::models::leagues::Commissioner;
+    use crate::models::runs::tests::create_run_request;
+    use crate::models::runs::{RunDetailsResponse, RunLap, RunsImportResponse};
+    use crate::schema::{league_commissioners, run_laps};
+    use crate::tests::helpers::tests::{
+        assert_get, get_login_user, get_pool, test_post, test_upload,
+    };
+    use actix_web::http::StatusCode;
+    use actix_web::test::read_body;
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn it_gets_all_runs() {
+        let url = format!("/api/v1/users/{}/runs", player_one_id());
+        assert_get(&url).await;
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_log_a_run_for_another_user() {
+        let url = format!("/api/v1/users/{}/runs", player_one_id());
+        let response = test_post(&url, create_run_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn it_imports_runs_from_a_gpx_file() {
+        let points = [
+            trkpt(40.0, "2020-05-09T07:30:00Z", 1600.0),
+            trkpt(40.01447, "2020-05-09T07:40:00Z", 1620.0),
+        ]
+        .join("\n");
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let file = gpx_file(&[&points, &points]).into_bytes();
+        let response = test_upload(&url, MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+
+        let runs: RunDetailsResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(runs.total, 2);
+        assert!((runs.data[0].run.distance - 1.0).abs() < 0.01);
+        assert_eq!(runs.data[0].run.duration, 600);
+        assert_eq!(runs.data[0].run.heart_rate, Some(150));
+        assert_eq!(runs.data[0].laps.len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn it_imports_runs_from_a_tcx_file() {
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let file = include_bytes!("fixtures/multi-lap.tcx").to_vec();
+        let response = test_upload(&url, tcx::MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+
+        let runs: RunDetailsResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(runs.total, 1);
+        assert!((runs.data[0].run.distance - 3.0).abs() < 0.01);
+        assert_eq!(runs.data[0].run.duration, 1440);
+
+        let laps = &runs.data[0].laps;
+        assert_eq!(laps.len(), 3);
+        assert_eq!(laps[1].lap_number, 2);
+        assert_eq!(laps[1].started_at.to_string(), "2020-05-09 07:38:00");
+        assert!((laps[1].distance - 1.0).abs() < 0.01);
+        assert_eq!(laps[1].duration, 480);
+
+        let conn = get_pool().get().unwrap();
+        let stored: Vec<RunLap> = run_laps::table
+            .filter(run_laps::run_id.eq(runs.data[0].run.id))
+            .order(run_laps::lap_number)
+            .load(&conn)
+            .unwrap();
+        assert_eq!(&stored, laps);
+    }
+
+    #[actix_rt::test]
+    async fn it_imports_runs_from_a_fit_file() {
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let file = include_bytes!("fixtures/paused.fit").to_vec();
+        let response = test_upload(&url, fit::MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+
+        let runs: RunDetailsResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(runs.total, 1);
+        assert_eq!(runs.data[0].run.duration, 1800);
+        assert_eq!(runs.data[0].run.heart_rate, Some(150));
+    }
+
+    #[actix_rt::test]
+    async fn it_sniffs_the_format_of_a_file() {
+        // The content decides, not the Content-Type
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let file = include_bytes!("fixtures/multi-lap.fit").to_vec();
+        let response = test_upload(&url, MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn it_points_at_the_line_of_a_malformed_gpx_file() {
+        let url = format!("/api/v1/users/{}/runs/import", get_login_user().id);
+        let file = b"<gpx>\n<trk>\n</gpx>".to_vec();
+        let response = test_upload(&url, MEDIA_TYPE, file).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let error: ErrorResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert!(error.fields.unwrap()["file"][0].starts_with("line 3:"));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_import_runs_for_another_user() {
+        let url = format!("/api/v1/users/{}/runs/import", player_one_id());
+        let file = gpx_file(&[]).into_bytes();
+        let response = test_upload(&url, MEDIA_TYPE, file).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    /// A league the logged in user is a commissioner of, and a CSV file of
+    /// runs for its player
+    fn league_and_file(rows: &[&str]) -> (String, Vec<u8>) {
+        let (league_id, player) = create_league_with_player();
+        diesel::insert_into(league_commissioners::table)
+            .values(Commissioner {
+                id: Uuid::new_v4(),
+                user_id: get_login_user().id,
+                league_id,
+            })
+            .execute(&get_pool().get().unwrap())
+            .unwrap();
+        let rows: Vec<String> = rows
+            .iter()
+            .map(|row| format!("{},{}", player.email, row))
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let url = format!("/api/v1/leagues/{}/runs/import", league_id);
+        (url, csv_file(&rows).into_bytes())
+    }
+
+    #[actix_rt::test]
+    async fn it_imports_a_leagues_runs_from_a_csv_file() {
+        let (url, file) = league_and_file(&["2020-05-09,3.1,1680", "2020-05-10,6.2,0:56:00"]);
+        let response = test_upload(&url, csv::MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+
+        let import: RunsImportResponse =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert!(!import.dry_run);
+        assert_eq!(import.total, 2);
+    }
+
+    #[actix_rt::test]
+    async fn it_reports_the_errors_of_every_row_in_a_dry_run() {
+        let (url, file) = league_and_file(&["2020-05-09,3.1,1680", "May 10th,6.2,nope"]);
+        let url = format!("{}?dry_run=true", url);
+        let response = test_upload(&url, csv::MEDIA_TYPE, file).await;
+        assert!(response.status().is_success());
+
+        let import: RunsImportResponse =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert!(import.dry_run);
+        assert_eq!(import.total, 1);
+        assert_eq!(import.errors.len(), 2);
+        assert!(import.errors["row[1].date"][0].starts_with("line 3:"));
+        assert!(import.errors.contains_key("row[1].duration"));
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_import_a_csv_file_with_errors() {
+        let (url, file) = league_and_file(&["2020-05-09,3.1,1680", "2020-05-10,-1,1680"]);
+        let response = test_upload(&url, csv::MEDIA_TYPE, file).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let error: ErrorResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert!(error.fields.unwrap().contains_key("row[1].distance"));
+    }
+
+    #[actix_rt::test]
+    async fn it_only_lets_commissioners_import_a_leagues_runs() {
+        let (league_id, player) = create_league_with_player();
+        let url = format!("/api/v1/leagues/{}/runs/import", league_id);
+        let file = csv_file(&[&format!("{},2020-05-09,3.1,1680", player.email)]);
+        let response = test_upload(&url, csv::MEDIA_TYPE, file.into_bytes()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
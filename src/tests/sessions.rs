@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::{create_jwt, get_identity_service, PrivateClaim};
+    use crate::cache::add_cache;
+    use crate::database::add_pool;
+    use crate::middleware::request_id::RequestIds;
+    use crate::models::sessions::SessionsResponse;
+    use crate::models::users::LoginResponse;
+    use crate::routes::routes;
+    use crate::sessions::tests::get_data_sessions;
+    use crate::tests::helpers::tests::{get_login_user, login_request, test_get};
+    use actix_http::{cookie::Cookie, Request};
+    use actix_web::body::Body;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::{header::AUTHORIZATION, StatusCode};
+    use actix_web::{test, App, Error};
+
+    /// An app keeping sessions in an in-process store
+    async fn session_app(
+    ) -> impl Service<Request = Request, Response = ServiceResponse<Body>, Error = Error> {
+        test::init_service(
+            App::new()
+                .wrap(get_identity_service())
+                .wrap(RequestIds)
+                .configure(add_pool)
+                .configure(add_cache)
+                .app_data(get_data_sessions())
+                .configure(routes),
+        )
+        .await
+    }
+
+    /// Log in and get the session token and identity cookie
+    async fn login<S>(app: &mut S) -> (String, Cookie<'static>)
+    where
+        S: Service<Request = Request, Response = ServiceResponse<Body>, Error = Error>,
+    {
+        let response = test::call_service(app, login_request()).await;
+        let cookie = response.response().cookies().next().unwrap().into_owned();
+        let response: LoginResponse =
+            serde_json::from_slice(&test::read_body(response).await).unwrap();
+        (response.token, cookie)
+    }
+
+    fn request(method: &str, uri: &str, token: &str) -> Request {
+        let request = match method {
+            "delete" => test::TestRequest::delete(),
+            _ => test::TestRequest::get(),
+        };
+        request
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .uri(uri)
+            .to_request()
+    }
+
+    #[actix_rt::test]
+    async fn it_logs_in_with_a_session() {
+        let mut app = session_app().await;
+        let (token, cookie) = login(&mut app).await;
+        assert!(!token.contains('.'));
+
+        let response =
+            test::call_service(&mut app, request("get", "/api/v1/leagues", &token)).await;
+        assert!(response.status().is_success());
+
+        let with_cookie = test::TestRequest::get()
+            .cookie(cookie)
+            .uri("/api/v1/leagues")
+            .to_request();
+        let response = test::call_service(&mut app, with_cookie).await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn it_destroys_the_session_when_logging_out() {
+        let mut app = session_app().await;
+        let (token, cookie) = login(&mut app).await;
+        let logout = test::TestRequest::get()
+            .cookie(cookie)
+            .uri("/api/v1/auth/logout")
+            .to_request();
+        let response = test::call_service(&mut app, logout).await;
+        assert!(response.status().is_success());
+
+        let response =
+            test::call_service(&mut app, request("get", "/api/v1/leagues", &token)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_accept_jwts_in_session_mode() {
+        let mut app = session_app().await;
+        let user = get_login_user();
+        let jwt = create_jwt(PrivateClaim::new(user.id, user.email)).unwrap();
+        let response = test::call_service(&mut app, request("get", "/api/v1/leagues", &jwt)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn it_lists_and_revokes_sessions() {
+        let mut app = session_app().await;
+        let (token, _) = login(&mut app).await;
+        let (other_token, _) = login(&mut app).await;
+
+        let response =
+            test::call_service(&mut app, request("get", "/api/v1/auth/sessions", &token)).await;
+        let body = test::read_body(response).await;
+        let sessions: SessionsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sessions.total, 2);
+        let current: Vec<bool> = sessions
+            .data
+            .iter()
+            .map(|session| session.current)
+            .collect();
+        assert_eq!(current, vec![true, false]);
+
+        let other = format!("/api/v1/auth/sessions/{}", sessions.data[1].id);
+        let response = test::call_service(&mut app, request("delete", &other, &token)).await;
+        assert!(response.status().is_success());
+
+        let response =
+            test::call_service(&mut app, request("get", "/api/v1/leagues", &other_token)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response =
+            test::call_service(&mut app, request("get", "/api/v1/leagues", &token)).await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn it_logs_out_everywhere() {
+        let mut app = session_app().await;
+        let (token, _) = login(&mut app).await;
+        let (other_token, _) = login(&mut app).await;
+
+        let response =
+            test::call_service(&mut app, request("delete", "/api/v1/auth/sessions", &token)).await;
+        assert!(response.status().is_success());
+
+        for token in &[token, other_token] {
+            let response =
+                test::call_service(&mut app, request("get", "/api/v1/leagues", token)).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_list_sessions_when_using_jwts() {
+        let response = test_get("/api/v1/auth/sessions").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,31 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::teams::tests::create_team_request;
+    use crate::tests::helpers::tests::{assert_get, test_post};
+    use actix_web::http::StatusCode;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn it_gets_all_teams() {
+        assert_get("/api/v1/teams").await;
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_leagues_teams() {
+        let url = format!("/api/v1/leagues/{}/teams", Uuid::nil());
+        assert_get(&url).await;
+    }
+
+    #[actix_rt::test]
+    async fn it_gets_a_team() {
+        let url = format!("/api/v1/teams/{}", Uuid::nil());
+        assert_get(&url).await;
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_create_a_team_as_non_commissioner() {
+        let url = format!("/api/v1/leagues/{}/teams", Uuid::nil());
+        let response = test_post(&url, create_team_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
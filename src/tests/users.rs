@@ -2,7 +2,10 @@
 mod tests {
     use crate::handlers::users::tests::get_first_users_id;
     use crate::models::users::CreateUserRequest;
-    use crate::tests::helpers::tests::{assert_get, assert_post};
+    use crate::models::users::UsersResponse;
+    use crate::tests::helpers::tests::{assert_get, assert_post, test_get};
+    use actix_web::http::StatusCode;
+    use actix_web::test::read_body;
     use actix_web::web::Path;
     use uuid::Uuid;
 
@@ -20,12 +23,30 @@ mod tests {
         assert_get(PATH).await;
     }
 
+    #[actix_rt::test]
+    async fn it_pages_users() {
+        let url = format!("{}?limit=1&sort=email&email=test", PATH);
+        let response = assert_get(&url).await;
+        let users: UsersResponse = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(users.data.len(), 1);
+        assert!(users.data[0].email.contains("test"));
+        assert!(users.total > 1);
+        assert!(users.next_cursor.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn it_doesnt_sort_users_by_password() {
+        let url = format!("{}?sort=password", PATH);
+        let response = test_get(&url).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[actix_rt::test]
     async fn it_creates_a_user() {
         let params = CreateUserRequest {
             first_name: "Satoshi".into(),
             last_name: "Nakamoto".into(),
-            email: "satoshi@nakamotoinstitute.org".into(),
+            email: format!("satoshi-{}@nakamotoinstitute.org", Uuid::new_v4()),
             password: "123456".into(),
         };
         assert_post(PATH, params).await;
@@ -1,8 +1,8 @@
 //! Validation-related functions to work with the validator crate.
 
-use crate::errors::ApiError;
+use crate::errors::{ApiError, FieldErrors};
 use actix_web::web::Json;
-use validator::{Validate, ValidationErrors};
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
 
 /// Validate a struct and collect and return the errors
 pub fn validate<T>(params: &Json<T>) -> Result<(), ApiError>
@@ -15,21 +15,41 @@ where
   }
 }
 
-/// Collect ValidationErrors and return a vector of the messages
+/// Collect every message of ValidationErrors, keyed by field path
+/// (`name`, `runs[0].distance`...)
 /// Adds a default_error when none is supplied
-fn collect_errors(error: ValidationErrors) -> Vec<String> {
-  error
-    .field_errors()
-    .into_iter()
-    .map(|error| {
-      let default_error = format!("{} is required", error.0);
-      error.1[0]
-        .message
-        .as_ref()
-        .unwrap_or(&std::borrow::Cow::Owned(default_error))
-        .to_string()
-    })
-    .collect()
+pub fn collect_errors(error: ValidationErrors) -> FieldErrors {
+  let mut field_errors = FieldErrors::new();
+  collect_into(&mut field_errors, "", error);
+  field_errors
+}
+
+fn collect_into(field_errors: &mut FieldErrors, prefix: &str, error: ValidationErrors) {
+  for (field, kind) in error.errors() {
+    let path = if prefix.is_empty() {
+      field.to_string()
+    } else {
+      format!("{}.{}", prefix, field)
+    };
+    match kind {
+      ValidationErrorsKind::Field(errors) => {
+        let messages = errors.into_iter().map(|error| match error.message {
+          Some(message) => message.to_string(),
+          None => format!("{} is invalid", path),
+        });
+        field_errors
+          .entry(path.clone())
+          .or_default()
+          .extend(messages);
+      }
+      ValidationErrorsKind::Struct(errors) => collect_into(field_errors, &path, *errors),
+      ValidationErrorsKind::List(list) => {
+        for (index, errors) in list {
+          collect_into(field_errors, &format!("{}[{}]", path, index), *errors);
+        }
+      }
+    }
+  }
 }
 
 #[cfg(test)]
@@ -63,9 +83,29 @@ mod tests {
   fn it_validates() {
     let request = get_test_request();
     let response = validate(&Json(request)).unwrap_err();
-    let expected_error = ApiError::ValidationError(vec![
-      "first_name is required and must be at least 3 characters".to_string(),
-    ]);
+    let expected_error = ApiError::invalid_field(
+      "first_name",
+      "first_name is required and must be at least 3 characters",
+    );
     assert_eq!(response, expected_error);
   }
+
+  #[derive(Debug, Deserialize, Serialize, Validate)]
+  pub struct TestEmailRequest {
+    #[validate(length(min = 5), contains = "@")]
+    pub email: String,
+  }
+
+  #[test]
+  fn it_keeps_every_error_of_a_field() {
+    let request = TestEmailRequest { email: "a".into() };
+    let response = collect_errors(request.validate().unwrap_err());
+    assert_eq!(
+      response["email"],
+      vec![
+        "email is invalid".to_string(),
+        "email is invalid".to_string()
+      ]
+    );
+  }
 }